@@ -0,0 +1,488 @@
+//! Derive macros for `openai-structured-client`. Use them through the main
+//! crate, which re-exports them.
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{format_ident, quote};
+use syn::punctuated::Punctuated;
+use syn::{
+    parenthesized, parse_macro_input, Data, DeriveInput, Error, Fields, GenericArgument, LitBool,
+    LitFloat, LitInt, LitStr, PathArguments, Token, Type,
+};
+
+/// Derives `PromptVariables` and a validated builder for a struct of prompt
+/// template variables.
+///
+/// Every named field is a variable rendered with `Display`; `Option` fields are
+/// optional. Fields take `#[prompt(max_len = N)]` to limit the rendered length
+/// in characters and `#[prompt(one_of("a", "b"))]` to restrict it to the
+/// listed values. `Struct::builder()` returns a `StructBuilder` whose `build`
+/// fails with a `PromptError` on a missing or invalid variable.
+#[proc_macro_derive(PromptVariables, attributes(prompt))]
+pub fn derive_prompt_variables(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(input)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
+/// Derives `FieldSampling` for a struct whose fields marked
+/// `#[sampling(deterministic)]` are extracted at temperature 0 by
+/// `call_schema_two_pass`, and those marked `#[sampling(temperature = 0.9)]`
+/// or `#[sampling(model = "...")]` in a pass with that temperature or model.
+/// Fields renamed with `#[serde(rename = "...")]` are
+/// matched by their serialized name; `rename_all` is not supported.
+#[proc_macro_derive(FieldSampling, attributes(sampling))]
+pub fn derive_field_sampling(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand_field_sampling(input)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
+/// Derives `StructuredOutput` and an inherent `extract(client, prompt)` for an
+/// output type.
+///
+/// The type takes `#[structured(name = "...")]` for the schema name,
+/// `system_prompt = "..."` for the system role of its calls,
+/// `strict = false` to turn off strict decoding and `description = "..."` to
+/// replace its doc comment; fields take `#[structured(description = "...")]`.
+/// Fields renamed with `#[serde(rename = "...")]` are matched by their
+/// serialized name; `rename_all` is not supported with field descriptions.
+#[proc_macro_derive(StructuredOutput, attributes(structured))]
+pub fn derive_structured_output(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand_structured_output(input)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
+struct Variable {
+    ident: syn::Ident,
+    ty: Type,
+    optional: bool,
+    max_len: Option<usize>,
+    one_of: Vec<LitStr>,
+}
+
+fn expand(input: DeriveInput) -> syn::Result<TokenStream2> {
+    if !input.generics.params.is_empty() {
+        return Err(Error::new_spanned(
+            &input.generics,
+            "PromptVariables does not support generic structs",
+        ));
+    }
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => {
+                return Err(Error::new_spanned(
+                    &input.ident,
+                    "PromptVariables requires named fields",
+                ))
+            }
+        },
+        _ => {
+            return Err(Error::new_spanned(
+                &input.ident,
+                "PromptVariables can only be derived for structs",
+            ))
+        }
+    };
+    let variables = fields
+        .iter()
+        .map(|field| {
+            let (ty, optional) = match option_inner(&field.ty) {
+                Some(inner) => (inner.clone(), true),
+                None => (field.ty.clone(), false),
+            };
+            let mut variable = Variable {
+                ident: field.ident.clone().expect("named field"),
+                ty,
+                optional,
+                max_len: None,
+                one_of: Vec::new(),
+            };
+            for attr in field.attrs.iter().filter(|a| a.path().is_ident("prompt")) {
+                attr.parse_nested_meta(|meta| {
+                    if meta.path.is_ident("max_len") {
+                        variable.max_len = Some(meta.value()?.parse::<LitInt>()?.base10_parse()?);
+                        Ok(())
+                    } else if meta.path.is_ident("one_of") {
+                        let content;
+                        parenthesized!(content in meta.input);
+                        variable.one_of =
+                            Punctuated::<LitStr, Token![,]>::parse_terminated(&content)?
+                                .into_iter()
+                                .collect();
+                        Ok(())
+                    } else {
+                        Err(meta.error("expected `max_len` or `one_of`"))
+                    }
+                })?;
+            }
+            Ok(variable)
+        })
+        .collect::<syn::Result<Vec<_>>>()?;
+
+    let krate = quote!(::openai_structured_client::prompt);
+    let vis = &input.vis;
+    let name = &input.ident;
+    let builder = format_ident!("{}Builder", name);
+    let builder_doc = format!("Builds a validated [`{}`].", name);
+
+    let builder_fields = variables.iter().map(|v| {
+        let (ident, ty) = (&v.ident, &v.ty);
+        quote!(#ident: ::std::option::Option<#ty>)
+    });
+    let setters = variables.iter().map(|v| {
+        let (ident, ty) = (&v.ident, &v.ty);
+        quote! {
+            #vis fn #ident(mut self, value: impl ::std::convert::Into<#ty>) -> Self {
+                self.#ident = ::std::option::Option::Some(value.into());
+                self
+            }
+        }
+    });
+    let checks = variables.iter().map(|v| {
+        let ident = &v.ident;
+        let key = ident.to_string();
+        let max_len = match v.max_len {
+            Some(max) => quote!(::std::option::Option::Some(#max)),
+            None => quote!(::std::option::Option::None),
+        };
+        let one_of = &v.one_of;
+        let check = quote! {
+            #krate::check_variable(#key, &value.to_string(), #max_len, &[#(#one_of),*])?;
+        };
+        if v.optional {
+            quote! {
+                let #ident = self.#ident;
+                if let ::std::option::Option::Some(value) = &#ident {
+                    #check
+                }
+            }
+        } else {
+            quote! {
+                let #ident = self
+                    .#ident
+                    .ok_or(#krate::PromptError::Missing { variable: #key })?;
+                let value = &#ident;
+                #check
+            }
+        }
+    });
+    let idents = variables.iter().map(|v| &v.ident);
+    let keys = variables.iter().map(|v| v.ident.to_string());
+    let values = variables.iter().map(|v| {
+        let ident = &v.ident;
+        let key = ident.to_string();
+        if v.optional {
+            quote! {
+                if let ::std::option::Option::Some(value) = &self.#ident {
+                    variables.push((#key, value.to_string()));
+                }
+            }
+        } else {
+            quote!(variables.push((#key, self.#ident.to_string()));)
+        }
+    });
+
+    Ok(quote! {
+        #[doc = #builder_doc]
+        #[derive(Default)]
+        #vis struct #builder {
+            #(#builder_fields,)*
+        }
+
+        impl #builder {
+            #(#setters)*
+
+            #vis fn build(self) -> ::std::result::Result<#name, #krate::PromptError> {
+                #(#checks)*
+                ::std::result::Result::Ok(#name { #(#idents,)* })
+            }
+        }
+
+        impl #name {
+            #vis fn builder() -> #builder {
+                #builder::default()
+            }
+        }
+
+        impl #krate::PromptVariables for #name {
+            const NAMES: &'static [&'static str] = &[#(#keys),*];
+
+            fn variables(&self) -> ::std::vec::Vec<(&'static str, ::std::string::String)> {
+                let mut variables = ::std::vec::Vec::new();
+                #(#values)*
+                variables
+            }
+        }
+    })
+}
+
+fn expand_field_sampling(input: DeriveInput) -> syn::Result<TokenStream2> {
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => {
+                return Err(Error::new_spanned(
+                    &input.ident,
+                    "FieldSampling requires named fields",
+                ))
+            }
+        },
+        _ => {
+            return Err(Error::new_spanned(
+                &input.ident,
+                "FieldSampling can only be derived for structs",
+            ))
+        }
+    };
+    reject_rename_all(&input, "FieldSampling")?;
+
+    let mut deterministic = Vec::new();
+    let mut hints = Vec::new();
+    for field in fields {
+        let mut is_deterministic = false;
+        let mut temperature = None;
+        let mut model = None;
+        for attr in field.attrs.iter().filter(|a| a.path().is_ident("sampling")) {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("deterministic") {
+                    is_deterministic = true;
+                    Ok(())
+                } else if meta.path.is_ident("temperature") {
+                    let value = meta.value()?.parse::<LitFloat>()?;
+                    if !(0.0..=2.0).contains(&value.base10_parse::<f32>()?) {
+                        return Err(Error::new_spanned(
+                            &value,
+                            "temperature must be between 0 and 2",
+                        ));
+                    }
+                    temperature = Some(value);
+                    Ok(())
+                } else if meta.path.is_ident("model") {
+                    model = Some(meta.value()?.parse::<LitStr>()?);
+                    Ok(())
+                } else {
+                    Err(meta.error("expected `deterministic`, `temperature` or `model`"))
+                }
+            })?;
+        }
+        let name = serialized_name(field);
+        if is_deterministic && temperature.is_some() {
+            return Err(Error::new_spanned(
+                field,
+                "a deterministic field cannot take a temperature",
+            ));
+        }
+        if is_deterministic && model.is_none() {
+            deterministic.push(name);
+        } else if is_deterministic || temperature.is_some() || model.is_some() {
+            let temperature = match (is_deterministic, temperature) {
+                (true, _) => quote!(::std::option::Option::Some(0.0)),
+                (false, Some(value)) => quote!(::std::option::Option::Some(#value)),
+                (false, None) => quote!(::std::option::Option::None),
+            };
+            let model = match model {
+                Some(model) => quote!(::std::option::Option::Some(#model)),
+                None => quote!(::std::option::Option::None),
+            };
+            hints.push(quote! {
+                ::openai_structured_client::sampling::FieldHint {
+                    field: #name,
+                    temperature: #temperature,
+                    model: #model,
+                }
+            });
+        }
+    }
+
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::openai_structured_client::sampling::FieldSampling
+            for #name #ty_generics #where_clause
+        {
+            const DETERMINISTIC: &'static [&'static str] = &[#(#deterministic),*];
+            const HINTS: &'static [::openai_structured_client::sampling::FieldHint] =
+                &[#(#hints),*];
+        }
+    })
+}
+
+fn expand_structured_output(input: DeriveInput) -> syn::Result<TokenStream2> {
+    let mut schema_name = None;
+    let mut system_prompt = None;
+    let mut strict = true;
+    let mut description = None;
+    for attr in input
+        .attrs
+        .iter()
+        .filter(|a| a.path().is_ident("structured"))
+    {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("name") {
+                let name = meta.value()?.parse::<LitStr>()?;
+                let valid = !name.value().is_empty()
+                    && name
+                        .value()
+                        .chars()
+                        .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+                if !valid {
+                    return Err(Error::new_spanned(
+                        name,
+                        "schema names may only contain letters, digits, `_` and `-`",
+                    ));
+                }
+                schema_name = Some(name);
+            } else if meta.path.is_ident("system_prompt") {
+                system_prompt = Some(meta.value()?.parse::<LitStr>()?);
+            } else if meta.path.is_ident("strict") {
+                strict = meta.value()?.parse::<LitBool>()?.value;
+            } else if meta.path.is_ident("description") {
+                description = Some(meta.value()?.parse::<LitStr>()?);
+            } else {
+                return Err(
+                    meta.error("expected `name`, `system_prompt`, `strict` or `description`")
+                );
+            }
+            Ok(())
+        })?;
+    }
+
+    let mut field_descriptions = Vec::new();
+    if let Data::Struct(data) = &input.data {
+        for field in &data.fields {
+            for attr in field
+                .attrs
+                .iter()
+                .filter(|a| a.path().is_ident("structured"))
+            {
+                let Some(ident) = &field.ident else {
+                    return Err(Error::new_spanned(
+                        attr,
+                        "field descriptions require named fields",
+                    ));
+                };
+                attr.parse_nested_meta(|meta| {
+                    if meta.path.is_ident("description") {
+                        let description = meta.value()?.parse::<LitStr>()?;
+                        field_descriptions.push((serialized_name(field), description));
+                        Ok(())
+                    } else {
+                        Err(meta.error(format!("expected `description` on `{}`", ident)))
+                    }
+                })?;
+            }
+        }
+    }
+    if !field_descriptions.is_empty() {
+        reject_rename_all(&input, "StructuredOutput")?;
+    }
+
+    let option = |value: Option<LitStr>| match value {
+        Some(value) => quote!(::std::option::Option::Some(#value)),
+        None => quote!(::std::option::Option::None),
+    };
+    let schema_name = option(schema_name);
+    let system_prompt = option(system_prompt);
+    let description = option(description);
+    let (fields, descriptions): (Vec<_>, Vec<_>) = field_descriptions.into_iter().unzip();
+
+    let krate = quote!(::openai_structured_client);
+    let vis = &input.vis;
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics #krate::structured::StructuredOutput for #name #ty_generics #where_clause {
+            const SCHEMA_NAME: ::std::option::Option<&'static str> = #schema_name;
+            const SYSTEM_PROMPT: ::std::option::Option<&'static str> = #system_prompt;
+            const STRICT: bool = #strict;
+            const DESCRIPTION: ::std::option::Option<&'static str> = #description;
+            const FIELD_DESCRIPTIONS: &'static [(&'static str, &'static str)] =
+                &[#((#fields, #descriptions)),*];
+        }
+
+        impl #impl_generics #name #ty_generics #where_clause {
+            /// Extracts this type from `prompt` with `client`, using the
+            /// schema and system prompt declared on the type.
+            #vis async fn extract(
+                client: &#krate::openai::OpenAiClient,
+                prompt: &str,
+            ) -> ::std::result::Result<Self, ::std::boxed::Box<dyn ::std::error::Error>> {
+                client
+                    .call_structured::<Self>(prompt, &#krate::options::RequestOptions::default())
+                    .await
+            }
+        }
+    })
+}
+
+/// Fails on `#[serde(rename_all = "...")]`, which `serialized_name` can't follow.
+fn reject_rename_all(input: &DeriveInput, what: &str) -> syn::Result<()> {
+    for attr in input.attrs.iter().filter(|a| a.path().is_ident("serde")) {
+        let mut rename_all = false;
+        let _ = attr.parse_nested_meta(|meta| {
+            rename_all |= meta.path.is_ident("rename_all");
+            skip_meta_value(&meta)
+        });
+        if rename_all {
+            return Err(Error::new_spanned(
+                attr,
+                format!(
+                    "{} does not support `rename_all`; rename fields individually",
+                    what
+                ),
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// The name of a field as serialized, following `#[serde(rename = "...")]`.
+fn serialized_name(field: &syn::Field) -> String {
+    let mut name = field.ident.as_ref().expect("named field").to_string();
+    for attr in field.attrs.iter().filter(|a| a.path().is_ident("serde")) {
+        let _ = attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("rename") && meta.input.peek(Token![=]) {
+                name = meta.value()?.parse::<LitStr>()?.value();
+                Ok(())
+            } else {
+                skip_meta_value(&meta)
+            }
+        });
+    }
+    name
+}
+
+/// Consumes the value of a nested meta item that is not inspected, such as
+/// `default = "..."` or `rename(serialize = "...")`.
+fn skip_meta_value(meta: &syn::meta::ParseNestedMeta) -> syn::Result<()> {
+    if meta.input.peek(Token![=]) {
+        meta.value()?.parse::<syn::Expr>()?;
+    } else if meta.input.peek(syn::token::Paren) {
+        let _content;
+        parenthesized!(_content in meta.input);
+    }
+    Ok(())
+}
+
+fn option_inner(ty: &Type) -> Option<&Type> {
+    let Type::Path(path) = ty else {
+        return None;
+    };
+    let segment = path.path.segments.last()?;
+    if segment.ident != "Option" {
+        return None;
+    }
+    match &segment.arguments {
+        PathArguments::AngleBracketed(args) => match args.args.first()? {
+            GenericArgument::Type(inner) => Some(inner),
+            _ => None,
+        },
+        _ => None,
+    }
+}
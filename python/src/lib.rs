@@ -0,0 +1,157 @@
+//! Python bindings: `structured_client.Client` runs calls with a JSON schema
+//! given as a dict (e.g. `Model.model_json_schema()` of a pydantic model) or
+//! as JSON text, and returns the output as a dict. Build with `maturin build`
+//! in this directory.
+
+use openai_structured_client::cache::MemoryCache;
+use openai_structured_client::dynamic::DynSchema;
+use openai_structured_client::openai::{OpenAiClient, ParseError, Refusal};
+use openai_structured_client::options::RequestOptions;
+use openai_structured_client::refusal::RefusalsExhausted;
+use openai_structured_client::retry::RetryPolicy;
+use pyo3::create_exception;
+use pyo3::exceptions::PyException;
+use pyo3::prelude::*;
+use pyo3::types::PyString;
+use serde_json::Value;
+use std::error::Error;
+use std::time::Duration;
+use tokio::runtime::{self, Runtime};
+
+create_exception!(structured_client, ClientError, PyException);
+create_exception!(structured_client, RefusalError, ClientError);
+create_exception!(structured_client, OutputError, ClientError);
+
+/// A structured-output client with the Rust client's retries, repair, rate
+/// limiting and caching. Calls block, with the GIL released.
+#[pyclass(frozen)]
+struct Client {
+    client: OpenAiClient,
+    runtime: Runtime,
+}
+
+#[pymethods]
+impl Client {
+    #[new]
+    #[pyo3(signature = (
+        endpoint,
+        model,
+        api_key,
+        *,
+        system_role = None,
+        max_attempts = None,
+        timeout = None,
+        repair_attempts = 0,
+        requests_per_minute = None,
+        tokens_per_minute = None,
+        cache_size = None,
+        insecure_loopback = false,
+    ))]
+    #[allow(clippy::too_many_arguments)]
+    fn new(
+        endpoint: &str,
+        model: &str,
+        api_key: &str,
+        system_role: Option<String>,
+        max_attempts: Option<u32>,
+        timeout: Option<f64>,
+        repair_attempts: u32,
+        requests_per_minute: Option<u32>,
+        tokens_per_minute: Option<u32>,
+        cache_size: Option<usize>,
+        insecure_loopback: bool,
+    ) -> PyResult<Self> {
+        let mut client = OpenAiClient::new(reqwest::Client::new(), endpoint, model, api_key)
+            .with_repair(repair_attempts)
+            .with_insecure_loopback(insecure_loopback);
+        if let Some(role) = system_role {
+            client = client.with_system_role(role);
+        }
+        if let Some(max_attempts) = max_attempts {
+            client = client.with_retry(RetryPolicy {
+                max_attempts,
+                ..RetryPolicy::default()
+            });
+        }
+        if let Some(timeout) = timeout {
+            let timeout = Duration::try_from_secs_f64(timeout)
+                .map_err(|err| ClientError::new_err(err.to_string()))?;
+            client = client.with_timeout(timeout);
+        }
+        if requests_per_minute.is_some() || tokens_per_minute.is_some() {
+            client = client.with_rate_limit(
+                requests_per_minute.unwrap_or(u32::MAX),
+                tokens_per_minute.unwrap_or(u32::MAX),
+            );
+        }
+        if let Some(capacity) = cache_size {
+            client = client.with_cache(MemoryCache::new(capacity));
+        }
+        let runtime = runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .map_err(|err| ClientError::new_err(err.to_string()))?;
+        Ok(Self { client, runtime })
+    }
+
+    /// Extracts an output matching `schema` from `prompt`. Strict schemas need
+    /// `additionalProperties: false` and every property required; pass
+    /// `strict=False` for schemas that don't, such as most pydantic ones.
+    #[pyo3(signature = (schema, prompt, *, name = "output", strict = true, temperature = None, seed = None))]
+    #[allow(clippy::too_many_arguments)]
+    fn call(
+        &self,
+        py: Python<'_>,
+        schema: &Bound<'_, PyAny>,
+        prompt: &str,
+        name: &str,
+        strict: bool,
+        temperature: Option<f32>,
+        seed: Option<i64>,
+    ) -> PyResult<Py<PyAny>> {
+        let json = py.import("json")?;
+        let schema_text: String = if schema.is_instance_of::<PyString>() {
+            schema.extract()?
+        } else {
+            json.call_method1("dumps", (schema,))?.extract()?
+        };
+        let schema: Value = serde_json::from_str(&schema_text)
+            .map_err(|err| ClientError::new_err(format!("invalid schema: {}", err)))?;
+        let schema = DynSchema::new(name, schema).with_strict(strict);
+        let mut options = RequestOptions::default();
+        if let Some(temperature) = temperature {
+            options = options.temperature(temperature);
+        }
+        if let Some(seed) = seed {
+            options = options.seed(seed);
+        }
+
+        let output = py.detach(|| {
+            self.runtime
+                .block_on(self.client.call_schema_value(&schema, prompt, &options))
+                .map(|value| value.to_string())
+                .map_err(to_py_err)
+        })?;
+        Ok(json.call_method1("loads", (output,))?.unbind())
+    }
+}
+
+fn to_py_err(err: Box<dyn Error>) -> PyErr {
+    let message = err.to_string();
+    if err.is::<Refusal>() || err.is::<RefusalsExhausted>() {
+        RefusalError::new_err(message)
+    } else if err.is::<ParseError>() {
+        OutputError::new_err(message)
+    } else {
+        ClientError::new_err(message)
+    }
+}
+
+#[pymodule]
+fn structured_client(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<Client>()?;
+    m.add("ClientError", m.py().get_type::<ClientError>())?;
+    m.add("RefusalError", m.py().get_type::<RefusalError>())?;
+    m.add("OutputError", m.py().get_type::<OutputError>())?;
+    Ok(())
+}
@@ -0,0 +1,250 @@
+use crate::openai::{Cancelled, OpenAiClient};
+use crate::options::RequestOptions;
+use crate::tools::{ToolCall, ToolResponse, ToolSet};
+use futures::future::LocalBoxFuture;
+use futures::stream::{self, StreamExt};
+use schemars::JsonSchema;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::future::Future;
+use tokio_util::sync::CancellationToken;
+
+const DEFAULT_MAX_ITERATIONS: usize = 10;
+const DEFAULT_MAX_CONCURRENT_TOOLS: usize = 8;
+
+type Handler = Box<dyn Fn(&ToolCall) -> LocalBoxFuture<'static, Result<Value, Box<dyn Error>>>>;
+type CancelHook = Box<dyn Fn(&[ToolCall])>;
+
+/// Runs tool calls for the model until it gives a final structured answer.
+///
+/// Every model turn that asks for tools has its calls run concurrently by the
+/// registered handlers, and their results are sent back as `tool` messages
+/// under the IDs of the calls. A handler error, or a call to a tool without a
+/// handler, is reported to the model as `{"error": "..."}` so that it can
+/// recover, while the other calls of the turn complete.
+///
+/// A run stops at its next await point when its future is dropped or its
+/// cancellation token fires: the in-flight model request is aborted and the
+/// running handler futures are dropped. Work a handler spawned outside its
+/// future keeps running; release it in the `on_cancel` hook.
+pub struct Agent {
+    client: OpenAiClient,
+    tools: ToolSet,
+    handlers: HashMap<String, Handler>,
+    max_iterations: usize,
+    max_concurrent_tools: usize,
+    cancellation: Option<CancellationToken>,
+    on_cancel: Option<CancelHook>,
+}
+
+/// The final answer of an agent run and every tool call made on the way.
+#[derive(Debug, Clone)]
+pub struct AgentRun<T> {
+    pub answer: T,
+    pub executions: Vec<ToolExecution>,
+}
+
+/// One tool call and what its handler returned, in the order the model made
+/// the calls.
+#[derive(Debug, Clone)]
+pub struct ToolExecution {
+    pub call: ToolCall,
+    /// The handler's result, or the error message sent to the model.
+    pub output: Result<Value, String>,
+}
+
+/// Returned when the model still asks for tools after the last iteration.
+#[derive(Debug, Clone)]
+pub struct IterationsExhausted {
+    pub max_iterations: usize,
+    /// The tool calls of the final turn.
+    pub pending: Vec<ToolCall>,
+}
+
+impl fmt::Display for IterationsExhausted {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "no final answer after {} iterations of tool calls",
+            self.max_iterations
+        )
+    }
+}
+
+impl Error for IterationsExhausted {}
+
+/// Calls the cancel hook if a run is dropped before it finishes.
+struct CancelGuard<'a> {
+    hook: Option<&'a CancelHook>,
+    in_flight: Vec<ToolCall>,
+    finished: bool,
+}
+
+impl Drop for CancelGuard<'_> {
+    fn drop(&mut self) {
+        if let (Some(hook), false) = (self.hook, self.finished) {
+            hook(&self.in_flight);
+        }
+    }
+}
+
+impl Agent {
+    pub fn new(client: OpenAiClient) -> Self {
+        Self {
+            client,
+            tools: ToolSet::new(),
+            handlers: HashMap::new(),
+            max_iterations: DEFAULT_MAX_ITERATIONS,
+            max_concurrent_tools: DEFAULT_MAX_CONCURRENT_TOOLS,
+            cancellation: None,
+            on_cancel: None,
+        }
+    }
+
+    /// Registers a tool whose arguments deserialize into `A`, run by `handler`.
+    pub fn with_tool<A, R, F, Fut>(
+        mut self,
+        name: impl Into<String>,
+        description: impl Into<String>,
+        handler: F,
+    ) -> Self
+    where
+        A: DeserializeOwned + JsonSchema + 'static,
+        R: Serialize,
+        F: Fn(A) -> Fut + 'static,
+        Fut: Future<Output = Result<R, Box<dyn Error>>> + 'static,
+    {
+        let name = name.into();
+        self.tools = self.tools.with_tool::<A>(name.clone(), description);
+        let handler: Handler = Box::new(move |call| {
+            let arguments = call.arguments::<A>();
+            let result = arguments.map(&handler);
+            Box::pin(async move { Ok(serde_json::to_value(result?.await?)?) })
+        });
+        self.handlers.insert(name, handler);
+        self
+    }
+
+    /// Model turns allowed before giving up with `IterationsExhausted`; 10 by
+    /// default.
+    pub fn with_max_iterations(mut self, max_iterations: usize) -> Self {
+        self.max_iterations = max_iterations;
+        self
+    }
+
+    /// Tool calls of one turn run at the same time; 8 by default.
+    pub fn with_max_concurrent_tools(mut self, max_concurrent_tools: usize) -> Self {
+        self.max_concurrent_tools = max_concurrent_tools.max(1);
+        self
+    }
+
+    /// Stops runs with `Cancelled` once `token` is cancelled.
+    pub fn with_cancellation(mut self, token: CancellationToken) -> Self {
+        self.cancellation = Some(token);
+        self
+    }
+
+    /// Called when a run is cancelled or dropped before it finishes, with the
+    /// tool calls whose handlers were running (none if the run was waiting on
+    /// the model).
+    pub fn on_cancel(mut self, hook: impl Fn(&[ToolCall]) + 'static) -> Self {
+        self.on_cancel = Some(Box::new(hook));
+        self
+    }
+
+    /// Runs the conversation from `user_prompt` to a final T.
+    pub async fn run<T: DeserializeOwned + JsonSchema + 'static>(
+        &self,
+        user_prompt: &str,
+    ) -> Result<T, Box<dyn Error>> {
+        Ok(self.run_with_executions(user_prompt).await?.answer)
+    }
+
+    /// Like `run`, also returning the tool calls made and their outputs.
+    pub async fn run_with_executions<T: DeserializeOwned + JsonSchema + 'static>(
+        &self,
+        user_prompt: &str,
+    ) -> Result<AgentRun<T>, Box<dyn Error>> {
+        let mut guard = CancelGuard {
+            hook: self.on_cancel.as_ref(),
+            in_flight: Vec::new(),
+            finished: false,
+        };
+        let run = self.run_turns(user_prompt, &mut guard);
+        let result = match &self.cancellation {
+            Some(token) => token.run_until_cancelled(run).await,
+            None => Some(run.await),
+        };
+        guard.finished = result.is_some();
+        result.unwrap_or_else(|| Err(Box::new(Cancelled)))
+    }
+
+    async fn run_turns<T: DeserializeOwned + JsonSchema + 'static>(
+        &self,
+        user_prompt: &str,
+        guard: &mut CancelGuard<'_>,
+    ) -> Result<AgentRun<T>, Box<dyn Error>> {
+        let mut messages = self
+            .client
+            .tool_conversation::<T>(user_prompt, &RequestOptions::default());
+        let mut executions = Vec::new();
+        let mut pending = Vec::new();
+        for _ in 0..self.max_iterations {
+            let calls = match self
+                .client
+                .continue_with_tools::<T>(&messages, &self.tools, &RequestOptions::default())
+                .await?
+            {
+                ToolResponse::Answer(answer) => return Ok(AgentRun { answer, executions }),
+                ToolResponse::ToolCalls(calls) => calls,
+            };
+            guard.in_flight = calls.clone();
+            let outputs: Vec<_> = stream::iter(calls.iter().map(|call| self.execute(call)))
+                .buffered(self.max_concurrent_tools)
+                .collect()
+                .await;
+            guard.in_flight.clear();
+            messages.push(json!({
+                "role": "assistant",
+                "content": null,
+                "tool_calls": calls.iter().map(|call| json!({
+                    "id": call.id,
+                    "type": "function",
+                    "function": { "name": call.name, "arguments": call.arguments }
+                })).collect::<Vec<_>>()
+            }));
+            for (call, output) in calls.iter().zip(outputs) {
+                let content = match &output {
+                    Ok(value) => value.to_string(),
+                    Err(message) => json!({ "error": message }).to_string(),
+                };
+                messages.push(json!({
+                    "role": "tool",
+                    "tool_call_id": call.id,
+                    "content": content
+                }));
+                executions.push(ToolExecution {
+                    call: call.clone(),
+                    output,
+                });
+            }
+            pending = calls;
+        }
+        Err(Box::new(IterationsExhausted {
+            max_iterations: self.max_iterations,
+            pending,
+        }))
+    }
+
+    /// Runs one tool call; failures become an error message for the model.
+    async fn execute(&self, call: &ToolCall) -> Result<Value, String> {
+        match self.handlers.get(&call.name) {
+            Some(handler) => handler(call).await.map_err(|err| err.to_string()),
+            None => Err(format!("unknown tool `{}`", call.name)),
+        }
+    }
+}
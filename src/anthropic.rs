@@ -0,0 +1,154 @@
+//! Translation between chat-completions shapes and Anthropic's Messages API.
+//! Structured output is obtained by forcing a single tool whose input schema
+//! is the generated schema; the tool call's input is the typed result.
+
+use serde_json::{json, Map, Value};
+
+pub(crate) const API_VERSION: &str = "2023-06-01";
+
+/// Anthropic requires `max_tokens`; used when the caller does not set one.
+const DEFAULT_MAX_TOKENS: u32 = 4096;
+
+/// Options without an equivalent in the Messages API.
+const UNSUPPORTED_OPTIONS: &[&str] = &[
+    "seed",
+    "logprobs",
+    "frequency_penalty",
+    "presence_penalty",
+    "reasoning_effort",
+    "store",
+    "metadata",
+];
+
+/// Builds a Messages API body forcing a tool call that carries the structured output.
+pub(crate) fn request_body(
+    model: &str,
+    messages: &[Value],
+    schema_name: &str,
+    schema: Value,
+) -> Value {
+    let mut body = text_request_body(model, messages);
+    body["tools"] = json!([{
+        "name": schema_name,
+        "description": "Respond with the structured output.",
+        "input_schema": schema
+    }]);
+    body["tool_choice"] = json!({ "type": "tool", "name": schema_name });
+    body
+}
+
+/// Builds a body without a schema. The Messages API has no JSON mode, so JSON
+/// output has to be asked for in the prompt.
+pub(crate) fn text_request_body(model: &str, messages: &[Value]) -> Value {
+    let system: Vec<&str> = messages
+        .iter()
+        .filter(|m| m["role"] == "system" || m["role"] == "developer")
+        .filter_map(|m| m["content"].as_str())
+        .collect();
+    let messages: Vec<Value> = messages
+        .iter()
+        .filter(|m| m["role"] != "system" && m["role"] != "developer")
+        .map(to_message)
+        .collect();
+
+    let mut body = json!({
+        "model": model,
+        "max_tokens": DEFAULT_MAX_TOKENS,
+        "messages": messages,
+    });
+    if !system.is_empty() {
+        body["system"] = json!(system.join("\n\n"));
+    }
+    body
+}
+
+/// Renames and drops chat-completions options for the Messages API.
+pub(crate) fn adapt_options(body: &mut Value) {
+    let Some(body) = body.as_object_mut() else {
+        return;
+    };
+    if let Some(max_tokens) = body.remove("max_completion_tokens") {
+        body.insert("max_tokens".into(), max_tokens);
+    }
+    if let Some(stop) = body.remove("stop") {
+        body.insert("stop_sequences".into(), stop);
+    }
+    // Anthropic's own metadata only carries the end-user id.
+    let user = body.remove("user");
+    for option in UNSUPPORTED_OPTIONS {
+        body.remove(*option);
+    }
+    if let Some(user) = user {
+        body.insert("metadata".into(), json!({ "user_id": user }));
+    }
+}
+
+fn to_message(message: &Value) -> Value {
+    let content = match (&message["content"], &message["refusal"]) {
+        (Value::Array(parts), _) => Value::Array(parts.iter().map(to_content_block).collect()),
+        (Value::Null, Value::String(refusal)) => Value::String(refusal.clone()),
+        (content, _) => content.clone(),
+    };
+    json!({ "role": message["role"], "content": content })
+}
+
+fn to_content_block(part: &Value) -> Value {
+    match part["type"].as_str() {
+        Some("text") => json!({ "type": "text", "text": part["text"] }),
+        Some("image_url") => {
+            let url = part["image_url"]["url"].as_str().unwrap_or_default();
+            let source = match url
+                .strip_prefix("data:")
+                .and_then(|rest| rest.split_once(";base64,"))
+            {
+                Some((mime, data)) => json!({ "type": "base64", "media_type": mime, "data": data }),
+                None => json!({ "type": "url", "url": url }),
+            };
+            json!({ "type": "image", "source": source })
+        }
+        _ => part.clone(),
+    }
+}
+
+/// Converts a Messages API response into the equivalent chat-completions
+/// response. Error bodies pass through.
+pub(crate) fn to_chat_completion(response: Value) -> Value {
+    let Some(blocks) = response["content"].as_array() else {
+        return response;
+    };
+
+    let mut message = Map::new();
+    message.insert("role".into(), json!("assistant"));
+    let stop_reason = response["stop_reason"].as_str().unwrap_or_default();
+    if stop_reason == "refusal" {
+        let text: Vec<&str> = blocks.iter().filter_map(|b| b["text"].as_str()).collect();
+        message.insert("refusal".into(), json!(text.join("\n")));
+    } else if let Some(tool_use) = blocks.iter().find(|b| b["type"] == "tool_use") {
+        message.insert("content".into(), json!(tool_use["input"].to_string()));
+    } else {
+        let text: String = blocks.iter().filter_map(|b| b["text"].as_str()).collect();
+        message.insert("content".into(), json!(text));
+    }
+
+    let finish_reason = match stop_reason {
+        "max_tokens" => "length",
+        _ => "stop",
+    };
+    let usage = &response["usage"];
+    let input = usage["input_tokens"].as_u64().unwrap_or(0);
+    let output = usage["output_tokens"].as_u64().unwrap_or(0);
+    let cached = usage["cache_read_input_tokens"].as_u64().unwrap_or(0);
+
+    json!({
+        "id": response["id"],
+        "model": response["model"],
+        "created": 0,
+        "choices": [{ "message": message, "finish_reason": finish_reason }],
+        "usage": {
+            "prompt_tokens": input + cached,
+            "completion_tokens": output,
+            "total_tokens": input + cached + output,
+            "prompt_tokens_details": { "cached_tokens": cached }
+        }
+    })
+}
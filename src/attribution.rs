@@ -0,0 +1,161 @@
+use crate::cost::ModelPrice;
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+/// Completion tokens attributed to the top-level fields of the output, computed
+/// from the token logprobs returned with `RequestOptions::logprobs(true)`.
+///
+/// Each token counts toward the field whose key or value it starts in; tokens
+/// starting on braces, separators or whitespace between fields are `structural`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TokenAttribution {
+    pub by_field: BTreeMap<String, u32>,
+    pub structural: u32,
+    /// Cost in USD of each field's tokens at the model's output price; empty
+    /// unless the client prices the model (`OpenAiClient::with_pricing`).
+    pub cost_by_field: BTreeMap<String, f64>,
+    /// Cost in USD of the structural tokens, likewise.
+    pub structural_cost: Option<f64>,
+}
+
+impl TokenAttribution {
+    /// Fields ordered from most to least expensive.
+    pub fn ranked(&self) -> Vec<(&str, u32)> {
+        let mut ranked: Vec<(&str, u32)> = self
+            .by_field
+            .iter()
+            .map(|(field, tokens)| (field.as_str(), *tokens))
+            .collect();
+        ranked.sort_by_key(|(_, tokens)| std::cmp::Reverse(*tokens));
+        ranked
+    }
+
+    /// Fills the costs from the model's price.
+    pub(crate) fn priced(mut self, price: &ModelPrice) -> Self {
+        let cost = |tokens: u32| f64::from(tokens) * price.output / 1_000_000.0;
+        self.cost_by_field = self
+            .by_field
+            .iter()
+            .map(|(field, tokens)| (field.clone(), cost(*tokens)))
+            .collect();
+        self.structural_cost = Some(cost(self.structural));
+        self
+    }
+}
+
+/// Attributes the logprob tokens of a chat-completions choice, or `None` when
+/// the choice carries no logprobs or its content is not a JSON object.
+pub(crate) fn from_choice(choice: &Value) -> Option<TokenAttribution> {
+    let content = choice["message"]["content"].as_str()?;
+    let tokens = choice["logprobs"]["content"].as_array()?;
+    let members = object_members(content)?;
+
+    let mut attribution = TokenAttribution::default();
+    let mut offset = 0;
+    for token in tokens {
+        // `bytes` is exact even when a token splits a multi-byte character.
+        let len = match token["bytes"].as_array() {
+            Some(bytes) => bytes.len(),
+            None => token["token"].as_str().map_or(0, str::len),
+        };
+        match members
+            .iter()
+            .find(|(_, start, end)| (*start..*end).contains(&offset))
+        {
+            Some((field, _, _)) => *attribution.by_field.entry(field.clone()).or_default() += 1,
+            None => attribution.structural += 1,
+        }
+        offset += len;
+    }
+    Some(attribution)
+}
+
+/// Byte ranges (key start to value end) of the members of a top-level object.
+fn object_members(json: &str) -> Option<Vec<(String, usize, usize)>> {
+    let bytes = json.as_bytes();
+    let mut pos = skip_whitespace(bytes, 0);
+    if bytes.get(pos) != Some(&b'{') {
+        return None;
+    }
+    pos += 1;
+
+    let mut members = Vec::new();
+    loop {
+        pos = skip_whitespace(bytes, pos);
+        match bytes.get(pos) {
+            Some(b'}') => return Some(members),
+            Some(b',') => {
+                pos += 1;
+                continue;
+            }
+            Some(b'"') => {}
+            _ => return None,
+        }
+        let start = pos;
+        let key_end = skip_string(bytes, pos)?;
+        let key: String = serde_json::from_str(&json[start..key_end]).ok()?;
+        pos = skip_whitespace(bytes, key_end);
+        if bytes.get(pos) != Some(&b':') {
+            return None;
+        }
+        pos = skip_whitespace(bytes, pos + 1);
+        let end = skip_value(bytes, pos)?;
+        members.push((key, start, end));
+        pos = end;
+    }
+}
+
+fn skip_whitespace(bytes: &[u8], mut pos: usize) -> usize {
+    while bytes.get(pos).is_some_and(u8::is_ascii_whitespace) {
+        pos += 1;
+    }
+    pos
+}
+
+/// Position just past the string starting at `pos`.
+fn skip_string(bytes: &[u8], mut pos: usize) -> Option<usize> {
+    pos += 1;
+    loop {
+        match bytes.get(pos)? {
+            b'\\' => pos += 2,
+            b'"' => return Some(pos + 1),
+            _ => pos += 1,
+        }
+    }
+}
+
+/// Position just past the value starting at `pos`.
+fn skip_value(bytes: &[u8], mut pos: usize) -> Option<usize> {
+    match bytes.get(pos)? {
+        b'"' => skip_string(bytes, pos),
+        b'{' | b'[' => {
+            let mut depth = 0;
+            loop {
+                match bytes.get(pos)? {
+                    b'"' => {
+                        pos = skip_string(bytes, pos)?;
+                        continue;
+                    }
+                    b'{' | b'[' => depth += 1,
+                    b'}' | b']' => {
+                        depth -= 1;
+                        if depth == 0 {
+                            return Some(pos + 1);
+                        }
+                    }
+                    _ => {}
+                }
+                pos += 1;
+            }
+        }
+        _ => {
+            while bytes
+                .get(pos)
+                .is_some_and(|b| !matches!(b, b',' | b'}' | b']') && !b.is_ascii_whitespace())
+            {
+                pos += 1;
+            }
+            Some(pos)
+        }
+    }
+}
@@ -0,0 +1,236 @@
+use crate::canonical;
+use crate::openai::ResponseMeta;
+use crate::stats::CallOutcome;
+use crate::transport::HttpResponse;
+use jiff::Timestamp;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::error::Error;
+use std::fmt;
+use std::fs::OpenOptions;
+use std::future::Future;
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+const REDACTED: &str = "[REDACTED]";
+
+tokio::task_local! {
+    /// Set while a schema or text call runs, which writes one record for
+    /// all of its requests.
+    static IN_CALL: ();
+}
+
+/// Runs a call that writes its own audit record. The call is boxed, as schema
+/// calls are large futures.
+pub(crate) fn audited_call<F: Future>(call: F) -> impl Future<Output = F::Output> {
+    IN_CALL.scope((), Box::pin(call))
+}
+
+/// Whether requests sent now belong to a call that writes its own record.
+pub(crate) fn in_audited_call() -> bool {
+    IN_CALL.try_with(|_| ()).is_ok()
+}
+
+/// An audit trail of calls, written as one JSON line (`AuditRecord`) per call
+/// to a file or any writer. Schema and text calls get one record each, with
+/// their usage; every other request, such as a tool call, an embedding batch
+/// or a file upload, gets its own record from the shared send path.
+///
+/// Prompts are recorded as a hash unless enabled with `with_prompts`; recorded
+/// prompts have every match of the redaction patterns replaced by
+/// `[REDACTED]`, as do error messages, which often echo the input. Write
+/// errors are ignored so that logging never fails a call.
+#[derive(Clone)]
+pub struct AuditLog {
+    writer: Arc<Mutex<Box<dyn Write + Send>>>,
+    log_prompts: bool,
+    redactions: Vec<Regex>,
+}
+
+/// One line of the audit log.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AuditRecord {
+    /// When the call finished, in RFC 3339.
+    pub timestamp: String,
+    pub model: String,
+    /// Schema name of the output type; `None` for text calls.
+    pub schema: Option<String>,
+    /// The call's `RequestOptions::tag`.
+    pub tag: Option<String>,
+    /// Stable FNV-1a hash of the prompt in hex, to match records against
+    /// prompts without storing them.
+    pub prompt_hash: String,
+    /// The redacted prompt; requires `AuditLog::with_prompts`.
+    pub prompt: Option<String>,
+    pub latency_ms: u64,
+    pub input_tokens: Option<u32>,
+    pub output_tokens: Option<u32>,
+    /// `success`, `refusal` or `error`.
+    pub outcome: String,
+    pub error: Option<String>,
+    pub cached: bool,
+    /// Cost in USD; requires `OpenAiClient::with_pricing`.
+    pub cost: Option<f64>,
+}
+
+impl AuditLog {
+    pub fn new(writer: impl Write + Send + 'static) -> Self {
+        Self {
+            writer: Arc::new(Mutex::new(Box::new(writer))),
+            log_prompts: false,
+            redactions: Vec::new(),
+        }
+    }
+
+    /// Appends to the file at `path`, creating it if needed.
+    pub fn to_file(path: impl AsRef<Path>) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Self::new(file))
+    }
+
+    /// Records the user prompt itself alongside its hash.
+    pub fn with_prompts(mut self, log_prompts: bool) -> Self {
+        self.log_prompts = log_prompts;
+        self
+    }
+
+    /// Replaces matches of `pattern` in recorded prompts and errors, e.g.
+    /// e-mail addresses.
+    pub fn with_redaction(mut self, pattern: Regex) -> Self {
+        self.redactions.push(pattern);
+        self
+    }
+
+    /// Records a call; `meta` is the response metadata of a successful
+    /// schema call.
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn record<R>(
+        &self,
+        model: &str,
+        schema: Option<&str>,
+        tag: Option<&str>,
+        prompt: &str,
+        result: &Result<R, Box<dyn Error>>,
+        meta: Option<&ResponseMeta>,
+        latency: Duration,
+    ) {
+        let usage = meta.and_then(|meta| meta.usage.as_ref());
+        let outcome = match CallOutcome::of(result) {
+            CallOutcome::Success => "success",
+            CallOutcome::Refusal => "refusal",
+            CallOutcome::Error => "error",
+        };
+        let record = AuditRecord {
+            timestamp: Timestamp::now().to_string(),
+            model: model.to_string(),
+            schema: schema.map(str::to_string),
+            tag: tag.map(str::to_string),
+            prompt_hash: format!("{:016x}", canonical::fnv1a(prompt.as_bytes())),
+            prompt: self.log_prompts.then(|| self.redact(prompt)),
+            latency_ms: latency.as_millis() as u64,
+            input_tokens: usage.map(|usage| usage.prompt_tokens),
+            output_tokens: usage.map(|usage| usage.completion_tokens),
+            outcome: outcome.to_string(),
+            error: result
+                .as_ref()
+                .err()
+                .map(|err| self.redact(&err.to_string())),
+            cached: meta.is_some_and(|meta| meta.cached),
+            cost: meta.and_then(|meta| meta.cost),
+        };
+        self.write(&record);
+    }
+
+    /// Records a request sent outside schema and text calls, taking the model,
+    /// schema and prompt from its JSON body where it has one.
+    pub(crate) fn record_request(
+        &self,
+        model: &str,
+        body: Option<&Value>,
+        result: &Result<HttpResponse, Box<dyn Error>>,
+        latency: Duration,
+    ) {
+        let prompt = body.map(request_prompt).unwrap_or_default();
+        let error = match result {
+            Ok(res) if res.status.is_success() => None,
+            Ok(res) => Some(format!("HTTP {}", res.status)),
+            Err(err) => Some(self.redact(&err.to_string())),
+        };
+        let record = AuditRecord {
+            timestamp: Timestamp::now().to_string(),
+            model: body
+                .and_then(|body| body["model"].as_str())
+                .unwrap_or(model)
+                .to_string(),
+            schema: body
+                .and_then(|body| body["response_format"]["json_schema"]["name"].as_str())
+                .map(str::to_string),
+            tag: None,
+            prompt_hash: format!("{:016x}", canonical::fnv1a(prompt.as_bytes())),
+            prompt: self.log_prompts.then(|| self.redact(&prompt)),
+            latency_ms: latency.as_millis() as u64,
+            input_tokens: None,
+            output_tokens: None,
+            outcome: if error.is_some() { "error" } else { "success" }.to_string(),
+            error,
+            cached: false,
+            cost: None,
+        };
+        self.write(&record);
+    }
+
+    fn write(&self, record: &AuditRecord) {
+        let Ok(mut line) = serde_json::to_string(record) else {
+            return;
+        };
+        line.push('\n');
+        let mut writer = self.writer.lock().unwrap();
+        let _ = writer
+            .write_all(line.as_bytes())
+            .and_then(|_| writer.flush());
+    }
+
+    fn redact(&self, text: &str) -> String {
+        self.redactions
+            .iter()
+            .fold(text.to_string(), |text, pattern| {
+                pattern.replace_all(&text, REDACTED).into_owned()
+            })
+    }
+}
+
+/// The user's text in a request body: the last user message of a chat, or
+/// the `input` of an embedding or moderation request.
+fn request_prompt(body: &Value) -> String {
+    let text_of = |content: &Value| match content {
+        Value::String(text) => text.clone(),
+        Value::Array(parts) => parts
+            .iter()
+            .filter_map(|part| part.as_str().or_else(|| part["text"].as_str()))
+            .collect::<Vec<_>>()
+            .join("\n"),
+        _ => String::new(),
+    };
+    let last_user = body["messages"].as_array().and_then(|messages| {
+        messages
+            .iter()
+            .rev()
+            .find(|message| message["role"] == "user")
+    });
+    match last_user {
+        Some(message) => text_of(&message["content"]),
+        None => text_of(&body["input"]),
+    }
+}
+
+impl fmt::Debug for AuditLog {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AuditLog")
+            .field("log_prompts", &self.log_prompts)
+            .field("redactions", &self.redactions)
+            .finish()
+    }
+}
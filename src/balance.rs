@@ -0,0 +1,460 @@
+use crate::credentials::{ExposeSecret, SecretString};
+use crate::retry;
+use crate::time::Instant;
+use crate::transport::{
+    self, HttpRequest, HttpResponse, HttpTransport, InsecureEndpoint, TransportError,
+};
+use futures::future::BoxFuture;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, AUTHORIZATION, RETRY_AFTER};
+use reqwest::Url;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+const DEFAULT_FAILURE_THRESHOLD: u32 = 3;
+const DEFAULT_COOLDOWN: Duration = Duration::from_secs(30);
+/// Weight of the newest sample in a deployment's latency average.
+const LATENCY_SMOOTHING: f64 = 0.3;
+/// Smallest share of its weight a just rate-limited deployment keeps with
+/// `Routing::LeastRecentlyRateLimited`.
+const MIN_RATE_LIMITED_SHARE: f64 = 0.05;
+/// Headers other than `Authorization` that carry an API key, by provider.
+const API_KEY_HEADERS: &[&str] = &["api-key", "x-api-key", "x-goog-api-key"];
+
+/// How a `LoadBalancer` chooses among healthy deployments.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Routing {
+    /// Smooth weighted round-robin.
+    #[default]
+    RoundRobin,
+    /// Weighted round-robin that scales down deployments rate limited within
+    /// the last two cooldowns by how recently that was, so requests stay off
+    /// keys close to their limits while those keys keep some share.
+    LeastRecentlyRateLimited,
+}
+
+/// One deployment of the model, e.g. an Azure OpenAI deployment in one region.
+#[derive(Debug, Clone)]
+pub struct Deployment {
+    endpoint: String,
+    weight: u32,
+    api_key: Option<SecretString>,
+    headers: Vec<(String, String)>,
+}
+
+impl Deployment {
+    /// `endpoint` is the deployment's chat completions URL, in the same form
+    /// as the client's endpoint.
+    pub fn new(endpoint: impl Into<String>) -> Self {
+        Self {
+            endpoint: endpoint.into(),
+            weight: 1,
+            api_key: None,
+            headers: Vec::new(),
+        }
+    }
+
+    /// Share of requests relative to the other deployments; 1 by default.
+    pub fn with_weight(mut self, weight: u32) -> Self {
+        self.weight = weight;
+        self
+    }
+
+    /// Sends this key instead of the client's, in whichever header the
+    /// client's provider uses for it.
+    pub fn with_api_key(mut self, api_key: impl Into<String>) -> Self {
+        self.api_key = Some(SecretString::from(api_key.into()));
+        self
+    }
+
+    /// Sets a header on requests to this deployment, replacing the client's,
+    /// e.g. its own `api-key`.
+    pub fn with_header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+
+    /// The endpoint without `/chat/completions` or a query, which prefixes
+    /// the URLs of every resource of the deployment.
+    fn base(&self) -> &str {
+        let endpoint = self.endpoint.split('?').next().unwrap_or_default();
+        let endpoint = endpoint.trim_end_matches('/');
+        endpoint
+            .strip_suffix("/chat/completions")
+            .unwrap_or(endpoint)
+    }
+}
+
+/// The health and latency of a deployment as seen by a `LoadBalancer`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DeploymentStatus {
+    pub endpoint: String,
+    pub healthy: bool,
+    /// Moving average of the time to response headers.
+    pub latency: Option<Duration>,
+    pub requests: u64,
+    pub failures: u64,
+    /// Requests answered with a 429.
+    pub rate_limited: u64,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    current: f64,
+    consecutive_failures: u32,
+    ejected_until: Option<Instant>,
+    last_rate_limited: Option<Instant>,
+    latency: Option<f64>,
+    requests: u64,
+    failures: u64,
+    rate_limited: u64,
+}
+
+/// A transport that spreads requests over several deployments of the same
+/// model by smooth weighted round-robin, presenting them to the client as
+/// one endpoint:
+///
+/// ```ignore
+/// let client = OpenAiClient::new(http, &east, model, key).with_transport(
+///     LoadBalancer::new(http, [Deployment::new(&east).with_weight(3), Deployment::new(&west)]),
+/// );
+/// ```
+///
+/// Requests to any deployment's URLs (completions and sibling resources such
+/// as embeddings) are rebased onto the chosen deployment; other requests are
+/// sent unchanged. A deployment that fails several requests in a row, with a
+/// transport error or a 5xx, is skipped for a cooldown and then tried again;
+/// one answering 429 or 401 is skipped right away, for as long as its
+/// Retry-After asks or else the cooldown, and that Retry-After is dropped from
+/// the response when another deployment can take the client's retry. If
+/// every deployment is cooling down, the one that recovers first is used.
+/// Health is judged only from the responses to requests sent; deployments
+/// are not probed in the background.
+/// Requests are refused with `InsecureEndpoint` rather than rebased onto a
+/// deployment reached less securely than the URL the client allowed, such as
+/// plain HTTP instead of https.
+///
+/// Deployments may share an endpoint, so several API keys sharing the load
+/// of one organization are balanced the same way:
+///
+/// ```ignore
+/// let client = OpenAiClient::new(http, &endpoint, model, &keys[0]).with_transport(
+///     LoadBalancer::for_api_keys(http, &endpoint, keys)
+///         .with_routing(Routing::LeastRecentlyRateLimited),
+/// );
+/// ```
+#[derive(Clone)]
+pub struct LoadBalancer {
+    inner: Arc<dyn HttpTransport>,
+    deployments: Arc<Vec<Deployment>>,
+    state: Arc<Mutex<Vec<State>>>,
+    failure_threshold: u32,
+    cooldown: Duration,
+    latency_aware: bool,
+    routing: Routing,
+}
+
+impl LoadBalancer {
+    pub fn new(
+        inner: impl HttpTransport + 'static,
+        deployments: impl IntoIterator<Item = Deployment>,
+    ) -> Self {
+        let deployments: Vec<Deployment> = deployments.into_iter().collect();
+        let state = deployments.iter().map(|_| State::default()).collect();
+        Self {
+            inner: Arc::new(inner),
+            deployments: Arc::new(deployments),
+            state: Arc::new(Mutex::new(state)),
+            failure_threshold: DEFAULT_FAILURE_THRESHOLD,
+            cooldown: DEFAULT_COOLDOWN,
+            latency_aware: false,
+            routing: Routing::RoundRobin,
+        }
+    }
+
+    /// One deployment of `endpoint` per API key.
+    pub fn for_api_keys(
+        inner: impl HttpTransport + 'static,
+        endpoint: impl Into<String>,
+        api_keys: impl IntoIterator<Item = impl Into<String>>,
+    ) -> Self {
+        let endpoint = endpoint.into();
+        let deployments = api_keys
+            .into_iter()
+            .map(|key| Deployment::new(endpoint.clone()).with_api_key(key));
+        Self::new(inner, deployments)
+    }
+
+    /// Consecutive failures after which a deployment is skipped (default 3),
+    /// and for how long (default 30s).
+    pub fn with_health_check(mut self, failure_threshold: u32, cooldown: Duration) -> Self {
+        self.failure_threshold = failure_threshold.max(1);
+        self.cooldown = cooldown;
+        self
+    }
+
+    /// Scales each deployment's weight by how much slower than the fastest
+    /// deployment it has been responding, so slow regions get fewer requests.
+    pub fn with_latency_routing(mut self, latency_aware: bool) -> Self {
+        self.latency_aware = latency_aware;
+        self
+    }
+
+    pub fn with_routing(mut self, routing: Routing) -> Self {
+        self.routing = routing;
+        self
+    }
+
+    pub fn status(&self) -> Vec<DeploymentStatus> {
+        let now = Instant::now();
+        let state = self.state.lock().unwrap();
+        self.deployments
+            .iter()
+            .zip(state.iter())
+            .map(|(deployment, state)| DeploymentStatus {
+                endpoint: deployment.endpoint.clone(),
+                healthy: state.ejected_until.is_none_or(|until| until <= now),
+                latency: state.latency.map(Duration::from_secs_f64),
+                requests: state.requests,
+                failures: state.failures,
+                rate_limited: state.rate_limited,
+            })
+            .collect()
+    }
+
+    /// Picks the next deployment by smooth weighted round-robin among the
+    /// healthy ones.
+    fn pick(&self) -> Option<usize> {
+        let now = Instant::now();
+        let mut state = self.state.lock().unwrap();
+        let healthy: Vec<usize> = (0..state.len())
+            .filter(|&index| state[index].ejected_until.is_none_or(|until| until <= now))
+            .filter(|&index| self.deployments[index].weight > 0)
+            .collect();
+        if healthy.is_empty() {
+            return (0..state.len()).min_by_key(|&index| state[index].ejected_until);
+        }
+        let fastest = healthy
+            .iter()
+            .filter_map(|&index| state[index].latency)
+            .fold(f64::INFINITY, f64::min);
+        let weights: Vec<(usize, f64)> = healthy
+            .iter()
+            .map(|&index| {
+                let weight = f64::from(self.deployments[index].weight)
+                    * self.rate_limited_share(&state[index], now);
+                match (self.latency_aware, state[index].latency) {
+                    (true, Some(latency)) if latency > 0.0 => (index, weight * fastest / latency),
+                    _ => (index, weight),
+                }
+            })
+            .collect();
+        let total: f64 = weights.iter().map(|(_, weight)| weight).sum();
+        for (index, weight) in &weights {
+            state[*index].current += weight;
+        }
+        let (chosen, _) = weights.iter().copied().max_by(|(a, _), (b, _)| {
+            state[*a]
+                .current
+                .total_cmp(&state[*b].current)
+                // Prefer the earlier deployment on ties.
+                .then(b.cmp(a))
+        })?;
+        state[chosen].current -= total;
+        Some(chosen)
+    }
+
+    /// The share of its weight a deployment gets for having been rate limited:
+    /// rising linearly from `MIN_RATE_LIMITED_SHARE` back to all of it over
+    /// two cooldowns.
+    fn rate_limited_share(&self, state: &State, now: Instant) -> f64 {
+        let (Routing::LeastRecentlyRateLimited, Some(limited)) =
+            (self.routing, state.last_rate_limited)
+        else {
+            return 1.0;
+        };
+        let memory = self.cooldown.saturating_mul(2).as_secs_f64();
+        if memory == 0.0 {
+            return 1.0;
+        }
+        let elapsed = now.saturating_duration_since(limited).as_secs_f64();
+        (elapsed / memory).clamp(MIN_RATE_LIMITED_SHARE, 1.0)
+    }
+
+    /// Whether a deployment other than `index` can take requests now.
+    fn has_alternative(&self, index: usize) -> bool {
+        let now = Instant::now();
+        let state = self.state.lock().unwrap();
+        (0..state.len()).any(|other| {
+            other != index
+                && self.deployments[other].weight > 0
+                && state[other].ejected_until.is_none_or(|until| until <= now)
+        })
+    }
+
+    fn record(
+        &self,
+        index: usize,
+        result: &Result<HttpResponse, TransportError>,
+        latency: Duration,
+    ) {
+        let mut state = self.state.lock().unwrap();
+        let state = &mut state[index];
+        state.requests += 1;
+        let status = result.as_ref().ok().map(|res| res.status.as_u16());
+        if let (Ok(res), Some(401 | 429)) = (result, status) {
+            let now = Instant::now();
+            state.failures += 1;
+            state.consecutive_failures = 0;
+            state.ejected_until =
+                Some(now + retry::retry_after(&res.headers).unwrap_or(self.cooldown));
+            if status == Some(429) {
+                state.rate_limited += 1;
+                state.last_rate_limited = Some(now);
+            }
+            return;
+        }
+        let failed = match result {
+            Ok(res) => res.status.is_server_error(),
+            Err(_) => true,
+        };
+        if failed {
+            state.failures += 1;
+            state.consecutive_failures += 1;
+            if state.consecutive_failures >= self.failure_threshold {
+                state.ejected_until = Some(Instant::now() + self.cooldown);
+                state.consecutive_failures = 0;
+            }
+            return;
+        }
+        state.consecutive_failures = 0;
+        state.ejected_until = None;
+        let latency = latency.as_secs_f64();
+        state.latency = Some(match state.latency {
+            Some(average) => average + LATENCY_SMOOTHING * (latency - average),
+            None => latency,
+        });
+    }
+
+    /// The part of `url` after the base of the deployment it targets, if any.
+    fn suffix(&self, url: &str) -> Option<String> {
+        self.deployments.iter().find_map(|deployment| {
+            let rest = url.strip_prefix(deployment.base())?;
+            (rest.is_empty() || rest.starts_with(['/', '?'])).then(|| rest.to_string())
+        })
+    }
+
+    /// Moves a request onto the deployment at `index`.
+    fn rebase(&self, mut request: HttpRequest, suffix: &str, index: usize) -> HttpRequest {
+        let target = &self.deployments[index];
+        request.url = format!("{}{}", target.base(), suffix);
+        if let Some(api_key) = &target.api_key {
+            set_api_key(&mut request.headers, api_key.expose_secret());
+        }
+        // The target's own query parameters, such as Azure's `api-version`,
+        // replace the request's parameters of the same name.
+        if target.endpoint.contains('?') {
+            request.url = merge_query(&request.url, &target.endpoint);
+        }
+        for (name, value) in &target.headers {
+            if let (Ok(name), Ok(value)) = (
+                HeaderName::try_from(name.as_str()),
+                HeaderValue::from_str(value),
+            ) {
+                request.headers.insert(name, value);
+            }
+        }
+        request
+    }
+}
+
+impl HttpTransport for LoadBalancer {
+    fn send(&self, request: HttpRequest) -> BoxFuture<'_, Result<HttpResponse, TransportError>> {
+        Box::pin(async move {
+            // Only requests for a deployment take a turn of the rotation.
+            let Some(suffix) = self.suffix(&request.url) else {
+                return self.inner.send(request).await;
+            };
+            let Some(index) = self.pick() else {
+                return self.inner.send(request).await;
+            };
+            let original = request.url.clone();
+            let request = self.rebase(request, &suffix, index);
+            // The client checked the URL it was given, not the deployment's.
+            if exposure(&request.url) > exposure(&original) {
+                return Err(Box::new(InsecureEndpoint { url: request.url }) as TransportError);
+            }
+            let started = Instant::now();
+            let mut result = self.inner.send(request).await;
+            self.record(index, &result, started.elapsed());
+            // The client would wait out the benched deployment's Retry-After
+            // although its retry goes to another one.
+            if let Ok(res) = &mut result {
+                if res.status.as_u16() == 429 && self.has_alternative(index) {
+                    res.headers.remove(RETRY_AFTER);
+                }
+            }
+            result
+        })
+    }
+}
+
+impl fmt::Debug for LoadBalancer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LoadBalancer")
+            .field("deployments", &self.deployments)
+            .field("failure_threshold", &self.failure_threshold)
+            .field("cooldown", &self.cooldown)
+            .field("latency_aware", &self.latency_aware)
+            .field("routing", &self.routing)
+            .finish()
+    }
+}
+
+/// Sets the query parameters of `endpoint` on `url`, keeping its other ones.
+fn merge_query(url: &str, endpoint: &str) -> String {
+    let (Ok(mut merged), Ok(target)) = (Url::parse(url), Url::parse(endpoint)) else {
+        return url.to_string();
+    };
+    let overrides: Vec<(String, String)> = target.query_pairs().into_owned().collect();
+    let kept: Vec<(String, String)> = merged
+        .query_pairs()
+        .into_owned()
+        .filter(|(name, _)| !overrides.iter().any(|(other, _)| other == name))
+        .collect();
+    merged
+        .query_pairs_mut()
+        .clear()
+        .extend_pairs(kept)
+        .extend_pairs(overrides);
+    merged.into()
+}
+
+/// How openly a request to `url` travels: over TLS, over plain HTTP on this
+/// machine, or over plain HTTP to another host.
+fn exposure(url: &str) -> u8 {
+    match (transport::is_plain_http(url), transport::is_loopback(url)) {
+        (false, _) => 0,
+        (true, true) => 1,
+        (true, false) => 2,
+    }
+}
+
+/// Replaces the key in the header that carries one, or sends it as a bearer
+/// token.
+fn set_api_key(headers: &mut HeaderMap, api_key: &str) {
+    let name = API_KEY_HEADERS
+        .iter()
+        .find(|name| headers.contains_key(**name))
+        .map(|name| HeaderName::from_static(name))
+        .unwrap_or(AUTHORIZATION);
+    let value = if name == AUTHORIZATION {
+        format!("Bearer {}", api_key)
+    } else {
+        api_key.to_string()
+    };
+    if let Ok(mut value) = HeaderValue::from_str(&value) {
+        value.set_sensitive(true);
+        headers.insert(name, value);
+    }
+}
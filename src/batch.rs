@@ -0,0 +1,22 @@
+use crate::openai::OpenAiClient;
+use futures::stream::{self, StreamExt};
+use schemars::JsonSchema;
+use serde::de::DeserializeOwned;
+use std::error::Error;
+
+impl OpenAiClient {
+    /// Runs `call_schema` for every prompt with at most `concurrency` requests in
+    /// flight. Results are returned in input order; a failed item does not affect
+    /// the others.
+    pub async fn call_schema_batch<T: DeserializeOwned + JsonSchema + Clone + 'static>(
+        &self,
+        prompts: Vec<String>,
+        concurrency: usize,
+    ) -> Vec<Result<T, Box<dyn Error>>> {
+        stream::iter(prompts)
+            .map(|prompt| async move { self.call_schema::<T>(&prompt).await })
+            .buffered(concurrency.max(1))
+            .collect()
+            .await
+    }
+}
@@ -0,0 +1,157 @@
+use base64::engine::general_purpose::STANDARD;
+use base64::read::DecoderReader;
+use base64::Engine;
+use bytes::Bytes;
+use schemars::gen::SchemaGenerator;
+use schemars::schema::{InstanceType, Metadata, Schema, SchemaObject, StringValidation};
+use schemars::JsonSchema;
+use serde::de::{self, Deserializer, Visitor};
+use serde::{Deserialize, Serialize, Serializer};
+use std::error::Error;
+use std::fmt;
+use std::io::Read;
+use std::ops::Deref;
+
+/// Binary data carried in structured output as a standard base64 string of at
+/// most `MAX_BYTES` decoded bytes.
+///
+/// The schema bounds the string's `maxLength`, so the model is told the limit
+/// and the token planner can size it. Oversized payloads are rejected before
+/// decoding; the rest are decoded as a stream, without an intermediate buffer.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+pub struct Base64Bytes<const MAX_BYTES: usize>(Bytes);
+
+impl<const MAX_BYTES: usize> Base64Bytes<MAX_BYTES> {
+    pub fn new(data: impl Into<Bytes>) -> Result<Self, BinaryError> {
+        let data = data.into();
+        if data.len() > MAX_BYTES {
+            return Err(BinaryError::TooLarge {
+                max_bytes: MAX_BYTES,
+            });
+        }
+        Ok(Self(data))
+    }
+
+    pub fn into_bytes(self) -> Bytes {
+        self.0
+    }
+
+    pub fn into_vec(self) -> Vec<u8> {
+        self.0.into()
+    }
+}
+
+impl<const MAX_BYTES: usize> Deref for Base64Bytes<MAX_BYTES> {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl<const MAX_BYTES: usize> AsRef<[u8]> for Base64Bytes<MAX_BYTES> {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+/// Decodes standard base64 from a reader, failing as soon as the output would
+/// exceed `max_bytes`.
+pub fn decode_base64(reader: impl Read, max_bytes: usize) -> Result<Bytes, BinaryError> {
+    let mut decoded = Vec::new();
+    DecoderReader::new(reader, &STANDARD)
+        .take(max_bytes as u64 + 1)
+        .read_to_end(&mut decoded)
+        .map_err(|err| BinaryError::Invalid(err.to_string()))?;
+    if decoded.len() > max_bytes {
+        return Err(BinaryError::TooLarge { max_bytes });
+    }
+    Ok(decoded.into())
+}
+
+/// Length of the base64 encoding of `bytes` bytes, including padding.
+fn encoded_len(bytes: usize) -> usize {
+    bytes.div_ceil(3) * 4
+}
+
+impl<const MAX_BYTES: usize> Serialize for Base64Bytes<MAX_BYTES> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&STANDARD.encode(&self.0))
+    }
+}
+
+impl<'de, const MAX_BYTES: usize> Deserialize<'de> for Base64Bytes<MAX_BYTES> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct Base64Visitor<const MAX_BYTES: usize>;
+
+        impl<const MAX_BYTES: usize> Visitor<'_> for Base64Visitor<MAX_BYTES> {
+            type Value = Base64Bytes<MAX_BYTES>;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "a base64 string of at most {} bytes", MAX_BYTES)
+            }
+
+            fn visit_str<E: de::Error>(self, encoded: &str) -> Result<Self::Value, E> {
+                if encoded.len() > encoded_len(MAX_BYTES) {
+                    return Err(E::custom(BinaryError::TooLarge {
+                        max_bytes: MAX_BYTES,
+                    }));
+                }
+                decode_base64(encoded.as_bytes(), MAX_BYTES)
+                    .map(Base64Bytes)
+                    .map_err(E::custom)
+            }
+        }
+
+        deserializer.deserialize_str(Base64Visitor::<MAX_BYTES>)
+    }
+}
+
+impl<const MAX_BYTES: usize> JsonSchema for Base64Bytes<MAX_BYTES> {
+    fn schema_name() -> String {
+        format!("Base64Bytes_{}", MAX_BYTES)
+    }
+
+    fn is_referenceable() -> bool {
+        false
+    }
+
+    fn json_schema(_: &mut SchemaGenerator) -> Schema {
+        SchemaObject {
+            instance_type: Some(InstanceType::String.into()),
+            metadata: Some(Box::new(Metadata {
+                description: Some(format!(
+                    "Standard base64-encoded data, at most {} bytes before encoding",
+                    MAX_BYTES
+                )),
+                ..Default::default()
+            })),
+            string: Some(Box::new(StringValidation {
+                max_length: Some(encoded_len(MAX_BYTES).min(u32::MAX as usize) as u32),
+                min_length: None,
+                pattern: Some("^[A-Za-z0-9+/]*={0,2}$".to_string()),
+            })),
+            ..Default::default()
+        }
+        .into()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BinaryError {
+    TooLarge { max_bytes: usize },
+    Invalid(String),
+}
+
+impl fmt::Display for BinaryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BinaryError::TooLarge { max_bytes } => {
+                write!(f, "Binary field exceeds {} bytes", max_bytes)
+            }
+            BinaryError::Invalid(message) => write!(f, "Invalid base64: {}", message),
+        }
+    }
+}
+
+impl Error for BinaryError {}
@@ -0,0 +1,104 @@
+//! A synchronous client for CLI tools and codebases without an async runtime.
+//!
+//! ```ignore
+//! let client = blocking::OpenAiClient::new(
+//!     openai::OpenAiClient::new(http, endpoint, model, key).with_system_role("..."),
+//! )?;
+//! let review: Review = client.call_schema("Check this")?;
+//! ```
+//!
+//! Calls run on a runtime owned by the client, so they must not be made from
+//! within an async context.
+
+use crate::dynamic::DynSchema;
+use crate::openai::{self, StructuredResponse};
+use crate::options::RequestOptions;
+use schemars::JsonSchema;
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use std::error::Error;
+use std::future::Future;
+use std::io;
+use std::sync::Arc;
+use tokio::runtime::{self, Runtime};
+
+/// Drives an async `OpenAiClient` to completion on every call. Clones share
+/// the runtime.
+#[derive(Clone)]
+pub struct OpenAiClient {
+    client: openai::OpenAiClient,
+    runtime: Arc<Runtime>,
+}
+
+impl OpenAiClient {
+    /// Wraps a configured async client.
+    pub fn new(client: openai::OpenAiClient) -> io::Result<Self> {
+        let runtime = runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()?;
+        Ok(Self {
+            client,
+            runtime: Arc::new(runtime),
+        })
+    }
+
+    /// The wrapped async client.
+    pub fn inner(&self) -> &openai::OpenAiClient {
+        &self.client
+    }
+
+    /// Blocking `call_schema`; panics if called inside a tokio runtime.
+    pub fn call_schema<T: DeserializeOwned + JsonSchema + Clone + 'static>(
+        &self,
+        user_prompt: &str,
+    ) -> Result<T, Box<dyn Error>> {
+        self.runtime.block_on(self.client.call_schema(user_prompt))
+    }
+
+    /// Blocking `call_schema_with`; panics if called inside a tokio runtime.
+    pub fn call_schema_with<T: DeserializeOwned + JsonSchema + Clone + 'static>(
+        &self,
+        user_prompt: &str,
+        options: &RequestOptions,
+    ) -> Result<T, Box<dyn Error>> {
+        self.runtime
+            .block_on(self.client.call_schema_with(user_prompt, options))
+    }
+
+    /// Blocking `call_schema_with_meta`; panics if called inside a tokio runtime.
+    pub fn call_schema_with_meta<T: DeserializeOwned + JsonSchema + Clone + 'static>(
+        &self,
+        user_prompt: &str,
+        options: &RequestOptions,
+    ) -> Result<StructuredResponse<T>, Box<dyn Error>> {
+        self.runtime
+            .block_on(self.client.call_schema_with_meta(user_prompt, options))
+    }
+
+    /// Blocking `call_schema_value`; panics if called inside a tokio runtime.
+    pub fn call_schema_value(
+        &self,
+        schema: &DynSchema,
+        user_prompt: &str,
+        options: &RequestOptions,
+    ) -> Result<Value, Box<dyn Error>> {
+        self.runtime
+            .block_on(self.client.call_schema_value(schema, user_prompt, options))
+    }
+
+    /// Blocking `call_text`; panics if called inside a tokio runtime.
+    pub fn call_text(&self, user_prompt: &str) -> Result<String, Box<dyn Error>> {
+        self.runtime.block_on(self.client.call_text(user_prompt))
+    }
+
+    /// Runs any other method of the async client to completion, e.g.
+    /// `client.run(|client| client.moderate(text))`; panics if called inside
+    /// a tokio runtime.
+    pub fn run<'a, F, Fut>(&'a self, call: F) -> Fut::Output
+    where
+        F: FnOnce(&'a openai::OpenAiClient) -> Fut,
+        Fut: Future,
+    {
+        self.runtime.block_on(call(&self.client))
+    }
+}
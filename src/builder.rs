@@ -0,0 +1,385 @@
+use crate::credentials::CredentialProvider;
+use crate::openai::OpenAiClient;
+use crate::retry::RetryPolicy;
+use crate::transport;
+use reqwest::{Client, Url};
+use serde::Deserialize;
+use std::error::Error;
+use std::fmt;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+const DEFAULT_BASE_URL: &str = "https://api.openai.com/v1";
+
+/// Settings read by `OpenAiClientBuilder::from_config_file`.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ConfigFile {
+    api_key: Option<String>,
+    /// Environment variable holding the API key.
+    api_key_env: Option<String>,
+    base_url: Option<String>,
+    model: Option<String>,
+    organization: Option<String>,
+    project: Option<String>,
+    proxy: Option<String>,
+    timeout_secs: Option<f64>,
+    max_attempts: Option<u32>,
+    system_role: Option<String>,
+    insecure_loopback: Option<bool>,
+}
+
+/// Builds an `OpenAiClient` from explicit settings, the environment and
+/// config files, so keys stay out of source code. Later sources override
+/// earlier ones:
+///
+/// ```ignore
+/// let client = OpenAiClient::builder()
+///     .from_config_file("llm.toml")?
+///     .from_env()
+///     .build()?;
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct OpenAiClientBuilder {
+    api_key: Option<String>,
+    credentials: Option<Credentials>,
+    base_url: Option<String>,
+    model: Option<String>,
+    organization: Option<String>,
+    project: Option<String>,
+    proxy: Option<String>,
+    timeout: Option<Duration>,
+    max_attempts: Option<u32>,
+    system_role: Option<String>,
+    insecure_loopback: bool,
+}
+
+/// The credential provider set with `OpenAiClientBuilder::credentials`.
+#[derive(Clone)]
+struct Credentials(Arc<dyn CredentialProvider>);
+
+impl fmt::Debug for Credentials {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Credentials")
+    }
+}
+
+/// Settings that are missing or malformed, found when building a client.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfigError {
+    MissingApiKey,
+    MissingModel,
+    /// `api_key_env` names a variable that is not set.
+    UnsetKeyVariable {
+        name: String,
+    },
+    /// A config file sets both `api_key` and `api_key_env`.
+    ConflictingApiKeys,
+    InvalidTimeout {
+        secs: String,
+        reason: String,
+    },
+    InvalidBaseUrl {
+        url: String,
+        reason: String,
+    },
+    InvalidProxy {
+        url: String,
+        reason: String,
+    },
+    /// A plain-HTTP base URL, refused unless it is on this machine and
+    /// `insecure_loopback` is set.
+    InsecureBaseUrl {
+        url: String,
+    },
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::MissingApiKey => write!(
+                f,
+                "No API key; set OPENAI_API_KEY, `api_key_env` in the config file or call \
+                 api_key() or credentials()"
+            ),
+            ConfigError::MissingModel => write!(
+                f,
+                "No model; set OPENAI_MODEL, `model` in the config file or call model()"
+            ),
+            ConfigError::UnsetKeyVariable { name } => {
+                write!(f, "The API key variable {} is not set", name)
+            }
+            ConfigError::ConflictingApiKeys => write!(
+                f,
+                "The config file sets both `api_key` and `api_key_env`; keep one"
+            ),
+            ConfigError::InvalidTimeout { secs, reason } => {
+                write!(f, "Invalid `timeout_secs` {}: {}", secs, reason)
+            }
+            ConfigError::InvalidBaseUrl { url, reason } => {
+                write!(f, "Invalid base URL {:?}: {}", url, reason)
+            }
+            ConfigError::InvalidProxy { url, reason } => {
+                write!(f, "Invalid proxy {:?}: {}", url, reason)
+            }
+            ConfigError::InsecureBaseUrl { url } => write!(
+                f,
+                "Refusing plain-HTTP base URL {:?}; use https, or set `insecure_loopback` \
+                 for a server on this machine",
+                url
+            ),
+        }
+    }
+}
+
+impl Error for ConfigError {}
+
+impl OpenAiClient {
+    pub fn builder() -> OpenAiClientBuilder {
+        OpenAiClientBuilder::default()
+    }
+}
+
+impl OpenAiClientBuilder {
+    pub fn api_key(mut self, api_key: impl Into<String>) -> Self {
+        self.api_key = Some(api_key.into());
+        self
+    }
+
+    /// Asks `provider` for the API key before every request, instead of a
+    /// static key. See `OpenAiClient::with_credentials`.
+    pub fn credentials(mut self, provider: impl CredentialProvider + 'static) -> Self {
+        self.credentials = Some(Credentials(Arc::new(provider)));
+        self
+    }
+
+    /// The API root, e.g. `https://api.openai.com/v1` (the default) or a
+    /// gateway's; requests go to `<base_url>/chat/completions`.
+    pub fn base_url(mut self, base_url: impl Into<String>) -> Self {
+        self.base_url = Some(base_url.into());
+        self
+    }
+
+    pub fn model(mut self, model: impl Into<String>) -> Self {
+        self.model = Some(model.into());
+        self
+    }
+
+    pub fn organization(mut self, organization: impl Into<String>) -> Self {
+        self.organization = Some(organization.into());
+        self
+    }
+
+    pub fn project(mut self, project: impl Into<String>) -> Self {
+        self.project = Some(project.into());
+        self
+    }
+
+    /// Sends every request through an HTTP(S) or SOCKS proxy. Without one,
+    /// the `HTTPS_PROXY` and `NO_PROXY` variables apply.
+    pub fn proxy(mut self, url: impl Into<String>) -> Self {
+        self.proxy = Some(url.into());
+        self
+    }
+
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Retries transient failures with the default backoff, up to
+    /// `max_attempts` attempts in total.
+    pub fn max_attempts(mut self, max_attempts: u32) -> Self {
+        self.max_attempts = Some(max_attempts);
+        self
+    }
+
+    pub fn system_role(mut self, role: impl Into<String>) -> Self {
+        self.system_role = Some(role.into());
+        self
+    }
+
+    /// Allows a plain-HTTP base URL on this machine, such as a local Ollama
+    /// server. See `OpenAiClient::with_insecure_loopback`.
+    pub fn insecure_loopback(mut self, allow: bool) -> Self {
+        self.insecure_loopback = allow;
+        self
+    }
+
+    /// Reads `OPENAI_API_KEY`, `OPENAI_BASE_URL`, `OPENAI_ORG_ID`,
+    /// `OPENAI_PROJECT_ID`, `OPENAI_MODEL` and `OPENAI_PROXY`, where set and
+    /// not empty, and `OPENAI_INSECURE_LOOPBACK` (`true` or `1`).
+    #[allow(clippy::wrong_self_convention)]
+    pub fn from_env(self) -> Self {
+        self.read_vars(|name| std::env::var(name).ok())
+    }
+
+    fn read_vars(mut self, var: impl Fn(&str) -> Option<String>) -> Self {
+        let var = |name| var(name).filter(|value| !value.trim().is_empty());
+        let settings = [
+            ("OPENAI_API_KEY", &mut self.api_key),
+            ("OPENAI_BASE_URL", &mut self.base_url),
+            ("OPENAI_ORG_ID", &mut self.organization),
+            ("OPENAI_PROJECT_ID", &mut self.project),
+            ("OPENAI_MODEL", &mut self.model),
+            ("OPENAI_PROXY", &mut self.proxy),
+        ];
+        for (name, setting) in settings {
+            if let Some(value) = var(name) {
+                *setting = Some(value);
+            }
+        }
+        if let Some(value) = var("OPENAI_INSECURE_LOOPBACK") {
+            let value = value.trim();
+            self.insecure_loopback = value == "1" || value.eq_ignore_ascii_case("true");
+        }
+        self
+    }
+
+    /// Reads the settings of a TOML file, e.g.
+    ///
+    /// ```toml
+    /// model = "gpt-4o-2024-08-06"
+    /// api_key_env = "ACME_OPENAI_KEY"   # or `api_key`, kept out of version control
+    /// base_url = "https://gateway.acme.internal/v1"
+    /// organization = "org-..."
+    /// project = "proj_..."
+    /// proxy = "http://proxy.acme.internal:3128"
+    /// timeout_secs = 30
+    /// max_attempts = 3
+    /// system_role = "You extract invoices."
+    /// insecure_loopback = false         # true for an http://localhost server
+    /// ```
+    #[allow(clippy::wrong_self_convention)]
+    pub fn from_config_file(self, path: impl AsRef<Path>) -> Result<Self, Box<dyn Error>> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path)
+            .map_err(|err| format!("Cannot read {}: {}", path.display(), err))?;
+        let file: ConfigFile = toml::from_str(&text)
+            .map_err(|err| format!("Invalid config file {}: {}", path.display(), err))?;
+        self.read_file(file)
+    }
+
+    fn read_file(mut self, file: ConfigFile) -> Result<Self, Box<dyn Error>> {
+        if file.api_key.is_some() && file.api_key_env.is_some() {
+            return Err(Box::new(ConfigError::ConflictingApiKeys));
+        }
+        if let Some(name) = file.api_key_env {
+            let key = std::env::var(&name)
+                .map_err(|_| ConfigError::UnsetKeyVariable { name: name.clone() })?;
+            self.api_key = Some(key);
+        }
+        let settings = [
+            (file.api_key, &mut self.api_key),
+            (file.base_url, &mut self.base_url),
+            (file.model, &mut self.model),
+            (file.organization, &mut self.organization),
+            (file.project, &mut self.project),
+            (file.proxy, &mut self.proxy),
+            (file.system_role, &mut self.system_role),
+        ];
+        for (value, setting) in settings {
+            if value.is_some() {
+                *setting = value;
+            }
+        }
+        if let Some(timeout_secs) = file.timeout_secs {
+            let timeout = Duration::try_from_secs_f64(timeout_secs).map_err(|err| {
+                ConfigError::InvalidTimeout {
+                    secs: timeout_secs.to_string(),
+                    reason: err.to_string(),
+                }
+            })?;
+            self.timeout = Some(timeout);
+        }
+        if file.max_attempts.is_some() {
+            self.max_attempts = file.max_attempts;
+        }
+        if let Some(allow) = file.insecure_loopback {
+            self.insecure_loopback = allow;
+        }
+        Ok(self)
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn http_client(proxy: Option<&str>) -> Result<Client, Box<dyn Error>> {
+        let mut http = Client::builder();
+        if let Some(url) = proxy {
+            let proxy = reqwest::Proxy::all(url).map_err(|err| ConfigError::InvalidProxy {
+                url: url.to_string(),
+                reason: err.to_string(),
+            })?;
+            http = http.proxy(proxy);
+        }
+        Ok(http.build()?)
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    fn http_client(proxy: Option<&str>) -> Result<Client, Box<dyn Error>> {
+        match proxy {
+            Some(url) => Err(Box::new(ConfigError::InvalidProxy {
+                url: url.to_string(),
+                reason: "proxies are up to the host on wasm32".to_string(),
+            })),
+            None => Ok(Client::builder().build()?),
+        }
+    }
+
+    /// Validates the settings and builds the client.
+    pub fn build(self) -> Result<OpenAiClient, Box<dyn Error>> {
+        let api_key = self.api_key.filter(|key| !key.trim().is_empty());
+        if api_key.is_none() && self.credentials.is_none() {
+            return Err(Box::new(ConfigError::MissingApiKey));
+        }
+        let model = self
+            .model
+            .filter(|model| !model.trim().is_empty())
+            .ok_or(ConfigError::MissingModel)?;
+        let base_url = self
+            .base_url
+            .unwrap_or_else(|| DEFAULT_BASE_URL.to_string());
+        let parsed = Url::parse(&base_url).map_err(|err| ConfigError::InvalidBaseUrl {
+            url: base_url.clone(),
+            reason: err.to_string(),
+        })?;
+        if !matches!(parsed.scheme(), "http" | "https") {
+            return Err(Box::new(ConfigError::InvalidBaseUrl {
+                url: base_url,
+                reason: "expected an http or https URL".to_string(),
+            }));
+        }
+        let allowed = self.insecure_loopback && transport::is_loopback(&base_url);
+        if transport::is_plain_http(&base_url) && !allowed {
+            return Err(Box::new(ConfigError::InsecureBaseUrl { url: base_url }));
+        }
+
+        let endpoint = format!("{}/chat/completions", base_url.trim_end_matches('/'));
+        let http = Self::http_client(self.proxy.as_deref())?;
+        let mut client = OpenAiClient::new(http, endpoint, model, api_key.unwrap_or_default())
+            .with_api_base(base_url)
+            .with_insecure_loopback(self.insecure_loopback);
+        if let Some(Credentials(provider)) = self.credentials {
+            client = client.with_credentials(provider);
+        }
+        if let Some(organization) = self.organization {
+            client = client.with_organization(organization);
+        }
+        if let Some(project) = self.project {
+            client = client.with_project(project);
+        }
+        if let Some(timeout) = self.timeout {
+            client = client.with_timeout(timeout);
+        }
+        if let Some(max_attempts) = self.max_attempts {
+            client = client.with_retry(RetryPolicy {
+                max_attempts,
+                ..RetryPolicy::default()
+            });
+        }
+        if let Some(role) = self.system_role {
+            client = client.with_system_role(role);
+        }
+        Ok(client)
+    }
+}
@@ -0,0 +1,137 @@
+use crate::canonical;
+use crate::time::Instant;
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Storage for cached responses. Keys are hex hashes of the full request (model,
+/// messages, schema and options); values are the normalized response bodies.
+///
+/// Calls are synchronous and made on the request path, so backends over a
+/// network store (disk, Redis) should use a blocking client with a short timeout.
+pub trait CacheBackend: Send + Sync {
+    fn get(&self, key: &str) -> Option<String>;
+    fn put(&self, key: &str, value: String);
+}
+
+/// Cache key of a request body: its canonical JSON hash, so field order and
+/// number formatting do not matter.
+pub(crate) fn key(body: &Value) -> String {
+    format!(
+        "{:016x}",
+        canonical::fnv1a(canonical::canonicalize(body).as_bytes())
+    )
+}
+
+/// In-memory cache that evicts the least recently used entry when full, and
+/// optionally expires entries after a time to live.
+pub struct MemoryCache {
+    capacity: usize,
+    ttl: Option<Duration>,
+    state: Mutex<MemoryState>,
+}
+
+#[derive(Default)]
+struct MemoryState {
+    entries: HashMap<String, Entry>,
+    /// Last-use tick => key, oldest first.
+    recency: BTreeMap<u64, String>,
+    tick: u64,
+}
+
+struct Entry {
+    value: String,
+    inserted: Instant,
+    used: u64,
+}
+
+impl MemoryCache {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            ttl: None,
+            state: Mutex::new(MemoryState::default()),
+        }
+    }
+
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = Some(ttl);
+        self
+    }
+
+    pub fn len(&self) -> usize {
+        self.state.lock().unwrap().entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn clear(&self) {
+        *self.state.lock().unwrap() = MemoryState::default();
+    }
+}
+
+impl MemoryState {
+    fn remove(&mut self, key: &str) -> Option<Entry> {
+        let entry = self.entries.remove(key)?;
+        self.recency.remove(&entry.used);
+        Some(entry)
+    }
+
+    fn next_tick(&mut self) -> u64 {
+        self.tick += 1;
+        self.tick
+    }
+}
+
+impl CacheBackend for MemoryCache {
+    fn get(&self, key: &str) -> Option<String> {
+        let mut state = self.state.lock().unwrap();
+        let mut entry = state.remove(key)?;
+        if self.ttl.is_some_and(|ttl| entry.inserted.elapsed() > ttl) {
+            return None;
+        }
+        entry.used = state.next_tick();
+        let value = entry.value.clone();
+        state.recency.insert(entry.used, key.to_string());
+        state.entries.insert(key.to_string(), entry);
+        Some(value)
+    }
+
+    fn put(&self, key: &str, value: String) {
+        if self.capacity == 0 {
+            return;
+        }
+        let mut state = self.state.lock().unwrap();
+        state.remove(key);
+        while state.entries.len() >= self.capacity {
+            let Some((_, oldest)) = state.recency.pop_first() else {
+                break;
+            };
+            state.entries.remove(&oldest);
+        }
+        let used = state.next_tick();
+        state.recency.insert(used, key.to_string());
+        state.entries.insert(
+            key.to_string(),
+            Entry {
+                value,
+                inserted: Instant::now(),
+                used,
+            },
+        );
+    }
+}
+
+impl fmt::Debug for MemoryCache {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MemoryCache")
+            .field("capacity", &self.capacity)
+            .field("ttl", &self.ttl)
+            .field("len", &self.len())
+            .finish()
+    }
+}
@@ -0,0 +1,79 @@
+use serde::Serialize;
+use serde_json::{Number, Value};
+use std::fmt::Write;
+
+/// Serializes `value` to canonical JSON: object keys sorted, no whitespace,
+/// integral floats written as integers (`1.0` => `1`, `-0.0` => `0`) and
+/// strings escaped the same way serde_json does. Semantically identical values
+/// always produce the same string, regardless of field order in the source.
+pub fn to_canonical_string<T: Serialize + ?Sized>(value: &T) -> Result<String, serde_json::Error> {
+    let value = serde_json::to_value(value)?;
+    Ok(canonicalize(&value))
+}
+
+/// Canonical JSON for an already-parsed value. See `to_canonical_string`.
+pub fn canonicalize(value: &Value) -> String {
+    let mut out = String::new();
+    write_value(&mut out, value);
+    out
+}
+
+/// Stable 64-bit FNV-1a hash of the canonical JSON, suitable for dedup keys.
+/// Unlike `DefaultHasher`, the result is identical across runs and platforms.
+pub fn canonical_hash<T: Serialize + ?Sized>(value: &T) -> Result<u64, serde_json::Error> {
+    Ok(fnv1a(to_canonical_string(value)?.as_bytes()))
+}
+
+pub(crate) fn fnv1a(bytes: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in bytes {
+        hash ^= u64::from(*byte);
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
+
+fn write_value(out: &mut String, value: &Value) {
+    match value {
+        Value::Null | Value::Bool(_) => out.push_str(&value.to_string()),
+        Value::Number(number) => write_number(out, number),
+        Value::String(string) => out.push_str(&Value::String(string.clone()).to_string()),
+        Value::Array(items) => {
+            out.push('[');
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                write_value(out, item);
+            }
+            out.push(']');
+        }
+        Value::Object(map) => {
+            let mut entries: Vec<_> = map.iter().collect();
+            entries.sort_by(|a, b| a.0.cmp(b.0));
+            out.push('{');
+            for (i, (key, item)) in entries.into_iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                out.push_str(&Value::String(key.clone()).to_string());
+                out.push(':');
+                write_value(out, item);
+            }
+            out.push('}');
+        }
+    }
+}
+
+fn write_number(out: &mut String, number: &Number) {
+    // Integral floats within the exactly-representable range print as integers.
+    const MAX_EXACT: f64 = 9_007_199_254_740_992.0;
+    match number.as_f64() {
+        Some(float) if number.is_f64() && float.fract() == 0.0 && float.abs() <= MAX_EXACT => {
+            let _ = write!(out, "{}", float as i64);
+        }
+        _ => {
+            let _ = write!(out, "{}", number);
+        }
+    }
+}
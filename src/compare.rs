@@ -0,0 +1,259 @@
+use crate::openai::OpenAiClient;
+use crate::options::RequestOptions;
+use crate::time::Instant;
+use futures::future;
+use schemars::JsonSchema;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::time::Duration;
+
+/// Runs the same operation through several clients (providers or models) to
+/// see where their outputs agree and what each costs, e.g. to find the
+/// cheapest model that still matches a reference model on an operation.
+///
+/// Give clients a pricing table (`OpenAiClient::with_pricing`) to compare cost.
+#[derive(Clone, Default)]
+pub struct Comparison {
+    candidates: Vec<(String, OpenAiClient)>,
+    options: RequestOptions,
+}
+
+/// Results of one candidate across all prompts.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CandidateReport {
+    pub name: String,
+    pub successes: usize,
+    pub failures: usize,
+    pub mean_latency: Duration,
+    pub total_tokens: u64,
+    /// Cost in USD, if every successful response was priced.
+    pub cost: Option<f64>,
+}
+
+/// Agreement between candidates, field by field, and their cost and latency.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ComparisonReport {
+    pub prompts: usize,
+    pub candidates: Vec<CandidateReport>,
+    /// For each top-level output field, `[i][j]` is the fraction of prompts on
+    /// which candidates i and j produced equal values, among the prompts both
+    /// succeeded on; `None` if there were none.
+    pub agreement: BTreeMap<String, Vec<Vec<Option<f64>>>>,
+}
+
+impl Comparison {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_candidate(mut self, name: impl Into<String>, client: OpenAiClient) -> Self {
+        self.candidates.push((name.into(), client));
+        self
+    }
+
+    /// Request options used for every call.
+    pub fn with_options(mut self, options: RequestOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Calls every candidate with every prompt, the candidates concurrently
+    /// and the prompts one after another.
+    pub async fn run<T>(&self, prompts: &[&str]) -> ComparisonReport
+    where
+        T: Serialize + DeserializeOwned + JsonSchema + Clone + 'static,
+    {
+        let mut candidates: Vec<CandidateReport> = self
+            .candidates
+            .iter()
+            .map(|(name, _)| CandidateReport {
+                name: name.clone(),
+                successes: 0,
+                failures: 0,
+                mean_latency: Duration::ZERO,
+                total_tokens: 0,
+                cost: Some(0.0),
+            })
+            .collect();
+        let mut latencies = vec![Duration::ZERO; candidates.len()];
+        let mut outputs: Vec<Vec<Option<BTreeMap<String, Value>>>> = Vec::new();
+
+        for prompt in prompts {
+            let results = future::join_all(self.candidates.iter().map(|(_, client)| async {
+                let started = Instant::now();
+                let result = client
+                    .call_schema_with_meta::<T>(prompt, &self.options)
+                    .await;
+                (result, started.elapsed())
+            }))
+            .await;
+
+            let mut row = Vec::with_capacity(results.len());
+            for (i, (result, latency)) in results.into_iter().enumerate() {
+                let report = &mut candidates[i];
+                latencies[i] += latency;
+                match result {
+                    Ok(response) => {
+                        report.successes += 1;
+                        report.total_tokens += response
+                            .meta
+                            .usage
+                            .as_ref()
+                            .map_or(0, |usage| u64::from(usage.total_tokens));
+                        report.cost = report.cost.zip(response.meta.cost).map(|(a, b)| a + b);
+                        row.push(Some(fields(&response.value)));
+                    }
+                    Err(_) => {
+                        report.failures += 1;
+                        row.push(None);
+                    }
+                }
+            }
+            outputs.push(row);
+        }
+
+        for (report, latency) in candidates.iter_mut().zip(latencies) {
+            if !prompts.is_empty() {
+                report.mean_latency = latency / prompts.len() as u32;
+            }
+            if report.successes == 0 {
+                report.cost = None;
+            }
+        }
+        ComparisonReport {
+            prompts: prompts.len(),
+            agreement: agreement(&outputs, candidates.len()),
+            candidates,
+        }
+    }
+}
+
+impl fmt::Debug for Comparison {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Comparison")
+            .field(
+                "candidates",
+                &self
+                    .candidates
+                    .iter()
+                    .map(|(name, _)| name)
+                    .collect::<Vec<_>>(),
+            )
+            .field("options", &self.options)
+            .finish()
+    }
+}
+
+impl ComparisonReport {
+    /// Mean agreement of candidate `name` with `reference` over all fields.
+    pub fn agreement_with(&self, reference: &str, name: &str) -> Option<f64> {
+        let i = self.index(reference)?;
+        let j = self.index(name)?;
+        let scores: Vec<f64> = self
+            .agreement
+            .values()
+            .filter_map(|matrix| matrix[i][j])
+            .collect();
+        (!scores.is_empty()).then(|| scores.iter().sum::<f64>() / scores.len() as f64)
+    }
+
+    /// The cheapest priced candidate whose mean agreement with `reference` is
+    /// at least `min_agreement`.
+    pub fn cheapest_agreeing_with(
+        &self,
+        reference: &str,
+        min_agreement: f64,
+    ) -> Option<&CandidateReport> {
+        self.candidates
+            .iter()
+            .filter(|candidate| {
+                self.agreement_with(reference, &candidate.name)
+                    .is_some_and(|agreement| agreement >= min_agreement)
+            })
+            .filter(|candidate| candidate.cost.is_some())
+            .min_by(|a, b| a.cost.partial_cmp(&b.cost).unwrap())
+    }
+
+    fn index(&self, name: &str) -> Option<usize> {
+        self.candidates.iter().position(|c| c.name == name)
+    }
+}
+
+impl fmt::Display for ComparisonReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{} prompts", self.prompts)?;
+        for c in &self.candidates {
+            let cost = c
+                .cost
+                .map_or("-".to_string(), |cost| format!("${:.4}", cost));
+            writeln!(
+                f,
+                "{}: {} ok, {} failed, {} ms mean, {} tokens, {}",
+                c.name,
+                c.successes,
+                c.failures,
+                c.mean_latency.as_millis(),
+                c.total_tokens,
+                cost
+            )?;
+        }
+        for (field, matrix) in &self.agreement {
+            writeln!(f, "{}:", field)?;
+            for (name, row) in self.candidates.iter().map(|c| &c.name).zip(matrix) {
+                let cells: Vec<String> = row
+                    .iter()
+                    .map(|cell| cell.map_or("-".to_string(), |a| format!("{:.2}", a)))
+                    .collect();
+                writeln!(f, "  {}: {}", name, cells.join(" "))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Top-level fields of an output; outputs that are not objects are one
+/// field named `value`.
+fn fields<T: Serialize>(output: &T) -> BTreeMap<String, Value> {
+    match serde_json::to_value(output).unwrap_or_default() {
+        Value::Object(fields) => fields.into_iter().collect(),
+        value => BTreeMap::from([("value".to_string(), value)]),
+    }
+}
+
+fn agreement(
+    outputs: &[Vec<Option<BTreeMap<String, Value>>>],
+    candidates: usize,
+) -> BTreeMap<String, Vec<Vec<Option<f64>>>> {
+    let names: BTreeSet<&String> = outputs
+        .iter()
+        .flatten()
+        .flatten()
+        .flat_map(|fields| fields.keys())
+        .collect();
+    names
+        .into_iter()
+        .map(|field| {
+            let matrix = (0..candidates)
+                .map(|i| {
+                    (0..candidates)
+                        .map(|j| {
+                            let both: Vec<bool> = outputs
+                                .iter()
+                                .filter_map(|row| match (&row[i], &row[j]) {
+                                    (Some(a), Some(b)) => Some(a.get(field) == b.get(field)),
+                                    _ => None,
+                                })
+                                .collect();
+                            let agreeing = both.iter().filter(|equal| **equal).count();
+                            (!both.is_empty()).then(|| agreeing as f64 / both.len() as f64)
+                        })
+                        .collect()
+                })
+                .collect();
+            (field.clone(), matrix)
+        })
+        .collect()
+}
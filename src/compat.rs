@@ -0,0 +1,322 @@
+use crate::openai::OpenAiClient;
+use schemars::JsonSchema;
+use serde_json::{Map, Value};
+use std::collections::BTreeSet;
+use std::error::Error;
+use std::fmt;
+use std::path::Path;
+
+/// References are followed at most this deep, which also stops recursive types.
+const MAX_DEPTH: usize = 32;
+
+/// One difference between a baseline schema and the current one, at a JSON
+/// pointer into the output (`/*` for array items).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SchemaChange {
+    pub path: String,
+    pub kind: ChangeKind,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ChangeKind {
+    FieldAdded {
+        nullable: bool,
+    },
+    FieldRemoved,
+    TypeChanged {
+        old: String,
+        new: String,
+    },
+    /// The field no longer accepts null.
+    NullRemoved,
+    NullAdded,
+    EnumValueAdded {
+        value: Value,
+    },
+    EnumValueRemoved {
+        value: Value,
+    },
+    DescriptionChanged,
+}
+
+impl SchemaChange {
+    /// Whether outputs stored under the baseline may no longer deserialize:
+    /// new non-nullable fields, type changes, removed enum values and fields
+    /// that no longer accept null. Removed fields are ignored when
+    /// deserializing, so they are compatible.
+    pub fn breaks_stored_data(&self) -> bool {
+        matches!(
+            self.kind,
+            ChangeKind::FieldAdded { nullable: false }
+                | ChangeKind::TypeChanged { .. }
+                | ChangeKind::NullRemoved
+                | ChangeKind::EnumValueRemoved { .. }
+        )
+    }
+
+    /// Whether prompts tuned against the baseline may refer to what changed:
+    /// removed fields and enum values, type changes and changed descriptions,
+    /// which the model reads as instructions.
+    pub fn breaks_prompts(&self) -> bool {
+        matches!(
+            self.kind,
+            ChangeKind::FieldRemoved
+                | ChangeKind::TypeChanged { .. }
+                | ChangeKind::EnumValueRemoved { .. }
+                | ChangeKind::DescriptionChanged
+        )
+    }
+}
+
+impl fmt::Display for SchemaChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let path = if self.path.is_empty() {
+            "/"
+        } else {
+            &self.path
+        };
+        match &self.kind {
+            ChangeKind::FieldAdded { nullable: true } => {
+                write!(f, "{}: nullable field added", path)
+            }
+            ChangeKind::FieldAdded { nullable: false } => write!(f, "{}: field added", path),
+            ChangeKind::FieldRemoved => write!(f, "{}: field removed", path),
+            ChangeKind::TypeChanged { old, new } => {
+                write!(f, "{}: type changed from {} to {}", path, old, new)
+            }
+            ChangeKind::NullRemoved => write!(f, "{}: no longer nullable", path),
+            ChangeKind::NullAdded => write!(f, "{}: now nullable", path),
+            ChangeKind::EnumValueAdded { value } => write!(f, "{}: value {} added", path, value),
+            ChangeKind::EnumValueRemoved { value } => {
+                write!(f, "{}: value {} removed", path, value)
+            }
+            ChangeKind::DescriptionChanged => write!(f, "{}: description changed", path),
+        }
+    }
+}
+
+/// Every change between a baseline schema and the current one.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CompatReport {
+    pub changes: Vec<SchemaChange>,
+}
+
+impl CompatReport {
+    pub fn breaks_stored_data(&self) -> bool {
+        self.changes.iter().any(SchemaChange::breaks_stored_data)
+    }
+
+    pub fn breaks_prompts(&self) -> bool {
+        self.changes.iter().any(SchemaChange::breaks_prompts)
+    }
+
+    pub fn is_unchanged(&self) -> bool {
+        self.changes.is_empty()
+    }
+}
+
+impl fmt::Display for CompatReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for change in &self.changes {
+            let mut breaks = Vec::new();
+            if change.breaks_stored_data() {
+                breaks.push("stored data");
+            }
+            if change.breaks_prompts() {
+                breaks.push("prompts");
+            }
+            if breaks.is_empty() {
+                writeln!(f, "{} (compatible)", change)?;
+            } else {
+                writeln!(f, "{} (breaks {})", change, breaks.join(" and "))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Writes the schema sent for T (`OpenAiClient::schema_for`) to `path`, to be
+/// committed as the baseline of a release.
+pub fn write_baseline<T: JsonSchema + 'static>(
+    path: impl AsRef<Path>,
+) -> Result<(), Box<dyn Error>> {
+    let schema = OpenAiClient::schema_for::<T>()?;
+    std::fs::write(path, serde_json::to_string_pretty(&schema)? + "\n")?;
+    Ok(())
+}
+
+/// Compares the schema sent for T against the baseline at `path`, e.g. in a
+/// test that fails on `report.breaks_stored_data()`.
+pub fn check_baseline<T: JsonSchema + 'static>(
+    path: impl AsRef<Path>,
+) -> Result<CompatReport, Box<dyn Error>> {
+    let baseline: Value = serde_json::from_slice(&std::fs::read(path)?)?;
+    Ok(compare(&baseline, &OpenAiClient::schema_for::<T>()?))
+}
+
+/// Compares two schemas as generated by this crate, following references.
+pub fn compare(old: &Value, new: &Value) -> CompatReport {
+    let (old_definitions, new_definitions) = (definitions(old), definitions(new));
+    let mut diff = Diff {
+        old_definitions: &old_definitions,
+        new_definitions: &new_definitions,
+        changes: Vec::new(),
+    };
+    diff.compare(old, new, String::new(), 0);
+    CompatReport {
+        changes: diff.changes,
+    }
+}
+
+fn definitions(schema: &Value) -> Map<String, Value> {
+    schema
+        .get("definitions")
+        .and_then(Value::as_object)
+        .cloned()
+        .unwrap_or_default()
+}
+
+struct Diff<'a> {
+    old_definitions: &'a Map<String, Value>,
+    new_definitions: &'a Map<String, Value>,
+    changes: Vec<SchemaChange>,
+}
+
+/// A schema node with references resolved and nullability split off.
+struct Shape<'a> {
+    node: &'a Value,
+    types: BTreeSet<String>,
+    nullable: bool,
+}
+
+impl<'a> Diff<'a> {
+    fn push(&mut self, path: &str, kind: ChangeKind) {
+        self.changes.push(SchemaChange {
+            path: path.to_string(),
+            kind,
+        });
+    }
+
+    fn compare(&mut self, old: &'a Value, new: &'a Value, path: String, depth: usize) {
+        if depth > MAX_DEPTH {
+            return;
+        }
+        let old = shape(old, self.old_definitions);
+        let new = shape(new, self.new_definitions);
+
+        if old.node.get("description") != new.node.get("description") {
+            self.push(&path, ChangeKind::DescriptionChanged);
+        }
+        match (old.nullable, new.nullable) {
+            (true, false) => self.push(&path, ChangeKind::NullRemoved),
+            (false, true) => self.push(&path, ChangeKind::NullAdded),
+            _ => {}
+        }
+        if old.types != new.types {
+            self.push(
+                &path,
+                ChangeKind::TypeChanged {
+                    old: type_name(&old.types),
+                    new: type_name(&new.types),
+                },
+            );
+            return;
+        }
+
+        let old_values = enum_values(old.node);
+        let new_values = enum_values(new.node);
+        for value in old_values.iter().filter(|v| !new_values.contains(v)) {
+            let value = (*value).clone();
+            self.push(&path, ChangeKind::EnumValueRemoved { value });
+        }
+        for value in new_values.iter().filter(|v| !old_values.contains(v)) {
+            let value = (*value).clone();
+            self.push(&path, ChangeKind::EnumValueAdded { value });
+        }
+
+        let old_properties = properties(old.node);
+        let new_properties = properties(new.node);
+        for (name, old_property) in old_properties.into_iter().flatten() {
+            let path = format!("{}/{}", path, name);
+            match new_properties.and_then(|properties| properties.get(name)) {
+                Some(new_property) => self.compare(old_property, new_property, path, depth + 1),
+                None => self.push(&path, ChangeKind::FieldRemoved),
+            }
+        }
+        for (name, new_property) in new_properties.into_iter().flatten() {
+            if !old_properties.is_some_and(|properties| properties.contains_key(name)) {
+                let nullable = shape(new_property, self.new_definitions).nullable;
+                self.push(
+                    &format!("{}/{}", path, name),
+                    ChangeKind::FieldAdded { nullable },
+                );
+            }
+        }
+
+        if let (Some(old_items), Some(new_items)) = (old.node.get("items"), new.node.get("items")) {
+            self.compare(old_items, new_items, format!("{}/*", path), depth + 1);
+        }
+    }
+}
+
+fn shape<'a>(node: &'a Value, definitions: &'a Map<String, Value>) -> Shape<'a> {
+    let mut node = resolve(node, definitions);
+    let mut nullable = false;
+    // A nullable reference or type is an `anyOf` of the type and `null`.
+    if let Some(variants) = node.get("anyOf").and_then(Value::as_array) {
+        let non_null: Vec<&Value> = variants
+            .iter()
+            .filter(|variant| variant.get("type") != Some(&Value::from("null")))
+            .collect();
+        nullable = non_null.len() < variants.len();
+        if let [single] = non_null.as_slice() {
+            node = resolve(single, definitions);
+        }
+    }
+    let mut types = BTreeSet::new();
+    match node.get("type") {
+        Some(Value::String(name)) => {
+            types.insert(name.clone());
+        }
+        Some(Value::Array(names)) => {
+            types.extend(names.iter().filter_map(Value::as_str).map(str::to_string))
+        }
+        _ => {}
+    }
+    if types.remove("null") {
+        nullable = true;
+    }
+    Shape {
+        node,
+        types,
+        nullable,
+    }
+}
+
+fn resolve<'a>(node: &'a Value, definitions: &'a Map<String, Value>) -> &'a Value {
+    node.get("$ref")
+        .and_then(Value::as_str)
+        .and_then(|r| r.strip_prefix("#/definitions/"))
+        .and_then(|name| definitions.get(name))
+        .unwrap_or(node)
+}
+
+fn enum_values(node: &Value) -> Vec<&Value> {
+    node.get("enum")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter(|value| !value.is_null())
+        .collect()
+}
+
+fn properties(node: &Value) -> Option<&Map<String, Value>> {
+    node.get("properties").and_then(Value::as_object)
+}
+
+fn type_name(types: &BTreeSet<String>) -> String {
+    match types.len() {
+        0 => "any".to_string(),
+        _ => types.iter().cloned().collect::<Vec<_>>().join(" | "),
+    }
+}
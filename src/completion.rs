@@ -0,0 +1,23 @@
+use crate::openai::OpenAiClient;
+use schemars::JsonSchema;
+use serde::de::DeserializeOwned;
+use std::error::Error;
+use std::future::Future;
+
+/// Anything that can turn a prompt into a typed structured output. Code written
+/// against this trait can be exercised with `MockClient` instead of the network.
+pub trait StructuredCompletion {
+    fn call_schema<T: DeserializeOwned + JsonSchema + Clone + 'static>(
+        &self,
+        user_prompt: &str,
+    ) -> impl Future<Output = Result<T, Box<dyn Error>>>;
+}
+
+impl StructuredCompletion for OpenAiClient {
+    fn call_schema<T: DeserializeOwned + JsonSchema + Clone + 'static>(
+        &self,
+        user_prompt: &str,
+    ) -> impl Future<Output = Result<T, Box<dyn Error>>> {
+        OpenAiClient::call_schema(self, user_prompt)
+    }
+}
@@ -0,0 +1,91 @@
+use crate::openai::OpenAiClient;
+use crate::options::RequestOptions;
+use futures::future;
+use schemars::JsonSchema;
+use serde::de::DeserializeOwned;
+use std::error::Error;
+use std::fmt;
+
+impl OpenAiClient {
+    /// Sends the same call `n` times concurrently and returns every output that
+    /// parsed, in the order the calls were made. Fails with the first error
+    /// only if no call succeeded.
+    ///
+    /// The samples are independent calls, so they only differ with a
+    /// temperature above zero and without a response cache.
+    pub async fn call_schema_samples<T: DeserializeOwned + JsonSchema + Clone + 'static>(
+        &self,
+        user_prompt: &str,
+        n: usize,
+        options: &RequestOptions,
+    ) -> Result<Vec<T>, Box<dyn Error>> {
+        let results = future::join_all(
+            (0..n.max(1)).map(|_| self.call_schema_with::<T>(user_prompt, options)),
+        )
+        .await;
+        let mut samples = Vec::with_capacity(results.len());
+        let mut first_error = None;
+        for result in results {
+            match result {
+                Ok(sample) => samples.push(sample),
+                Err(err) => {
+                    first_error.get_or_insert(err);
+                }
+            }
+        }
+        match first_error {
+            Some(err) if samples.is_empty() => Err(err),
+            _ => Ok(samples),
+        }
+    }
+
+    /// Samples `n` outputs and returns the one `pick` chooses, e.g. `majority`
+    /// for classification-style extraction, or the best by a caller's score.
+    pub async fn call_schema_consensus<T, F>(
+        &self,
+        user_prompt: &str,
+        n: usize,
+        options: &RequestOptions,
+        pick: F,
+    ) -> Result<T, Box<dyn Error>>
+    where
+        T: DeserializeOwned + JsonSchema + Clone + 'static,
+        F: FnOnce(Vec<T>) -> Option<T>,
+    {
+        let samples = self
+            .call_schema_samples::<T>(user_prompt, n, options)
+            .await?;
+        let candidates = samples.len();
+        pick(samples).ok_or_else(|| Box::new(NoConsensus { candidates }) as Box<dyn Error>)
+    }
+}
+
+/// Picks the most frequent candidate; ties go to the one seen first.
+pub fn majority<T: PartialEq>(candidates: Vec<T>) -> Option<T> {
+    let mut votes: Vec<(T, usize)> = Vec::new();
+    for candidate in candidates {
+        match votes.iter_mut().find(|(seen, _)| *seen == candidate) {
+            Some((_, count)) => *count += 1,
+            None => votes.push((candidate, 1)),
+        }
+    }
+    let top = votes.iter().map(|(_, count)| *count).max()?;
+    votes
+        .into_iter()
+        .find(|(_, count)| *count == top)
+        .map(|(candidate, _)| candidate)
+}
+
+/// The consensus function chose none of the sampled outputs.
+#[derive(Debug, Clone)]
+pub struct NoConsensus {
+    pub candidates: usize,
+}
+
+impl fmt::Display for NoConsensus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "No consensus among {} candidates", self.candidates)
+    }
+}
+
+impl Error for NoConsensus {}
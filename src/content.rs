@@ -0,0 +1,47 @@
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use serde_json::{json, Value};
+
+/// An image sent alongside the text prompt.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ImagePart {
+    /// A publicly reachable image URL.
+    Url(String),
+    /// Base64-encoded image data with its MIME type, e.g. `image/png`.
+    Base64 { mime: String, data: String },
+}
+
+impl ImagePart {
+    /// Encodes raw image bytes.
+    pub fn from_bytes(mime: impl Into<String>, bytes: &[u8]) -> Self {
+        ImagePart::Base64 {
+            mime: mime.into(),
+            data: STANDARD.encode(bytes),
+        }
+    }
+
+    fn url(&self) -> String {
+        match self {
+            ImagePart::Url(url) => url.clone(),
+            ImagePart::Base64 { mime, data } => format!("data:{};base64,{}", mime, data),
+        }
+    }
+
+    fn to_content_part(&self) -> Value {
+        json!({
+            "type": "image_url",
+            "image_url": { "url": self.url() }
+        })
+    }
+}
+
+/// Builds the user message `content`: a plain string without images, otherwise
+/// the multi-part array of the text followed by the images.
+pub(crate) fn user_content(text: String, images: &[ImagePart]) -> Value {
+    if images.is_empty() {
+        return Value::String(text);
+    }
+    let mut parts = vec![json!({ "type": "text", "text": text })];
+    parts.extend(images.iter().map(ImagePart::to_content_part));
+    Value::Array(parts)
+}
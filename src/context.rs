@@ -0,0 +1,82 @@
+use jiff::tz::TimeZone;
+use jiff::Timestamp;
+use std::error::Error;
+use std::time::SystemTime;
+
+/// Measurement system the model should assume for quantities.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnitSystem {
+    Metric,
+    Imperial,
+}
+
+/// Facts about the caller's situation added to the system prompt, so that
+/// relative dates such as "next Tuesday" resolve against the caller's day and
+/// time zone rather than whatever the model assumes.
+///
+/// The current time is read once per call, so retries and repairs within a
+/// call see the same context; `with_now` pins it across calls.
+#[derive(Debug, Clone, Default)]
+pub struct PromptContext {
+    time_zone: Option<TimeZone>,
+    locale: Option<String>,
+    units: Option<UnitSystem>,
+    now: Option<Timestamp>,
+}
+
+impl PromptContext {
+    /// A context with the current date and time in UTC.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Uses an IANA time zone such as `Europe/Berlin` for the current time.
+    pub fn with_time_zone(mut self, name: &str) -> Result<Self, Box<dyn Error>> {
+        self.time_zone = Some(TimeZone::get(name)?);
+        Ok(self)
+    }
+
+    /// A BCP 47 locale such as `de-DE`, for number, date and language conventions.
+    pub fn with_locale(mut self, locale: impl Into<String>) -> Self {
+        self.locale = Some(locale.into());
+        self
+    }
+
+    pub fn with_units(mut self, units: UnitSystem) -> Self {
+        self.units = Some(units);
+        self
+    }
+
+    /// Pins the current time, e.g. to the start of a multi-call conversation.
+    pub fn with_now(mut self, now: SystemTime) -> Result<Self, Box<dyn Error>> {
+        self.now = Some(Timestamp::try_from(now)?);
+        Ok(self)
+    }
+
+    /// The context block as added to the system prompt.
+    pub fn render(&self) -> String {
+        let time_zone = self.time_zone.clone().unwrap_or(TimeZone::UTC);
+        let now = self.now.unwrap_or_else(Timestamp::now).to_zoned(time_zone);
+        let zone_name = now.time_zone().iana_name().unwrap_or("UTC").to_string();
+        let mut lines = vec![format!(
+            "- Current date and time: {} ({}, UTC{})",
+            now.strftime("%A, %Y-%m-%d %H:%M"),
+            zone_name,
+            now.strftime("%:z")
+        )];
+        if let Some(locale) = &self.locale {
+            lines.push(format!("- Locale: {}", locale));
+        }
+        if let Some(units) = self.units {
+            let units = match units {
+                UnitSystem::Metric => "metric",
+                UnitSystem::Imperial => "imperial",
+            };
+            lines.push(format!("- Units: {}", units));
+        }
+        format!(
+            "Context:\n{}\nInterpret relative dates, times and quantities against this context.",
+            lines.join("\n")
+        )
+    }
+}
@@ -0,0 +1,279 @@
+use crate::openai::Usage;
+use crate::time::Instant;
+use std::collections::{BTreeMap, VecDeque};
+use std::error::Error;
+use std::fmt;
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// List prices of known model families in USD per million tokens, matched by
+/// prefix. Prices change; override them with `PricingTable::with_model`.
+const PRICES: &[(&str, ModelPrice)] = &[
+    ("gpt-4o", ModelPrice::new(2.50, 10.00, 1.25)),
+    ("gpt-4o-mini", ModelPrice::new(0.15, 0.60, 0.075)),
+    ("gpt-4.1", ModelPrice::new(2.00, 8.00, 0.50)),
+    ("gpt-4.1-mini", ModelPrice::new(0.40, 1.60, 0.10)),
+    ("gpt-4.1-nano", ModelPrice::new(0.10, 0.40, 0.025)),
+    ("o1-mini", ModelPrice::new(1.10, 4.40, 0.55)),
+    ("o1", ModelPrice::new(15.00, 60.00, 7.50)),
+    ("o3-mini", ModelPrice::new(1.10, 4.40, 0.55)),
+    ("o3", ModelPrice::new(2.00, 8.00, 0.50)),
+    ("o4-mini", ModelPrice::new(1.10, 4.40, 0.275)),
+];
+
+/// Token prices of a model in USD per million tokens.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ModelPrice {
+    pub input: f64,
+    pub output: f64,
+    /// Price of prompt tokens served from the provider's prompt cache.
+    pub cached_input: f64,
+}
+
+impl ModelPrice {
+    pub const fn new(input: f64, output: f64, cached_input: f64) -> Self {
+        Self {
+            input,
+            output,
+            cached_input,
+        }
+    }
+
+    /// Cost in USD of a response's token usage.
+    pub fn cost(&self, usage: &Usage) -> f64 {
+        let cached = usage.cached_tokens().min(usage.prompt_tokens);
+        let uncached = usage.prompt_tokens - cached;
+        (f64::from(uncached) * self.input
+            + f64::from(cached) * self.cached_input
+            + f64::from(usage.completion_tokens) * self.output)
+            / 1_000_000.0
+    }
+}
+
+/// Model prices by name prefix, starting from a built-in table of OpenAI list
+/// prices. The longest matching prefix wins.
+#[derive(Debug, Clone)]
+pub struct PricingTable {
+    models: Vec<(String, ModelPrice)>,
+}
+
+impl Default for PricingTable {
+    fn default() -> Self {
+        Self {
+            models: PRICES
+                .iter()
+                .map(|(prefix, price)| (prefix.to_string(), *price))
+                .collect(),
+        }
+    }
+}
+
+impl PricingTable {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the price of models whose name starts with `prefix`, e.g. for
+    /// negotiated rates or other providers.
+    pub fn with_model(mut self, prefix: impl Into<String>, price: ModelPrice) -> Self {
+        let prefix = prefix.into();
+        self.models.retain(|(known, _)| *known != prefix);
+        self.models.push((prefix, price));
+        self
+    }
+
+    pub fn get(&self, model: &str) -> Option<ModelPrice> {
+        self.models
+            .iter()
+            .filter(|(prefix, _)| model.starts_with(prefix.as_str()))
+            .max_by_key(|(prefix, _)| prefix.len())
+            .map(|(_, price)| *price)
+    }
+}
+
+/// Token usage and cost accumulated by a client (and its clones) across every
+/// API response, including repair attempts and retried refusals.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CostSummary {
+    pub responses: u64,
+    pub input_tokens: u64,
+    pub cached_input_tokens: u64,
+    pub output_tokens: u64,
+    /// Output tokens reasoning models spent on reasoning.
+    pub reasoning_tokens: u64,
+    /// Total cost in USD of the priced responses.
+    pub total_cost: f64,
+    /// Responses from models missing from the pricing table, or all responses
+    /// when the client has none.
+    pub unpriced_responses: u64,
+    /// Cost in USD per call tag (see `RequestOptions::tag`), e.g. per tenant.
+    pub by_tag: BTreeMap<String, f64>,
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct CostTracker {
+    summary: Mutex<CostSummary>,
+}
+
+impl CostTracker {
+    pub(crate) fn record(&self, tag: Option<&str>, usage: &Usage, cost: Option<f64>) {
+        let mut summary = self.summary.lock().unwrap();
+        summary.responses += 1;
+        summary.input_tokens += u64::from(usage.prompt_tokens);
+        summary.cached_input_tokens += u64::from(usage.cached_tokens());
+        summary.output_tokens += u64::from(usage.completion_tokens);
+        summary.reasoning_tokens += u64::from(usage.reasoning_tokens());
+        match cost {
+            Some(cost) => {
+                summary.total_cost += cost;
+                if let Some(tag) = tag {
+                    *summary.by_tag.entry(tag.to_string()).or_default() += cost;
+                }
+            }
+            None => summary.unpriced_responses += 1,
+        }
+    }
+
+    pub(crate) fn summary(&self) -> CostSummary {
+        self.summary.lock().unwrap().clone()
+    }
+
+    pub(crate) fn take(&self) -> CostSummary {
+        std::mem::take(&mut *self.summary.lock().unwrap())
+    }
+}
+
+/// A spending limit for a client and its clones, in USD (which requires a
+/// pricing table), tokens, or both. Without a window it covers the client's
+/// lifetime.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Budget {
+    pub max_usd: Option<f64>,
+    pub max_tokens: Option<u64>,
+    pub window: Option<Duration>,
+}
+
+impl Budget {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn max_usd(mut self, max_usd: f64) -> Self {
+        self.max_usd = Some(max_usd);
+        self
+    }
+
+    pub fn max_tokens(mut self, max_tokens: u64) -> Self {
+        self.max_tokens = Some(max_tokens);
+        self
+    }
+
+    /// Only counts spending within the last `window`, e.g. a day.
+    pub fn per(mut self, window: Duration) -> Self {
+        self.window = Some(window);
+        self
+    }
+}
+
+/// Returned instead of sending once the client's budget is spent.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BudgetExceeded {
+    pub spent_usd: f64,
+    pub spent_tokens: u64,
+    pub budget: Budget,
+}
+
+impl fmt::Display for BudgetExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Budget exceeded: spent ${:.4} and {} tokens",
+            self.spent_usd, self.spent_tokens
+        )?;
+        if let Some(window) = self.budget.window {
+            write!(f, " in the last {}s", window.as_secs())?;
+        }
+        Ok(())
+    }
+}
+
+impl Error for BudgetExceeded {}
+
+/// Returned by `OpenAiClient::with_budget` for a USD limit on a client that
+/// cannot price its model's responses, which would never reach the limit.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnpricedBudget {
+    pub model: String,
+}
+
+impl fmt::Display for UnpricedBudget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "A USD budget needs a price for {}; call with_pricing first",
+            self.model
+        )
+    }
+}
+
+impl Error for UnpricedBudget {}
+
+#[derive(Debug)]
+pub(crate) struct BudgetGuard {
+    budget: Budget,
+    spending: Mutex<VecDeque<(Instant, f64, u64)>>,
+}
+
+impl BudgetGuard {
+    pub(crate) fn new(budget: Budget) -> Self {
+        Self {
+            budget,
+            spending: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    /// Fails once spending has reached either limit.
+    pub(crate) fn check(&self) -> Result<(), BudgetExceeded> {
+        let mut spending = self.spending.lock().unwrap();
+        if let Some(window) = self.budget.window {
+            while spending
+                .front()
+                .is_some_and(|(at, _, _)| at.elapsed() > window)
+            {
+                spending.pop_front();
+            }
+        }
+        let (spent_usd, spent_tokens) = spending
+            .iter()
+            .fold((0.0, 0), |(usd, tokens), (_, cost, used)| {
+                (usd + cost, tokens + used)
+            });
+        let over_usd = self.budget.max_usd.is_some_and(|max| spent_usd >= max);
+        let over_tokens = self
+            .budget
+            .max_tokens
+            .is_some_and(|max| spent_tokens >= max);
+        if over_usd || over_tokens {
+            return Err(BudgetExceeded {
+                spent_usd,
+                spent_tokens,
+                budget: self.budget,
+            });
+        }
+        Ok(())
+    }
+
+    /// Adds a response's spending. Without a window, spending is kept as a
+    /// single running total.
+    pub(crate) fn record(&self, cost: Option<f64>, tokens: u32) {
+        let (cost, tokens) = (cost.unwrap_or(0.0), u64::from(tokens));
+        let mut spending = self.spending.lock().unwrap();
+        match (self.budget.window, spending.back_mut()) {
+            (None, Some((_, spent_usd, spent_tokens))) => {
+                *spent_usd += cost;
+                *spent_tokens += tokens;
+            }
+            _ => spending.push_back((Instant::now(), cost, tokens)),
+        }
+    }
+}
@@ -0,0 +1,70 @@
+//! Where the API key comes from. The client asks its `CredentialProvider` for
+//! a token before every request, so a provider backed by Vault or AWS Secrets
+//! Manager can rotate keys without the client being rebuilt.
+
+use futures::future::BoxFuture;
+use std::error::Error;
+use std::fmt;
+use std::sync::Arc;
+
+pub use secrecy::{ExposeSecret, SecretString};
+
+/// Error returned by a `CredentialProvider`.
+pub type CredentialError = Box<dyn Error + Send + Sync>;
+
+/// Supplies the API key of every request. Providers that fetch keys remotely
+/// should cache them and refresh before they expire.
+pub trait CredentialProvider: Send + Sync {
+    fn token(&self) -> BoxFuture<'_, Result<SecretString, CredentialError>>;
+}
+
+impl<P: CredentialProvider + ?Sized> CredentialProvider for Arc<P> {
+    fn token(&self) -> BoxFuture<'_, Result<SecretString, CredentialError>> {
+        (**self).token()
+    }
+}
+
+/// Returned when the `CredentialProvider` cannot supply the API key of a
+/// request; the request is not sent.
+#[derive(Debug)]
+pub struct CredentialUnavailable {
+    pub source: CredentialError,
+}
+
+impl fmt::Display for CredentialUnavailable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Cannot get API key: {}", self.source)
+    }
+}
+
+impl Error for CredentialUnavailable {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(&*self.source)
+    }
+}
+
+/// A single fixed key, used by `OpenAiClient::new`.
+#[derive(Clone)]
+pub struct StaticCredential {
+    token: SecretString,
+}
+
+impl StaticCredential {
+    pub fn new(token: impl Into<String>) -> Self {
+        Self {
+            token: SecretString::from(token.into()),
+        }
+    }
+}
+
+impl CredentialProvider for StaticCredential {
+    fn token(&self) -> BoxFuture<'_, Result<SecretString, CredentialError>> {
+        Box::pin(async move { Ok(self.token.clone()) })
+    }
+}
+
+impl fmt::Debug for StaticCredential {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("StaticCredential").finish_non_exhaustive()
+    }
+}
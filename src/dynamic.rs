@@ -0,0 +1,105 @@
+use crate::completion::StructuredCompletion;
+use crate::openai::OpenAiClient;
+use crate::options::RequestOptions;
+use futures::future::LocalBoxFuture;
+use schemars::JsonSchema;
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use std::error::Error;
+
+/// An output schema known only at runtime, e.g. one supplied by a plugin or
+/// a scripting binding.
+///
+/// The schema is sent as given, so unless `strict` is turned off it has to
+/// satisfy strict mode itself (`additionalProperties: false`, every property
+/// required); a non-object root is wrapped like a typed one. The name may only
+/// contain letters, digits, `_` and `-`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DynSchema {
+    pub name: String,
+    pub schema: Value,
+    /// Whether OpenAI enforces the schema while decoding; on by default.
+    pub strict: bool,
+}
+
+impl DynSchema {
+    pub fn new(name: impl Into<String>, schema: Value) -> Self {
+        Self {
+            name: name.into(),
+            schema,
+            strict: true,
+        }
+    }
+
+    pub fn with_strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    /// The schema and name a typed call for T would send.
+    pub fn of<T: JsonSchema + 'static>() -> Result<Self, Box<dyn Error>> {
+        Ok(Self::new(
+            OpenAiClient::schema_name_for::<T>(),
+            OpenAiClient::generate_schema::<T>()?,
+        ))
+    }
+}
+
+/// An object-safe structured client for hosts that can't be generic over
+/// output types: schemas go in and JSON values come out.
+///
+/// `OpenAiClient` implements it with its full pipeline (retries, repair,
+/// validation, caching, limits), and `dyn DynStructuredClient` implements
+/// `StructuredCompletion` to get typed outputs back. Calls are not `Send`,
+/// like the typed ones, so they run on the caller's task.
+pub trait DynStructuredClient {
+    fn call_schema_value<'a>(
+        &'a self,
+        schema: &'a DynSchema,
+        user_prompt: &'a str,
+        options: &'a RequestOptions,
+    ) -> LocalBoxFuture<'a, Result<Value, Box<dyn Error>>>;
+}
+
+impl DynStructuredClient for OpenAiClient {
+    fn call_schema_value<'a>(
+        &'a self,
+        schema: &'a DynSchema,
+        user_prompt: &'a str,
+        options: &'a RequestOptions,
+    ) -> LocalBoxFuture<'a, Result<Value, Box<dyn Error>>> {
+        Box::pin(OpenAiClient::call_schema_value(
+            self,
+            schema,
+            user_prompt,
+            options,
+        ))
+    }
+}
+
+impl OpenAiClient {
+    /// Like `call_schema_with`, with the output schema given at runtime.
+    pub async fn call_schema_value(
+        &self,
+        schema: &DynSchema,
+        user_prompt: &str,
+        options: &RequestOptions,
+    ) -> Result<Value, Box<dyn Error>> {
+        let mut options = options.clone();
+        options.output_schema = Some(schema.clone());
+        self.call_schema_with::<Value>(user_prompt, &options).await
+    }
+}
+
+impl StructuredCompletion for dyn DynStructuredClient + '_ {
+    async fn call_schema<T: DeserializeOwned + JsonSchema + Clone + 'static>(
+        &self,
+        user_prompt: &str,
+    ) -> Result<T, Box<dyn Error>> {
+        let schema = DynSchema::of::<T>()?;
+        let value = self
+            .call_schema_value(&schema, user_prompt, &RequestOptions::default())
+            .await?;
+        Ok(serde_json::from_value(value)?)
+    }
+}
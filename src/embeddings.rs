@@ -0,0 +1,133 @@
+use crate::openai::{OpenAiClient, StageTimings, Usage};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::error::Error;
+
+/// The API caps the inputs of one embeddings request.
+pub const MAX_BATCH_SIZE: usize = 2048;
+
+/// Embeds texts with the same endpoint, credentials, headers, transport,
+/// middleware, retries and rate limits as an `OpenAiClient`.
+///
+/// Requests go to `/embeddings` under the client's API base (see
+/// `OpenAiClient::with_api_base`) unless set with `with_endpoint`.
+#[derive(Clone)]
+pub struct EmbeddingsClient {
+    client: OpenAiClient,
+    endpoint: Option<String>,
+    model: String,
+    dimensions: Option<u32>,
+    batch_size: usize,
+}
+
+#[derive(Debug, Deserialize)]
+struct EmbeddingsResponse {
+    data: Vec<EmbeddingData>,
+    #[serde(default)]
+    usage: Option<EmbeddingsUsage>,
+}
+
+#[derive(Debug, Deserialize)]
+struct EmbeddingsUsage {
+    prompt_tokens: u32,
+    total_tokens: u32,
+}
+
+#[derive(Debug, Deserialize)]
+struct EmbeddingData {
+    index: usize,
+    embedding: Vec<f32>,
+}
+
+impl EmbeddingsClient {
+    pub fn new(client: OpenAiClient, model: impl Into<String>) -> Self {
+        Self {
+            client,
+            endpoint: None,
+            model: model.into(),
+            dimensions: None,
+            batch_size: MAX_BATCH_SIZE,
+        }
+    }
+
+    pub fn with_endpoint(mut self, endpoint: impl Into<String>) -> Self {
+        self.endpoint = Some(endpoint.into());
+        self
+    }
+
+    /// Shortens the embeddings to `dimensions`, for models that support it
+    /// (`text-embedding-3-*`).
+    pub fn with_dimensions(mut self, dimensions: u32) -> Self {
+        self.dimensions = Some(dimensions);
+        self
+    }
+
+    /// Texts sent per request (default and maximum: 2048).
+    pub fn with_batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size.clamp(1, MAX_BATCH_SIZE);
+        self
+    }
+
+    /// Embeds `texts` in order, one request per batch.
+    pub async fn embed<S: AsRef<str>>(&self, texts: &[S]) -> Result<Vec<Vec<f32>>, Box<dyn Error>> {
+        Ok(self.embed_with_usage(texts).await?.0)
+    }
+
+    pub async fn embed_one(&self, text: &str) -> Result<Vec<f32>, Box<dyn Error>> {
+        let mut embeddings = self.embed(&[text]).await?;
+        Ok(embeddings.pop().unwrap_or_default())
+    }
+
+    /// Embeds `texts` and sums the usage reported for every batch; embeddings
+    /// have no completion tokens.
+    pub async fn embed_with_usage<S: AsRef<str>>(
+        &self,
+        texts: &[S],
+    ) -> Result<(Vec<Vec<f32>>, Usage), Box<dyn Error>> {
+        let endpoint = match &self.endpoint {
+            Some(endpoint) => endpoint.clone(),
+            None => self.client.api_url("embeddings")?,
+        };
+        let mut embeddings = Vec::with_capacity(texts.len());
+        let mut usage = Usage::default();
+        for batch in texts.chunks(self.batch_size) {
+            let input: Vec<&str> = batch.iter().map(AsRef::as_ref).collect();
+            let mut body = json!({ "model": self.model, "input": input });
+            if let Some(dimensions) = self.dimensions {
+                body["dimensions"] = Value::from(dimensions);
+            }
+            let res = self
+                .client
+                .send_to(&endpoint, &body, &mut StageTimings::default())
+                .await?;
+            let mut response: EmbeddingsResponse = self.client.read_billed_json(res).await?;
+            if response.data.len() != batch.len() {
+                return Err(format!(
+                    "Expected {} embeddings, got {}",
+                    batch.len(),
+                    response.data.len()
+                )
+                .into());
+            }
+            response.data.sort_by_key(|data| data.index);
+            embeddings.extend(response.data.into_iter().map(|data| data.embedding));
+            if let Some(batch_usage) = response.usage {
+                usage.prompt_tokens += batch_usage.prompt_tokens;
+                usage.total_tokens += batch_usage.total_tokens;
+            }
+        }
+        Ok((embeddings, usage))
+    }
+}
+
+/// Cosine similarity of two embeddings of the same length, in `[-1, 1]`.
+pub fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+    let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let norm = |v: &[f32]| v.iter().map(|x| x * x).sum::<f32>().sqrt();
+    let norms = norm(a) * norm(b);
+    if norms == 0.0 {
+        0.0
+    } else {
+        dot / norms
+    }
+}
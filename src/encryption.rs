@@ -0,0 +1,263 @@
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng, Payload};
+use chacha20poly1305::ChaCha20Poly1305;
+use serde::Serialize;
+use serde_json::Value;
+use std::error::Error;
+use std::fmt;
+use std::sync::Arc;
+
+const PREFIX: &str = "enc:v1:";
+const NONCE_LEN: usize = 12;
+
+/// Supplies 256-bit keys for field encryption. New values are encrypted with
+/// the current key; older values name the key they were encrypted with, so
+/// keys can be rotated while old ones stay available for decryption.
+pub trait KeyProvider: Send + Sync {
+    fn current_key_id(&self) -> String;
+    fn key(&self, key_id: &str) -> Option<[u8; 32]>;
+}
+
+/// A single fixed key.
+#[derive(Clone)]
+pub struct StaticKey {
+    id: String,
+    key: [u8; 32],
+}
+
+impl StaticKey {
+    pub fn new(id: impl Into<String>, key: [u8; 32]) -> Self {
+        Self { id: id.into(), key }
+    }
+}
+
+impl KeyProvider for StaticKey {
+    fn current_key_id(&self) -> String {
+        self.id.clone()
+    }
+
+    fn key(&self, key_id: &str) -> Option<[u8; 32]> {
+        (key_id == self.id).then_some(self.key)
+    }
+}
+
+impl fmt::Debug for StaticKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("StaticKey").field("id", &self.id).finish()
+    }
+}
+
+/// Encrypts designated fields of structured output so extracted PII is
+/// protected at rest, while the remaining fields stay readable and queryable.
+///
+/// Fields are selected by JSON pointer (`/customer/email`); a `*` segment
+/// matches every array item or object member (`/contacts/*/phone`). Each value
+/// is replaced by the string `enc:v1:<key id>:<base64 nonce and ciphertext>`,
+/// sealed with ChaCha20-Poly1305 and bound to its path.
+#[derive(Clone)]
+pub struct FieldEncryptor {
+    keys: Arc<dyn KeyProvider>,
+    paths: Vec<String>,
+}
+
+impl FieldEncryptor {
+    pub fn new(keys: impl KeyProvider + 'static) -> Self {
+        Self {
+            keys: Arc::new(keys),
+            paths: Vec::new(),
+        }
+    }
+
+    pub fn with_field(mut self, pointer: impl Into<String>) -> Self {
+        self.paths.push(pointer.into());
+        self
+    }
+
+    /// Serializes `value` and encrypts its designated fields.
+    pub fn encrypt_value<T: Serialize>(&self, value: &T) -> Result<Value, Box<dyn Error>> {
+        let mut value = serde_json::to_value(value)?;
+        self.encrypt(&mut value)?;
+        Ok(value)
+    }
+
+    /// Encrypts the designated fields in place. Missing fields are skipped.
+    pub fn encrypt(&self, value: &mut Value) -> Result<(), EncryptionError> {
+        let key_id = self.keys.current_key_id();
+        let key = self
+            .keys
+            .key(&key_id)
+            .ok_or_else(|| EncryptionError::new(format!("unknown key {}", key_id)))?;
+        let cipher = ChaCha20Poly1305::new(&key.into());
+        self.for_each_field(value, &mut |path, field| {
+            if is_encrypted(field) {
+                return Ok(());
+            }
+            let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+            let plaintext = serde_json::to_vec(field).map_err(EncryptionError::from)?;
+            let payload = Payload {
+                msg: &plaintext,
+                aad: path.as_bytes(),
+            };
+            let mut sealed = nonce.to_vec();
+            sealed.extend(
+                cipher
+                    .encrypt(&nonce, payload)
+                    .map_err(|_| EncryptionError::new("encryption failed"))?,
+            );
+            *field = Value::String(format!("{}{}:{}", PREFIX, key_id, STANDARD.encode(sealed)));
+            Ok(())
+        })
+    }
+
+    /// Decrypts the designated fields in place. Fields that are not encrypted
+    /// are left as they are.
+    pub fn decrypt(&self, value: &mut Value) -> Result<(), EncryptionError> {
+        self.for_each_field(value, &mut |path, field| {
+            let Some((key_id, sealed)) = field
+                .as_str()
+                .and_then(|s| s.strip_prefix(PREFIX))
+                .and_then(|s| s.split_once(':'))
+            else {
+                return Ok(());
+            };
+            let key = self
+                .keys
+                .key(key_id)
+                .ok_or_else(|| EncryptionError::new(format!("unknown key {}", key_id)))?;
+            let sealed = STANDARD
+                .decode(sealed)
+                .map_err(|err| EncryptionError::new(err.to_string()))?;
+            if sealed.len() < NONCE_LEN {
+                return Err(EncryptionError::new("ciphertext too short"));
+            }
+            let (nonce, ciphertext) = sealed.split_at(NONCE_LEN);
+            let payload = Payload {
+                msg: ciphertext,
+                aad: path.as_bytes(),
+            };
+            let plaintext = ChaCha20Poly1305::new(&key.into())
+                .decrypt(nonce.into(), payload)
+                .map_err(|_| EncryptionError::new(format!("cannot decrypt {}", path)))?;
+            *field = serde_json::from_slice(&plaintext).map_err(EncryptionError::from)?;
+            Ok(())
+        })
+    }
+
+    /// Encrypts the designated fields of the message content of a normalized
+    /// chat-completions response, e.g. before it is cached.
+    pub(crate) fn encrypt_content(&self, raw: &Value) -> Result<Value, EncryptionError> {
+        self.map_content(raw, |value| self.encrypt(value))
+    }
+
+    pub(crate) fn decrypt_content(&self, raw: &Value) -> Result<Value, EncryptionError> {
+        self.map_content(raw, |value| self.decrypt(value))
+    }
+
+    fn map_content(
+        &self,
+        raw: &Value,
+        f: impl FnOnce(&mut Value) -> Result<(), EncryptionError>,
+    ) -> Result<Value, EncryptionError> {
+        let mut raw = raw.clone();
+        let content = &mut raw["choices"][0]["message"]["content"];
+        if let Some(text) = content.as_str() {
+            let mut value: Value = serde_json::from_str(text)?;
+            f(&mut value)?;
+            *content = Value::String(value.to_string());
+        }
+        Ok(raw)
+    }
+
+    fn for_each_field(
+        &self,
+        value: &mut Value,
+        f: &mut dyn FnMut(&str, &mut Value) -> Result<(), EncryptionError>,
+    ) -> Result<(), EncryptionError> {
+        for pointer in &self.paths {
+            let segments: Vec<&str> = pointer.split('/').skip(1).collect();
+            visit(value, &segments, String::new(), f)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Debug for FieldEncryptor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FieldEncryptor")
+            .field("paths", &self.paths)
+            .finish()
+    }
+}
+
+fn is_encrypted(value: &Value) -> bool {
+    value.as_str().is_some_and(|s| s.starts_with(PREFIX))
+}
+
+/// Calls `f` with the concrete path of every value matching `segments`.
+fn visit(
+    value: &mut Value,
+    segments: &[&str],
+    path: String,
+    f: &mut dyn FnMut(&str, &mut Value) -> Result<(), EncryptionError>,
+) -> Result<(), EncryptionError> {
+    let Some((segment, rest)) = segments.split_first() else {
+        return f(&path, value);
+    };
+    match (value, *segment) {
+        (Value::Array(items), "*") => {
+            for (index, item) in items.iter_mut().enumerate() {
+                visit(item, rest, format!("{}/{}", path, index), f)?;
+            }
+        }
+        (Value::Object(members), "*") => {
+            for (name, member) in members.iter_mut() {
+                visit(member, rest, format!("{}/{}", path, escape(name)), f)?;
+            }
+        }
+        (Value::Array(items), index) => {
+            if let Some(item) = index.parse::<usize>().ok().and_then(|i| items.get_mut(i)) {
+                visit(item, rest, format!("{}/{}", path, index), f)?;
+            }
+        }
+        (Value::Object(members), name) => {
+            let name = name.replace("~1", "/").replace("~0", "~");
+            if let Some(member) = members.get_mut(&name) {
+                visit(member, rest, format!("{}/{}", path, escape(&name)), f)?;
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+fn escape(name: &str) -> String {
+    name.replace('~', "~0").replace('/', "~1")
+}
+
+#[derive(Debug, Clone)]
+pub struct EncryptionError {
+    pub message: String,
+}
+
+impl EncryptionError {
+    fn new(message: impl Into<String>) -> Self {
+        Self {
+            message: message.into(),
+        }
+    }
+}
+
+impl From<serde_json::Error> for EncryptionError {
+    fn from(err: serde_json::Error) -> Self {
+        Self::new(err.to_string())
+    }
+}
+
+impl fmt::Display for EncryptionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Field encryption error: {}", self.message)
+    }
+}
+
+impl Error for EncryptionError {}
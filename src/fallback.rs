@@ -0,0 +1,138 @@
+use regex::Regex;
+use serde_json::{Map, Value};
+use std::sync::OnceLock;
+
+/// A way of reading the model's message content as JSON. Structured outputs
+/// always produce strict JSON; the other parsers recover output from weaker
+/// models that drift from the format despite instructions.
+///
+/// Parsers are tried in order until one's reading deserializes into the
+/// target type; `ResponseMeta::parser` reports which one did.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ContentParser {
+    /// The content as-is.
+    #[default]
+    Json,
+    /// JSON inside code fences or prose, with trailing commas or brackets left
+    /// unclosed by truncation.
+    RepairedJson,
+    /// A YAML mapping or sequence, optionally in code fences.
+    Yaml,
+    /// One `key: value` or `key = value` line per top-level field. Keys are
+    /// lowercased with spaces and dashes turned into underscores; values are
+    /// read as JSON where possible and as strings otherwise.
+    KeyValue,
+}
+
+impl ContentParser {
+    /// Every parser, from strictest to loosest.
+    pub fn chain() -> Vec<ContentParser> {
+        vec![
+            ContentParser::Json,
+            ContentParser::RepairedJson,
+            ContentParser::Yaml,
+            ContentParser::KeyValue,
+        ]
+    }
+
+    /// Reads `content` as JSON, or returns `None` if it is not in this format.
+    pub fn parse(&self, content: &str) -> Option<Value> {
+        match self {
+            ContentParser::Json => serde_json::from_str(content).ok(),
+            ContentParser::RepairedJson => repair_json(content),
+            ContentParser::Yaml => {
+                match serde_yaml::from_str::<Value>(strip_code_fence(content)).ok()? {
+                    value @ (Value::Object(_) | Value::Array(_)) => Some(value),
+                    _ => None,
+                }
+            }
+            ContentParser::KeyValue => key_values(content),
+        }
+    }
+}
+
+fn strip_code_fence(content: &str) -> &str {
+    let trimmed = content.trim();
+    match trimmed.strip_prefix("```") {
+        Some(rest) => {
+            let body = rest.split_once('\n').map_or("", |(_, body)| body);
+            body.trim_end().strip_suffix("```").unwrap_or(body)
+        }
+        None => trimmed,
+    }
+}
+
+/// Takes the first JSON object or array in the content, dropping trailing
+/// commas and closing whatever strings and brackets are still open at the end.
+fn repair_json(content: &str) -> Option<Value> {
+    let start = content.find(['{', '['])?;
+    let mut repaired = String::with_capacity(content.len() - start);
+    let mut closers = Vec::new();
+    let mut in_string = false;
+    let mut escaped = false;
+    for c in content[start..].chars() {
+        if in_string {
+            repaired.push(c);
+            if escaped {
+                escaped = false;
+            } else if c == '\\' {
+                escaped = true;
+            } else if c == '"' {
+                in_string = false;
+            }
+            continue;
+        }
+        match c {
+            '"' => in_string = true,
+            '{' => closers.push('}'),
+            '[' => closers.push(']'),
+            '}' | ']' => {
+                if closers.pop() != Some(c) {
+                    return None;
+                }
+                strip_trailing_comma(&mut repaired);
+                repaired.push(c);
+                if closers.is_empty() {
+                    break;
+                }
+                continue;
+            }
+            _ => {}
+        }
+        repaired.push(c);
+    }
+    if in_string {
+        repaired.push('"');
+    }
+    while let Some(closer) = closers.pop() {
+        strip_trailing_comma(&mut repaired);
+        repaired.push(closer);
+    }
+    serde_json::from_str(&repaired).ok()
+}
+
+fn strip_trailing_comma(json: &mut String) {
+    json.truncate(json.trim_end().len());
+    if json.ends_with(',') {
+        json.pop();
+    }
+}
+
+fn key_values(content: &str) -> Option<Value> {
+    static LINE: OnceLock<Regex> = OnceLock::new();
+    let line_pattern = LINE.get_or_init(|| {
+        Regex::new(r#"^\s*(?:[-*]\s+)?["']?([A-Za-z][\w \-]*?)["']?\s*[:=]\s*(.+?)\s*,?\s*$"#)
+            .unwrap()
+    });
+    let mut fields = Map::new();
+    for line in strip_code_fence(content).lines() {
+        if let Some(captures) = line_pattern.captures(line) {
+            let key = captures[1].trim().to_lowercase().replace([' ', '-'], "_");
+            let value = &captures[2];
+            let value = serde_json::from_str(value)
+                .unwrap_or_else(|_| Value::String(value.trim_matches(['"', '\'']).to_string()));
+            fields.insert(key, value);
+        }
+    }
+    (!fields.is_empty()).then_some(Value::Object(fields))
+}
@@ -0,0 +1,274 @@
+//! C ABI over dynamic schema calls, for hosts in other languages. Requests and
+//! results are JSON strings; build a `cdylib` with the `ffi` feature to link
+//! against it, e.g. `cargo rustc --release --features ffi --crate-type cdylib`.
+//!
+//! Every function returns one of the `OSC_*` codes. Strings returned through
+//! `out` parameters are owned by the caller and freed with `osc_string_free`.
+
+use crate::dynamic::DynSchema;
+use crate::openai::{OpenAiClient, ParseError, Refusal};
+use crate::options::RequestOptions;
+use crate::refusal::RefusalsExhausted;
+use crate::retry::RetryPolicy;
+use serde::Deserialize;
+use serde_json::Value;
+use std::error::Error;
+use std::ffi::{c_char, c_int, CStr, CString};
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+use std::time::Duration;
+use tokio::runtime::{self, Runtime};
+
+pub const OSC_OK: c_int = 0;
+/// A null pointer, a string that is not UTF-8 or JSON that doesn't match.
+pub const OSC_INVALID_ARGUMENT: c_int = 1;
+/// The model refused to answer.
+pub const OSC_REFUSAL: c_int = 2;
+/// The model's output did not match the schema, after any repairs.
+pub const OSC_PARSE: c_int = 3;
+/// Any other failure, such as a network or API error.
+pub const OSC_ERROR: c_int = 4;
+
+/// A client and the runtime its calls are driven on.
+pub struct OscClient {
+    client: OpenAiClient,
+    runtime: Runtime,
+}
+
+/// `osc_client_new` configuration.
+#[derive(Deserialize)]
+struct ClientConfig {
+    endpoint: String,
+    model: String,
+    api_key: String,
+    system_role: Option<String>,
+    /// Total attempts of a request; retries are off when unset.
+    max_attempts: Option<u32>,
+    timeout_ms: Option<u64>,
+    #[serde(default)]
+    repair_attempts: u32,
+    /// Allows a plain-HTTP endpoint on a loopback address.
+    #[serde(default)]
+    insecure_loopback: bool,
+}
+
+/// `osc_call` request.
+#[derive(Deserialize)]
+struct CallRequest {
+    schema_name: String,
+    schema: Value,
+    prompt: String,
+    #[serde(default = "strict_by_default")]
+    strict: bool,
+    temperature: Option<f32>,
+    seed: Option<i64>,
+    max_completion_tokens: Option<u32>,
+}
+
+fn strict_by_default() -> bool {
+    true
+}
+
+struct Failure {
+    code: c_int,
+    message: String,
+}
+
+impl Failure {
+    fn invalid(message: impl ToString) -> Self {
+        Self {
+            code: OSC_INVALID_ARGUMENT,
+            message: message.to_string(),
+        }
+    }
+
+    fn of_call(err: Box<dyn Error>) -> Self {
+        let code = if err.is::<Refusal>() || err.is::<RefusalsExhausted>() {
+            OSC_REFUSAL
+        } else if err.is::<ParseError>() {
+            OSC_PARSE
+        } else {
+            OSC_ERROR
+        };
+        Self {
+            code,
+            message: err.to_string(),
+        }
+    }
+}
+
+/// Creates a client from a JSON configuration with `endpoint`, `model` and
+/// `api_key`, and optionally `system_role`, `max_attempts`, `timeout_ms`,
+/// `repair_attempts` and `insecure_loopback`. On success `*client` is set to
+/// the client, to be freed with `osc_client_free`; otherwise `*error` is set
+/// to a message.
+///
+/// # Safety
+///
+/// `config` must be null or a NUL-terminated string, and `client` and `error`
+/// must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn osc_client_new(
+    config: *const c_char,
+    client: *mut *mut OscClient,
+    error: *mut *mut c_char,
+) -> c_int {
+    let result = guard(|| {
+        let config: ClientConfig =
+            serde_json::from_str(read_str(config)?).map_err(Failure::invalid)?;
+        let runtime = runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .map_err(|err| Failure {
+                code: OSC_ERROR,
+                message: err.to_string(),
+            })?;
+        Ok(OscClient {
+            client: build_client(config),
+            runtime,
+        })
+    });
+    match result {
+        Ok(created) => {
+            *client = Box::into_raw(Box::new(created));
+            OSC_OK
+        }
+        Err(failure) => {
+            *client = ptr::null_mut();
+            *error = to_c_string(failure.message);
+            failure.code
+        }
+    }
+}
+
+/// Runs a call described by a JSON request with `schema_name`, `schema` and
+/// `prompt`, and optionally `strict` (default true), `temperature`, `seed`
+/// and `max_completion_tokens`. Blocks until the call finishes, with all of
+/// the client's retries, validation and repairs. `*out` is set to the JSON
+/// output on success and to an error message otherwise.
+///
+/// # Safety
+///
+/// `client` must come from `osc_client_new` and not be freed yet, `request`
+/// must be null or a NUL-terminated string, and `out` must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn osc_call(
+    client: *const OscClient,
+    request: *const c_char,
+    out: *mut *mut c_char,
+) -> c_int {
+    let result = guard(|| {
+        let client = client
+            .as_ref()
+            .ok_or_else(|| Failure::invalid("client is null"))?;
+        let request: CallRequest =
+            serde_json::from_str(read_str(request)?).map_err(Failure::invalid)?;
+        let schema =
+            DynSchema::new(request.schema_name, request.schema).with_strict(request.strict);
+        let mut options = RequestOptions::default();
+        if let Some(temperature) = request.temperature {
+            options = options.temperature(temperature);
+        }
+        if let Some(seed) = request.seed {
+            options = options.seed(seed);
+        }
+        if let Some(max_completion_tokens) = request.max_completion_tokens {
+            options = options.max_completion_tokens(max_completion_tokens);
+        }
+        let value = client
+            .runtime
+            .block_on(
+                client
+                    .client
+                    .call_schema_value(&schema, &request.prompt, &options),
+            )
+            .map_err(Failure::of_call)?;
+        Ok(value.to_string())
+    });
+    match result {
+        Ok(output) => {
+            *out = to_c_string(output);
+            OSC_OK
+        }
+        Err(failure) => {
+            *out = to_c_string(failure.message);
+            failure.code
+        }
+    }
+}
+
+/// Frees a string returned by this library.
+///
+/// # Safety
+///
+/// `string` must be null or a string returned by this library that has not
+/// been freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn osc_string_free(string: *mut c_char) {
+    if !string.is_null() {
+        drop(CString::from_raw(string));
+    }
+}
+
+/// Frees a client.
+///
+/// # Safety
+///
+/// `client` must be null or come from `osc_client_new` and not be freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn osc_client_free(client: *mut OscClient) {
+    if !client.is_null() {
+        drop(Box::from_raw(client));
+    }
+}
+
+fn build_client(config: ClientConfig) -> OpenAiClient {
+    let mut client = OpenAiClient::new(
+        reqwest::Client::new(),
+        config.endpoint,
+        config.model,
+        config.api_key,
+    )
+    .with_repair(config.repair_attempts)
+    .with_insecure_loopback(config.insecure_loopback);
+    if let Some(role) = config.system_role {
+        client = client.with_system_role(role);
+    }
+    if let Some(max_attempts) = config.max_attempts {
+        client = client.with_retry(RetryPolicy {
+            max_attempts,
+            ..RetryPolicy::default()
+        });
+    }
+    if let Some(timeout_ms) = config.timeout_ms {
+        client = client.with_timeout(Duration::from_millis(timeout_ms));
+    }
+    client
+}
+
+/// Runs `f`, turning a panic into an error so that it does not unwind into
+/// the host.
+fn guard<R>(f: impl FnOnce() -> Result<R, Failure>) -> Result<R, Failure> {
+    panic::catch_unwind(AssertUnwindSafe(f)).unwrap_or_else(|_| {
+        Err(Failure {
+            code: OSC_ERROR,
+            message: "panicked".to_string(),
+        })
+    })
+}
+
+/// # Safety
+///
+/// `string` must be null or a NUL-terminated string.
+unsafe fn read_str<'a>(string: *const c_char) -> Result<&'a str, Failure> {
+    if string.is_null() {
+        return Err(Failure::invalid("string is null"));
+    }
+    CStr::from_ptr(string).to_str().map_err(Failure::invalid)
+}
+
+/// Converts to a C string, dropping interior NUL bytes.
+fn to_c_string(string: String) -> *mut c_char {
+    let string = CString::new(string.replace('\0', "")).expect("no NUL bytes");
+    string.into_raw()
+}
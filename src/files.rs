@@ -0,0 +1,173 @@
+use crate::openai::OpenAiClient;
+use crate::transport::Multipart;
+use bytes::Bytes;
+use reqwest::Method;
+use serde::Deserialize;
+use std::error::Error;
+use tokio::io::AsyncRead;
+
+/// Files listed per page when following `list_files` pagination.
+const PAGE_SIZE: u32 = 100;
+
+/// What a file is for. Uploads use the first six; the API gives its own
+/// output files the others.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(from = "String")]
+pub enum FilePurpose {
+    /// Requests for the Batch API, as JSONL.
+    Batch,
+    /// Training or validation data for fine-tuning, as JSONL.
+    FineTune,
+    Assistants,
+    Vision,
+    UserData,
+    Evals,
+    BatchOutput,
+    FineTuneResults,
+    AssistantsOutput,
+    /// A purpose this version does not know, sent and read as it is.
+    Other(String),
+}
+
+impl From<String> for FilePurpose {
+    fn from(purpose: String) -> Self {
+        match purpose.as_str() {
+            "batch" => FilePurpose::Batch,
+            "fine-tune" => FilePurpose::FineTune,
+            "assistants" => FilePurpose::Assistants,
+            "vision" => FilePurpose::Vision,
+            "user_data" => FilePurpose::UserData,
+            "evals" => FilePurpose::Evals,
+            "batch_output" => FilePurpose::BatchOutput,
+            "fine-tune-results" => FilePurpose::FineTuneResults,
+            "assistants_output" => FilePurpose::AssistantsOutput,
+            _ => FilePurpose::Other(purpose),
+        }
+    }
+}
+
+impl FilePurpose {
+    fn as_str(&self) -> &str {
+        match self {
+            FilePurpose::Batch => "batch",
+            FilePurpose::FineTune => "fine-tune",
+            FilePurpose::Assistants => "assistants",
+            FilePurpose::Vision => "vision",
+            FilePurpose::UserData => "user_data",
+            FilePurpose::Evals => "evals",
+            FilePurpose::BatchOutput => "batch_output",
+            FilePurpose::FineTuneResults => "fine-tune-results",
+            FilePurpose::AssistantsOutput => "assistants_output",
+            FilePurpose::Other(purpose) => purpose,
+        }
+    }
+}
+
+/// A file stored with the Files API.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct FileObject {
+    pub id: String,
+    /// Size in bytes.
+    pub bytes: u64,
+    /// Unix timestamps in seconds.
+    pub created_at: u64,
+    #[serde(default)]
+    pub expires_at: Option<u64>,
+    pub filename: String,
+    pub purpose: FilePurpose,
+}
+
+#[derive(Debug, Deserialize)]
+struct FileList {
+    data: Vec<FileObject>,
+    #[serde(default)]
+    has_more: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct DeletedFile {
+    deleted: bool,
+}
+
+impl OpenAiClient {
+    /// Uploads a file to `/files` next to the client's `/chat/completions`
+    /// endpoint, reading it from `reader`, e.g. a `tokio::fs::File` or a
+    /// byte slice.
+    ///
+    /// The file is streamed while the request is sent rather than held in
+    /// memory, so middleware sees the request without its body.
+    pub async fn upload_file(
+        &self,
+        reader: impl AsyncRead + Send + 'static,
+        file_name: &str,
+        purpose: FilePurpose,
+    ) -> Result<FileObject, Box<dyn Error>> {
+        let mut form = Multipart::new();
+        form.text("purpose", purpose.as_str());
+        let (content_type, body) = form.finish_with_file("file", file_name, reader);
+
+        let request = self
+            .authorized(Method::POST, &self.api_url("files")?)?
+            .streaming_body(&content_type, body)?;
+        let res = self.execute(request).await?;
+        self.read_json(res).await
+    }
+
+    /// Lists stored files, optionally only those with `purpose`, following
+    /// pagination until every file has been read.
+    pub async fn list_files(
+        &self,
+        purpose: Option<FilePurpose>,
+    ) -> Result<Vec<FileObject>, Box<dyn Error>> {
+        let mut files: Vec<FileObject> = Vec::new();
+        loop {
+            let mut params = vec![("limit".to_string(), PAGE_SIZE.to_string())];
+            if let Some(purpose) = &purpose {
+                params.push(("purpose".to_string(), purpose.as_str().to_string()));
+            }
+            if let Some(last) = files.last() {
+                params.push(("after".to_string(), last.id.clone()));
+            }
+            let request = self
+                .authorized(Method::GET, &self.api_url("files")?)?
+                .query(&params)?;
+            let res = self.execute(request).await?;
+            let page: FileList = self.read_json(res).await?;
+            let done = !page.has_more || page.data.is_empty();
+            files.extend(page.data);
+            if done {
+                return Ok(files);
+            }
+        }
+    }
+
+    pub async fn retrieve_file(&self, id: &str) -> Result<FileObject, Box<dyn Error>> {
+        let request = self.authorized(Method::GET, &self.api_url(&format!("files/{}", id))?)?;
+        let res = self.execute(request).await?;
+        self.read_json(res).await
+    }
+
+    /// Downloads a file's content, such as the output of a batch.
+    pub async fn file_content(&self, id: &str) -> Result<Bytes, Box<dyn Error>> {
+        let request = self.authorized(
+            Method::GET,
+            &self.api_url(&format!("files/{}/content", id))?,
+        )?;
+        let res = self.execute(request).await?;
+        if !res.status.is_success() {
+            let status = res.status;
+            // Surfaces the API's error, if the body carries one.
+            self.read_json::<serde_json::Value>(res).await?;
+            return Err(format!("Downloading file {} failed with {}", id, status).into());
+        }
+        res.bytes().await.map_err(|err| err as Box<dyn Error>)
+    }
+
+    /// Deletes a file, returning whether the API deleted it.
+    pub async fn delete_file(&self, id: &str) -> Result<bool, Box<dyn Error>> {
+        let request = self.authorized(Method::DELETE, &self.api_url(&format!("files/{}", id))?)?;
+        let res = self.execute(request).await?;
+        let deleted: DeletedFile = self.read_json(res).await?;
+        Ok(deleted.deleted)
+    }
+}
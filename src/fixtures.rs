@@ -0,0 +1,253 @@
+use crate::canonical;
+use crate::transport::{HttpRequest, HttpResponse, HttpTransport, TransportError};
+use futures::future::BoxFuture;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use reqwest::StatusCode;
+use serde_json::{json, Map, Value};
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+/// Request headers whose values are replaced before a fixture is written.
+const SECRET_HEADERS: &[&str] = &[
+    "authorization",
+    "proxy-authorization",
+    "cookie",
+    "api-key",
+    "x-api-key",
+    "x-goog-api-key",
+    "openai-organization",
+    "openai-project",
+];
+/// Query parameters whose values are replaced before a fixture is written.
+const SECRET_PARAMS: &[&str] = &["key", "api_key", "api-key"];
+const REDACTED: &str = "REDACTED";
+
+#[derive(Clone)]
+enum Mode {
+    Record(Arc<dyn HttpTransport>),
+    Replay,
+}
+
+/// A transport that records every request and response to a fixtures
+/// directory, or replays them from it without touching the network, for
+/// deterministic tests of code built on the client:
+///
+/// ```ignore
+/// let client = client.with_transport(FixtureTransport::replay("tests/fixtures"));
+/// ```
+///
+/// Requests are matched by method, URL and JSON body (ignoring key order),
+/// and repeats of the same request by the order they were made in. Headers
+/// are not matched. API keys, cookies, organization and project ids, and
+/// headers set with `OpenAiClient::with_secret_header`, are scrubbed from
+/// recorded requests.
+#[derive(Clone)]
+pub struct FixtureTransport {
+    dir: PathBuf,
+    mode: Mode,
+    seen: Arc<Mutex<HashMap<u64, usize>>>,
+}
+
+impl FixtureTransport {
+    /// Sends requests through `inner`, writing each exchange to `dir`.
+    pub fn record(dir: impl Into<PathBuf>, inner: impl HttpTransport + 'static) -> Self {
+        Self::with_mode(dir, Mode::Record(Arc::new(inner)))
+    }
+
+    /// Answers requests from the fixtures in `dir`, failing with
+    /// `MissingFixture` for a request that was not recorded.
+    pub fn replay(dir: impl Into<PathBuf>) -> Self {
+        Self::with_mode(dir, Mode::Replay)
+    }
+
+    fn with_mode(dir: impl Into<PathBuf>, mode: Mode) -> Self {
+        Self {
+            dir: dir.into(),
+            mode,
+            seen: Arc::default(),
+        }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// The fixture file for the request: its hash and how many identical
+    /// requests came before it.
+    fn path_for(&self, request: &HttpRequest) -> PathBuf {
+        let body = match &request.body {
+            Some(body) => match serde_json::from_slice::<Value>(body) {
+                Ok(json) => canonical::canonicalize(&json),
+                Err(_) => String::from_utf8_lossy(body).into_owned(),
+            },
+            None => String::new(),
+        };
+        let key = format!("{} {}\n{}", request.method, scrub_url(&request.url), body);
+        let hash = canonical::fnv1a(key.as_bytes());
+        let mut seen = self.seen.lock().unwrap();
+        let count = seen.entry(hash).or_default();
+        let path = self.dir.join(format!("{:016x}-{}.json", hash, count));
+        *count += 1;
+        path
+    }
+
+    async fn record_exchange(
+        &self,
+        inner: &dyn HttpTransport,
+        request: HttpRequest,
+        path: PathBuf,
+    ) -> Result<HttpResponse, TransportError> {
+        let recorded_request = json!({
+            "method": request.method.as_str(),
+            "url": scrub_url(&request.url),
+            "headers": headers_to_json(&request.headers, true),
+            "body": body_to_json(request.body.as_deref().unwrap_or_default()),
+        });
+        let response = inner.send(request).await?;
+        let (status, headers) = (response.status, response.headers.clone());
+        let body = response.bytes().await?;
+        let fixture = json!({
+            "request": recorded_request,
+            "response": {
+                "status": status.as_u16(),
+                "headers": headers_to_json(&headers, false),
+                "body": body_to_json(&body),
+            },
+        });
+        tokio::fs::create_dir_all(&self.dir).await?;
+        tokio::fs::write(&path, serde_json::to_vec_pretty(&fixture)?).await?;
+        Ok(HttpResponse::new(status, headers, body))
+    }
+
+    async fn replay_exchange(
+        &self,
+        request: HttpRequest,
+        path: PathBuf,
+    ) -> Result<HttpResponse, TransportError> {
+        let fixture: Value = match tokio::fs::read(&path).await {
+            Ok(bytes) => serde_json::from_slice(&bytes)?,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+                return Err(Box::new(MissingFixture {
+                    method: request.method.to_string(),
+                    url: scrub_url(&request.url),
+                    path,
+                }));
+            }
+            Err(err) => return Err(err.into()),
+        };
+        let response = &fixture["response"];
+        let status = StatusCode::from_u16(response["status"].as_u64().unwrap_or(200) as u16)?;
+        let mut headers = HeaderMap::new();
+        for (name, value) in response["headers"].as_object().into_iter().flatten() {
+            if let (Ok(name), Some(Ok(value))) = (
+                HeaderName::try_from(name.as_str()),
+                value.as_str().map(HeaderValue::from_str),
+            ) {
+                headers.insert(name, value);
+            }
+        }
+        let body = match &response["body"] {
+            Value::String(text) => text.clone().into_bytes(),
+            Value::Null => Vec::new(),
+            json => serde_json::to_vec(json)?,
+        };
+        Ok(HttpResponse::new(status, headers, body))
+    }
+}
+
+impl HttpTransport for FixtureTransport {
+    fn send(&self, request: HttpRequest) -> BoxFuture<'_, Result<HttpResponse, TransportError>> {
+        let path = self.path_for(&request);
+        Box::pin(async move {
+            match &self.mode {
+                Mode::Record(inner) => self.record_exchange(inner.as_ref(), request, path).await,
+                Mode::Replay => self.replay_exchange(request, path).await,
+            }
+        })
+    }
+}
+
+impl fmt::Debug for FixtureTransport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mode = match self.mode {
+            Mode::Record(_) => "record",
+            Mode::Replay => "replay",
+        };
+        f.debug_struct("FixtureTransport")
+            .field("dir", &self.dir)
+            .field("mode", &mode)
+            .finish()
+    }
+}
+
+/// Returned in replay mode for a request that has no recorded fixture.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MissingFixture {
+    pub method: String,
+    pub url: String,
+    pub path: PathBuf,
+}
+
+impl fmt::Display for MissingFixture {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "No fixture recorded for {} {} (expected {})",
+            self.method,
+            self.url,
+            self.path.display()
+        )
+    }
+}
+
+impl Error for MissingFixture {}
+
+/// JSON bodies are stored as JSON so fixtures stay readable and editable;
+/// anything else, such as a server-sent event stream, as a string.
+fn body_to_json(body: &[u8]) -> Value {
+    if body.is_empty() {
+        return Value::Null;
+    }
+    match serde_json::from_slice::<Value>(body) {
+        Ok(json) if !json.is_string() => json,
+        _ => Value::String(String::from_utf8_lossy(body).into_owned()),
+    }
+}
+
+fn headers_to_json(headers: &HeaderMap, scrub: bool) -> Value {
+    let mut map = Map::new();
+    for (name, value) in headers {
+        let value = if scrub && (value.is_sensitive() || SECRET_HEADERS.contains(&name.as_str())) {
+            REDACTED
+        } else {
+            value.to_str().unwrap_or_default()
+        };
+        map.insert(name.to_string(), json!(value));
+    }
+    Value::Object(map)
+}
+
+fn scrub_url(url: &str) -> String {
+    let Ok(mut parsed) = reqwest::Url::parse(url) else {
+        return url.to_string();
+    };
+    if parsed.query().is_none() {
+        return url.to_string();
+    }
+    let pairs: Vec<(String, String)> = parsed
+        .query_pairs()
+        .map(|(name, value)| {
+            let value = if SECRET_PARAMS.contains(&name.as_ref()) {
+                REDACTED.to_string()
+            } else {
+                value.into_owned()
+            };
+            (name.into_owned(), value)
+        })
+        .collect();
+    parsed.query_pairs_mut().clear().extend_pairs(pairs);
+    parsed.to_string()
+}
@@ -0,0 +1,284 @@
+//! Translation between chat-completions shapes and Gemini's `generateContent`.
+//! The generated draft-07 schema is downgraded to the OpenAPI 3.0 subset
+//! accepted by `generationConfig.responseSchema`.
+
+use serde_json::{json, Map, Value};
+
+/// Keywords Gemini's response schema does not accept.
+const UNSUPPORTED_KEYWORDS: &[&str] = &[
+    "$schema",
+    "$id",
+    "title",
+    "additionalProperties",
+    "definitions",
+    "default",
+    "examples",
+    "readOnly",
+    "writeOnly",
+];
+
+/// Recursive types are inlined up to this depth, then left as plain objects.
+const MAX_INLINE_DEPTH: usize = 8;
+
+/// Chat-completions option name => `generationConfig` field.
+const GENERATION_OPTIONS: &[(&str, &str)] = &[
+    ("temperature", "temperature"),
+    ("top_p", "topP"),
+    ("max_completion_tokens", "maxOutputTokens"),
+    ("stop", "stopSequences"),
+    ("seed", "seed"),
+    ("frequency_penalty", "frequencyPenalty"),
+    ("presence_penalty", "presencePenalty"),
+];
+
+/// Builds a `generateContent` body. The model is part of the endpoint URL.
+pub(crate) fn request_body(messages: &[Value], schema: Value) -> Value {
+    let mut body = text_request_body(messages, true);
+    body["generationConfig"]["responseSchema"] = downgrade_schema(&schema);
+    body
+}
+
+/// Builds a body without a schema, optionally constrained to JSON.
+pub(crate) fn text_request_body(messages: &[Value], json_mode: bool) -> Value {
+    let system: Vec<Value> = messages
+        .iter()
+        .filter(|m| m["role"] == "system" || m["role"] == "developer")
+        .filter_map(|m| m["content"].as_str())
+        .map(|text| json!({ "text": text }))
+        .collect();
+    let contents: Vec<Value> = messages
+        .iter()
+        .filter(|m| m["role"] != "system" && m["role"] != "developer")
+        .map(to_content)
+        .collect();
+
+    let mut body = json!({ "contents": contents });
+    if json_mode {
+        body["generationConfig"] = json!({ "responseMimeType": "application/json" });
+    }
+    if !system.is_empty() {
+        body["systemInstruction"] = json!({ "parts": system });
+    }
+    body
+}
+
+/// Moves sampling options into `generationConfig` and drops the rest.
+pub(crate) fn adapt_options(body: &mut Value) {
+    let Some(object) = body.as_object_mut() else {
+        return;
+    };
+    let mut config = Map::new();
+    for (option, field) in GENERATION_OPTIONS {
+        if let Some(value) = object.remove(*option) {
+            config.insert(field.to_string(), value);
+        }
+    }
+    object.retain(|key, _| {
+        matches!(
+            key.as_str(),
+            "contents" | "generationConfig" | "systemInstruction"
+        )
+    });
+    if config.is_empty() {
+        return;
+    }
+    if let Value::Object(generation_config) = object
+        .entry("generationConfig")
+        .or_insert_with(|| Value::Object(Map::new()))
+    {
+        generation_config.extend(config);
+    }
+}
+
+fn to_content(message: &Value) -> Value {
+    let role = if message["role"] == "assistant" {
+        "model"
+    } else {
+        "user"
+    };
+    let parts = match (&message["content"], &message["refusal"]) {
+        (Value::Array(parts), _) => parts.iter().map(to_part).collect(),
+        (Value::String(text), _) | (Value::Null, Value::String(text)) => {
+            vec![json!({ "text": text })]
+        }
+        _ => Vec::new(),
+    };
+    json!({ "role": role, "parts": parts })
+}
+
+fn to_part(part: &Value) -> Value {
+    match part["type"].as_str() {
+        Some("text") => json!({ "text": part["text"] }),
+        Some("image_url") => {
+            let url = part["image_url"]["url"].as_str().unwrap_or_default();
+            match url
+                .strip_prefix("data:")
+                .and_then(|rest| rest.split_once(";base64,"))
+            {
+                Some((mime, data)) => json!({ "inlineData": { "mimeType": mime, "data": data } }),
+                None => json!({ "fileData": { "fileUri": url } }),
+            }
+        }
+        _ => part.clone(),
+    }
+}
+
+/// Rewrites a draft-07 schema into Gemini's dialect: references are inlined,
+/// nullable unions become `nullable: true`, `const` becomes a single-value
+/// `enum` and unsupported keywords are removed. Objects list their properties
+/// in `propertyOrdering`, since Gemini otherwise orders them alphabetically.
+pub(crate) fn downgrade_schema(schema: &Value) -> Value {
+    let definitions = schema
+        .get("definitions")
+        .and_then(Value::as_object)
+        .cloned()
+        .unwrap_or_default();
+    downgrade(schema, &definitions, 0)
+}
+
+fn downgrade(schema: &Value, definitions: &Map<String, Value>, depth: usize) -> Value {
+    let Some(object) = schema.as_object() else {
+        return schema.clone();
+    };
+
+    if let Some(name) = object
+        .get("$ref")
+        .and_then(Value::as_str)
+        .and_then(|r| r.strip_prefix("#/definitions/"))
+    {
+        return match definitions.get(name) {
+            Some(resolved) if depth < MAX_INLINE_DEPTH => {
+                downgrade(resolved, definitions, depth + 1)
+            }
+            _ => json!({ "type": "object" }),
+        };
+    }
+
+    let mut out = Map::new();
+    for (key, value) in object {
+        if UNSUPPORTED_KEYWORDS.contains(&key.as_str()) {
+            continue;
+        }
+        match key.as_str() {
+            "type" => match value {
+                Value::Array(types) => {
+                    let non_null: Vec<&Value> = types.iter().filter(|t| *t != "null").collect();
+                    if non_null.len() < types.len() {
+                        out.insert("nullable".into(), json!(true));
+                    }
+                    out.insert(
+                        "type".into(),
+                        non_null
+                            .first()
+                            .cloned()
+                            .cloned()
+                            .unwrap_or(json!("string")),
+                    );
+                }
+                _ => {
+                    out.insert("type".into(), value.clone());
+                }
+            },
+            "const" => {
+                out.insert("enum".into(), json!([value]));
+            }
+            "properties" => {
+                let properties: Map<String, Value> = value
+                    .as_object()
+                    .into_iter()
+                    .flatten()
+                    .map(|(name, property)| (name.clone(), downgrade(property, definitions, depth)))
+                    .collect();
+                out.insert(
+                    "propertyOrdering".into(),
+                    properties.keys().cloned().collect(),
+                );
+                out.insert("properties".into(), Value::Object(properties));
+            }
+            "items" => {
+                out.insert("items".into(), downgrade(value, definitions, depth));
+            }
+            "anyOf" | "oneOf" | "allOf" => {
+                let variants: Vec<&Value> = value.as_array().into_iter().flatten().collect();
+                let non_null: Vec<Value> = variants
+                    .iter()
+                    .filter(|v| v.get("type") != Some(&json!("null")))
+                    .map(|v| downgrade(v, definitions, depth))
+                    .collect();
+                if non_null.len() < variants.len() {
+                    out.insert("nullable".into(), json!(true));
+                }
+                match non_null.len() {
+                    1 => {
+                        if let Value::Object(single) = &non_null[0] {
+                            out.extend(single.clone());
+                        }
+                    }
+                    _ => {
+                        out.insert("anyOf".into(), Value::Array(non_null));
+                    }
+                }
+            }
+            _ => {
+                out.insert(key.clone(), value.clone());
+            }
+        }
+    }
+    Value::Object(out)
+}
+
+/// Converts a `generateContent` response into the equivalent chat-completions
+/// response. Blocked prompts and safety stops become refusals; error bodies
+/// pass through.
+pub(crate) fn to_chat_completion(response: Value) -> Value {
+    if response.get("candidates").is_none() && response.get("promptFeedback").is_none() {
+        return response;
+    }
+
+    let candidate = &response["candidates"][0];
+    let finish = candidate["finishReason"].as_str().unwrap_or("STOP");
+    let mut message = Map::new();
+    message.insert("role".into(), json!("assistant"));
+    let blocked = response["promptFeedback"]["blockReason"]
+        .as_str()
+        .or(match finish {
+            "SAFETY" | "RECITATION" | "BLOCKLIST" | "PROHIBITED_CONTENT" | "SPII" => Some(finish),
+            _ => None,
+        });
+    match blocked {
+        Some(reason) => {
+            message.insert(
+                "refusal".into(),
+                json!(format!("Blocked by Gemini: {}", reason)),
+            );
+        }
+        None => {
+            let text: String = candidate["content"]["parts"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .filter_map(|part| part["text"].as_str())
+                .collect();
+            message.insert("content".into(), json!(text));
+        }
+    }
+
+    let usage = &response["usageMetadata"];
+    json!({
+        "id": response["responseId"].as_str().unwrap_or_default(),
+        "model": response["modelVersion"].as_str().unwrap_or_default(),
+        "created": 0,
+        "choices": [{
+            "message": message,
+            "finish_reason": if finish == "MAX_TOKENS" { "length" } else { "stop" }
+        }],
+        "usage": {
+            "prompt_tokens": usage["promptTokenCount"].as_u64().unwrap_or(0),
+            "completion_tokens": usage["candidatesTokenCount"].as_u64().unwrap_or(0),
+            "total_tokens": usage["totalTokenCount"].as_u64().unwrap_or(0),
+            "prompt_tokens_details": {
+                "cached_tokens": usage["cachedContentTokenCount"].as_u64().unwrap_or(0)
+            }
+        }
+    })
+}
@@ -0,0 +1,96 @@
+use serde::Deserialize;
+use unicode_normalization::{is_nfc, UnicodeNormalization};
+
+/// Sanitizes prompts before they are sent: NFC normalization, removal of
+/// invisible/control characters and optional transliteration of look-alike
+/// punctuation to ASCII.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct InputGuard {
+    pub normalize_nfc: bool,
+    pub strip_invisible: bool,
+    pub transliterate: bool,
+}
+
+impl Default for InputGuard {
+    fn default() -> Self {
+        Self {
+            normalize_nfc: true,
+            strip_invisible: true,
+            transliterate: false,
+        }
+    }
+}
+
+/// What the guard changed in a given input. Indices are char positions in the
+/// NFC-normalized input.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct GuardReport {
+    pub normalized: bool,
+    pub removed: Vec<(usize, char)>,
+    pub transliterated: Vec<(usize, char)>,
+}
+
+impl GuardReport {
+    pub fn is_clean(&self) -> bool {
+        !self.normalized && self.removed.is_empty() && self.transliterated.is_empty()
+    }
+}
+
+impl InputGuard {
+    /// Returns the sanitized input together with a report of the modifications.
+    pub fn apply(&self, input: &str) -> (String, GuardReport) {
+        let mut report = GuardReport::default();
+
+        let normalized: String = if self.normalize_nfc && !is_nfc(input) {
+            report.normalized = true;
+            input.nfc().collect()
+        } else {
+            input.to_string()
+        };
+
+        let mut output = String::with_capacity(normalized.len());
+        for (index, ch) in normalized.chars().enumerate() {
+            if self.strip_invisible && is_invisible(ch) {
+                report.removed.push((index, ch));
+                continue;
+            }
+            if self.transliterate {
+                if let Some(replacement) = transliterate(ch) {
+                    report.transliterated.push((index, ch));
+                    output.push_str(replacement);
+                    continue;
+                }
+            }
+            output.push(ch);
+        }
+
+        (output, report)
+    }
+}
+
+fn is_invisible(ch: char) -> bool {
+    match ch {
+        '\n' | '\r' | '\t' => false,
+        // Zero-width characters, joiners and the byte order mark
+        '\u{200B}'..='\u{200D}' | '\u{2060}' | '\u{FEFF}' | '\u{00AD}' => true,
+        // Bidirectional overrides and isolates
+        '\u{200E}' | '\u{200F}' | '\u{202A}'..='\u{202E}' | '\u{2066}'..='\u{2069}' => true,
+        // Tag characters, which can smuggle hidden instructions
+        '\u{E0000}'..='\u{E007F}' => true,
+        _ => ch.is_control(),
+    }
+}
+
+fn transliterate(ch: char) -> Option<&'static str> {
+    let replacement = match ch {
+        '\u{2018}' | '\u{2019}' | '\u{201A}' | '\u{2032}' => "'",
+        '\u{201C}' | '\u{201D}' | '\u{201E}' | '\u{2033}' => "\"",
+        '\u{2010}'..='\u{2015}' | '\u{2212}' => "-",
+        '\u{2026}' => "...",
+        '\u{00A0}' | '\u{2000}'..='\u{200A}' | '\u{202F}' | '\u{205F}' | '\u{3000}' => " ",
+        '\u{2022}' => "*",
+        _ => return None,
+    };
+    Some(replacement)
+}
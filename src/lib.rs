@@ -0,0 +1,66 @@
+pub mod agent;
+mod anthropic;
+pub mod attribution;
+pub mod audit;
+pub mod balance;
+pub mod batch;
+pub mod binary;
+#[cfg(feature = "blocking")]
+pub mod blocking;
+pub mod builder;
+pub mod cache;
+pub mod canonical;
+pub mod compare;
+pub mod compat;
+pub mod completion;
+pub mod consensus;
+pub mod content;
+pub mod context;
+pub mod cost;
+pub mod credentials;
+pub mod dynamic;
+pub mod embeddings;
+pub mod encryption;
+pub mod fallback;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+pub mod files;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod fixtures;
+mod gemini;
+pub mod guard;
+pub mod limits;
+mod local;
+pub mod middleware;
+pub mod mock;
+pub mod moderation;
+pub mod monitor;
+pub mod openai;
+pub mod operations;
+pub mod options;
+#[cfg(feature = "otel")]
+pub mod otel;
+pub mod partial;
+pub mod planner;
+pub mod probe;
+pub mod prompt;
+mod ratelimit;
+#[cfg(feature = "realtime")]
+pub mod realtime;
+pub mod reasoning;
+pub mod refusal;
+mod responses;
+pub mod retry;
+pub mod revalidate;
+pub mod review;
+pub mod sampling;
+pub mod stats;
+pub mod stored;
+pub mod structured;
+pub mod style;
+mod time;
+pub mod tokens;
+pub mod tools;
+mod trace;
+pub mod transcription;
+pub mod transport;
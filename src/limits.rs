@@ -0,0 +1,297 @@
+use serde_json::{Map, Value};
+use std::error::Error;
+use std::fmt;
+
+/// Keywords rejected by OpenAI strict mode on some models.
+const UNSUPPORTED_KEYWORDS: &[&str] = &[
+    "minLength",
+    "maxLength",
+    "pattern",
+    "format",
+    "minimum",
+    "maximum",
+    "exclusiveMinimum",
+    "exclusiveMaximum",
+    "multipleOf",
+    "patternProperties",
+    "propertyNames",
+    "minProperties",
+    "maxProperties",
+    "minItems",
+    "maxItems",
+    "uniqueItems",
+    "contains",
+];
+
+/// Structured-output limits a schema is checked against before it is sent, so
+/// schemas the API would reject fail fast without a round-trip.
+#[derive(Debug, Clone)]
+pub struct SchemaLimits {
+    /// Total object properties across the schema and its definitions.
+    pub max_properties: usize,
+    /// Levels of nested objects, the root object being level 1.
+    pub max_depth: usize,
+    /// Values of a single enum.
+    pub max_enum_values: usize,
+    pub unsupported_keywords: Vec<String>,
+    /// Remove unsupported keywords instead of rejecting the schema (the
+    /// default).
+    pub strip_unsupported: bool,
+}
+
+impl Default for SchemaLimits {
+    fn default() -> Self {
+        Self::openai()
+    }
+}
+
+impl SchemaLimits {
+    /// OpenAI's documented limits for strict structured outputs. Unsupported
+    /// keywords, such as the `minimum` schemars adds to unsigned integers, are
+    /// stripped; `strip_unsupported(false)` rejects them instead.
+    pub fn openai() -> Self {
+        Self {
+            max_properties: 100,
+            max_depth: 5,
+            max_enum_values: 500,
+            unsupported_keywords: UNSUPPORTED_KEYWORDS.iter().map(|k| k.to_string()).collect(),
+            strip_unsupported: true,
+        }
+    }
+
+    pub fn strip_unsupported(mut self, strip: bool) -> Self {
+        self.strip_unsupported = strip;
+        self
+    }
+
+    /// Strips unsupported keywords if configured, then validates the schema.
+    pub fn apply(&self, schema: &mut Value) -> Result<(), SchemaLimitError> {
+        if self.strip_unsupported {
+            self.strip(schema);
+        }
+        self.validate(schema)
+    }
+
+    /// Returns the first violated limit, with the JSON pointer of the offending schema.
+    pub fn validate(&self, schema: &Value) -> Result<(), SchemaLimitError> {
+        let definitions = schema.get("definitions").and_then(Value::as_object);
+        let mut properties = 0;
+        self.check(schema, "#", &mut properties)?;
+        for (name, definition) in definitions.into_iter().flatten() {
+            self.check(
+                definition,
+                &format!("#/definitions/{}", name),
+                &mut properties,
+            )?;
+        }
+        self.check_depth(schema, "#", 0, definitions, &mut Vec::new())
+    }
+
+    /// Removes unsupported keywords from every subschema.
+    pub fn strip(&self, schema: &mut Value) {
+        let Some(object) = schema.as_object_mut() else {
+            return;
+        };
+        object.retain(|key, _| !self.unsupported_keywords.contains(key));
+        for (key, value) in object.iter_mut() {
+            for_each_subschema_mut(key, value, &mut |subschema| self.strip(subschema));
+        }
+    }
+
+    /// Checks keywords, enum sizes and the running property count of one
+    /// subschema tree, without following references.
+    fn check(
+        &self,
+        schema: &Value,
+        path: &str,
+        properties: &mut usize,
+    ) -> Result<(), SchemaLimitError> {
+        let Some(object) = schema.as_object() else {
+            return Ok(());
+        };
+        if let Some(keyword) = object
+            .keys()
+            .find(|k| self.unsupported_keywords.contains(k))
+        {
+            return Err(SchemaLimitError::new(
+                path,
+                Limit::UnsupportedKeyword(keyword.clone()),
+            ));
+        }
+        if let Some(Value::Array(values)) = object.get("enum") {
+            if values.len() > self.max_enum_values {
+                return Err(SchemaLimitError::new(
+                    path,
+                    Limit::EnumValues {
+                        max: self.max_enum_values,
+                        found: values.len(),
+                    },
+                ));
+            }
+        }
+        if let Some(Value::Object(props)) = object.get("properties") {
+            *properties += props.len();
+            if *properties > self.max_properties {
+                return Err(SchemaLimitError::new(
+                    path,
+                    Limit::TotalProperties {
+                        max: self.max_properties,
+                    },
+                ));
+            }
+        }
+        for (key, value) in object {
+            if key == "definitions" {
+                continue;
+            }
+            let mut result = Ok(());
+            for_each_subschema(key, value, &mut |suffix, subschema| {
+                if result.is_ok() {
+                    result = self.check(subschema, &format!("{}/{}", path, suffix), properties);
+                }
+            });
+            result?;
+        }
+        Ok(())
+    }
+
+    /// Follows references to measure object nesting. A definition already on
+    /// the current path is recursive and is not expanded again.
+    fn check_depth(
+        &self,
+        schema: &Value,
+        path: &str,
+        depth: usize,
+        definitions: Option<&Map<String, Value>>,
+        stack: &mut Vec<String>,
+    ) -> Result<(), SchemaLimitError> {
+        let Some(object) = schema.as_object() else {
+            return Ok(());
+        };
+        if let Some(name) = object
+            .get("$ref")
+            .and_then(Value::as_str)
+            .and_then(|r| r.strip_prefix("#/definitions/"))
+        {
+            if stack.iter().any(|n| n == name) {
+                return Ok(());
+            }
+            let Some(definition) = definitions.and_then(|d| d.get(name)) else {
+                return Ok(());
+            };
+            stack.push(name.to_string());
+            let result = self.check_depth(definition, path, depth, definitions, stack);
+            stack.pop();
+            return result;
+        }
+
+        let depth = if object.contains_key("properties") {
+            depth + 1
+        } else {
+            depth
+        };
+        if depth > self.max_depth {
+            return Err(SchemaLimitError::new(
+                path,
+                Limit::Depth {
+                    max: self.max_depth,
+                },
+            ));
+        }
+        for (key, value) in object {
+            if key == "definitions" {
+                continue;
+            }
+            let mut result = Ok(());
+            for_each_subschema(key, value, &mut |suffix, subschema| {
+                if result.is_ok() {
+                    result = self.check_depth(
+                        subschema,
+                        &format!("{}/{}", path, suffix),
+                        depth,
+                        definitions,
+                        stack,
+                    );
+                }
+            });
+            result?;
+        }
+        Ok(())
+    }
+}
+
+/// Calls `f` with the path suffix of every direct subschema under `key`.
+fn for_each_subschema(key: &str, value: &Value, f: &mut dyn FnMut(String, &Value)) {
+    match (key, value) {
+        ("properties", Value::Object(props)) => {
+            for (name, prop) in props {
+                f(format!("properties/{}", name), prop);
+            }
+        }
+        ("items" | "additionalProperties" | "not", Value::Object(_)) => f(key.to_string(), value),
+        ("items" | "anyOf" | "oneOf" | "allOf", Value::Array(schemas)) => {
+            for (index, schema) in schemas.iter().enumerate() {
+                f(format!("{}/{}", key, index), schema);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn for_each_subschema_mut(key: &str, value: &mut Value, f: &mut dyn FnMut(&mut Value)) {
+    match (key, value) {
+        ("properties" | "definitions", Value::Object(schemas)) => schemas.values_mut().for_each(f),
+        ("items" | "anyOf" | "oneOf" | "allOf", Value::Array(schemas)) => {
+            schemas.iter_mut().for_each(f)
+        }
+        ("items" | "additionalProperties" | "not", value @ Value::Object(_)) => f(value),
+        _ => {}
+    }
+}
+
+/// The limit a schema violates.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Limit {
+    TotalProperties { max: usize },
+    Depth { max: usize },
+    EnumValues { max: usize, found: usize },
+    UnsupportedKeyword(String),
+}
+
+impl fmt::Display for Limit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Limit::TotalProperties { max } => write!(f, "more than {} properties in total", max),
+            Limit::Depth { max } => write!(f, "objects nested deeper than {} levels", max),
+            Limit::EnumValues { max, found } => {
+                write!(f, "enum of {} values exceeds {}", found, max)
+            }
+            Limit::UnsupportedKeyword(keyword) => write!(f, "unsupported keyword `{}`", keyword),
+        }
+    }
+}
+
+/// Returned before sending when the generated schema violates `SchemaLimits`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SchemaLimitError {
+    /// JSON pointer of the offending subschema, e.g. `#/properties/items/items`.
+    pub path: String,
+    pub limit: Limit,
+}
+
+impl SchemaLimitError {
+    fn new(path: &str, limit: Limit) -> Self {
+        Self {
+            path: path.to_string(),
+            limit,
+        }
+    }
+}
+
+impl fmt::Display for SchemaLimitError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Schema limit exceeded at {}: {}", self.path, self.limit)
+    }
+}
+
+impl Error for SchemaLimitError {}
@@ -0,0 +1,158 @@
+//! Translation for OpenAI-compatible local servers. Ollama's native `/api/chat`
+//! takes the raw schema in `format`; llama.cpp's server takes it in
+//! `json_schema`. Neither understands `strict`, so it is never sent.
+
+use crate::openai::LocalServer;
+use serde_json::{json, Map, Value};
+
+/// Chat-completions option name => Ollama `options` field.
+const OLLAMA_OPTIONS: &[(&str, &str)] = &[
+    ("temperature", "temperature"),
+    ("top_p", "top_p"),
+    ("max_completion_tokens", "num_predict"),
+    ("stop", "stop"),
+    ("seed", "seed"),
+    ("frequency_penalty", "frequency_penalty"),
+    ("presence_penalty", "presence_penalty"),
+];
+
+pub(crate) fn request_body(
+    server: LocalServer,
+    model: &str,
+    messages: &[Value],
+    schema: Value,
+) -> Value {
+    let mut body = text_request_body(server, model, messages, false);
+    match server {
+        LocalServer::Ollama => body["format"] = schema,
+        LocalServer::LlamaCpp => body["json_schema"] = schema,
+    }
+    body
+}
+
+/// Builds a body without a schema, optionally in the server's JSON mode.
+pub(crate) fn text_request_body(
+    server: LocalServer,
+    model: &str,
+    messages: &[Value],
+    json_mode: bool,
+) -> Value {
+    match server {
+        LocalServer::Ollama => {
+            let mut body = json!({
+                "model": model,
+                "messages": messages.iter().map(to_ollama_message).collect::<Vec<_>>(),
+                "stream": false
+            });
+            if json_mode {
+                body["format"] = json!("json");
+            }
+            body
+        }
+        LocalServer::LlamaCpp => {
+            let mut body = json!({ "model": model, "messages": messages });
+            if json_mode {
+                body["response_format"] = json!({ "type": "json_object" });
+            }
+            body
+        }
+    }
+}
+
+/// Moves options to where the server expects them and drops those it lacks.
+pub(crate) fn adapt_options(server: LocalServer, body: &mut Value) {
+    let Some(object) = body.as_object_mut() else {
+        return;
+    };
+    for unsupported in ["user", "store", "metadata", "reasoning_effort"] {
+        object.remove(unsupported);
+    }
+    match server {
+        LocalServer::Ollama => {
+            object.remove("logprobs");
+            let mut options = Map::new();
+            for (option, field) in OLLAMA_OPTIONS {
+                if let Some(value) = object.remove(*option) {
+                    options.insert(field.to_string(), value);
+                }
+            }
+            if !options.is_empty() {
+                object.insert("options".into(), Value::Object(options));
+            }
+        }
+        LocalServer::LlamaCpp => {
+            if let Some(max_tokens) = object.remove("max_completion_tokens") {
+                object.insert("max_tokens".into(), max_tokens);
+            }
+        }
+    }
+}
+
+/// Ollama messages carry plain-text content plus a list of base64 images.
+/// Image URLs cannot be fetched by the server and are left out.
+fn to_ollama_message(message: &Value) -> Value {
+    let mut text = String::new();
+    let mut images = Vec::new();
+    match (&message["content"], &message["refusal"]) {
+        (Value::Array(parts), _) => {
+            for part in parts {
+                if let Some(part_text) = part["text"].as_str() {
+                    text.push_str(part_text);
+                }
+                if let Some((_, data)) = part["image_url"]["url"]
+                    .as_str()
+                    .and_then(|url| url.strip_prefix("data:"))
+                    .and_then(|rest| rest.split_once(";base64,"))
+                {
+                    images.push(json!(data));
+                }
+            }
+        }
+        (Value::String(content), _) | (Value::Null, Value::String(content)) => {
+            text.push_str(content)
+        }
+        _ => {}
+    }
+
+    let mut converted = json!({ "role": message["role"], "content": text });
+    if !images.is_empty() {
+        converted["images"] = Value::Array(images);
+    }
+    converted
+}
+
+/// Converts a local server response into the chat-completions shape. Both
+/// servers may report errors as a bare string, which is wrapped in the usual
+/// error object.
+pub(crate) fn to_chat_completion(server: LocalServer, response: Value) -> Value {
+    if let Some(message) = response["error"].as_str() {
+        return json!({ "error": { "message": message } });
+    }
+    match server {
+        LocalServer::LlamaCpp => response,
+        LocalServer::Ollama => {
+            if response.get("message").is_none() {
+                return response;
+            }
+            let prompt_tokens = response["prompt_eval_count"].as_u64().unwrap_or(0);
+            let completion_tokens = response["eval_count"].as_u64().unwrap_or(0);
+            json!({
+                "id": "",
+                "model": response["model"],
+                "created": 0,
+                "choices": [{
+                    "message": {
+                        "role": "assistant",
+                        "content": response["message"]["content"]
+                    },
+                    "finish_reason": if response["done_reason"] == "length" { "length" } else { "stop" }
+                }],
+                "usage": {
+                    "prompt_tokens": prompt_tokens,
+                    "completion_tokens": completion_tokens,
+                    "total_tokens": prompt_tokens + completion_tokens
+                }
+            })
+        }
+    }
+}
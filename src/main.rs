@@ -1,18 +1,21 @@
-mod openai;
-
-use crate::openai::OpenAiClient;
+use openai_structured_client::openai::OpenAiClient;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 #[tokio::main]
 async fn main() {
-    let http_client = reqwest::Client::new();
-    let endpoint = "https://api.openai.com/v1/chat/completions";
-    let model = "gpt-4o-2024-08-06";
-    let api_key = "apikey";
-
-    let openai = OpenAiClient::new(http_client, endpoint, model, api_key)
-        .with_system_role("You are a helpful English tutor.");
+    let openai = match OpenAiClient::builder()
+        .model("gpt-4o-2024-08-06")
+        .system_role("You are a helpful English tutor.")
+        .from_env()
+        .build()
+    {
+        Ok(client) => client,
+        Err(err) => {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        }
+    };
 
     let prompt = "Explain the errors in the following sentences... Sentence 1: This is a penn. Sentence 2: This tomato has green.";
     let review: Reviews = openai
@@ -0,0 +1,41 @@
+use bytes::Bytes;
+use reqwest::header::HeaderMap;
+use reqwest::{Method, StatusCode};
+use serde_json::Value;
+
+/// Hooks around every HTTP request the client sends, including each retry, so
+/// gateways' extra headers, custom auth, payload logging or redaction can be
+/// added without changing how requests are built.
+///
+/// Middleware runs in the order it was added, after the client has set its own
+/// headers, so it can also replace them.
+pub trait Middleware: Send + Sync {
+    fn on_request(&self, _request: &mut RequestParts) {}
+
+    fn on_response(&self, _response: &ResponseParts) {}
+}
+
+/// A request about to be sent.
+#[derive(Debug, Clone)]
+pub struct RequestParts {
+    pub method: Method,
+    pub url: String,
+    pub headers: HeaderMap,
+    /// The JSON body, if the request has one.
+    pub body: Option<Value>,
+}
+
+/// A response as received, before it is parsed.
+#[derive(Debug, Clone)]
+pub struct ResponseParts {
+    pub status: StatusCode,
+    pub headers: HeaderMap,
+    pub body: Bytes,
+}
+
+impl ResponseParts {
+    /// The body parsed as JSON, if it is JSON.
+    pub fn json(&self) -> Option<Value> {
+        serde_json::from_slice(&self.body).ok()
+    }
+}
@@ -0,0 +1,83 @@
+use crate::completion::StructuredCompletion;
+use crate::openai::Refusal;
+use schemars::JsonSchema;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::VecDeque;
+use std::error::Error;
+use std::fmt;
+use std::sync::Mutex;
+
+#[derive(Debug, Clone)]
+enum Scripted {
+    Value(Value),
+    Refusal(String),
+    Error(String),
+}
+
+/// A `StructuredCompletion` that replays scripted responses in order, for tests.
+#[derive(Debug, Default)]
+pub struct MockClient {
+    responses: Mutex<VecDeque<Scripted>>,
+    prompts: Mutex<Vec<String>>,
+}
+
+impl MockClient {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Queues a response that is deserialized into the requested type.
+    pub fn with_response(self, response: impl Serialize) -> Self {
+        let value = serde_json::to_value(response).expect("mock response serializes to JSON");
+        self.push(Scripted::Value(value))
+    }
+
+    /// Queues a refusal, surfaced as the same error the real client returns.
+    pub fn with_refusal(self, refusal: impl Into<String>) -> Self {
+        self.push(Scripted::Refusal(refusal.into()))
+    }
+
+    /// Queues an arbitrary error.
+    pub fn with_error(self, message: impl Into<String>) -> Self {
+        self.push(Scripted::Error(message.into()))
+    }
+
+    /// The prompts received so far, in call order.
+    pub fn prompts(&self) -> Vec<String> {
+        self.prompts.lock().unwrap().clone()
+    }
+
+    fn push(self, scripted: Scripted) -> Self {
+        self.responses.lock().unwrap().push_back(scripted);
+        self
+    }
+}
+
+impl StructuredCompletion for MockClient {
+    async fn call_schema<T: DeserializeOwned + JsonSchema + Clone + 'static>(
+        &self,
+        user_prompt: &str,
+    ) -> Result<T, Box<dyn Error>> {
+        self.prompts.lock().unwrap().push(user_prompt.to_string());
+        let scripted = self.responses.lock().unwrap().pop_front();
+        match scripted {
+            Some(Scripted::Value(value)) => Ok(serde_json::from_value(value)?),
+            Some(Scripted::Refusal(refusal)) => Err(Box::new(Refusal { refusal })),
+            Some(Scripted::Error(message)) => Err(Box::new(MockError(message))),
+            None => Err(Box::new(MockError("no scripted response left".into()))),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct MockError(pub String);
+
+impl fmt::Display for MockError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Mock error: {}", self.0)
+    }
+}
+
+impl Error for MockError {}
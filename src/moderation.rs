@@ -0,0 +1,137 @@
+use crate::openai::OpenAiClient;
+use reqwest::Method;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt;
+
+/// Screens prompts and outputs of schema and text calls with the moderation
+/// endpoint under the client's API base, once per call however many repairs
+/// or refusal retries it takes. Flagged content fails the call with
+/// `ModerationFlagged`; flagged outputs are not cached.
+#[derive(Debug, Clone)]
+pub struct ModerationPolicy {
+    pub screen_input: bool,
+    pub screen_output: bool,
+    /// e.g. `omni-moderation-latest`; the API's default when `None`.
+    pub model: Option<String>,
+}
+
+impl Default for ModerationPolicy {
+    fn default() -> Self {
+        Self {
+            screen_input: true,
+            screen_output: false,
+            model: None,
+        }
+    }
+}
+
+/// The moderation result for one text.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+pub struct Moderation {
+    pub flagged: bool,
+    #[serde(default)]
+    pub categories: BTreeMap<String, bool>,
+    #[serde(default)]
+    pub category_scores: BTreeMap<String, f64>,
+}
+
+impl Moderation {
+    /// The categories the text was flagged for.
+    pub fn flagged_categories(&self) -> Vec<&str> {
+        self.categories
+            .iter()
+            .filter(|(_, flagged)| **flagged)
+            .map(|(category, _)| category.as_str())
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ModerationStage {
+    Input,
+    Output,
+}
+
+/// Returned when a screened prompt or output is flagged.
+#[derive(Debug, Clone)]
+pub struct ModerationFlagged {
+    pub stage: ModerationStage,
+    pub moderation: Moderation,
+}
+
+impl fmt::Display for ModerationFlagged {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let stage = match self.stage {
+            ModerationStage::Input => "Prompt",
+            ModerationStage::Output => "Output",
+        };
+        write!(
+            f,
+            "{} flagged by moderation: {}",
+            stage,
+            self.moderation.flagged_categories().join(", ")
+        )
+    }
+}
+
+impl Error for ModerationFlagged {}
+
+#[derive(Debug, Deserialize)]
+struct ModerationResponse {
+    results: Vec<Moderation>,
+}
+
+impl OpenAiClient {
+    /// Classifies `text` with the moderation endpoint.
+    pub async fn moderate(&self, text: &str) -> Result<Moderation, Box<dyn Error>> {
+        self.moderate_with(text, None).await
+    }
+
+    async fn moderate_with(
+        &self,
+        text: &str,
+        model: Option<&str>,
+    ) -> Result<Moderation, Box<dyn Error>> {
+        let mut body = json!({ "input": text });
+        if let Some(model) = model {
+            body["model"] = Value::from(model);
+        }
+        let request = self
+            .authorized(Method::POST, &self.api_url("moderations")?)?
+            .json(&body)?;
+        let res = self.execute(request).await?;
+        let response: ModerationResponse = self.read_billed_json(res).await?;
+        response
+            .results
+            .into_iter()
+            .next()
+            .ok_or_else(|| "Moderation response has no results".into())
+    }
+
+    /// Fails with `ModerationFlagged` if the policy screens `stage` and
+    /// `text` is flagged.
+    pub(crate) async fn screen(
+        &self,
+        stage: ModerationStage,
+        text: &str,
+    ) -> Result<(), Box<dyn Error>> {
+        let Some(policy) = self.moderation() else {
+            return Ok(());
+        };
+        let screened = match stage {
+            ModerationStage::Input => policy.screen_input,
+            ModerationStage::Output => policy.screen_output,
+        };
+        if !screened {
+            return Ok(());
+        }
+        let moderation = self.moderate_with(text, policy.model.as_deref()).await?;
+        if moderation.flagged {
+            return Err(Box::new(ModerationFlagged { stage, moderation }));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,290 @@
+use crate::openai::OpenAiClient;
+use crate::retry;
+use schemars::JsonSchema;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, VecDeque};
+use std::error::Error;
+use std::fmt;
+use std::sync::Mutex;
+
+/// Name under which the judge's score is reported.
+pub const JUDGE: &str = "judge";
+
+type Check<T> = Box<dyn Fn(&T) -> f64 + Send + Sync>;
+type DriftCallback = Box<dyn Fn(&DriftAlert) + Send + Sync>;
+
+/// Watches the quality of production outputs of type T for silent degradation,
+/// e.g. after a model or prompt update.
+///
+/// A fraction of calls is sampled and scored by heuristic checks and optionally
+/// an LLM judge, each from 0.0 (bad) to 1.0 (good). The first `baseline` samples
+/// set the expected quality; once the mean of the most recent `window` samples
+/// falls more than `tolerance` below it, the drift callbacks are called. They
+/// are called again only after quality has recovered and drifted anew.
+pub struct QualityMonitor<T> {
+    sample_rate: f64,
+    checks: Vec<(String, Check<T>)>,
+    judge: Option<Judge<T>>,
+    baseline: usize,
+    window: usize,
+    tolerance: f64,
+    on_drift: Vec<DriftCallback>,
+    state: Mutex<MonitorState>,
+}
+
+struct Judge<T> {
+    client: OpenAiClient,
+    criteria: String,
+    serialize: fn(&T) -> Value,
+}
+
+#[derive(Default)]
+struct MonitorState {
+    sampled: usize,
+    baseline: Vec<f64>,
+    recent: VecDeque<BTreeMap<String, f64>>,
+    drifting: bool,
+}
+
+/// The judge's structured verdict on one output. The reason comes first so the
+/// model explains itself before it scores.
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+struct Judgement {
+    #[allow(dead_code)]
+    reason: String,
+    /// From 0.0 (fails the criteria) to 1.0 (fully meets them).
+    score: f64,
+}
+
+/// Quality of the sampled outputs so far.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct QualityReport {
+    pub samples: usize,
+    /// Mean score of the baseline samples, once all have been collected.
+    pub baseline: Option<f64>,
+    /// Mean score of the samples in the recent window.
+    pub recent: Option<f64>,
+    /// Mean score of each check (and the judge) in the recent window.
+    pub by_check: BTreeMap<String, f64>,
+    pub drifting: bool,
+}
+
+/// Passed to drift callbacks when recent quality falls below the baseline.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DriftAlert {
+    pub baseline: f64,
+    pub recent: f64,
+    pub by_check: BTreeMap<String, f64>,
+}
+
+impl fmt::Display for DriftAlert {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Quality drift: recent score {:.3} against baseline {:.3}",
+            self.recent, self.baseline
+        )
+    }
+}
+
+impl<T> QualityMonitor<T> {
+    /// Samples `sample_rate` (0.0 to 1.0) of the observed outputs.
+    pub fn new(sample_rate: f64) -> Self {
+        Self {
+            sample_rate: sample_rate.clamp(0.0, 1.0),
+            checks: Vec::new(),
+            judge: None,
+            baseline: 50,
+            window: 50,
+            tolerance: 0.1,
+            on_drift: Vec::new(),
+            state: Mutex::new(MonitorState::default()),
+        }
+    }
+
+    /// Adds a heuristic check scoring an output from 0.0 to 1.0, e.g. whether
+    /// a list is non-empty or an extracted date is plausible.
+    pub fn with_check(
+        mut self,
+        name: impl Into<String>,
+        check: impl Fn(&T) -> f64 + Send + Sync + 'static,
+    ) -> Self {
+        self.checks.push((name.into(), Box::new(check)));
+        self
+    }
+
+    /// Number of samples that establish the expected quality (default 50).
+    pub fn with_baseline(mut self, samples: usize) -> Self {
+        self.baseline = samples.max(1);
+        self
+    }
+
+    /// Number of recent samples compared against the baseline (default 50).
+    pub fn with_window(mut self, samples: usize) -> Self {
+        self.window = samples.max(1);
+        self
+    }
+
+    /// How far the recent mean may fall below the baseline (default 0.1).
+    pub fn with_tolerance(mut self, tolerance: f64) -> Self {
+        self.tolerance = tolerance;
+        self
+    }
+
+    pub fn on_drift(mut self, callback: impl Fn(&DriftAlert) + Send + Sync + 'static) -> Self {
+        self.on_drift.push(Box::new(callback));
+        self
+    }
+
+    /// Calls the client and observes the output.
+    pub async fn call(&self, client: &OpenAiClient, prompt: &str) -> Result<T, Box<dyn Error>>
+    where
+        T: DeserializeOwned + JsonSchema + Clone + 'static,
+    {
+        let output = client.call_schema::<T>(prompt).await?;
+        self.observe(prompt, &output).await;
+        Ok(output)
+    }
+
+    /// Scores the output if it is sampled. Judge failures leave the judge's
+    /// score out of the sample rather than failing the observed call.
+    pub async fn observe(&self, prompt: &str, output: &T) {
+        if self.sample_rate < 1.0 && retry::random_fraction() >= self.sample_rate {
+            return;
+        }
+        let mut scores: BTreeMap<String, f64> = self
+            .checks
+            .iter()
+            .map(|(name, check)| (name.clone(), check(output).clamp(0.0, 1.0)))
+            .collect();
+        if let Some(judge) = &self.judge {
+            if let Ok(judgement) = judge.judge(prompt, output).await {
+                scores.insert(JUDGE.to_string(), judgement.score.clamp(0.0, 1.0));
+            }
+        }
+        if !scores.is_empty() {
+            self.record(scores);
+        }
+    }
+
+    pub fn report(&self) -> QualityReport {
+        let state = self.state.lock().unwrap();
+        QualityReport {
+            samples: state.sampled,
+            baseline: self.baseline_mean(&state),
+            recent: recent_mean(&state.recent),
+            by_check: check_means(&state.recent),
+            drifting: state.drifting,
+        }
+    }
+
+    fn record(&self, scores: BTreeMap<String, f64>) {
+        let alert = {
+            let mut state = self.state.lock().unwrap();
+            state.sampled += 1;
+            if state.baseline.len() < self.baseline {
+                state.baseline.push(mean(scores.values().copied()));
+            }
+            state.recent.push_back(scores);
+            if state.recent.len() > self.window {
+                state.recent.pop_front();
+            }
+
+            let (Some(baseline), Some(recent)) =
+                (self.baseline_mean(&state), recent_mean(&state.recent))
+            else {
+                return;
+            };
+            let drifting = state.recent.len() >= self.window && baseline - recent > self.tolerance;
+            let newly_drifting = drifting && !state.drifting;
+            state.drifting = drifting;
+            newly_drifting.then(|| DriftAlert {
+                baseline,
+                recent,
+                by_check: check_means(&state.recent),
+            })
+        };
+        if let Some(alert) = alert {
+            for callback in &self.on_drift {
+                callback(&alert);
+            }
+        }
+    }
+
+    fn baseline_mean(&self, state: &MonitorState) -> Option<f64> {
+        (state.baseline.len() >= self.baseline).then(|| mean(state.baseline.iter().copied()))
+    }
+}
+
+impl<T: Serialize> QualityMonitor<T> {
+    /// Also has each sampled output scored by a model, through `client`,
+    /// against the given criteria (e.g. "Every person named in the request is
+    /// extracted with the correct role"). Sampled calls wait for the judge.
+    pub fn with_judge(mut self, client: OpenAiClient, criteria: impl Into<String>) -> Self {
+        self.judge = Some(Judge {
+            client,
+            criteria: criteria.into(),
+            serialize: |output| serde_json::to_value(output).unwrap_or_default(),
+        });
+        self
+    }
+}
+
+impl<T> Judge<T> {
+    async fn judge(&self, prompt: &str, output: &T) -> Result<Judgement, Box<dyn Error>> {
+        let request = format!(
+            "Rate how well the output satisfies the criteria for the request, from 0.0 \
+             (not at all) to 1.0 (fully).\n\nCriteria: {}\n\nRequest:\n{}\n\nOutput:\n{}",
+            self.criteria,
+            prompt,
+            (self.serialize)(output)
+        );
+        self.client.call_schema(&request).await
+    }
+}
+
+impl<T> fmt::Debug for QualityMonitor<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("QualityMonitor")
+            .field("sample_rate", &self.sample_rate)
+            .field(
+                "checks",
+                &self.checks.iter().map(|(name, _)| name).collect::<Vec<_>>(),
+            )
+            .field("judge", &self.judge.is_some())
+            .field("baseline", &self.baseline)
+            .field("window", &self.window)
+            .field("tolerance", &self.tolerance)
+            .finish()
+    }
+}
+
+fn mean(values: impl Iterator<Item = f64>) -> f64 {
+    let (sum, count) = values.fold((0.0, 0), |(sum, count), v| (sum + v, count + 1));
+    if count == 0 {
+        0.0
+    } else {
+        sum / count as f64
+    }
+}
+
+fn recent_mean(recent: &VecDeque<BTreeMap<String, f64>>) -> Option<f64> {
+    (!recent.is_empty()).then(|| mean(recent.iter().map(|s| mean(s.values().copied()))))
+}
+
+fn check_means(recent: &VecDeque<BTreeMap<String, f64>>) -> BTreeMap<String, f64> {
+    let mut totals: BTreeMap<String, (f64, usize)> = BTreeMap::new();
+    for sample in recent {
+        for (name, score) in sample {
+            let total = totals.entry(name.clone()).or_default();
+            total.0 += score;
+            total.1 += 1;
+        }
+    }
+    totals
+        .into_iter()
+        .map(|(name, (sum, count))| (name, sum / count as f64))
+        .collect()
+}
@@ -1,25 +1,178 @@
+use crate::anthropic;
+use crate::attribution::{self, TokenAttribution};
+use crate::audit::{self, AuditLog};
+use crate::cache::{self, CacheBackend};
+use crate::content;
+use crate::context::PromptContext;
+use crate::cost::{Budget, BudgetGuard, CostSummary, CostTracker, PricingTable, UnpricedBudget};
+use crate::credentials::{
+    CredentialProvider, CredentialUnavailable, ExposeSecret, StaticCredential,
+};
+use crate::encryption::{EncryptionError, FieldEncryptor};
+use crate::fallback::ContentParser;
+use crate::gemini;
+use crate::guard::InputGuard;
+use crate::limits::SchemaLimits;
+use crate::local;
+use crate::middleware::{Middleware, RequestParts, ResponseParts};
+use crate::moderation::{ModerationPolicy, ModerationStage};
+use crate::options::{Instruction, InstructionRole, RequestOptions};
+#[cfg(feature = "otel")]
+use crate::otel::CallLogger;
+use crate::ratelimit::{self, RateLimiter};
+use crate::reasoning;
+use crate::refusal::{PromptAdjustment, RefusalAttempt, RefusalPolicy, RefusalsExhausted};
+use crate::responses;
+use crate::retry::{self, RetryPolicy};
+use crate::stats::{CallOutcome, ClientStats, StatsRecorder};
+use crate::style::{ResponseStyle, StyleViolation};
+use crate::time::{self, Instant};
+use crate::tokens::{self, ContextCheck, ContextOverflow, ContextWindows};
+use crate::tools::{ToolCallingResponse, ToolResponse, ToolSet};
+use crate::trace;
+use crate::transport::{
+    self, HttpRequest, HttpResponse, HttpTransport, InsecureEndpoint, InvalidHeader,
+    PrivacyProfile, ProxiedCustomTransport, TransportError,
+};
 use regex::Regex;
-use reqwest::Client;
+use reqwest::header::{HeaderMap, USER_AGENT};
+use reqwest::{Client, Method};
 use schemars::schema::{RootSchema, Schema, SchemaObject};
 use schemars::schema_for;
 use schemars::JsonSchema;
 use serde::de::{self, DeserializeOwned, Deserializer, MapAccess, SeqAccess, Visitor};
-use serde::Deserialize;
-use serde_json;
-use serde_json::{json, Value};
-use std::any::type_name;
-use std::collections::BTreeSet;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use std::any::{type_name, TypeId};
+use std::collections::{BTreeSet, HashMap};
 use std::error::Error;
 use std::fmt;
+use std::future::Future;
 use std::marker::PhantomData;
+use std::sync::{Arc, OnceLock, RwLock};
+use std::time::Duration;
+use tokio_util::sync::CancellationToken;
+
+/// Property holding the output when the schema root is not an object.
+const ROOT_WRAPPER_KEY: &str = "value";
+/// Property holding the output when the schema root is an array.
+const LIST_WRAPPER_KEY: &str = "items";
+const DEFAULT_USER_AGENT: &str = concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION"));
+
+/// How the output sent for a schema differs from the output type.
+struct OutputShape {
+    /// The output is inside this wrapper property.
+    wrapper: Option<&'static str>,
+    /// The output has this scratchpad property added by `with_scratchpad`.
+    scratchpad: Option<String>,
+}
+
+/// Output read back into the output type.
+struct ParsedOutput<T> {
+    response: OpenAIResponse<T>,
+    parser: ContentParser,
+    scratchpad: Option<String>,
+}
+
+/// Which OpenAI API the configured endpoint speaks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ApiFlavor {
+    /// `/v1/chat/completions` with `response_format`.
+    #[default]
+    ChatCompletions,
+    /// `/v1/responses` with `text.format`.
+    Responses,
+}
+
+/// How structured calls to an OpenAI-compatible chat completions endpoint ask
+/// for output, for servers that lack structured outputs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OutputMode {
+    /// `response_format` with the strict schema.
+    #[default]
+    JsonSchema,
+    /// JSON mode (`json_object`), with the schema given as an instruction.
+    JsonObject,
+    /// The schema given as an instruction only.
+    Prompted,
+}
+
+/// The vendor behind the endpoint. Every provider is driven through the same
+/// `call_schema<T>` API; requests and responses are translated as needed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Provider {
+    #[default]
+    OpenAi,
+    /// Anthropic Messages API (`/v1/messages`), using a forced tool call for structured output.
+    Anthropic,
+    /// Gemini `generateContent` (AI Studio or Vertex), using `responseSchema`. The
+    /// endpoint is the full `models/<model>:generateContent` URL.
+    Gemini,
+    /// A local server, which needs no API key. Runs fully offline.
+    Local(LocalServer),
+}
+
+/// Local servers supported by `Provider::Local`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LocalServer {
+    /// Ollama's native `/api/chat`, with the schema in `format`.
+    Ollama,
+    /// llama.cpp's `llama-server` `/v1/chat/completions`, with the schema in `json_schema`.
+    LlamaCpp,
+}
 
 #[derive(Clone)]
 pub struct OpenAiClient {
-    http_client: Client,
+    transport: Arc<dyn HttpTransport>,
+    /// Whether the transport was given by the caller rather than built from
+    /// a `reqwest::Client`.
+    custom_transport: bool,
     endpoint: String,
+    api_base: Option<String>,
     model: String,
-    api_key: String,
+    reasoning_model: Option<bool>,
+    credentials: Arc<dyn CredentialProvider>,
+    organization: Option<String>,
+    project: Option<String>,
     system_role: Option<String>,
+    instructions: Vec<Instruction>,
+    retry_policy: Option<RetryPolicy>,
+    input_guard: Option<InputGuard>,
+    moderation: Option<ModerationPolicy>,
+    stats: Arc<StatsRecorder>,
+    pricing: Option<PricingTable>,
+    costs: Arc<CostTracker>,
+    budget: Option<Arc<BudgetGuard>>,
+    refusal_policy: Option<RefusalPolicy>,
+    max_repair_attempts: u32,
+    user_agent: String,
+    client_headers: Vec<(String, String)>,
+    headers: HeaderMap,
+    query_params: Vec<(String, String)>,
+    api_flavor: ApiFlavor,
+    output_mode: OutputMode,
+    provider: Provider,
+    cache: Option<Arc<dyn CacheBackend>>,
+    rate_limiter: Option<Arc<RateLimiter>>,
+    privacy: Option<PrivacyProfile>,
+    field_encryption: Option<FieldEncryptor>,
+    schema_limits: Option<SchemaLimits>,
+    field_orders: HashMap<TypeId, Vec<String>>,
+    scratchpad: Option<String>,
+    audit_log: Option<AuditLog>,
+    timeout: Option<Duration>,
+    deadline: Option<Duration>,
+    dry_run: bool,
+    middleware: Vec<Arc<dyn Middleware>>,
+    insecure_loopback: bool,
+    insecure_remote: bool,
+    parsers: Vec<ContentParser>,
+    context_windows: ContextWindows,
+    context_check: Option<ContextCheck>,
+    context: Option<PromptContext>,
+    examples: HashMap<TypeId, Vec<(String, String)>>,
+    #[cfg(feature = "otel")]
+    call_logger: Option<CallLogger>,
 }
 
 impl OpenAiClient {
@@ -30,33 +183,575 @@ impl OpenAiClient {
         api_key: impl Into<String>,
     ) -> Self {
         Self {
-            http_client,
+            custom_transport: false,
+            ..Self::with_http_transport(http_client, endpoint, model, api_key)
+        }
+    }
+
+    /// Creates a client that sends its requests through `transport`, such as
+    /// a hyper, isahc or fetch-based stack, instead of a `reqwest::Client`.
+    /// Schemas, parsing, retries and every other feature work the same.
+    pub fn with_http_transport(
+        transport: impl HttpTransport + 'static,
+        endpoint: impl Into<String>,
+        model: impl Into<String>,
+        api_key: impl Into<String>,
+    ) -> Self {
+        Self {
+            transport: Arc::new(transport),
+            custom_transport: true,
             endpoint: endpoint.into(),
+            api_base: None,
             model: model.into(),
-            api_key: api_key.into(),
+            reasoning_model: None,
+            credentials: Arc::new(StaticCredential::new(api_key)),
+            organization: None,
+            project: None,
             system_role: None,
+            instructions: Vec::new(),
+            retry_policy: None,
+            input_guard: None,
+            moderation: None,
+            stats: Arc::new(StatsRecorder::new(Duration::from_secs(300))),
+            pricing: None,
+            costs: Arc::default(),
+            budget: None,
+            refusal_policy: None,
+            max_repair_attempts: 0,
+            user_agent: DEFAULT_USER_AGENT.to_string(),
+            client_headers: Vec::new(),
+            headers: HeaderMap::new(),
+            query_params: Vec::new(),
+            api_flavor: ApiFlavor::ChatCompletions,
+            output_mode: OutputMode::JsonSchema,
+            provider: Provider::OpenAi,
+            cache: None,
+            rate_limiter: None,
+            privacy: None,
+            field_encryption: None,
+            schema_limits: None,
+            field_orders: HashMap::new(),
+            scratchpad: None,
+            audit_log: None,
+            timeout: None,
+            deadline: None,
+            dry_run: false,
+            middleware: Vec::new(),
+            insecure_loopback: false,
+            insecure_remote: false,
+            parsers: vec![ContentParser::Json],
+            context_windows: ContextWindows::default(),
+            context_check: None,
+            context: None,
+            examples: HashMap::new(),
+            #[cfg(feature = "otel")]
+            call_logger: None,
         }
     }
 
+    /// Replaces the model given to `new`, e.g. on a clone of the client for
+    /// calls that need a different model.
+    pub fn with_model(mut self, model: impl Into<String>) -> Self {
+        self.model = model.into();
+        self
+    }
+
     pub fn with_system_role(mut self, role: impl Into<String>) -> Self {
         self.system_role = Some(role.into());
         self
     }
 
-    fn schema_name_for_type<T>() -> String {
+    /// Instruction messages sent after the system role and before the user
+    /// prompt, each with its own role. Developer messages are sent as system
+    /// messages to providers without a developer role, and every instruction
+    /// is sent as a developer message to reasoning models.
+    pub fn with_instructions(
+        mut self,
+        instructions: impl IntoIterator<Item = Instruction>,
+    ) -> Self {
+        self.instructions = instructions.into_iter().collect();
+        self
+    }
+
+    /// Asks `provider` for the API key before every request instead of using
+    /// the key the client was created with.
+    pub fn with_credentials(mut self, provider: impl CredentialProvider + 'static) -> Self {
+        self.credentials = Arc::new(provider);
+        self
+    }
+
+    /// The API root that other resources such as `embeddings`, `files` and
+    /// `moderations` are found under, e.g. `https://api.openai.com/v1`. Only
+    /// needed when the endpoint is not an OpenAI-style `/chat/completions` or
+    /// `/responses` URL, such as an Azure deployment.
+    pub fn with_api_base(mut self, api_base: impl Into<String>) -> Self {
+        self.api_base = Some(api_base.into());
+        self
+    }
+
+    /// Sends the `OpenAI-Organization` header on every request, billing calls
+    /// to that organization when the key belongs to several.
+    pub fn with_organization(mut self, organization: impl Into<String>) -> Self {
+        self.organization = Some(organization.into());
+        self
+    }
+
+    /// Sends the `OpenAI-Project` header on every request, attributing usage
+    /// and limits to that project.
+    pub fn with_project(mut self, project: impl Into<String>) -> Self {
+        self.project = Some(project.into());
+        self
+    }
+
+    /// Selects the API the endpoint speaks. With `ApiFlavor::Responses` the endpoint
+    /// should be the `/v1/responses` URL; `call_with_tools` always uses chat completions.
+    pub fn with_api_flavor(mut self, flavor: ApiFlavor) -> Self {
+        self.api_flavor = flavor;
+        self
+    }
+
+    /// Selects how structured calls ask for output on OpenAI-compatible chat
+    /// completions endpoints; `OpenAiClient::with_probe` picks it automatically.
+    /// Modes other than `JsonSchema` pair well with `ContentParser::chain()`.
+    pub fn with_output_mode(mut self, mode: OutputMode) -> Self {
+        self.output_mode = mode;
+        self
+    }
+
+    pub(crate) fn model(&self) -> &str {
+        &self.model
+    }
+
+    /// Whether requests are shaped for a reasoning model; see `with_reasoning_model`.
+    fn is_reasoning_model(&self) -> bool {
+        self.reasoning_model
+            .unwrap_or_else(|| reasoning::is_reasoning_model(&self.model))
+    }
+
+    /// Whether requests go to an OpenAI-compatible chat completions endpoint.
+    pub(crate) fn speaks_chat_completions(&self) -> bool {
+        (self.provider, self.api_flavor) == (Provider::OpenAi, ApiFlavor::ChatCompletions)
+    }
+
+    pub(crate) fn parsers(&self) -> &[ContentParser] {
+        &self.parsers
+    }
+
+    /// Selects the vendor the endpoint belongs to; defaults to OpenAI.
+    /// `call_with_tools` always sends the OpenAI tools format.
+    pub fn with_provider(mut self, provider: Provider) -> Self {
+        self.provider = provider;
+        self
+    }
+
+    /// Checks every generated schema against the limits before sending, failing
+    /// with a `SchemaLimitError` instead of spending an API call.
+    pub fn with_schema_limits(mut self, limits: SchemaLimits) -> Self {
+        self.schema_limits = Some(limits);
+        self
+    }
+
+    /// Puts the listed fields of T first in its schema, in the given order.
+    /// Models fill fields in schema order, so fields like reasoning or an
+    /// explanation placed before the answer improve it. Schemas otherwise
+    /// follow the declaration order of the struct's fields.
+    pub fn with_field_order<T: 'static>(mut self, fields: &[&str]) -> Self {
+        self.field_orders.insert(
+            TypeId::of::<T>(),
+            fields.iter().map(|field| field.to_string()).collect(),
+        );
+        self
+    }
+
+    /// Adds a string property `field` (e.g. `reasoning`) at the front of every
+    /// output schema for the model to think in before it answers, and removes
+    /// it before deserializing, so output types stay free of it. Its content
+    /// is returned in `ResponseMeta::scratchpad`. Types that have a property
+    /// of that name already are left as they are.
+    pub fn with_scratchpad(mut self, field: impl Into<String>) -> Self {
+        self.scratchpad = Some(field.into());
+        self
+    }
+
+    /// Estimates, before sending, whether the prompt, schema and
+    /// `max_completion_tokens` fit the model's context window, and warns or
+    /// rejects when they do not. Models missing from the context window table
+    /// are not checked.
+    pub fn with_context_check(mut self, check: ContextCheck) -> Self {
+        self.context_check = Some(check);
+        self
+    }
+
+    /// Replaces the context window table, e.g. to add fine-tuned or local models.
+    pub fn with_context_windows(mut self, windows: ContextWindows) -> Self {
+        self.context_windows = windows;
+        self
+    }
+
+    /// Adds the date, time zone, locale and units of `context` to the system
+    /// prompt of every call. Overridden per call by `RequestOptions::context`.
+    pub fn with_context(mut self, context: PromptContext) -> Self {
+        self.context = Some(context);
+        self
+    }
+
+    /// Few-shot examples for calls whose output type is T, sent as alternating
+    /// user and assistant messages before the prompt. Being typed, the example
+    /// outputs always match the schema the model is asked to follow.
+    pub fn with_examples<T: Serialize + JsonSchema + 'static>(
+        mut self,
+        examples: Vec<(String, T)>,
+    ) -> Result<Self, Box<dyn Error>> {
+        let wrapper = Self::wrap_non_object_root(&mut Self::generate_schema::<T>()?);
+        let examples = examples
+            .into_iter()
+            .map(|(prompt, output)| {
+                let mut output = serde_json::to_value(output)?;
+                if let Some(key) = wrapper {
+                    output = json!({ key: output });
+                }
+                Ok((prompt, output.to_string()))
+            })
+            .collect::<Result<_, serde_json::Error>>()?;
+        self.examples.insert(TypeId::of::<T>(), examples);
+        Ok(self)
+    }
+
+    /// Builds requests without sending them: calls fail with a `DryRun` error
+    /// holding the request body that would have been sent.
+    pub fn with_dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+
+    /// Retries transient failures according to the given policy.
+    pub fn with_retry(mut self, policy: RetryPolicy) -> Self {
+        self.retry_policy = Some(policy);
+        self
+    }
+
+    /// Sanitizes the system role and prompts with the given guard before sending.
+    pub fn with_input_guard(mut self, guard: InputGuard) -> Self {
+        self.input_guard = Some(guard);
+        self
+    }
+
+    /// Whether the model is a reasoning model (o1, o3, o4-mini, gpt-5), whose
+    /// requests send developer instead of system messages and leave out the
+    /// sampling options it rejects, such as `temperature`. Detected from the
+    /// model name by default; set it for deployments named otherwise.
+    pub fn with_reasoning_model(mut self, reasoning_model: bool) -> Self {
+        self.reasoning_model = Some(reasoning_model);
+        self
+    }
+
+    /// Screens prompts and/or outputs of schema calls with the moderation
+    /// endpoint, failing flagged calls with `ModerationFlagged`.
+    pub fn with_moderation(mut self, policy: ModerationPolicy) -> Self {
+        self.moderation = Some(policy);
+        self
+    }
+
+    /// Retries refused calls according to the given policy.
+    pub fn with_refusal_policy(mut self, policy: RefusalPolicy) -> Self {
+        self.refusal_policy = Some(policy);
+        self
+    }
+
+    /// When the model output fails to deserialize into T, sends the invalid output
+    /// and the parse error back to the model and asks for corrected JSON, up to
+    /// `max_attempts` times.
+    pub fn with_repair(mut self, max_attempts: u32) -> Self {
+        self.max_repair_attempts = max_attempts;
+        self
+    }
+
+    /// Appends an application identifier (e.g. "billing-service/2.1") to the
+    /// default `User-Agent` of crate name and version.
+    pub fn with_app_name(mut self, app: impl AsRef<str>) -> Self {
+        self.user_agent = format!("{} {}", DEFAULT_USER_AGENT, app.as_ref());
+        self
+    }
+
+    /// Replaces the `User-Agent` header entirely.
+    pub fn with_user_agent(mut self, user_agent: impl Into<String>) -> Self {
+        self.user_agent = user_agent.into();
+        self
+    }
+
+    /// Sends `X-Client-<name>: <value>` on every request, e.g. `("Operation", "invoice-extraction")`.
+    pub fn with_client_header(mut self, name: impl AsRef<str>, value: impl Into<String>) -> Self {
+        let name = format!("X-Client-{}", name.as_ref());
+        self.client_headers
+            .retain(|(existing, _)| !existing.eq_ignore_ascii_case(&name));
+        self.client_headers.push((name, value.into()));
+        self
+    }
+
+    /// Sends `<name>: <value>` on every request, e.g. `("Helicone-User-Id", "alice")`
+    /// for a gateway that routes or attributes calls by header; use
+    /// `with_secret_header` for the gateway's key. Unlike client
+    /// headers these are sent under `PrivacyProfile::minimal_headers` too.
+    /// Fails if the name or value cannot be sent in an HTTP header.
+    pub fn with_header(
+        mut self,
+        name: impl AsRef<str>,
+        value: impl AsRef<str>,
+    ) -> Result<Self, InvalidHeader> {
+        let (name, value) = transport::parse_header(name.as_ref(), value.as_ref())?;
+        self.headers.insert(name, value);
+        Ok(self)
+    }
+
+    /// Like `with_header` for credentials, such as a gateway key: the value is
+    /// marked sensitive and scrubbed from recorded fixtures.
+    pub fn with_secret_header(
+        mut self,
+        name: impl AsRef<str>,
+        value: impl AsRef<str>,
+    ) -> Result<Self, InvalidHeader> {
+        let (name, mut value) = transport::parse_header(name.as_ref(), value.as_ref())?;
+        value.set_sensitive(true);
+        self.headers.insert(name, value);
+        Ok(self)
+    }
+
+    /// Appends `<name>=<value>` to the query of every request URL, e.g.
+    /// `("api-version", "2024-06-01")`.
+    pub fn with_query_param(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        let name = name.into();
+        self.query_params.retain(|(existing, _)| *existing != name);
+        self.query_params.push((name, value.into()));
+        self
+    }
+
+    /// Runs the middleware's hooks around every request, after any added before it.
+    pub fn with_middleware(mut self, middleware: impl Middleware + 'static) -> Self {
+        self.middleware.push(Arc::new(middleware));
+        self
+    }
+
+    /// Allows plain-HTTP endpoints on this machine (`localhost`, `127.0.0.0/8`,
+    /// `::1`), such as a local inference server or one reached through
+    /// `transport::unix_socket_client`. Plain HTTP to other hosts is refused
+    /// with `InsecureEndpoint` unless allowed with `with_insecure_remote`.
+    pub fn with_insecure_loopback(mut self, allow: bool) -> Self {
+        self.insecure_loopback = allow;
+        self
+    }
+
+    /// Allows plain-HTTP endpoints on any host, such as an internal gateway
+    /// that terminates TLS elsewhere. Requests and API keys then cross the
+    /// network unencrypted.
+    pub fn with_insecure_remote(mut self, allow: bool) -> Self {
+        self.insecure_remote = allow;
+        self
+    }
+
+    /// Parsers tried in order on output that is not strict JSON (default: only
+    /// `ContentParser::Json`), e.g. `ContentParser::chain()` for weaker local
+    /// models. Overridden per call by `RequestOptions::parsers`.
+    pub fn with_parsers(mut self, parsers: impl IntoIterator<Item = ContentParser>) -> Self {
+        self.parsers = parsers.into_iter().collect();
+        self
+    }
+
+    /// Sends requests through a custom HTTP stack instead of the `reqwest::Client`
+    /// given to `new`. This also replaces the proxied client of a privacy
+    /// profile set earlier, so set the profile afterwards.
+    pub fn with_transport(mut self, transport: impl HttpTransport + 'static) -> Self {
+        self.transport = Arc::new(transport);
+        self.custom_transport = true;
+        self
+    }
+
+    /// Fails any single HTTP request that has not been fully received within
+    /// `timeout` with an `io::ErrorKind::TimedOut` error, which the retry
+    /// policy retries. Applies to every transport, on top of any timeout the
+    /// `reqwest::Client` has.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Fails calls that have not finished within `deadline` with
+    /// `DeadlineExceeded`, counting every retry, repair and refusal retry.
+    pub fn with_deadline(mut self, deadline: Duration) -> Self {
+        self.deadline = Some(deadline);
+        self
+    }
+
+    /// Applies the profile's request jitter and minimal headers and, if it has
+    /// a proxy, switches to an HTTP client using it. Fails if the proxy URL is
+    /// invalid, or with `ProxiedCustomTransport` if the client sends through a
+    /// custom transport, which the proxy cannot be applied to.
+    pub fn with_privacy_profile(mut self, profile: PrivacyProfile) -> Result<Self, Box<dyn Error>> {
+        if profile.proxy.is_some() {
+            if self.custom_transport {
+                return Err(Box::new(ProxiedCustomTransport));
+            }
+            self.transport = Arc::new(profile.http_client()?);
+        }
+        self.privacy = Some(profile);
+        Ok(self)
+    }
+
+    /// Delays outgoing requests so they stay within the given requests and tokens
+    /// per minute, using token buckets shared by clones of the client. Token cost
+    /// is estimated before sending and settled from the reported usage; when the
+    /// API sends `x-ratelimit-remaining-*` headers the budget follows them.
+    pub fn with_rate_limit(mut self, requests_per_minute: u32, tokens_per_minute: u32) -> Self {
+        self.rate_limiter = Some(Arc::new(RateLimiter::new(
+            requests_per_minute,
+            tokens_per_minute,
+        )));
+        self
+    }
+
+    /// Serves repeated identical requests (same model, messages, schema and options)
+    /// from the cache instead of the API. Only successfully parsed responses are
+    /// stored. Clones of a client share the cache.
+    pub fn with_cache(mut self, cache: impl CacheBackend + 'static) -> Self {
+        self.cache = Some(Arc::new(cache));
+        self
+    }
+
+    /// Encrypts the encryptor's fields of every response before it is written to
+    /// the cache; cached entries are decrypted when served. Entries that fail
+    /// either way are skipped and reported in `ResponseMeta::cache_error`.
+    pub fn with_field_encryption(mut self, encryptor: FieldEncryptor) -> Self {
+        self.field_encryption = Some(encryptor);
+        self
+    }
+
+    /// Appends a record of every call to the audit log: one per schema or
+    /// text call, and one per request for the others, such as tool calls.
+    pub fn with_audit_log(mut self, log: AuditLog) -> Self {
+        self.audit_log = Some(log);
+        self
+    }
+
+    /// Emits an OpenTelemetry log record for every `call_schema` call.
+    #[cfg(feature = "otel")]
+    pub fn with_call_logger(mut self, logger: CallLogger) -> Self {
+        self.call_logger = Some(logger);
+        self
+    }
+
+    /// Sets the rolling window over which `stats()` aggregates calls (default 5 minutes).
+    /// Clones of a client share their statistics.
+    pub fn with_stats_window(mut self, window: Duration) -> Self {
+        self.stats = Arc::new(StatsRecorder::new(window));
+        self
+    }
+
+    /// Rolling-window aggregates of the calls made through this client, overall
+    /// and per `RequestOptions::tag`.
+    pub fn stats(&self) -> ClientStats {
+        self.stats.snapshot()
+    }
+
+    /// Prices responses with the given table, filling `ResponseMeta::cost` and
+    /// the cost totals of `cost_summary`.
+    pub fn with_pricing(mut self, pricing: PricingTable) -> Self {
+        self.pricing = Some(pricing);
+        self
+    }
+
+    /// Refuses requests with `BudgetExceeded` once the spending of this client
+    /// and its clones reaches the budget, so a runaway job cannot keep spending.
+    /// A USD limit fails with `UnpricedBudget` unless `with_pricing` was called
+    /// first with a price for the client's model.
+    pub fn with_budget(mut self, budget: Budget) -> Result<Self, UnpricedBudget> {
+        let priced = self
+            .pricing
+            .as_ref()
+            .is_some_and(|pricing| pricing.get(&self.model).is_some());
+        if budget.max_usd.is_some() && !priced {
+            return Err(UnpricedBudget {
+                model: self.model.clone(),
+            });
+        }
+        self.budget = Some(Arc::new(BudgetGuard::new(budget)));
+        Ok(self)
+    }
+
+    /// Token usage and cost of every response received by this client and its
+    /// clones since it was created or the summary was last taken.
+    pub fn cost_summary(&self) -> CostSummary {
+        self.costs.summary()
+    }
+
+    /// Returns the cost summary and starts a new one, e.g. once per billing period.
+    pub fn take_cost_summary(&self) -> CostSummary {
+        self.costs.take()
+    }
+
+    /// The strict-mode JSON schema sent for T, e.g. to snapshot-test it or to
+    /// register it elsewhere. Schemas whose root is not an object are wrapped
+    /// in a `value` property (`items` for sequences), as they are when sent.
+    /// Schema limits configured on a client are not applied.
+    pub fn schema_for<T: JsonSchema + 'static>() -> Result<Value, Box<dyn Error>> {
+        let mut schema = Self::generate_schema::<T>()?;
+        Self::wrap_non_object_root(&mut schema);
+        Ok(schema)
+    }
+
+    /// The schema name sent alongside `schema_for::<T>()`: the full type name
+    /// with characters outside `[a-zA-Z0-9_-]` replaced, lowercased, with a
+    /// `_response` suffix.
+    pub fn schema_name_for<T: 'static>() -> String {
+        Self::schema_name_for_type::<T>()
+    }
+
+    fn schema_name_for_type<T: 'static>() -> String {
+        static NAMES: OnceLock<RwLock<HashMap<TypeId, String>>> = OnceLock::new();
+        static SANITIZE: OnceLock<Regex> = OnceLock::new();
+
+        let names = NAMES.get_or_init(Default::default);
+        if let Some(name) = names.read().unwrap().get(&TypeId::of::<T>()) {
+            return name.clone();
+        }
+
         let full_type_name = type_name::<T>();
 
         // Replace anything not in [a-zA-Z0-9_-] with underscores.
-        let re = Regex::new("[^a-zA-Z0-9_-]+").unwrap();
+        let re = SANITIZE.get_or_init(|| Regex::new("[^a-zA-Z0-9_-]+").unwrap());
         let sanitized = re.replace_all(full_type_name, "_").to_string();
 
-        format!("{}_response", sanitized.to_lowercase())
+        let name = format!("{}_response", sanitized.to_lowercase());
+        names
+            .write()
+            .unwrap()
+            .insert(TypeId::of::<T>(), name.clone());
+        name
+    }
+
+    /// The schema name sent for T, or the call's dynamic schema.
+    fn output_schema_name<T: 'static>(options: &RequestOptions) -> String {
+        match &options.output_schema {
+            Some(dynamic) => dynamic.name.clone(),
+            None => Self::schema_name_for_type::<T>(),
+        }
     }
 
     /// Generates a JSON schema for T, ensuring additionalProperties=false
-    /// for all nested object types.
-    fn generate_schema<T: JsonSchema>() -> Result<Value, Box<dyn Error>> {
-        Self::generate_schema_with_no_additional::<T>()
+    /// for all nested object types. Schemas are cached per type, so repeat
+    /// calls only clone the cached value.
+    pub(crate) fn generate_schema<T: JsonSchema + 'static>() -> Result<Value, Box<dyn Error>> {
+        static SCHEMAS: OnceLock<RwLock<HashMap<TypeId, Value>>> = OnceLock::new();
+
+        let schemas = SCHEMAS.get_or_init(Default::default);
+        if let Some(schema) = schemas.read().unwrap().get(&TypeId::of::<T>()) {
+            return Ok(schema.clone());
+        }
+
+        let schema = Self::generate_schema_with_no_additional::<T>()?;
+        schemas
+            .write()
+            .unwrap()
+            .insert(TypeId::of::<T>(), schema.clone());
+        Ok(schema)
     }
 
     fn generate_schema_with_no_additional<T: JsonSchema>() -> Result<Value, Box<dyn Error>> {
@@ -73,15 +768,46 @@ impl OpenAiClient {
         }
 
         // 3. Convert RootSchema => JSON
-        let schema_value = serde_json::to_value(&root_schema)?;
+        let mut schema_value = serde_json::to_value(&root_schema)?;
+
+        // 4. Strict mode makes every property required, so optional values must
+        //    be expressed as nullable types
+        Self::make_nullable_enums_accept_null(&mut schema_value);
         Ok(schema_value)
     }
 
+    /// `Option<T>` of an inline enum schema becomes `"type": ["string", "null"]`
+    /// while its `enum` still lacks null, so strict mode could never produce the
+    /// `None` case. Adds null to the enum values of every such nullable schema.
+    fn make_nullable_enums_accept_null(schema: &mut Value) {
+        match schema {
+            Value::Object(object) => {
+                let nullable = match object.get("type") {
+                    Some(Value::Array(types)) => types.iter().any(|t| t == "null"),
+                    _ => false,
+                };
+                if let (true, Some(Value::Array(values))) = (nullable, object.get_mut("enum")) {
+                    if !values.contains(&Value::Null) {
+                        values.push(Value::Null);
+                    }
+                }
+                object
+                    .values_mut()
+                    .for_each(Self::make_nullable_enums_accept_null);
+            }
+            Value::Array(items) => items
+                .iter_mut()
+                .for_each(Self::make_nullable_enums_accept_null),
+            _ => {}
+        }
+    }
+
     fn set_no_additional_properties(schema_obj: &mut SchemaObject) {
-        // 1. If this schema is "type: object", set additionalProperties = false
+        // 1. If this schema is "type: object" (or a nullable object, as generated
+        //    for Option<T> of inline object types), set additionalProperties = false
         //    and force all properties to appear in the 'required' list.
-        if let Some(schemars::schema::SingleOrVec::Single(ref t)) = schema_obj.instance_type {
-            if **t == schemars::schema::InstanceType::Object {
+        if let Some(ref instance_type) = schema_obj.instance_type {
+            if instance_type.contains(&schemars::schema::InstanceType::Object) {
                 // If there's no ObjectValidation yet, create one
                 let ov = schema_obj
                     .object
@@ -162,79 +888,1453 @@ impl OpenAiClient {
                     Self::set_no_additional_properties(nested_obj);
                 }
             }
+            // Strict mode rejects oneOf. Enums are generated as a oneOf of
+            // variants that are told apart by their tag or single key, so
+            // anyOf accepts exactly the same values.
+            if subs.any_of.is_none() {
+                subs.any_of = subs.one_of.take();
+            }
         }
     }
 
-    /// Calls the OpenAI endpoint, passing the JSON schema in 'response_format.json_schema.schema'.
-    /// Expects a typed response conforming to T.
-    pub async fn call_schema<T: DeserializeOwned + JsonSchema + Clone>(
-        &self,
-        user_prompt: &str,
-    ) -> Result<T, Box<dyn std::error::Error>> {
-        let schema_value = Self::generate_schema::<T>()?;
-        let schema_name = Self::schema_name_for_type::<T>();
+    /// Strict mode requires an object at the root, so any other schema is sent
+    /// as the single property of a wrapper object: `items` for sequences such
+    /// as `Vec<T>`, `value` for anything else (such as an enum used as the
+    /// output type). Returns the wrapper property, if the schema was wrapped.
+    pub(crate) fn wrap_non_object_root(schema: &mut Value) -> Option<&'static str> {
+        let Value::Object(root) = schema else {
+            return None;
+        };
+        let key = match root.get("type").and_then(Value::as_str) {
+            Some("object") => return None,
+            Some("array") => LIST_WRAPPER_KEY,
+            _ => ROOT_WRAPPER_KEY,
+        };
+        let mut wrapper = Map::new();
+        for key in ["$schema", "title", "definitions"] {
+            if let Some(value) = root.remove(key) {
+                wrapper.insert(key.to_string(), value);
+            }
+        }
+        wrapper.insert("type".into(), json!("object"));
+        wrapper.insert(
+            "properties".into(),
+            json!({ key: Value::Object(std::mem::take(root)) }),
+        );
+        wrapper.insert("required".into(), json!([key]));
+        wrapper.insert("additionalProperties".into(), json!(false));
+        *schema = Value::Object(wrapper);
+        Some(key)
+    }
 
-        // Construct messages
-        let mut messages = Vec::new();
-        if let Some(system_content) = &self.system_role {
-            messages.push(json!({
-                "role": "system",
-                "content": system_content
-            }));
+    /// Moves the listed properties of the root object to the front, in order,
+    /// along with their entries in `required`.
+    fn order_fields(schema: &mut Value, order: &[String]) {
+        let rank = |name: &str| {
+            order
+                .iter()
+                .position(|field| field == name)
+                .unwrap_or(order.len())
+        };
+        if let Some(properties) = schema.get_mut("properties").and_then(Value::as_object_mut) {
+            let mut sorted: Vec<(String, Value)> = std::mem::take(properties).into_iter().collect();
+            sorted.sort_by_key(|(name, _)| rank(name));
+            properties.extend(sorted);
         }
-        messages.push(json!({
-            "role": "user",
-            "content": user_prompt
-        }));
+        if let Some(required) = schema.get_mut("required").and_then(Value::as_array_mut) {
+            required.sort_by_key(|name| rank(name.as_str().unwrap_or_default()));
+        }
+    }
 
-        // Build the request body
-        let body = json!({
-            "model": self.model,
-            "messages": messages,
-            "response_format": {
-                "type": "json_schema",
-                "json_schema": {
-                    "name": schema_name,
-                    "strict": true,
-                    "schema": schema_value
-                }
-            }
-        });
+    /// Adds the scratchpad property at the front of the root object, unless it
+    /// has a property of that name already. Returns whether it was added.
+    fn add_scratchpad(schema: &mut Value, field: &str) -> bool {
+        let Some(properties) = schema.get_mut("properties").and_then(Value::as_object_mut) else {
+            return false;
+        };
+        if properties.contains_key(field) {
+            return false;
+        }
+        let rest = std::mem::take(properties);
+        properties.insert(
+            field.to_string(),
+            json!({
+                "type": "string",
+                "description": "Think through the answer step by step before giving it."
+            }),
+        );
+        properties.extend(rest);
+        if let Some(required) = schema.get_mut("required").and_then(Value::as_array_mut) {
+            required.insert(0, json!(field));
+        }
+        true
+    }
 
-        let res = self
-            .http_client
-            .post(&self.endpoint)
-            .bearer_auth(&self.api_key)
-            .json(&body)
-            .send()
-            .await?;
+    /// Removes the scratchpad property from the message content of a normalized
+    /// response, returning it separately.
+    fn take_scratchpad(raw: &Value, field: &str) -> (Value, Option<String>) {
+        let mut raw = raw.clone();
+        let content = &mut raw["choices"][0]["message"]["content"];
+        let Some(mut output) = content
+            .as_str()
+            .and_then(|text| serde_json::from_str::<Value>(text).ok())
+        else {
+            return (raw, None);
+        };
+        let scratchpad = output
+            .as_object_mut()
+            .and_then(|object| object.remove(field))
+            .map(|value| match value {
+                Value::String(text) => text,
+                value => value.to_string(),
+            });
+        if scratchpad.is_some() {
+            *content = Value::String(output.to_string());
+        }
+        (raw, scratchpad)
+    }
 
-        let response: OpenAIResponse<T> = res.json().await?;
+    /// Fails with a `StyleViolation` if the text in the message content of a
+    /// normalized response, apart from the scratchpad, breaks the style.
+    fn check_style(
+        style: &ResponseStyle,
+        raw: &Value,
+        shape: &OutputShape,
+    ) -> Result<(), StyleViolation> {
+        let content = raw["choices"][0]["message"]["content"]
+            .as_str()
+            .unwrap_or_default();
+        let mut output = serde_json::from_str(content).unwrap_or(Value::Null);
+        if let (Some(field), Some(object)) = (&shape.scratchpad, output.as_object_mut()) {
+            object.remove(field);
+        }
+        let violations = style.check(&output);
+        if violations.is_empty() {
+            return Ok(());
+        }
+        Err(StyleViolation {
+            raw: content.to_string(),
+            violations,
+        })
+    }
 
-        match response {
-            OpenAIResponse::Ok(res) => match res.choices[0].message.clone() {
-                Message::Ok(content) => Ok(content.content),
-                Message::Err(refusal) => Err(Box::new(refusal)),
-            },
-            OpenAIResponse::Err(err) => Err(Box::new(err)),
+    /// Replaces the message content of a normalized response with the value
+    /// inside the wrapper property `key` added by `wrap_non_object_root`.
+    fn unwrap_root_value(raw: &Value, key: &str) -> Value {
+        let mut raw = raw.clone();
+        let content = &mut raw["choices"][0]["message"]["content"];
+        if let Some(mut wrapper) = content
+            .as_str()
+            .and_then(|text| serde_json::from_str::<Value>(text).ok())
+        {
+            if let Some(value) = wrapper.get_mut(key) {
+                *content = Value::String(value.take().to_string());
+            }
         }
+        raw
     }
-}
 
-#[derive(Debug, Deserialize)]
-#[serde(untagged)]
-#[serde(bound(deserialize = "T: DeserializeOwned"))]
-pub enum OpenAIResponse<T> {
-    Ok(ChatGPTResponse<T>),
-    Err(OpenAIError),
-}
+    /// Calls the OpenAI endpoint, passing the JSON schema in 'response_format.json_schema.schema'.
+    /// Expects a typed response conforming to T.
+    pub async fn call_schema<T: DeserializeOwned + JsonSchema + Clone + 'static>(
+        &self,
+        user_prompt: &str,
+    ) -> Result<T, Box<dyn std::error::Error>> {
+        self.call_schema_with(user_prompt, &RequestOptions::default())
+            .await
+    }
 
-#[derive(Debug, Deserialize)]
-pub struct OpenAIError {
-    error: OpenAIErrorDetails,
-}
+    /// Calls for a list of T. The list is sent as the `items` property of a
+    /// wrapper object and returned unwrapped, as for any `call_schema::<Vec<T>>`.
+    pub async fn call_schema_list<T: DeserializeOwned + JsonSchema + Clone + 'static>(
+        &self,
+        user_prompt: &str,
+    ) -> Result<Vec<T>, Box<dyn std::error::Error>> {
+        self.call_schema(user_prompt).await
+    }
 
-#[derive(Debug, Deserialize)]
+    /// Like `call_schema`, with per-call request options such as temperature or seed.
+    pub async fn call_schema_with<T: DeserializeOwned + JsonSchema + Clone + 'static>(
+        &self,
+        user_prompt: &str,
+        options: &RequestOptions,
+    ) -> Result<T, Box<dyn std::error::Error>> {
+        let response = self.call_schema_with_meta(user_prompt, options).await?;
+        Ok(response.value)
+    }
+
+    /// Like `call_schema_with`, additionally returning the response id, model,
+    /// finish reason and token usage.
+    pub async fn call_schema_with_meta<T: DeserializeOwned + JsonSchema + Clone + 'static>(
+        &self,
+        user_prompt: &str,
+        options: &RequestOptions,
+    ) -> Result<StructuredResponse<T>, Box<dyn std::error::Error>> {
+        let started = Instant::now();
+        let span = trace::call_span(&self.model, &Self::output_schema_name::<T>(options));
+        let mut billed = (0, None);
+        let result = audit::audited_call(trace::instrument(
+            span.clone(),
+            self.within_limits(options.cancellation.as_ref(), async {
+                self.screen(ModerationStage::Input, user_prompt).await?;
+                self.request_schema_with_refusals(user_prompt, options, &mut billed)
+                    .await
+            }),
+        ))
+        .await;
+        let usage = result.as_ref().ok().and_then(|r| r.meta.usage.as_ref());
+        trace::record_call(&span, usage, started.elapsed());
+        let timings = result
+            .as_ref()
+            .map(|response| response.meta.timings)
+            .unwrap_or_default();
+        let repaired = result
+            .as_ref()
+            .is_ok_and(|response| response.meta.repair_attempts > 0);
+        self.stats.record(
+            options.tag.as_deref(),
+            started.elapsed(),
+            CallOutcome::of(&result),
+            billed.0,
+            billed.1,
+            repaired,
+            timings,
+        );
+        if let Some(audit) = &self.audit_log {
+            audit.record(
+                &self.model,
+                Some(&Self::output_schema_name::<T>(options)),
+                options.tag.as_deref(),
+                user_prompt,
+                &result,
+                result.as_ref().ok().map(|response| &response.meta),
+                started.elapsed(),
+            );
+        }
+        #[cfg(feature = "otel")]
+        if let Some(logger) = &self.call_logger {
+            logger.log(
+                &self.model,
+                &Self::output_schema_name::<T>(options),
+                options.tag.as_deref(),
+                user_prompt,
+                &result,
+                started.elapsed(),
+            );
+        }
+        result
+    }
+
+    /// Sends the request, retrying refusals as configured by the refusal
+    /// policy, and adds the tokens and cost of every response to `billed`.
+    async fn request_schema_with_refusals<T: DeserializeOwned + JsonSchema + Clone + 'static>(
+        &self,
+        user_prompt: &str,
+        options: &RequestOptions,
+        billed: &mut (u32, Option<f64>),
+    ) -> Result<StructuredResponse<T>, Box<dyn std::error::Error>> {
+        let mut prompt = user_prompt.to_string();
+        let mut follow_ups: Vec<Value> = Vec::new();
+        let mut attempts = Vec::new();
+        let context = self.context_block(options);
+
+        loop {
+            let mut messages = self.build_messages(&prompt, options, context.as_deref());
+            self.insert_examples::<T>(&mut messages);
+            messages.extend(follow_ups.iter().cloned());
+
+            let result = self
+                .request_schema_with_repair(messages, options, billed)
+                .await;
+            let (policy, refusal) = match (&self.refusal_policy, &result) {
+                (Some(policy), Err(err)) => match err.downcast_ref::<Refusal>() {
+                    Some(refusal) => (policy, refusal.refusal.clone()),
+                    None => return result,
+                },
+                _ => return result,
+            };
+
+            let attempt = RefusalAttempt {
+                prompt: prompt.clone(),
+                refusal,
+            };
+            attempts.push(attempt.clone());
+            if attempts.len() > policy.max_retries as usize {
+                return Err(Box::new(RefusalsExhausted { attempts }));
+            }
+
+            match policy.adjustment(&attempt) {
+                PromptAdjustment::Unchanged => {}
+                PromptAdjustment::Rewrite(rewritten) => {
+                    prompt = rewritten;
+                    follow_ups.clear();
+                }
+                PromptAdjustment::Clarify(clarification) => {
+                    follow_ups.push(json!({
+                        "role": "assistant",
+                        "refusal": attempt.refusal
+                    }));
+                    follow_ups.push(json!({
+                        "role": "user",
+                        "content": self.guard_input(&clarification)
+                    }));
+                }
+            }
+        }
+    }
+
+    /// Sends the request, asking the model to repair outputs that fail to parse.
+    async fn request_schema_with_repair<T: DeserializeOwned + JsonSchema + Clone + 'static>(
+        &self,
+        mut messages: Vec<Value>,
+        options: &RequestOptions,
+        billed: &mut (u32, Option<f64>),
+    ) -> Result<StructuredResponse<T>, Box<dyn std::error::Error>> {
+        let mut repairs = 0;
+        let started = Instant::now();
+        loop {
+            let attempt_started = Instant::now();
+            let result = self
+                .request_schema::<T>(messages.clone(), options, billed)
+                .await;
+            let (raw, feedback) = match &result {
+                Err(err) if repairs < self.max_repair_attempts => {
+                    if let Some(parse_error) = err.downcast_ref::<ParseError>() {
+                        let feedback = format!(
+                            "Your previous response could not be parsed: {}. \
+                             Respond again with only the corrected JSON, conforming exactly to the schema.",
+                            parse_error.message
+                        );
+                        (parse_error.raw.clone(), feedback)
+                    } else if let Some(violation) = err.downcast_ref::<StyleViolation>() {
+                        let feedback = format!(
+                            "Your previous response did not follow the style instructions: {}. \
+                             Respond again with the corrected JSON.",
+                            violation.violations.join("; ")
+                        );
+                        (violation.raw.clone(), feedback)
+                    } else {
+                        return result;
+                    }
+                }
+                _ => {
+                    return result.map(|mut response| {
+                        response.meta.repair_attempts = repairs;
+                        if repairs > 0 {
+                            response.meta.timings.repair = attempt_started - started;
+                        }
+                        response
+                    })
+                }
+            };
+
+            repairs += 1;
+            messages.push(json!({
+                "role": "assistant",
+                "content": raw
+            }));
+            messages.push(json!({
+                "role": "user",
+                "content": feedback
+            }));
+        }
+    }
+
+    /// Returns the exact request body `call_schema_with` would send for the
+    /// prompt, without sending it, e.g. to review prompts or to keep generated
+    /// schemas as golden files.
+    pub fn build_request<T: JsonSchema + 'static>(
+        &self,
+        user_prompt: &str,
+        options: &RequestOptions,
+    ) -> Result<Value, Box<dyn Error>> {
+        let (schema_value, _) = self.output_schema::<T>(options, &mut StageTimings::default())?;
+        let context = self.context_block(options);
+        let mut messages = self.build_messages(user_prompt, options, context.as_deref());
+        self.insert_examples::<T>(&mut messages);
+        Ok(self.schema_request_body(
+            messages,
+            &Self::output_schema_name::<T>(options),
+            schema_value,
+            options,
+        ))
+    }
+
+    /// The schema sent for T (or the call's dynamic schema), checked against
+    /// the schema limits, and how the output has to be read back into T.
+    fn output_schema<T: JsonSchema + 'static>(
+        &self,
+        options: &RequestOptions,
+        timings: &mut StageTimings,
+    ) -> Result<(Value, OutputShape), Box<dyn Error>> {
+        let stage = Instant::now();
+        let mut schema_value = match &options.output_schema {
+            Some(dynamic) => dynamic.schema.clone(),
+            None => trace::in_span(&trace::schema_span(), Self::generate_schema::<T>)?,
+        };
+        let wrapper = Self::wrap_non_object_root(&mut schema_value);
+        if let Some(order) = self.field_orders.get(&TypeId::of::<T>()) {
+            Self::order_fields(&mut schema_value, order);
+        }
+        let scratchpad = self
+            .scratchpad
+            .clone()
+            .filter(|field| Self::add_scratchpad(&mut schema_value, field));
+        timings.schema = stage.elapsed();
+        if let Some(limits) = &self.schema_limits {
+            let stage = Instant::now();
+            limits.apply(&mut schema_value)?;
+            timings.validation = stage.elapsed();
+        }
+        Ok((
+            schema_value,
+            OutputShape {
+                wrapper,
+                scratchpad,
+            },
+        ))
+    }
+
+    /// Sends one request and parses its output, adding the tokens and cost
+    /// of a response not served from the cache to `billed`.
+    async fn request_schema<T: DeserializeOwned + JsonSchema + Clone + 'static>(
+        &self,
+        messages: Vec<Value>,
+        options: &RequestOptions,
+        billed: &mut (u32, Option<f64>),
+    ) -> Result<StructuredResponse<T>, Box<dyn std::error::Error>> {
+        let mut timings = StageTimings::default();
+        let (schema_value, shape) = self.output_schema::<T>(options, &mut timings)?;
+        let schema_name = Self::output_schema_name::<T>(options);
+        self.check_context(&messages, Some(&schema_value), options)?;
+
+        let body = self.schema_request_body(messages, &schema_name, schema_value, options);
+        if self.dry_run {
+            return Err(Box::new(DryRun { body }));
+        }
+        let cache_key = self.cache.as_ref().map(|_| cache::key(&body));
+        let mut cache_error = None;
+        let cached: Option<Value> = match (&self.cache, &cache_key) {
+            (Some(cache), Some(key)) => cache
+                .get(key)
+                .and_then(|s| serde_json::from_str(&s).ok())
+                .and_then(|raw| match &self.field_encryption {
+                    Some(encryption) => match encryption.decrypt_content(&raw) {
+                        Ok(raw) => Some(raw),
+                        Err(err) => {
+                            trace::warn_cache_error(&err);
+                            cache_error = Some(err);
+                            None
+                        }
+                    },
+                    None => Some(raw),
+                }),
+            _ => None,
+        };
+        let from_cache = cached.is_some();
+        let (raw, cost) = match cached {
+            Some(raw) => (raw, self.pricing.as_ref().map(|_| 0.0)),
+            None => {
+                let call = trace::current_span();
+                let http = trace::http_span();
+                let raw = trace::instrument(http.clone(), async {
+                    let res = self.send(&body, &mut timings).await?;
+                    trace::record_response(&call, &http, res.status.as_u16(), &res.headers);
+                    let stage = Instant::now();
+                    let raw = res.json().await.map_err(|err| err as Box<dyn Error>)?;
+                    timings.body = stage.elapsed();
+                    Ok::<Value, Box<dyn Error>>(raw)
+                })
+                .await?;
+                let raw = self.normalize_response(raw);
+                let cost = self.record_cost(options.tag.as_deref(), &raw);
+                let tokens = raw["usage"]["total_tokens"].as_u64().unwrap_or(0);
+                billed.0 = billed.0.saturating_add(tokens as u32);
+                if let Some(cost) = cost {
+                    *billed.1.get_or_insert(0.0) += cost;
+                }
+                (raw, cost)
+            }
+        };
+        let stage = Instant::now();
+        let parsers = options.parsers.as_deref().unwrap_or(&self.parsers);
+        let ParsedOutput {
+            response,
+            parser,
+            scratchpad,
+        } = trace::in_span(&trace::parse_span(), || {
+            Self::parse_output::<T>(&raw, &shape, parsers)
+        })?;
+        timings.parse = stage.elapsed();
+
+        match response {
+            OpenAIResponse::Ok(res) => {
+                if let (Some(limiter), Some(usage), false) =
+                    (&self.rate_limiter, &res.usage, from_cache)
+                {
+                    limiter.settle(ratelimit::estimate_tokens(&body), usage.total_tokens);
+                }
+                let choice = &res.choices[0];
+                let mut meta = ResponseMeta {
+                    id: res.id.clone(),
+                    model: res.model.clone(),
+                    created: res.created,
+                    finish_reason: choice.finish_reason.clone(),
+                    usage: res.usage.clone(),
+                    token_attribution: attribution::from_choice(&raw["choices"][0]).map(
+                        |attribution| match self.pricing.as_ref().and_then(|p| p.get(&self.model)) {
+                            Some(price) => attribution.priced(&price),
+                            None => attribution,
+                        },
+                    ),
+                    cached: from_cache,
+                    timings,
+                    parser,
+                    cost,
+                    scratchpad,
+                    cache_error,
+                    ..Default::default()
+                };
+                match choice.message.clone() {
+                    Message::Ok(content) => {
+                        if let Some(style) = &options.style {
+                            Self::check_style(style, &raw, &shape)?;
+                        }
+                        if let (Some(text), false) =
+                            (raw["choices"][0]["message"]["content"].as_str(), from_cache)
+                        {
+                            self.screen(ModerationStage::Output, text).await?;
+                        }
+                        if let (Some(cache), Some(key), false) =
+                            (&self.cache, &cache_key, from_cache)
+                        {
+                            let stored = match &self.field_encryption {
+                                Some(encryption) => encryption.encrypt_content(&raw),
+                                None => Ok(raw.clone()),
+                            };
+                            match stored {
+                                Ok(stored) => cache.put(key, stored.to_string()),
+                                Err(err) => {
+                                    trace::warn_cache_error(&err);
+                                    meta.cache_error = Some(err);
+                                }
+                            }
+                        }
+                        Ok(StructuredResponse {
+                            value: content.content,
+                            meta,
+                        })
+                    }
+                    Message::Err(refusal) => Err(Box::new(refusal)),
+                }
+            }
+            OpenAIResponse::Err(err) => Err(Box::new(err)),
+        }
+    }
+
+    /// Parses the response with the first parser whose reading of the message
+    /// content deserializes into T. Responses without text content, such as
+    /// refusals and API errors, are parsed as they are.
+    fn parse_output<T: DeserializeOwned>(
+        raw: &Value,
+        shape: &OutputShape,
+        parsers: &[ContentParser],
+    ) -> Result<ParsedOutput<T>, Box<dyn Error>> {
+        let parse = |raw: &Value| {
+            let (output, scratchpad) = match &shape.scratchpad {
+                Some(field) => Self::take_scratchpad(raw, field),
+                None => (raw.clone(), None),
+            };
+            let output = match shape.wrapper {
+                Some(key) => Self::unwrap_root_value(&output, key),
+                None => output,
+            };
+            serde_json::from_value(output.clone())
+                .map(|response| (response, scratchpad))
+                .map_err(|err| (output, err))
+        };
+        let content = raw["choices"][0]["message"]["content"].as_str();
+        let mut first_error = None;
+        for parser in parsers {
+            let result = match (parser, content) {
+                (ContentParser::Json, _) => parse(raw),
+                (parser, Some(content)) => match parser.parse(content) {
+                    Some(value) => {
+                        let mut raw = raw.clone();
+                        raw["choices"][0]["message"]["content"] = Value::String(value.to_string());
+                        parse(&raw)
+                    }
+                    None => continue,
+                },
+                (_, None) => continue,
+            };
+            match result {
+                Ok((response, scratchpad)) => {
+                    return Ok(ParsedOutput {
+                        response,
+                        parser: *parser,
+                        scratchpad,
+                    })
+                }
+                Err(err) => {
+                    first_error.get_or_insert(err);
+                }
+            }
+        }
+        let (output, err) = match first_error {
+            Some(err) => err,
+            None => match parse(raw) {
+                Ok((response, scratchpad)) => {
+                    return Ok(ParsedOutput {
+                        response,
+                        parser: ContentParser::Json,
+                        scratchpad,
+                    })
+                }
+                Err(err) => err,
+            },
+        };
+        Err(ParseError::from_response::<T>(&output, err))
+    }
+
+    /// Calls the OpenAI endpoint with the given tools available to the model.
+    /// Returns either the final structured answer T or the typed tool invocations
+    /// the model requested.
+    pub async fn call_with_tools<T: DeserializeOwned + JsonSchema + 'static>(
+        &self,
+        user_prompt: &str,
+        tools: &ToolSet,
+    ) -> Result<ToolResponse<T>, Box<dyn std::error::Error>> {
+        self.call_with_tools_with(user_prompt, tools, &RequestOptions::default())
+            .await
+    }
+
+    /// Like `call_with_tools`, with per-call request options such as
+    /// temperature or a cancellation token.
+    pub async fn call_with_tools_with<T: DeserializeOwned + JsonSchema + 'static>(
+        &self,
+        user_prompt: &str,
+        tools: &ToolSet,
+        options: &RequestOptions,
+    ) -> Result<ToolResponse<T>, Box<dyn std::error::Error>> {
+        let messages = self.tool_conversation::<T>(user_prompt, options);
+        self.continue_with_tools(&messages, tools, options).await
+    }
+
+    /// The opening messages of a tool-calling conversation.
+    pub(crate) fn tool_conversation<T: 'static>(
+        &self,
+        user_prompt: &str,
+        options: &RequestOptions,
+    ) -> Vec<Value> {
+        let mut messages =
+            self.build_messages(user_prompt, options, self.context_block(options).as_deref());
+        self.insert_examples::<T>(&mut messages);
+        messages
+    }
+
+    /// Sends a tool-calling conversation so far, returning the model's next turn.
+    pub(crate) async fn continue_with_tools<T: DeserializeOwned + JsonSchema + 'static>(
+        &self,
+        messages: &[Value],
+        tools: &ToolSet,
+        options: &RequestOptions,
+    ) -> Result<ToolResponse<T>, Box<dyn std::error::Error>> {
+        let mut schema_value = Self::generate_schema::<T>()?;
+        let wrapper = Self::wrap_non_object_root(&mut schema_value);
+        let schema_name = Self::schema_name_for_type::<T>();
+
+        let mut body = json!({
+            "model": self.model,
+            "messages": messages,
+            "tools": tools.to_request_value()?,
+            "response_format": {
+                "type": "json_schema",
+                "json_schema": {
+                    "name": schema_name,
+                    "strict": true,
+                    "schema": schema_value
+                }
+            }
+        });
+        if let Some(choice) = tools.choice_value()? {
+            body["tool_choice"] = choice;
+        }
+        self.apply_options(&mut body, options);
+
+        let started = Instant::now();
+        let mut billed = (0, None);
+        let result = self
+            .within_limits(
+                options.cancellation.as_ref(),
+                self.request_tools(&body, options.tag.as_deref(), wrapper, &mut billed),
+            )
+            .await;
+        self.stats.record(
+            options.tag.as_deref(),
+            started.elapsed(),
+            CallOutcome::of(&result),
+            billed.0,
+            billed.1,
+            false,
+            StageTimings::default(),
+        );
+        result
+    }
+
+    /// Sends a tool-calling request, setting `billed` to the tokens and cost
+    /// of the response.
+    async fn request_tools<T: DeserializeOwned>(
+        &self,
+        body: &Value,
+        tag: Option<&str>,
+        wrapper: Option<&str>,
+        billed: &mut (u32, Option<f64>),
+    ) -> Result<ToolResponse<T>, Box<dyn std::error::Error>> {
+        let res = self.send(body, &mut StageTimings::default()).await?;
+        let raw: Value = res.json().await.map_err(|err| err as Box<dyn Error>)?;
+        let tokens = raw["usage"]["total_tokens"].as_u64().unwrap_or(0);
+        *billed = (tokens as u32, self.record_cost(tag, &raw));
+        let response: ToolCallingResponse = serde_json::from_value(raw)?;
+
+        match response {
+            ToolCallingResponse::Ok(res) => res.choices[0].message.clone().into_response(wrapper),
+            ToolCallingResponse::Err(err) => Err(Box::new(err)),
+        }
+    }
+
+    /// Calls the endpoint without a schema and returns the model's free-form
+    /// answer, e.g. for summaries or chat-style replies.
+    pub async fn call_text(&self, user_prompt: &str) -> Result<String, Box<dyn Error>> {
+        self.call_text_with(user_prompt, &RequestOptions::default())
+            .await
+    }
+
+    /// Like `call_text`, with per-call request options.
+    pub async fn call_text_with(
+        &self,
+        user_prompt: &str,
+        options: &RequestOptions,
+    ) -> Result<String, Box<dyn Error>> {
+        self.record_text_call(user_prompt, options, false, Ok).await
+    }
+
+    /// Calls the endpoint in JSON mode, which guarantees syntactically valid
+    /// JSON but no particular shape. OpenAI requires the word "JSON" to appear
+    /// in the messages; Anthropic has no JSON mode, so the prompt alone decides.
+    pub async fn call_json(&self, user_prompt: &str) -> Result<Value, Box<dyn Error>> {
+        self.call_json_with(user_prompt, &RequestOptions::default())
+            .await
+    }
+
+    /// Like `call_json`, with per-call request options.
+    pub async fn call_json_with(
+        &self,
+        user_prompt: &str,
+        options: &RequestOptions,
+    ) -> Result<Value, Box<dyn Error>> {
+        self.record_text_call(user_prompt, options, true, |content| {
+            serde_json::from_str(&content).map_err(|err| {
+                Box::new(ParseError {
+                    raw: content.clone(),
+                    message: err.to_string(),
+                }) as Box<dyn Error>
+            })
+        })
+        .await
+    }
+
+    /// Makes a text or JSON-mode call, reads its content with `finish` and
+    /// records the call in the statistics and the audit log.
+    async fn record_text_call<R>(
+        &self,
+        user_prompt: &str,
+        options: &RequestOptions,
+        json_mode: bool,
+        finish: impl FnOnce(String) -> Result<R, Box<dyn Error>>,
+    ) -> Result<R, Box<dyn Error>> {
+        let started = Instant::now();
+        let mut billed = (0, None);
+        let request = async {
+            finish(
+                self.request_text(user_prompt, options, json_mode, &mut billed)
+                    .await?,
+            )
+        };
+        let result =
+            audit::audited_call(self.within_limits(options.cancellation.as_ref(), request)).await;
+        self.stats.record(
+            options.tag.as_deref(),
+            started.elapsed(),
+            CallOutcome::of(&result),
+            billed.0,
+            billed.1,
+            false,
+            StageTimings::default(),
+        );
+        if let Some(audit) = &self.audit_log {
+            audit.record(
+                &self.model,
+                None,
+                options.tag.as_deref(),
+                user_prompt,
+                &result,
+                None,
+                started.elapsed(),
+            );
+        }
+        result
+    }
+
+    /// Sends a text or JSON-mode request, setting `billed` to the tokens and
+    /// cost of the response.
+    async fn request_text(
+        &self,
+        user_prompt: &str,
+        options: &RequestOptions,
+        json_mode: bool,
+        billed: &mut (u32, Option<f64>),
+    ) -> Result<String, Box<dyn Error>> {
+        self.screen(ModerationStage::Input, user_prompt).await?;
+        let context = self.context_block(options);
+        let messages = self.build_messages(user_prompt, options, context.as_deref());
+        self.check_context(&messages, None, options)?;
+        let body = self.text_request_body(messages, json_mode, options);
+        if self.dry_run {
+            return Err(Box::new(DryRun { body }));
+        }
+        let res = self.send(&body, &mut StageTimings::default()).await?;
+        let raw: Value = res.json().await.map_err(|err| err as Box<dyn Error>)?;
+        let raw = self.normalize_response(raw);
+        let tokens = raw["usage"]["total_tokens"].as_u64().unwrap_or(0);
+        *billed = (
+            tokens as u32,
+            self.record_cost(options.tag.as_deref(), &raw),
+        );
+        if raw.get("error").is_some_and(Value::is_object) {
+            let err: OpenAIError = serde_json::from_value(raw)?;
+            return Err(Box::new(err));
+        }
+        let message = &raw["choices"][0]["message"];
+        if let Some(refusal) = message["refusal"].as_str() {
+            return Err(Box::new(Refusal {
+                refusal: refusal.to_string(),
+            }));
+        }
+        match message["content"].as_str() {
+            Some(content) => {
+                self.screen(ModerationStage::Output, content).await?;
+                Ok(content.to_string())
+            }
+            None => Err(Box::new(ParseError {
+                raw: raw.to_string(),
+                message: "response has no message content".to_string(),
+            })),
+        }
+    }
+
+    /// Adds a response's usage to the cost summary and returns its cost, if the
+    /// model is priced.
+    fn record_cost(&self, tag: Option<&str>, raw: &Value) -> Option<f64> {
+        self.record_cost_of(&self.model, tag, raw)
+    }
+
+    /// Like `record_cost`, for a response of another model, e.g. embeddings.
+    fn record_cost_of(&self, model: &str, tag: Option<&str>, raw: &Value) -> Option<f64> {
+        let usage: Usage = serde_json::from_value(raw.get("usage")?.clone()).ok()?;
+        let cost = self
+            .pricing
+            .as_ref()
+            .and_then(|pricing| pricing.get(model))
+            .map(|price| price.cost(&usage));
+        self.costs.record(tag, &usage, cost);
+        if let Some(budget) = &self.budget {
+            budget.record(cost, usage.total_tokens);
+        }
+        cost
+    }
+
+    /// Estimated prompt tokens of a call with this prompt, including the system
+    /// role. Exact for OpenAI models with the `tiktoken` feature.
+    pub fn estimate_tokens(&self, user_prompt: &str) -> u32 {
+        let options = RequestOptions::default();
+        let context = self.context_block(&options);
+        let messages = self.build_messages(user_prompt, &options, context.as_deref());
+        tokens::count_message_tokens(&self.model, &messages)
+    }
+
+    fn check_context(
+        &self,
+        messages: &[Value],
+        schema: Option<&Value>,
+        options: &RequestOptions,
+    ) -> Result<(), ContextOverflow> {
+        let Some(check) = self.context_check else {
+            return Ok(());
+        };
+        let Some(context_window) = self.context_windows.get(&self.model) else {
+            return Ok(());
+        };
+        let overflow = ContextOverflow {
+            model: self.model.clone(),
+            prompt_tokens: tokens::count_message_tokens(&self.model, messages),
+            schema_tokens: schema.map_or(0, |schema| {
+                tokens::count_tokens(&self.model, &schema.to_string())
+            }),
+            completion_tokens: options.max_completion_tokens.unwrap_or(0),
+            context_window,
+        };
+        if overflow.total_tokens() <= context_window {
+            return Ok(());
+        }
+        match check {
+            ContextCheck::Warn => {
+                trace::warn_context_overflow(&overflow);
+                Ok(())
+            }
+            ContextCheck::Reject => Err(overflow),
+        }
+    }
+
+    /// Builds the request body for a structured call in the format of the
+    /// configured provider and API flavor.
+    fn schema_request_body(
+        &self,
+        messages: Vec<Value>,
+        schema_name: &str,
+        schema_value: Value,
+        options: &RequestOptions,
+    ) -> Value {
+        let mut body = match (self.provider, self.api_flavor, self.output_mode) {
+            (Provider::OpenAi, ApiFlavor::ChatCompletions, OutputMode::JsonSchema) => json!({
+                "model": self.model,
+                "messages": messages,
+                "response_format": {
+                    "type": "json_schema",
+                    "json_schema": {
+                        "name": schema_name,
+                        "strict": true,
+                        "schema": schema_value
+                    }
+                }
+            }),
+            (Provider::OpenAi, ApiFlavor::ChatCompletions, mode) => {
+                let mut body = json!({
+                    "model": self.model,
+                    "messages": Self::with_schema_instruction(messages, &schema_value),
+                });
+                if mode == OutputMode::JsonObject {
+                    body["response_format"] = json!({ "type": "json_object" });
+                }
+                body
+            }
+            (Provider::OpenAi, ApiFlavor::Responses, _) => {
+                responses::request_body(&self.model, &messages, schema_name, schema_value)
+            }
+            (Provider::Anthropic, _, _) => {
+                anthropic::request_body(&self.model, &messages, schema_name, schema_value)
+            }
+            (Provider::Gemini, _, _) => gemini::request_body(&messages, schema_value),
+            (Provider::Local(server), _, _) => {
+                local::request_body(server, &self.model, &messages, schema_value)
+            }
+        };
+        if options
+            .output_schema
+            .as_ref()
+            .is_some_and(|schema| !schema.strict)
+        {
+            match (self.provider, self.api_flavor, self.output_mode) {
+                (Provider::OpenAi, ApiFlavor::ChatCompletions, OutputMode::JsonSchema) => {
+                    body["response_format"]["json_schema"]["strict"] = json!(false)
+                }
+                (Provider::OpenAi, ApiFlavor::Responses, _) => {
+                    body["text"]["format"]["strict"] = json!(false)
+                }
+                _ => {}
+            }
+        }
+        self.apply_options(&mut body, options);
+        body
+    }
+
+    /// Adds the schema as an instruction after the system messages, for output
+    /// modes without `response_format` schemas.
+    fn with_schema_instruction(mut messages: Vec<Value>, schema: &Value) -> Vec<Value> {
+        let at = messages
+            .iter()
+            .take_while(|message| matches!(message["role"].as_str(), Some("system" | "developer")))
+            .count();
+        messages.insert(
+            at,
+            json!({
+                "role": "system",
+                "content": format!(
+                    "Respond with only a JSON value conforming to this JSON schema:\n{}",
+                    schema
+                )
+            }),
+        );
+        messages
+    }
+
+    /// Builds the request body for an unstructured call, optionally in the
+    /// provider's JSON mode.
+    fn text_request_body(
+        &self,
+        messages: Vec<Value>,
+        json_mode: bool,
+        options: &RequestOptions,
+    ) -> Value {
+        let mut body = match (self.provider, self.api_flavor) {
+            (Provider::OpenAi, ApiFlavor::ChatCompletions) => {
+                let mut body = json!({ "model": self.model, "messages": messages });
+                if json_mode {
+                    body["response_format"] = json!({ "type": "json_object" });
+                }
+                body
+            }
+            (Provider::OpenAi, ApiFlavor::Responses) => {
+                responses::text_request_body(&self.model, &messages, json_mode)
+            }
+            (Provider::Anthropic, _) => anthropic::text_request_body(&self.model, &messages),
+            (Provider::Gemini, _) => gemini::text_request_body(&messages, json_mode),
+            (Provider::Local(server), _) => {
+                local::text_request_body(server, &self.model, &messages, json_mode)
+            }
+        };
+        self.apply_options(&mut body, options);
+        body
+    }
+
+    fn apply_options(&self, body: &mut Value, options: &RequestOptions) {
+        options.apply_to(body);
+        if self.provider == Provider::OpenAi {
+            if self.is_reasoning_model() {
+                reasoning::adapt_body(&self.model, body);
+            } else if let Some(object) = body.as_object_mut() {
+                object.remove("reasoning_effort");
+            }
+        }
+        match (self.provider, self.api_flavor) {
+            (Provider::OpenAi, ApiFlavor::ChatCompletions) => {}
+            (Provider::OpenAi, ApiFlavor::Responses) => responses::adapt_options(body),
+            (Provider::Anthropic, _) => anthropic::adapt_options(body),
+            (Provider::Gemini, _) => gemini::adapt_options(body),
+            (Provider::Local(server), _) => local::adapt_options(server, body),
+        }
+    }
+
+    /// Converts a provider response into the chat-completions shape the parser expects.
+    fn normalize_response(&self, raw: Value) -> Value {
+        match (self.provider, self.api_flavor) {
+            (Provider::OpenAi, ApiFlavor::ChatCompletions) => raw,
+            (Provider::OpenAi, ApiFlavor::Responses) => responses::to_chat_completion(raw),
+            (Provider::Anthropic, _) => anthropic::to_chat_completion(raw),
+            (Provider::Gemini, _) => gemini::to_chat_completion(raw),
+            (Provider::Local(server), _) => local::to_chat_completion(server, raw),
+        }
+    }
+
+    /// Constructs the message list: the optional system role, context block and
+    /// style instructions, then the instruction messages, followed by the user
+    /// prompt and any images of the call.
+    fn build_messages(
+        &self,
+        user_prompt: &str,
+        options: &RequestOptions,
+        context: Option<&str>,
+    ) -> Vec<Value> {
+        let mut messages = Vec::new();
+        let system_role = options.system_role.as_ref().or(self.system_role.as_ref());
+        let blocks: Vec<String> = system_role
+            .map(|role| self.guard_input(role))
+            .into_iter()
+            .chain(context.map(str::to_string))
+            .chain(options.style.as_ref().map(ResponseStyle::render))
+            .collect();
+        if !blocks.is_empty() {
+            messages.push(json!({
+                "role": "system",
+                "content": blocks.join("\n\n")
+            }));
+        }
+        let instructions = options.instructions.as_ref().unwrap_or(&self.instructions);
+        for instruction in instructions {
+            messages.push(json!({
+                "role": self.instruction_role(instruction.role),
+                "content": self.guard_input(&instruction.content)
+            }));
+        }
+        messages.push(json!({
+            "role": "user",
+            "content": content::user_content(self.guard_input(user_prompt), &options.images)
+        }));
+        messages
+    }
+
+    /// The role name of an instruction message for the provider; reasoning
+    /// models' system messages become developer messages in `apply_options`.
+    fn instruction_role(&self, role: InstructionRole) -> &'static str {
+        match (self.provider, role) {
+            (Provider::OpenAi, InstructionRole::Developer) => "developer",
+            _ => "system",
+        }
+    }
+
+    /// The context block of a call, rendered once so that every attempt of the
+    /// call sees the same time.
+    fn context_block(&self, options: &RequestOptions) -> Option<String> {
+        options
+            .context
+            .as_ref()
+            .or(self.context.as_ref())
+            .map(PromptContext::render)
+    }
+
+    /// Inserts the few-shot examples registered for T before the user prompt.
+    fn insert_examples<T: 'static>(&self, messages: &mut Vec<Value>) {
+        let Some(examples) = self.examples.get(&TypeId::of::<T>()) else {
+            return;
+        };
+        let at = messages.len() - 1;
+        let pairs = examples.iter().flat_map(|(prompt, output)| {
+            [
+                json!({ "role": "user", "content": self.guard_input(prompt) }),
+                json!({ "role": "assistant", "content": output }),
+            ]
+        });
+        messages.splice(at..at, pairs);
+    }
+
+    fn guard_input(&self, input: &str) -> String {
+        match &self.input_guard {
+            Some(guard) => {
+                let (guarded, report) = guard.apply(input);
+                if !report.is_clean() {
+                    trace::warn_guarded_input(&report);
+                }
+                guarded
+            }
+            None => input.to_string(),
+        }
+    }
+
+    /// Starts a request carrying the User-Agent, default headers and query
+    /// parameters; `execute` adds the API key.
+    pub(crate) fn authorized(
+        &self,
+        method: Method,
+        url: &str,
+    ) -> Result<HttpRequest, Box<dyn Error>> {
+        let minimal_headers = self.privacy.as_ref().is_some_and(|p| p.minimal_headers);
+        let mut request = HttpRequest::new(method, url);
+        if !self.query_params.is_empty() {
+            request = request.query(&self.query_params)?;
+        }
+        if !minimal_headers {
+            request = request.header(USER_AGENT, &self.user_agent)?;
+        }
+        if self.provider == Provider::Anthropic {
+            request = request.header("anthropic-version", anthropic::API_VERSION)?;
+        }
+        if let Some(organization) = &self.organization {
+            request = request.header("OpenAI-Organization", organization)?;
+        }
+        if let Some(project) = &self.project {
+            request = request.header("OpenAI-Project", project)?;
+        }
+        if !minimal_headers {
+            for (name, value) in &self.client_headers {
+                request = request.header(name, value)?;
+            }
+        }
+        for (name, value) in &self.headers {
+            request.headers.insert(name, value.clone());
+        }
+        Ok(request)
+    }
+
+    pub(crate) fn endpoint(&self) -> &str {
+        &self.endpoint
+    }
+
+    /// The URL of another API resource, e.g. `embeddings`, under the API base
+    /// or next to an OpenAI-style `/chat/completions` or `/responses` endpoint.
+    pub(crate) fn api_url(&self, resource: &str) -> Result<String, UnknownApiBase> {
+        let derived = || {
+            if !matches!(self.provider, Provider::OpenAi | Provider::Local(_))
+                || self.endpoint.contains('?')
+            {
+                return None;
+            }
+            let endpoint = self.endpoint.trim_end_matches('/');
+            endpoint
+                .strip_suffix("/chat/completions")
+                .or_else(|| endpoint.strip_suffix("/responses"))
+        };
+        let base = self
+            .api_base
+            .as_deref()
+            .or_else(derived)
+            .ok_or_else(|| UnknownApiBase {
+                resource: resource.to_string(),
+                endpoint: self.endpoint.clone(),
+            })?;
+        Ok(format!("{}/{}", base.trim_end_matches('/'), resource))
+    }
+
+    pub(crate) fn moderation(&self) -> Option<&ModerationPolicy> {
+        self.moderation.as_ref()
+    }
+
+    fn check_endpoint(&self, url: &str) -> Result<(), InsecureEndpoint> {
+        let allowed =
+            self.insecure_remote || (self.insecure_loopback && transport::is_loopback(url));
+        if transport::is_plain_http(url) && !allowed {
+            return Err(InsecureEndpoint {
+                url: url.to_string(),
+            });
+        }
+        Ok(())
+    }
+
+    /// Runs a call, failing it with `DeadlineExceeded` once the deadline passes
+    /// or with `Cancelled` once the token is cancelled.
+    async fn within_limits<R>(
+        &self,
+        cancellation: Option<&CancellationToken>,
+        call: impl Future<Output = Result<R, Box<dyn Error>>>,
+    ) -> Result<R, Box<dyn Error>> {
+        let call = async {
+            match self.deadline {
+                Some(deadline) => time::timeout(deadline, call)
+                    .await
+                    .unwrap_or_else(|_| Err(Box::new(DeadlineExceeded { deadline }))),
+                None => call.await,
+            }
+        };
+        match cancellation {
+            Some(token) => token
+                .run_until_cancelled(call)
+                .await
+                .unwrap_or_else(|| Err(Box::new(Cancelled))),
+            None => call.await,
+        }
+    }
+
+    /// Sends a request through the transport, within the request timeout.
+    async fn transport_send(&self, request: HttpRequest) -> Result<HttpResponse, TransportError> {
+        let Some(timeout) = self.timeout else {
+            return self.transport.send(request).await;
+        };
+        let deadline = Instant::now() + timeout;
+        let mut res = time::timeout_at(deadline, self.transport.send(request))
+            .await
+            .map_err(|_| transport::timed_out(timeout))??;
+        res.body = transport::body_until(res.body, deadline, timeout);
+        Ok(res)
+    }
+
+    /// Adds the provider's current API key in the header the API expects.
+    async fn authenticate(&self, request: HttpRequest) -> Result<HttpRequest, Box<dyn Error>> {
+        let token = self
+            .credentials
+            .token()
+            .await
+            .map_err(|source| CredentialUnavailable { source })?;
+        let token = token.expose_secret();
+        Ok(match self.provider {
+            Provider::OpenAi => request.bearer_auth(token)?,
+            Provider::Anthropic => request.header("x-api-key", token)?,
+            Provider::Gemini => request.header("x-goog-api-key", token)?,
+            Provider::Local(_) if token.is_empty() => request,
+            Provider::Local(_) => request.bearer_auth(token)?,
+        })
+    }
+
+    /// Sends a request through the middleware and the transport, without
+    /// retries, recording it in the audit log unless it is part of a schema or
+    /// text call.
+    pub(crate) async fn execute(
+        &self,
+        request: HttpRequest,
+    ) -> Result<HttpResponse, Box<dyn Error>> {
+        let Some(audit) = self
+            .audit_log
+            .as_ref()
+            .filter(|_| !audit::in_audited_call())
+        else {
+            return self.dispatch(request).await;
+        };
+        let body = request
+            .body
+            .as_ref()
+            .and_then(|body| serde_json::from_slice::<Value>(body).ok());
+        let started = Instant::now();
+        let result = self.dispatch(request).await;
+        audit.record_request(&self.model, body.as_ref(), &result, started.elapsed());
+        result
+    }
+
+    async fn dispatch(&self, request: HttpRequest) -> Result<HttpResponse, Box<dyn Error>> {
+        if let Some(budget) = &self.budget {
+            budget.check()?;
+        }
+        let request = self.authenticate(request).await?;
+        if self.middleware.is_empty() {
+            self.check_endpoint(&request.url)?;
+            return self
+                .transport_send(request)
+                .await
+                .map_err(|err| err as Box<dyn Error>);
+        }
+
+        let mut parts = RequestParts {
+            method: request.method,
+            url: request.url,
+            headers: request.headers,
+            body: request
+                .body
+                .as_ref()
+                .and_then(|body| serde_json::from_slice(body).ok()),
+        };
+        // Other bodies, such as multipart uploads, are sent as they are.
+        let raw_body = request.body.filter(|_| parts.body.is_none());
+        let body_stream = request.body_stream;
+        for middleware in &self.middleware {
+            middleware.on_request(&mut parts);
+        }
+        let mut request = HttpRequest::new(parts.method, parts.url);
+        request.headers = parts.headers;
+        request.body = match &parts.body {
+            Some(body) => Some(serde_json::to_vec(body)?.into()),
+            None => raw_body,
+        };
+        request.body_stream = body_stream;
+        self.check_endpoint(&request.url)?;
+
+        // The body is buffered so every middleware can see it.
+        let res = self
+            .transport_send(request)
+            .await
+            .map_err(|err| err as Box<dyn Error>)?;
+        let parts = ResponseParts {
+            status: res.status,
+            headers: res.headers.clone(),
+            body: res.bytes().await.map_err(|err| err as Box<dyn Error>)?,
+        };
+        for middleware in &self.middleware {
+            middleware.on_response(&parts);
+        }
+        Ok(HttpResponse::new(parts.status, parts.headers, parts.body))
+    }
+
+    /// Reads a JSON response of a non-completion endpoint, surfacing API errors.
+    pub(crate) async fn read_json<R: DeserializeOwned>(
+        &self,
+        res: HttpResponse,
+    ) -> Result<R, Box<dyn Error>> {
+        Ok(serde_json::from_value(self.read_api_json(res).await?)?)
+    }
+
+    /// Like `read_json` for endpoints that bill their usage, such as
+    /// embeddings, adding it to the costs and the budget.
+    pub(crate) async fn read_billed_json<R: DeserializeOwned>(
+        &self,
+        res: HttpResponse,
+    ) -> Result<R, Box<dyn Error>> {
+        let raw = self.read_api_json(res).await?;
+        let model = raw
+            .get("model")
+            .and_then(Value::as_str)
+            .unwrap_or(&self.model);
+        self.record_cost_of(model, None, &raw);
+        Ok(serde_json::from_value(raw)?)
+    }
+
+    async fn read_api_json(&self, res: HttpResponse) -> Result<Value, Box<dyn Error>> {
+        let raw: Value = res.json().await.map_err(|err| err as Box<dyn Error>)?;
+        if raw.get("error").is_some_and(Value::is_object) {
+            let err: OpenAIError = serde_json::from_value(raw)?;
+            return Err(Box::new(err));
+        }
+        Ok(raw)
+    }
+
+    /// Sends the body, retrying per the retry policy. Time spent waiting (rate
+    /// limiter, jitter, backoff and failed attempts) is added to `timings.queue`;
+    /// the final attempt's time to response headers becomes `timings.first_byte`.
+    pub(crate) async fn send(
+        &self,
+        body: &Value,
+        timings: &mut StageTimings,
+    ) -> Result<HttpResponse, Box<dyn Error>> {
+        self.send_to(&self.endpoint, body, timings).await
+    }
+
+    /// Like `send`, posting to another resource such as embeddings.
+    pub(crate) async fn send_to(
+        &self,
+        url: &str,
+        body: &Value,
+        timings: &mut StageTimings,
+    ) -> Result<HttpResponse, Box<dyn Error>> {
+        let mut attempt = 1;
+        loop {
+            let queued = Instant::now();
+            if let Some(limiter) = &self.rate_limiter {
+                limiter.acquire(ratelimit::estimate_tokens(body)).await;
+            }
+            if let Some(privacy) = &self.privacy {
+                time::sleep(privacy.jitter()).await;
+            }
+            timings.queue += queued.elapsed();
+            let sent = Instant::now();
+            let result = self
+                .execute(self.authorized(Method::POST, url)?.json(body)?)
+                .await;
+            if let (Some(limiter), Ok(res)) = (&self.rate_limiter, &result) {
+                limiter.sync(&res.headers);
+            }
+
+            timings.first_byte = sent.elapsed();
+
+            let policy = match &self.retry_policy {
+                Some(policy) if attempt < policy.max_attempts => policy,
+                _ => return result,
+            };
+            let retry_after = match &result {
+                Ok(res) if RetryPolicy::is_retryable_status(res.status) => {
+                    retry::retry_after(&res.headers)
+                }
+                Err(err) if RetryPolicy::is_retryable_error(err.as_ref()) => None,
+                _ => return result,
+            };
+
+            timings.queue += timings.first_byte;
+            let backoff = Instant::now();
+            time::sleep(policy.delay_for(attempt, retry_after)).await;
+            timings.queue += backoff.elapsed();
+            attempt += 1;
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+#[serde(bound(deserialize = "T: DeserializeOwned"))]
+pub enum OpenAIResponse<T> {
+    Ok(ChatGPTResponse<T>),
+    Err(OpenAIError),
+}
+
+#[derive(Debug, Deserialize)]
+pub struct OpenAIError {
+    error: OpenAIErrorDetails,
+}
+
+#[derive(Debug, Deserialize)]
 pub struct OpenAIErrorDetails {
     pub message: String,
     // pub r#type: String,
@@ -245,13 +2345,127 @@ pub struct OpenAIErrorDetails {
 #[derive(Debug, Deserialize, Clone)]
 #[serde(bound(deserialize = "T: DeserializeOwned"))]
 pub struct ChatGPTResponse<T> {
+    #[serde(default)]
+    pub id: String,
+    #[serde(default)]
+    pub model: String,
+    #[serde(default)]
+    pub created: u64,
     pub choices: Vec<Choice<T>>,
+    pub usage: Option<Usage>,
 }
 
 #[derive(Debug, Deserialize, Clone)]
 #[serde(bound(deserialize = "T: DeserializeOwned"))]
 pub struct Choice<T> {
     pub message: Message<T>,
+    pub finish_reason: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Clone, Default, PartialEq)]
+pub struct Usage {
+    pub prompt_tokens: u32,
+    /// Absent for embeddings.
+    #[serde(default)]
+    pub completion_tokens: u32,
+    pub total_tokens: u32,
+    pub prompt_tokens_details: Option<PromptTokensDetails>,
+    pub completion_tokens_details: Option<CompletionTokensDetails>,
+}
+
+#[derive(Debug, Deserialize, Clone, Default, PartialEq)]
+pub struct PromptTokensDetails {
+    #[serde(default)]
+    pub cached_tokens: u32,
+}
+
+#[derive(Debug, Deserialize, Clone, Default, PartialEq)]
+pub struct CompletionTokensDetails {
+    /// Tokens a reasoning model spent thinking, included in `completion_tokens`.
+    #[serde(default)]
+    pub reasoning_tokens: u32,
+}
+
+impl Usage {
+    /// Prompt tokens served from OpenAI's prompt cache.
+    pub fn cached_tokens(&self) -> u32 {
+        self.prompt_tokens_details
+            .as_ref()
+            .map_or(0, |details| details.cached_tokens)
+    }
+
+    /// Completion tokens a reasoning model spent on reasoning, billed as
+    /// output but not part of the response.
+    pub fn reasoning_tokens(&self) -> u32 {
+        self.completion_tokens_details
+            .as_ref()
+            .map_or(0, |details| details.reasoning_tokens)
+    }
+}
+
+/// A parsed structured output together with the response metadata.
+#[derive(Debug, Clone)]
+pub struct StructuredResponse<T> {
+    pub value: T,
+    pub meta: ResponseMeta,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ResponseMeta {
+    pub id: String,
+    pub model: String,
+    /// Unix timestamp (seconds) of when the completion was created.
+    pub created: u64,
+    pub finish_reason: Option<String>,
+    pub usage: Option<Usage>,
+    /// Number of repair round-trips needed before the output parsed.
+    pub repair_attempts: u32,
+    /// Completion tokens per top-level field; requires `RequestOptions::logprobs`.
+    pub token_attribution: Option<TokenAttribution>,
+    /// Whether the response was served from the client's cache without an API call.
+    pub cached: bool,
+    /// Where the call's time went, stage by stage.
+    pub timings: StageTimings,
+    /// The parser that read the output; anything but `ContentParser::Json`
+    /// means the model drifted from the format.
+    pub parser: ContentParser,
+    /// Cost in USD of this response's usage, zero if it was served from the
+    /// client's cache; requires `with_pricing`.
+    pub cost: Option<f64>,
+    /// What the model wrote in the scratchpad field; requires `with_scratchpad`.
+    pub scratchpad: Option<String>,
+    /// Why the cache was skipped: a cached response that no longer decrypts,
+    /// e.g. after its key was retired, or a response that could not be
+    /// encrypted and so was not cached. The call itself went ahead.
+    pub cache_error: Option<EncryptionError>,
+}
+
+/// Wall-clock time spent in each stage of a call. Apart from `repair`, the
+/// stages describe the final attempt.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct StageTimings {
+    /// Schema generation; cached after the first call for a type.
+    pub schema: Duration,
+    /// Checking the schema against `SchemaLimits`.
+    pub validation: Duration,
+    /// Rate limiting, request jitter, retry backoff and failed retried attempts.
+    pub queue: Duration,
+    /// Sending the request until the response headers arrive: connection setup
+    /// and, as responses are not streamed, generation.
+    pub first_byte: Duration,
+    /// Reading the response body.
+    pub body: Duration,
+    /// Deserializing the output into the target type.
+    pub parse: Duration,
+    /// Earlier attempts whose output failed to parse and was repaired.
+    pub repair: Duration,
+}
+
+impl ResponseMeta {
+    /// Whether generation stopped because it hit the token limit.
+    pub fn is_truncated(&self) -> bool {
+        self.finish_reason.as_deref() == Some("length")
+    }
 }
 
 #[derive(Debug, Deserialize, Clone)]
@@ -273,7 +2487,7 @@ pub struct Content<T> {
 #[derive(Debug, Deserialize, Clone)]
 pub struct Refusal {
     // role: String,
-    refusal: String,
+    pub(crate) refusal: String,
 }
 
 impl fmt::Display for Refusal {
@@ -284,6 +2498,111 @@ impl fmt::Display for Refusal {
 
 impl Error for Refusal {}
 
+/// The model's output did not deserialize into the requested type.
+#[derive(Debug, Clone)]
+pub struct ParseError {
+    /// The raw message content returned by the model.
+    pub raw: String,
+    pub message: String,
+}
+
+impl ParseError {
+    /// Builds the error for a response that failed to deserialize, pointing at the
+    /// message content when there is one.
+    fn from_response<T: DeserializeOwned>(
+        response: &Value,
+        err: serde_json::Error,
+    ) -> Box<dyn Error> {
+        match response["choices"][0]["message"]["content"].as_str() {
+            Some(raw) => {
+                let message = match serde_json::from_str::<T>(raw) {
+                    Err(content_err) => content_err.to_string(),
+                    Ok(_) => err.to_string(),
+                };
+                Box::new(ParseError {
+                    raw: raw.to_string(),
+                    message,
+                })
+            }
+            None => Box::new(err),
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Failed to parse LLM output: {}", self.message)
+    }
+}
+
+impl Error for ParseError {}
+
+/// Returned when the URL of a resource such as `embeddings` cannot be told
+/// from the client's endpoint; set it with `with_api_base`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnknownApiBase {
+    pub resource: String,
+    pub endpoint: String,
+}
+
+impl fmt::Display for UnknownApiBase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Cannot tell the {} URL from the endpoint {}; set the API root with with_api_base",
+            self.resource, self.endpoint
+        )
+    }
+}
+
+impl Error for UnknownApiBase {}
+
+/// Returned instead of sending when the client is in dry-run mode.
+#[derive(Debug, Clone)]
+pub struct DryRun {
+    /// The request body that would have been sent.
+    pub body: Value,
+}
+
+impl fmt::Display for DryRun {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Dry run: request was not sent")
+    }
+}
+
+impl Error for DryRun {}
+
+/// Returned when a call has not finished within the client's deadline.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeadlineExceeded {
+    pub deadline: Duration,
+}
+
+impl fmt::Display for DeadlineExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Call did not finish within its {} ms deadline",
+            self.deadline.as_millis()
+        )
+    }
+}
+
+impl Error for DeadlineExceeded {}
+
+/// Returned when a call is aborted through its `RequestOptions::cancellation`
+/// token, or an agent run through `Agent::with_cancellation`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cancelled;
+
+impl fmt::Display for Cancelled {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Call was cancelled")
+    }
+}
+
+impl Error for Cancelled {}
+
 impl fmt::Display for OpenAIError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "OpenAI Error: {}", self.error.message)
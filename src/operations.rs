@@ -0,0 +1,293 @@
+use crate::dynamic::DynSchema;
+use crate::guard::InputGuard;
+use crate::openai::OpenAiClient;
+use crate::options::RequestOptions;
+use crate::prompt::PromptTemplate;
+use schemars::JsonSchema;
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde_json::Value;
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap};
+use std::error::Error;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// An operation as declared in a config file:
+///
+/// ```yaml
+/// operations:
+///   classify_ticket:
+///     prompt: "Classify this support ticket: {ticket}"
+///     schema: Ticket                  # registered with `register_schema`
+///     model: gpt-4o-mini
+///     temperature: 0
+///   extract_invoice:
+///     prompt: "Extract the invoice: {text}"
+///     schema_file: schemas/invoice.json
+///     guard: { transliterate: true }
+/// ```
+///
+/// `schema` names a schema registered from Rust; `schema_file` is a JSON
+/// Schema file, relative to the config file, sent under its file stem.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct OperationSpec {
+    pub prompt: String,
+    #[serde(default)]
+    pub schema: Option<String>,
+    #[serde(default)]
+    pub schema_file: Option<PathBuf>,
+    #[serde(default = "strict_by_default")]
+    pub strict: bool,
+    /// Overrides the client's model.
+    #[serde(default)]
+    pub model: Option<String>,
+    #[serde(default)]
+    pub system_role: Option<String>,
+    #[serde(default)]
+    pub temperature: Option<f32>,
+    #[serde(default)]
+    pub max_completion_tokens: Option<u32>,
+    #[serde(default)]
+    pub seed: Option<i64>,
+    /// Sanitizes the rendered prompt; fields default as in `InputGuard`.
+    #[serde(default)]
+    pub guard: Option<InputGuard>,
+}
+
+fn strict_by_default() -> bool {
+    true
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct OperationsFile {
+    operations: BTreeMap<String, OperationSpec>,
+}
+
+/// A loaded operation with its schema resolved.
+#[derive(Debug, Clone)]
+pub struct Operation {
+    pub name: String,
+    pub prompt: PromptTemplate,
+    pub schema: DynSchema,
+    pub model: Option<String>,
+    pub options: RequestOptions,
+    pub guard: Option<InputGuard>,
+}
+
+/// Operations by name, loaded from YAML or TOML config files at startup.
+/// Schemas compiled into the application are registered by name first so
+/// that operations can refer to them.
+#[derive(Debug, Clone, Default)]
+pub struct OperationRegistry {
+    schemas: HashMap<String, DynSchema>,
+    operations: BTreeMap<String, Operation>,
+}
+
+/// A config file referred to something that does not exist.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OperationError {
+    UnknownOperation {
+        name: String,
+    },
+    UnknownSchema {
+        operation: String,
+        schema: String,
+    },
+    /// The operation names neither or both of `schema` and `schema_file`.
+    AmbiguousSchema {
+        operation: String,
+    },
+    UnsupportedFormat {
+        path: PathBuf,
+    },
+}
+
+impl fmt::Display for OperationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OperationError::UnknownOperation { name } => {
+                write!(f, "No operation named `{}`", name)
+            }
+            OperationError::UnknownSchema { operation, schema } => write!(
+                f,
+                "Operation `{}` uses unregistered schema `{}`",
+                operation, schema
+            ),
+            OperationError::AmbiguousSchema { operation } => write!(
+                f,
+                "Operation `{}` needs exactly one of `schema` and `schema_file`",
+                operation
+            ),
+            OperationError::UnsupportedFormat { path } => write!(
+                f,
+                "Cannot load operations from {}; use .yaml, .yml or .toml",
+                path.display()
+            ),
+        }
+    }
+}
+
+impl Error for OperationError {}
+
+impl OperationRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Makes T's schema available to operations as `schema: <name>`.
+    pub fn register_schema<T: JsonSchema + 'static>(
+        &mut self,
+        name: impl Into<String>,
+    ) -> Result<(), Box<dyn Error>> {
+        self.schemas.insert(name.into(), DynSchema::of::<T>()?);
+        Ok(())
+    }
+
+    /// Loads the operations of a `.yaml`, `.yml` or `.toml` file, replacing
+    /// loaded operations of the same name.
+    pub fn load(&mut self, path: impl AsRef<Path>) -> Result<(), Box<dyn Error>> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path)?;
+        let base_dir = path.parent().unwrap_or(Path::new(""));
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("yaml" | "yml") => self.load_yaml(&text, base_dir),
+            Some("toml") => self.load_toml(&text, base_dir),
+            _ => Err(Box::new(OperationError::UnsupportedFormat {
+                path: path.to_path_buf(),
+            })),
+        }
+    }
+
+    /// Loads operations from YAML; `schema_file` paths are relative to `base_dir`.
+    pub fn load_yaml(&mut self, yaml: &str, base_dir: &Path) -> Result<(), Box<dyn Error>> {
+        let file: OperationsFile = serde_yaml::from_str(yaml)?;
+        self.insert_all(file.operations, base_dir)
+    }
+
+    /// Loads operations from TOML, one `[operations.<name>]` table each.
+    pub fn load_toml(&mut self, toml: &str, base_dir: &Path) -> Result<(), Box<dyn Error>> {
+        let file: OperationsFile = toml::from_str(toml)?;
+        self.insert_all(file.operations, base_dir)
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Operation> {
+        self.operations.get(name)
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.operations.keys().map(String::as_str)
+    }
+
+    /// Resolves every operation before inserting any, so a broken file
+    /// leaves the registry unchanged.
+    fn insert_all(
+        &mut self,
+        specs: BTreeMap<String, OperationSpec>,
+        base_dir: &Path,
+    ) -> Result<(), Box<dyn Error>> {
+        let operations = specs
+            .into_iter()
+            .map(|(name, spec)| self.resolve(name, spec, base_dir))
+            .collect::<Result<Vec<_>, _>>()?;
+        for operation in operations {
+            self.operations.insert(operation.name.clone(), operation);
+        }
+        Ok(())
+    }
+
+    fn resolve(
+        &self,
+        name: String,
+        spec: OperationSpec,
+        base_dir: &Path,
+    ) -> Result<Operation, Box<dyn Error>> {
+        let schema =
+            match (&spec.schema, &spec.schema_file) {
+                (Some(schema), None) => self.schemas.get(schema).cloned().ok_or_else(|| {
+                    OperationError::UnknownSchema {
+                        operation: name.clone(),
+                        schema: schema.clone(),
+                    }
+                })?,
+                (None, Some(file)) => {
+                    let path = base_dir.join(file);
+                    let schema: Value = serde_json::from_str(&std::fs::read_to_string(&path)?)?;
+                    let stem = path
+                        .file_stem()
+                        .and_then(|stem| stem.to_str())
+                        .unwrap_or(&name);
+                    DynSchema::new(stem, schema)
+                }
+                _ => {
+                    return Err(Box::new(OperationError::AmbiguousSchema {
+                        operation: name,
+                    }))
+                }
+            };
+
+        let mut options = RequestOptions::new().tag(name.clone());
+        if let Some(role) = spec.system_role {
+            options = options.system_role(role);
+        }
+        if let Some(temperature) = spec.temperature {
+            options = options.temperature(temperature);
+        }
+        if let Some(max_completion_tokens) = spec.max_completion_tokens {
+            options = options.max_completion_tokens(max_completion_tokens);
+        }
+        if let Some(seed) = spec.seed {
+            options = options.seed(seed);
+        }
+        Ok(Operation {
+            name,
+            prompt: PromptTemplate::new(spec.prompt),
+            schema: schema.with_strict(spec.strict),
+            model: spec.model,
+            options,
+            guard: spec.guard,
+        })
+    }
+}
+
+impl OpenAiClient {
+    /// Runs a registered operation: renders its prompt with `variables`,
+    /// applies its guard and calls the model with its schema and options.
+    pub async fn call_operation(
+        &self,
+        registry: &OperationRegistry,
+        name: &str,
+        variables: &BTreeMap<String, String>,
+    ) -> Result<Value, Box<dyn Error>> {
+        let operation = registry
+            .get(name)
+            .ok_or_else(|| OperationError::UnknownOperation {
+                name: name.to_string(),
+            })?;
+        let mut prompt = operation.prompt.render_map(variables)?;
+        if let Some(guard) = &operation.guard {
+            prompt = guard.apply(&prompt).0;
+        }
+        let client = match &operation.model {
+            Some(model) => Cow::Owned(self.clone().with_model(model)),
+            None => Cow::Borrowed(self),
+        };
+        client
+            .call_schema_value(&operation.schema, &prompt, &operation.options)
+            .await
+    }
+
+    /// Like `call_operation`, deserializing the output into T, such as the
+    /// type whose schema the operation was registered with.
+    pub async fn call_operation_as<T: DeserializeOwned>(
+        &self,
+        registry: &OperationRegistry,
+        name: &str,
+        variables: &BTreeMap<String, String>,
+    ) -> Result<T, Box<dyn Error>> {
+        let output = self.call_operation(registry, name, variables).await?;
+        Ok(serde_json::from_value(output)?)
+    }
+}
@@ -0,0 +1,225 @@
+use crate::content::ImagePart;
+use crate::context::PromptContext;
+use crate::dynamic::DynSchema;
+use crate::fallback::ContentParser;
+use crate::reasoning::ReasoningEffort;
+use crate::style::ResponseStyle;
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use tokio_util::sync::CancellationToken;
+
+/// The role of an instruction message. OpenAI's newer models rank developer
+/// messages below the platform's system messages; other providers only have
+/// a system role, which is used for both.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum InstructionRole {
+    #[default]
+    System,
+    Developer,
+}
+
+/// An instruction message sent before the user prompt.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Instruction {
+    pub role: InstructionRole,
+    pub content: String,
+}
+
+impl Instruction {
+    pub fn system(content: impl Into<String>) -> Self {
+        Self {
+            role: InstructionRole::System,
+            content: content.into(),
+        }
+    }
+
+    pub fn developer(content: impl Into<String>) -> Self {
+        Self {
+            role: InstructionRole::Developer,
+            content: content.into(),
+        }
+    }
+}
+
+/// Per-call sampling and request parameters, merged into the request body.
+/// Unset options are omitted so the API defaults apply.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct RequestOptions {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) temperature: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) top_p: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) max_completion_tokens: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) seed: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) reasoning_effort: Option<ReasoningEffort>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) stop: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) frequency_penalty: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) presence_penalty: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) user: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) store: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) logprobs: Option<bool>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub(crate) metadata: BTreeMap<String, String>,
+    #[serde(skip)]
+    pub(crate) tag: Option<String>,
+    #[serde(skip)]
+    pub(crate) images: Vec<ImagePart>,
+    #[serde(skip)]
+    pub(crate) parsers: Option<Vec<ContentParser>>,
+    #[serde(skip)]
+    pub(crate) context: Option<PromptContext>,
+    #[serde(skip)]
+    pub(crate) cancellation: Option<CancellationToken>,
+    #[serde(skip)]
+    pub(crate) output_schema: Option<DynSchema>,
+    #[serde(skip)]
+    pub(crate) system_role: Option<String>,
+    #[serde(skip)]
+    pub(crate) instructions: Option<Vec<Instruction>>,
+    #[serde(skip)]
+    pub(crate) style: Option<ResponseStyle>,
+}
+
+impl RequestOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn temperature(mut self, temperature: f32) -> Self {
+        self.temperature = Some(temperature);
+        self
+    }
+
+    pub fn top_p(mut self, top_p: f32) -> Self {
+        self.top_p = Some(top_p);
+        self
+    }
+
+    pub fn max_completion_tokens(mut self, max_completion_tokens: u32) -> Self {
+        self.max_completion_tokens = Some(max_completion_tokens);
+        self
+    }
+
+    pub fn seed(mut self, seed: i64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    /// How long a reasoning model thinks before answering; left out for other
+    /// models, which reject it.
+    pub fn reasoning_effort(mut self, effort: ReasoningEffort) -> Self {
+        self.reasoning_effort = Some(effort);
+        self
+    }
+
+    pub fn stop<S: Into<String>>(mut self, stop: impl IntoIterator<Item = S>) -> Self {
+        self.stop = Some(stop.into_iter().map(Into::into).collect());
+        self
+    }
+
+    pub fn frequency_penalty(mut self, frequency_penalty: f32) -> Self {
+        self.frequency_penalty = Some(frequency_penalty);
+        self
+    }
+
+    pub fn presence_penalty(mut self, presence_penalty: f32) -> Self {
+        self.presence_penalty = Some(presence_penalty);
+        self
+    }
+
+    /// End-user identifier forwarded to OpenAI for abuse monitoring.
+    pub fn user(mut self, user: impl Into<String>) -> Self {
+        self.user = Some(user.into());
+        self
+    }
+
+    /// Stores the completion with OpenAI for later retrieval, evals or distillation.
+    pub fn store(mut self, store: bool) -> Self {
+        self.store = Some(store);
+        self
+    }
+
+    /// Attaches a metadata key-value pair to the stored completion.
+    pub fn metadata(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.metadata.insert(key.into(), value.into());
+        self
+    }
+
+    /// Returns token logprobs, which fill `ResponseMeta::token_attribution`.
+    pub fn logprobs(mut self, logprobs: bool) -> Self {
+        self.logprobs = Some(logprobs);
+        self
+    }
+
+    /// Attaches an image to the user message, e.g. a receipt to extract from.
+    pub fn image(mut self, image: ImagePart) -> Self {
+        self.images.push(image);
+        self
+    }
+
+    /// Context block for this call instead of the client's, e.g. the time zone
+    /// and locale of the user the call is made for.
+    pub fn context(mut self, context: PromptContext) -> Self {
+        self.context = Some(context);
+        self
+    }
+
+    /// System role for this call instead of the client's `with_system_role`.
+    pub fn system_role(mut self, role: impl Into<String>) -> Self {
+        self.system_role = Some(role.into());
+        self
+    }
+
+    /// Leading instruction messages for this call instead of the client's
+    /// `with_instructions`.
+    pub fn instructions(mut self, instructions: impl IntoIterator<Item = Instruction>) -> Self {
+        self.instructions = Some(instructions.into_iter().collect());
+        self
+    }
+
+    /// Language, tone and reading level of the output's text, instructed and
+    /// checked on schema calls.
+    pub fn style(mut self, style: ResponseStyle) -> Self {
+        self.style = Some(style);
+        self
+    }
+
+    /// Parsers to try on this call's output instead of the client's, e.g.
+    /// `ContentParser::chain()` for an operation a weaker model handles.
+    pub fn parsers(mut self, parsers: impl IntoIterator<Item = ContentParser>) -> Self {
+        self.parsers = Some(parsers.into_iter().collect());
+        self
+    }
+
+    /// Client-side label used to group calls in `OpenAiClient::stats`; not sent to the API.
+    pub fn tag(mut self, tag: impl Into<String>) -> Self {
+        self.tag = Some(tag.into());
+        self
+    }
+
+    /// Aborts the call, including any waits and retries, once `token` is
+    /// cancelled; the call then fails with `Cancelled`.
+    pub fn cancellation(mut self, token: CancellationToken) -> Self {
+        self.cancellation = Some(token);
+        self
+    }
+
+    /// Merges the set options into the top level of the request body.
+    pub(crate) fn apply_to(&self, body: &mut Value) {
+        if let (Value::Object(body), Ok(Value::Object(options))) =
+            (body, serde_json::to_value(self))
+        {
+            body.extend(options);
+        }
+    }
+}
@@ -0,0 +1,213 @@
+//! OpenTelemetry log records for schema calls, one per call. Requires the
+//! `otel` feature.
+
+use crate::limits::SchemaLimitError;
+use crate::openai::{DryRun, OpenAIError, ParseError, Refusal, StructuredResponse};
+use crate::refusal::RefusalsExhausted;
+use opentelemetry::logs::{AnyValue, LogRecord, Logger, Severity};
+use opentelemetry::Key;
+use regex::Regex;
+use std::error::Error;
+use std::fmt;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+const EVENT_NAME: &str = "llm.call";
+const REDACTED: &str = "[REDACTED]";
+
+/// Emits a log record per `call_schema` call through an OpenTelemetry logger,
+/// with the model, schema name, operation (the call's `RequestOptions::tag`),
+/// outcome, error category, latency and token usage as attributes.
+///
+/// Prompts are not logged unless enabled with `with_prompts`. Logged prompts
+/// and error messages, which often echo the input, have every match of the
+/// redaction patterns replaced by `[REDACTED]`.
+#[derive(Clone)]
+pub struct CallLogger {
+    emit: Arc<dyn Fn(CallRecord) + Send + Sync>,
+    log_prompts: bool,
+    redactions: Vec<Regex>,
+}
+
+struct CallRecord {
+    severity: Severity,
+    attributes: Vec<(Key, AnyValue)>,
+    body: Option<String>,
+}
+
+impl CallLogger {
+    /// Logs through `logger`, e.g. one obtained from the SDK logger provider
+    /// with `provider.logger("openai-structured-client")`.
+    pub fn new<L>(logger: L) -> Self
+    where
+        L: Logger + Send + Sync + 'static,
+    {
+        let emit = move |record: CallRecord| {
+            if !logger.event_enabled(record.severity, EVENT_NAME, Some(EVENT_NAME)) {
+                return;
+            }
+            let mut log = logger.create_log_record();
+            log.set_event_name(EVENT_NAME);
+            log.set_timestamp(SystemTime::now());
+            log.set_severity_number(record.severity);
+            log.set_severity_text(record.severity.name());
+            if let Some(body) = record.body {
+                log.set_body(body.into());
+            }
+            log.add_attributes(record.attributes);
+            logger.emit(log);
+        };
+        Self {
+            emit: Arc::new(emit),
+            log_prompts: false,
+            redactions: Vec::new(),
+        }
+    }
+
+    /// Includes the user prompt as the record body.
+    pub fn with_prompts(mut self, log_prompts: bool) -> Self {
+        self.log_prompts = log_prompts;
+        self
+    }
+
+    /// Replaces matches of `pattern` in logged prompts and error messages, e.g.
+    /// e-mail addresses.
+    pub fn with_redaction(mut self, pattern: Regex) -> Self {
+        self.redactions.push(pattern);
+        self
+    }
+
+    pub(crate) fn log<T>(
+        &self,
+        model: &str,
+        schema: &str,
+        operation: Option<&str>,
+        prompt: &str,
+        result: &Result<StructuredResponse<T>, Box<dyn Error>>,
+        latency: Duration,
+    ) {
+        let mut attributes = vec![
+            (
+                Key::from("gen_ai.request.model"),
+                AnyValue::from(model.to_string()),
+            ),
+            (Key::from("llm.schema"), schema.to_string().into()),
+            (
+                Key::from("llm.latency_ms"),
+                (latency.as_millis() as i64).into(),
+            ),
+        ];
+        if let Some(operation) = operation {
+            attributes.push((Key::from("llm.operation"), operation.to_string().into()));
+        }
+        let severity = match result {
+            Ok(response) => {
+                let meta = &response.meta;
+                attributes.push((Key::from("llm.outcome"), "success".into()));
+                attributes.push((Key::from("llm.cached"), meta.cached.into()));
+                attributes.push((
+                    Key::from("llm.repair_attempts"),
+                    i64::from(meta.repair_attempts).into(),
+                ));
+                if let Some(usage) = &meta.usage {
+                    attributes.push((
+                        Key::from("gen_ai.usage.input_tokens"),
+                        i64::from(usage.prompt_tokens).into(),
+                    ));
+                    attributes.push((
+                        Key::from("gen_ai.usage.output_tokens"),
+                        i64::from(usage.completion_tokens).into(),
+                    ));
+                }
+                Severity::Info
+            }
+            Err(err) => {
+                let category = ErrorCategory::of(err.as_ref());
+                let outcome = match category {
+                    ErrorCategory::Refusal => "refusal",
+                    _ => "error",
+                };
+                attributes.push((Key::from("llm.outcome"), outcome.into()));
+                attributes.push((Key::from("error.type"), category.to_string().into()));
+                let message = self.redact(&err.to_string());
+                attributes.push((Key::from("error.message"), message.into()));
+                match category {
+                    ErrorCategory::Refusal => Severity::Warn,
+                    _ => Severity::Error,
+                }
+            }
+        };
+        let body = self.log_prompts.then(|| self.redact(prompt));
+        (self.emit)(CallRecord {
+            severity,
+            attributes,
+            body,
+        });
+    }
+
+    fn redact(&self, text: &str) -> String {
+        self.redactions
+            .iter()
+            .fold(text.to_string(), |text, pattern| {
+                pattern.replace_all(&text, REDACTED).into_owned()
+            })
+    }
+}
+
+impl fmt::Debug for CallLogger {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CallLogger")
+            .field("log_prompts", &self.log_prompts)
+            .field("redactions", &self.redactions)
+            .finish()
+    }
+}
+
+/// Coarse classification of a failed call, logged as `error.type`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorCategory {
+    Refusal,
+    Parse,
+    SchemaLimit,
+    /// An error response from the API.
+    Api,
+    /// A connection, timeout or body decoding failure.
+    Http,
+    DryRun,
+    Other,
+}
+
+impl ErrorCategory {
+    pub fn of(err: &(dyn Error + 'static)) -> Self {
+        if err.is::<Refusal>() || err.is::<RefusalsExhausted>() {
+            ErrorCategory::Refusal
+        } else if err.is::<ParseError>() {
+            ErrorCategory::Parse
+        } else if err.is::<SchemaLimitError>() {
+            ErrorCategory::SchemaLimit
+        } else if err.is::<OpenAIError>() {
+            ErrorCategory::Api
+        } else if err.is::<reqwest::Error>() {
+            ErrorCategory::Http
+        } else if err.is::<DryRun>() {
+            ErrorCategory::DryRun
+        } else {
+            ErrorCategory::Other
+        }
+    }
+}
+
+impl fmt::Display for ErrorCategory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ErrorCategory::Refusal => "refusal",
+            ErrorCategory::Parse => "parse",
+            ErrorCategory::SchemaLimit => "schema_limit",
+            ErrorCategory::Api => "api",
+            ErrorCategory::Http => "http",
+            ErrorCategory::DryRun => "dry_run",
+            ErrorCategory::Other => "other",
+        };
+        f.write_str(name)
+    }
+}
@@ -0,0 +1,188 @@
+use crate::openai::{OpenAiClient, ParseError};
+use crate::options::RequestOptions;
+use schemars::JsonSchema;
+use serde::de::DeserializeOwned;
+use serde_json::{Map, Value};
+use std::error::Error;
+use std::fmt;
+use std::marker::PhantomData;
+
+/// Best-effort view of a `T` whose full parse failed: every top-level field is
+/// optional. Fields that were absent or did not match the schema are listed.
+pub struct Partial<T> {
+    pub fields: Map<String, Value>,
+    pub missing: Vec<String>,
+    pub invalid: Vec<String>,
+    _marker: PhantomData<T>,
+}
+
+impl<T> Partial<T> {
+    /// Deserializes a single valid field, or `None` if it is missing, invalid or
+    /// not of type `V`.
+    pub fn get<V: DeserializeOwned>(&self, field: &str) -> Option<V> {
+        if self.invalid.iter().any(|f| f == field) {
+            return None;
+        }
+        serde_json::from_value(self.fields.get(field)?.clone()).ok()
+    }
+
+    pub fn is_valid(&self, field: &str) -> bool {
+        self.fields.contains_key(field) && !self.invalid.iter().any(|f| f == field)
+    }
+}
+
+impl<T> fmt::Debug for Partial<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Partial")
+            .field("fields", &self.fields)
+            .field("missing", &self.missing)
+            .field("invalid", &self.invalid)
+            .finish()
+    }
+}
+
+impl<T> Clone for Partial<T> {
+    fn clone(&self) -> Self {
+        Self {
+            fields: self.fields.clone(),
+            missing: self.missing.clone(),
+            invalid: self.invalid.clone(),
+            _marker: PhantomData,
+        }
+    }
+}
+
+/// Result of a degrade-mode call.
+#[derive(Debug, Clone)]
+pub enum Degraded<T> {
+    Complete(T),
+    Partial(Partial<T>),
+}
+
+impl OpenAiClient {
+    /// Like `call_schema_with`, but if the output still fails to parse after any
+    /// configured repairs, returns the valid subset of fields instead of an error.
+    pub async fn call_schema_degraded<T: DeserializeOwned + JsonSchema + Clone + 'static>(
+        &self,
+        user_prompt: &str,
+        options: &RequestOptions,
+    ) -> Result<Degraded<T>, Box<dyn Error>> {
+        match self.call_schema_with::<T>(user_prompt, options).await {
+            Ok(value) => Ok(Degraded::Complete(value)),
+            Err(err) => match err.downcast_ref::<ParseError>() {
+                Some(parse_error) => Ok(Degraded::Partial(Partial::from_raw(&parse_error.raw)?)),
+                None => Err(err),
+            },
+        }
+    }
+}
+
+impl<T: JsonSchema + 'static> Partial<T> {
+    /// Builds the partial view of raw model output against T's strict schema.
+    pub fn from_raw(raw: &str) -> Result<Self, Box<dyn Error>> {
+        let schema = OpenAiClient::generate_schema::<T>()?;
+        let definitions = schema.get("definitions").and_then(Value::as_object);
+        let fields = match serde_json::from_str::<Value>(raw) {
+            Ok(Value::Object(fields)) => fields,
+            _ => Map::new(),
+        };
+
+        let mut partial = Partial {
+            fields,
+            missing: Vec::new(),
+            invalid: Vec::new(),
+            _marker: PhantomData,
+        };
+        let properties = schema.get("properties").and_then(Value::as_object);
+        for (name, property) in properties.into_iter().flatten() {
+            match partial.fields.get(name) {
+                None => partial.missing.push(name.clone()),
+                Some(value) if !conforms(value, property, definitions) => {
+                    partial.invalid.push(name.clone())
+                }
+                Some(_) => {}
+            }
+        }
+        Ok(partial)
+    }
+}
+
+/// Structural check of a value against a (schemars-generated) JSON schema.
+pub(crate) fn conforms(
+    value: &Value,
+    schema: &Value,
+    definitions: Option<&Map<String, Value>>,
+) -> bool {
+    let schema = match schema
+        .get("$ref")
+        .and_then(Value::as_str)
+        .and_then(|r| r.strip_prefix("#/definitions/"))
+        .and_then(|name| definitions?.get(name))
+    {
+        Some(resolved) => resolved,
+        None => schema,
+    };
+
+    if let Some(Value::Array(variants)) = schema.get("anyOf").or_else(|| schema.get("oneOf")) {
+        return variants.iter().any(|v| conforms(value, v, definitions));
+    }
+    if let Some(Value::Array(parts)) = schema.get("allOf") {
+        return parts.iter().all(|p| conforms(value, p, definitions));
+    }
+    if let Some(Value::Array(options)) = schema.get("enum") {
+        return options.contains(value);
+    }
+    if let Some(constant) = schema.get("const") {
+        return constant == value;
+    }
+
+    let type_matches = match schema.get("type") {
+        Some(Value::String(ty)) => matches_type(value, ty),
+        Some(Value::Array(types)) => types
+            .iter()
+            .filter_map(Value::as_str)
+            .any(|ty| matches_type(value, ty)),
+        _ => true,
+    };
+    if !type_matches {
+        return false;
+    }
+
+    match value {
+        Value::Array(items) => match schema.get("items") {
+            Some(item_schema) => items.iter().all(|i| conforms(i, item_schema, definitions)),
+            None => true,
+        },
+        Value::Object(object) => {
+            let properties = schema.get("properties").and_then(Value::as_object);
+            let required_present = schema
+                .get("required")
+                .and_then(Value::as_array)
+                .into_iter()
+                .flatten()
+                .filter_map(Value::as_str)
+                .all(|name| object.contains_key(name));
+            required_present
+                && object
+                    .iter()
+                    .all(|(name, field)| match properties.and_then(|p| p.get(name)) {
+                        Some(field_schema) => conforms(field, field_schema, definitions),
+                        None => schema.get("additionalProperties") != Some(&Value::Bool(false)),
+                    })
+        }
+        _ => true,
+    }
+}
+
+fn matches_type(value: &Value, ty: &str) -> bool {
+    match ty {
+        "null" => value.is_null(),
+        "boolean" => value.is_boolean(),
+        "string" => value.is_string(),
+        "integer" => value.is_i64() || value.is_u64(),
+        "number" => value.is_number(),
+        "array" => value.is_array(),
+        "object" => value.is_object(),
+        _ => true,
+    }
+}
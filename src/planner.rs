@@ -0,0 +1,220 @@
+use crate::openai::OpenAiClient;
+use crate::options::RequestOptions;
+use crate::tokens;
+use schemars::JsonSchema;
+use serde_json::{Map, Value};
+use std::error::Error;
+
+/// Maximum completion tokens for known model families, matched by prefix.
+/// More specific prefixes come first.
+const COMPLETION_LIMITS: &[(&str, u32)] = &[
+    ("gpt-4o-mini", 16_384),
+    ("gpt-4o", 16_384),
+    ("gpt-4.1", 32_768),
+    ("gpt-4-turbo", 4_096),
+    ("gpt-4", 8_192),
+    ("gpt-3.5-turbo", 4_096),
+    ("o1-mini", 65_536),
+    ("o1", 100_000),
+    ("o3", 100_000),
+    ("o4-mini", 100_000),
+];
+
+/// Nesting depth after which recursive schemas stop being expanded.
+const MAX_DEPTH: usize = 8;
+
+/// Estimates how many completion tokens a schema's output will take and picks
+/// `max_completion_tokens` with a safety margin.
+///
+/// String fields are sized from `maxLength` (or `default_string_tokens`), arrays
+/// from `maxItems`/`minItems` (or `default_array_items`).
+#[derive(Debug, Clone)]
+pub struct TokenPlanner {
+    pub default_string_tokens: u32,
+    pub default_array_items: u32,
+    pub safety_margin: f32,
+    /// Completion limit of the target model, if known.
+    pub completion_limit: Option<u32>,
+    /// Target model, whose tokenizer counts field names and enum values.
+    pub model: Option<String>,
+}
+
+impl Default for TokenPlanner {
+    fn default() -> Self {
+        Self {
+            default_string_tokens: 32,
+            default_array_items: 5,
+            safety_margin: 1.5,
+            completion_limit: None,
+            model: None,
+        }
+    }
+}
+
+/// The outcome of planning a schema.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TokenPlan {
+    pub estimated_tokens: u32,
+    pub max_completion_tokens: u32,
+    /// False when the planned budget exceeds the model's completion limit.
+    pub fits: bool,
+    pub warnings: Vec<String>,
+}
+
+impl TokenPlan {
+    /// Sets `max_completion_tokens` on the options to the planned budget.
+    pub fn apply(&self, options: RequestOptions) -> RequestOptions {
+        options.max_completion_tokens(self.max_completion_tokens)
+    }
+}
+
+impl TokenPlanner {
+    /// A planner using the completion limit of a known model family.
+    pub fn for_model(model: &str) -> Self {
+        Self {
+            completion_limit: completion_limit(model),
+            model: Some(model.to_string()),
+            ..Self::default()
+        }
+    }
+
+    pub fn plan<T: JsonSchema + 'static>(&self) -> Result<TokenPlan, Box<dyn Error>> {
+        let schema = OpenAiClient::generate_schema::<T>()?;
+        Ok(self.plan_schema(&schema))
+    }
+
+    pub fn plan_schema(&self, schema: &Value) -> TokenPlan {
+        let mut warnings = Vec::new();
+        let definitions = schema.get("definitions").and_then(Value::as_object);
+        let estimated = self.estimate(schema, definitions, 0, &mut warnings);
+        let estimated_tokens = estimated.min(u64::from(u32::MAX)) as u32;
+        let planned = (estimated_tokens as f32 * self.safety_margin).ceil() as u32;
+
+        let (max_completion_tokens, fits) = match self.completion_limit {
+            Some(limit) if planned > limit => {
+                warnings.push(format!(
+                    "planned {} tokens (estimate {}) exceed the model's completion limit of {}",
+                    planned, estimated_tokens, limit
+                ));
+                (limit, estimated_tokens <= limit)
+            }
+            _ => (planned, true),
+        };
+
+        TokenPlan {
+            estimated_tokens,
+            max_completion_tokens,
+            fits,
+            warnings,
+        }
+    }
+
+    /// Tokens of a short text, at least one.
+    fn text_tokens(&self, text: &str) -> u64 {
+        let model = self.model.as_deref().unwrap_or_default();
+        u64::from(tokens::count_tokens(model, text).max(1))
+    }
+
+    fn estimate(
+        &self,
+        schema: &Value,
+        definitions: Option<&Map<String, Value>>,
+        depth: usize,
+        warnings: &mut Vec<String>,
+    ) -> u64 {
+        if depth > MAX_DEPTH {
+            let warning = format!(
+                "schema nests deeper than {} levels; deeper levels are not counted",
+                MAX_DEPTH
+            );
+            if !warnings.contains(&warning) {
+                warnings.push(warning);
+            }
+            return 0;
+        }
+
+        if let Some(name) = schema
+            .get("$ref")
+            .and_then(Value::as_str)
+            .and_then(|r| r.strip_prefix("#/definitions/"))
+        {
+            return match definitions.and_then(|d| d.get(name)) {
+                Some(resolved) => self.estimate(resolved, definitions, depth + 1, warnings),
+                None => 0,
+            };
+        }
+
+        for key in ["anyOf", "oneOf"] {
+            if let Some(Value::Array(variants)) = schema.get(key) {
+                return variants
+                    .iter()
+                    .map(|v| self.estimate(v, definitions, depth, warnings))
+                    .max()
+                    .unwrap_or(0);
+            }
+        }
+        if let Some(Value::Array(parts)) = schema.get("allOf") {
+            return parts
+                .iter()
+                .map(|p| self.estimate(p, definitions, depth, warnings))
+                .sum();
+        }
+        if let Some(Value::Array(options)) = schema.get("enum") {
+            return options
+                .iter()
+                .map(|o| self.text_tokens(&o.to_string()))
+                .max()
+                .unwrap_or(1);
+        }
+
+        let ty = match schema.get("type") {
+            Some(Value::String(ty)) => ty.as_str(),
+            // Nullable unions such as ["string", "null"]: size the non-null type.
+            Some(Value::Array(types)) => types
+                .iter()
+                .filter_map(Value::as_str)
+                .find(|ty| *ty != "null")
+                .unwrap_or("null"),
+            _ => "object",
+        };
+        match ty {
+            "string" => match schema.get("maxLength").and_then(Value::as_u64) {
+                Some(max_length) => max_length.div_ceil(4) + 2,
+                None => u64::from(self.default_string_tokens),
+            },
+            "integer" | "number" => 3,
+            "boolean" | "null" => 1,
+            "array" => {
+                let items = schema
+                    .get("maxItems")
+                    .or_else(|| schema.get("minItems"))
+                    .and_then(Value::as_u64)
+                    .unwrap_or(u64::from(self.default_array_items));
+                let item = schema
+                    .get("items")
+                    .map_or(1, |i| self.estimate(i, definitions, depth + 1, warnings));
+                2 + items * (item + 1)
+            }
+            _ => {
+                let properties = schema.get("properties").and_then(Value::as_object);
+                2 + properties
+                    .into_iter()
+                    .flatten()
+                    .map(|(name, property)| {
+                        self.text_tokens(name)
+                            + 2
+                            + self.estimate(property, definitions, depth + 1, warnings)
+                    })
+                    .sum::<u64>()
+            }
+        }
+    }
+}
+
+/// Looks up the completion limit of a known model family.
+pub fn completion_limit(model: &str) -> Option<u32> {
+    COMPLETION_LIMITS
+        .iter()
+        .find(|(prefix, _)| model.starts_with(prefix))
+        .map(|(_, limit)| *limit)
+}
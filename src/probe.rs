@@ -0,0 +1,227 @@
+use crate::fallback::ContentParser;
+use crate::openai::{OpenAiClient, OutputMode, StageTimings};
+use crate::transport::HttpResponse;
+use reqwest::header::CONTENT_TYPE;
+use reqwest::StatusCode;
+use serde_json::{json, Value};
+use std::error::Error;
+use std::fmt;
+
+/// Statuses with which servers reject request fields they don't support.
+const UNSUPPORTED_STATUSES: [StatusCode; 6] = [
+    StatusCode::BAD_REQUEST,
+    StatusCode::NOT_FOUND,
+    StatusCode::METHOD_NOT_ALLOWED,
+    StatusCode::UNSUPPORTED_MEDIA_TYPE,
+    StatusCode::UNPROCESSABLE_ENTITY,
+    StatusCode::NOT_IMPLEMENTED,
+];
+
+/// What an endpoint supports, as detected by `OpenAiClient::probe`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Capabilities {
+    /// Structured outputs (`response_format` of type `json_schema`).
+    pub json_schema: bool,
+    /// JSON mode (`response_format` of type `json_object`).
+    pub json_object: bool,
+    pub tools: bool,
+    /// Server-sent event streams (`stream: true`).
+    pub streaming: bool,
+}
+
+impl Capabilities {
+    /// The strictest output mode the endpoint supports.
+    pub fn output_mode(&self) -> OutputMode {
+        if self.json_schema {
+            OutputMode::JsonSchema
+        } else if self.json_object {
+            OutputMode::JsonObject
+        } else {
+            OutputMode::Prompted
+        }
+    }
+}
+
+/// A probe request failed for a reason other than an unsupported feature,
+/// such as a wrong API key, so the capability is unknown.
+#[derive(Debug, Clone)]
+pub struct ProbeError {
+    pub capability: &'static str,
+    pub status: u16,
+    pub body: String,
+}
+
+impl fmt::Display for ProbeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "probing {} failed with status {}: {}",
+            self.capability, self.status, self.body
+        )
+    }
+}
+
+impl Error for ProbeError {}
+
+impl OpenAiClient {
+    /// Detects what the endpoint supports with one tiny request per
+    /// capability. A capability is unsupported when its request is rejected
+    /// with a client error (400, 404, 405, 415, 422) or 501, or when the
+    /// server ignores the field and answers in the wrong shape.
+    ///
+    /// Only OpenAI-compatible chat completions endpoints are probed; other
+    /// providers report every capability, as their translations only use what
+    /// they support.
+    pub async fn probe(&self) -> Result<Capabilities, Box<dyn Error>> {
+        if !self.speaks_chat_completions() {
+            return Ok(Capabilities {
+                json_schema: true,
+                json_object: true,
+                tools: true,
+                streaming: true,
+            });
+        }
+        let model = self.model();
+        let json_schema = json!({
+            "model": model,
+            "messages": [{ "role": "user", "content": "Reply with ok set to true." }],
+            "response_format": {
+                "type": "json_schema",
+                "json_schema": {
+                    "name": "probe",
+                    "strict": true,
+                    "schema": {
+                        "type": "object",
+                        "properties": { "ok": { "type": "boolean" } },
+                        "required": ["ok"],
+                        "additionalProperties": false
+                    }
+                }
+            }
+        });
+        let json_object = json!({
+            "model": model,
+            "messages": [{ "role": "user", "content": "Reply with the JSON object {\"ok\": true}." }],
+            "response_format": { "type": "json_object" }
+        });
+        let tools = json!({
+            "model": model,
+            "messages": [{ "role": "user", "content": "Call the probe tool." }],
+            "tools": [{
+                "type": "function",
+                "function": {
+                    "name": "probe",
+                    "parameters": { "type": "object", "properties": {} }
+                }
+            }],
+            "tool_choice": "required"
+        });
+        let streaming = json!({
+            "model": model,
+            "messages": [{ "role": "user", "content": "Reply with ok." }],
+            "stream": true
+        });
+
+        Ok(Capabilities {
+            json_schema: self
+                .probe_one("json_schema", &json_schema, |message| {
+                    content_json(message).is_some_and(|output| output["ok"].is_boolean())
+                })
+                .await?,
+            json_object: self
+                .probe_one("json_object", &json_object, |message| {
+                    content_json(message).is_some_and(|output| output.is_object())
+                })
+                .await?,
+            tools: self
+                .probe_one("tools", &tools, |message| {
+                    message["tool_calls"]
+                        .as_array()
+                        .is_some_and(|calls| !calls.is_empty())
+                })
+                .await?,
+            streaming: self.probe_streaming(&streaming).await?,
+        })
+    }
+
+    /// Configures the client for what the endpoint supports: the strictest
+    /// output mode, and every content parser for modes other than
+    /// `OutputMode::JsonSchema` unless parsers were set explicitly.
+    pub fn with_capabilities(self, capabilities: &Capabilities) -> Self {
+        let mode = capabilities.output_mode();
+        let client = self.with_output_mode(mode);
+        if mode != OutputMode::JsonSchema && client.parsers() == [ContentParser::Json] {
+            client.with_parsers(ContentParser::chain())
+        } else {
+            client
+        }
+    }
+
+    /// Probes the endpoint and configures the client for it, e.g. once at
+    /// startup against an unknown OpenAI-compatible server.
+    pub async fn with_probe(self) -> Result<Self, Box<dyn Error>> {
+        let capabilities = self.probe().await?;
+        Ok(self.with_capabilities(&capabilities))
+    }
+
+    /// Sends one probe, judging a successful response by its message.
+    async fn probe_one(
+        &self,
+        capability: &'static str,
+        body: &Value,
+        supported: impl Fn(&Value) -> bool,
+    ) -> Result<bool, Box<dyn Error>> {
+        let Some(response) = self.send_probe(capability, body).await? else {
+            return Ok(false);
+        };
+        let raw: Value = response.json().await.unwrap_or(Value::Null);
+        Ok(supported(&raw["choices"][0]["message"]))
+    }
+
+    async fn probe_streaming(&self, body: &Value) -> Result<bool, Box<dyn Error>> {
+        let Some(response) = self.send_probe("streaming", body).await? else {
+            return Ok(false);
+        };
+        let event_stream = response
+            .headers
+            .get(CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|value| value.starts_with("text/event-stream"));
+        let body = response
+            .bytes()
+            .await
+            .map_err(|err| err as Box<dyn Error>)?;
+        Ok(event_stream || body.starts_with(b"data:"))
+    }
+
+    /// The successful response to a probe, or `None` if the server rejected
+    /// the probed feature.
+    async fn send_probe(
+        &self,
+        capability: &'static str,
+        body: &Value,
+    ) -> Result<Option<HttpResponse>, Box<dyn Error>> {
+        let response = self.send(body, &mut StageTimings::default()).await?;
+        if response.status.is_success() {
+            return Ok(Some(response));
+        }
+        if UNSUPPORTED_STATUSES.contains(&response.status) {
+            return Ok(None);
+        }
+        let status = response.status.as_u16();
+        let body = response
+            .bytes()
+            .await
+            .map_err(|err| err as Box<dyn Error>)?;
+        let body = String::from_utf8_lossy(&body).into_owned();
+        Err(Box::new(ProbeError {
+            capability,
+            status,
+            body,
+        }))
+    }
+}
+
+fn content_json(message: &Value) -> Option<Value> {
+    serde_json::from_str(message["content"].as_str()?).ok()
+}
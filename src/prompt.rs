@@ -0,0 +1,178 @@
+use crate::openai::OpenAiClient;
+use regex::Regex;
+use schemars::JsonSchema;
+use serde::de::DeserializeOwned;
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt;
+use std::sync::OnceLock;
+
+pub use openai_structured_client_derive::PromptVariables;
+
+/// Variables of a prompt template, usually derived with
+/// `#[derive(PromptVariables)]` so that they are validated when built.
+pub trait PromptVariables {
+    /// Names of all variables, set or not.
+    const NAMES: &'static [&'static str];
+
+    /// Names and rendered values of the set variables.
+    fn variables(&self) -> Vec<(&'static str, String)>;
+}
+
+/// A prompt with `{name}` or `{{name}}` placeholders. Braces around anything
+/// other than a plain identifier, such as JSON examples, are left as they are.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PromptTemplate {
+    template: String,
+}
+
+impl PromptTemplate {
+    pub fn new(template: impl Into<String>) -> Self {
+        Self {
+            template: template.into(),
+        }
+    }
+
+    /// Fills in the placeholders. Unset optional variables render as empty;
+    /// placeholders that are not variables of `V` are an error.
+    pub fn render<V: PromptVariables>(&self, variables: &V) -> Result<String, PromptError> {
+        let values = variables.variables();
+        self.fill(|name| {
+            V::NAMES.contains(&name).then(|| {
+                values
+                    .iter()
+                    .find(|(variable, _)| *variable == name)
+                    .map(|(_, value)| value.clone())
+                    .unwrap_or_default()
+            })
+        })
+    }
+
+    /// Fills in the placeholders from a map, for templates that are not known
+    /// at compile time; placeholders missing from the map are an error.
+    pub fn render_map(&self, values: &BTreeMap<String, String>) -> Result<String, PromptError> {
+        self.fill(|name| values.get(name).cloned())
+    }
+
+    /// Replaces each placeholder with `value(name)`, failing on the first
+    /// placeholder without one.
+    fn fill(&self, value: impl Fn(&str) -> Option<String>) -> Result<String, PromptError> {
+        let mut unknown = None;
+        let rendered = placeholder().replace_all(&self.template, |captures: &regex::Captures| {
+            let name = captures.get(1).or(captures.get(2)).unwrap().as_str();
+            value(name).unwrap_or_else(|| {
+                unknown.get_or_insert_with(|| name.to_string());
+                String::new()
+            })
+        });
+        match unknown {
+            Some(variable) => Err(PromptError::Unknown { variable }),
+            None => Ok(rendered.into_owned()),
+        }
+    }
+}
+
+fn placeholder() -> &'static Regex {
+    static PLACEHOLDER: OnceLock<Regex> = OnceLock::new();
+    PLACEHOLDER.get_or_init(|| {
+        Regex::new(r"\{\{\s*([A-Za-z_][A-Za-z0-9_]*)\s*\}\}|\{([A-Za-z_][A-Za-z0-9_]*)\}").unwrap()
+    })
+}
+
+impl OpenAiClient {
+    /// Renders `template` with `variables` and calls `call_schema` with the
+    /// result. A template error fails the call before anything is sent.
+    pub async fn call_schema_templated<T, V>(
+        &self,
+        template: &PromptTemplate,
+        variables: &V,
+    ) -> Result<T, Box<dyn Error>>
+    where
+        T: DeserializeOwned + JsonSchema + Clone + 'static,
+        V: PromptVariables,
+    {
+        let prompt = template.render(variables)?;
+        self.call_schema(&prompt).await
+    }
+}
+
+/// A prompt variable was missing or invalid, or the template used one that
+/// does not exist.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PromptError {
+    Missing {
+        variable: &'static str,
+    },
+    TooLong {
+        variable: &'static str,
+        max_len: usize,
+        len: usize,
+    },
+    NotAllowed {
+        variable: &'static str,
+        value: String,
+        allowed: Vec<&'static str>,
+    },
+    Unknown {
+        variable: String,
+    },
+}
+
+impl fmt::Display for PromptError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PromptError::Missing { variable } => {
+                write!(f, "Prompt variable `{}` is required", variable)
+            }
+            PromptError::TooLong {
+                variable,
+                max_len,
+                len,
+            } => write!(
+                f,
+                "Prompt variable `{}` is {} characters long, more than {}",
+                variable, len, max_len
+            ),
+            PromptError::NotAllowed {
+                variable,
+                value,
+                allowed,
+            } => write!(
+                f,
+                "Prompt variable `{}` is {:?}, not one of {:?}",
+                variable, value, allowed
+            ),
+            PromptError::Unknown { variable } => {
+                write!(f, "Prompt template uses unknown variable `{}`", variable)
+            }
+        }
+    }
+}
+
+impl std::error::Error for PromptError {}
+
+/// Validates a rendered variable; called by the derived builders.
+#[doc(hidden)]
+pub fn check_variable(
+    variable: &'static str,
+    value: &str,
+    max_len: Option<usize>,
+    one_of: &[&'static str],
+) -> Result<(), PromptError> {
+    let len = value.chars().count();
+    if let Some(max_len) = max_len.filter(|max| len > *max) {
+        return Err(PromptError::TooLong {
+            variable,
+            max_len,
+            len,
+        });
+    }
+    if !one_of.is_empty() && !one_of.contains(&value) {
+        return Err(PromptError::NotAllowed {
+            variable,
+            value: value.to_string(),
+            allowed: one_of.to_vec(),
+        });
+    }
+    Ok(())
+}
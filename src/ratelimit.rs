@@ -0,0 +1,129 @@
+use crate::time::{self, Instant};
+use crate::tokens;
+use reqwest::header::HeaderMap;
+use serde_json::Value;
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Client-side token-bucket limiter for requests and tokens per minute.
+///
+/// Each request reserves its estimated token cost (prompt plus the requested
+/// completion budget) before it is sent and waits while either bucket is empty.
+/// Actual usage is settled afterwards, and when the server sends
+/// `x-ratelimit-remaining-*` headers the buckets are lowered to match them.
+#[derive(Debug)]
+pub(crate) struct RateLimiter {
+    buckets: Mutex<Buckets>,
+}
+
+#[derive(Debug)]
+struct Buckets {
+    requests: Bucket,
+    tokens: Bucket,
+}
+
+#[derive(Debug)]
+struct Bucket {
+    capacity: f64,
+    available: f64,
+    per_second: f64,
+    updated: Instant,
+}
+
+impl Bucket {
+    fn new(per_minute: u32) -> Self {
+        let capacity = f64::from(per_minute.max(1));
+        Self {
+            capacity,
+            available: capacity,
+            per_second: capacity / 60.0,
+            updated: Instant::now(),
+        }
+    }
+
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now.duration_since(self.updated).as_secs_f64();
+        self.available = (self.available + elapsed * self.per_second).min(self.capacity);
+        self.updated = now;
+    }
+
+    /// Time until `amount` is available; zero if it already is.
+    fn wait_for(&self, amount: f64) -> Duration {
+        let missing = amount.min(self.capacity) - self.available;
+        if missing <= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(missing / self.per_second)
+        }
+    }
+}
+
+impl RateLimiter {
+    pub(crate) fn new(requests_per_minute: u32, tokens_per_minute: u32) -> Self {
+        Self {
+            buckets: Mutex::new(Buckets {
+                requests: Bucket::new(requests_per_minute),
+                tokens: Bucket::new(tokens_per_minute),
+            }),
+        }
+    }
+
+    /// Waits until one request and `tokens` tokens are available, then takes them.
+    pub(crate) async fn acquire(&self, tokens: u32) {
+        let tokens = f64::from(tokens);
+        loop {
+            let wait = {
+                let mut buckets = self.buckets.lock().unwrap();
+                let now = Instant::now();
+                buckets.requests.refill(now);
+                buckets.tokens.refill(now);
+                let wait = buckets
+                    .requests
+                    .wait_for(1.0)
+                    .max(buckets.tokens.wait_for(tokens));
+                if wait.is_zero() {
+                    buckets.requests.available -= 1.0;
+                    buckets.tokens.available -= tokens.min(buckets.tokens.capacity);
+                    return;
+                }
+                wait
+            };
+            time::sleep(wait).await;
+        }
+    }
+
+    /// Corrects the token bucket once the actual usage of a request is known.
+    pub(crate) fn settle(&self, estimated: u32, actual: u32) {
+        let mut buckets = self.buckets.lock().unwrap();
+        buckets.tokens.available -= f64::from(actual) - f64::from(estimated);
+    }
+
+    /// Lowers the buckets to the remaining budget reported by the server.
+    pub(crate) fn sync(&self, headers: &HeaderMap) {
+        let remaining = |name: &str| {
+            headers
+                .get(name)
+                .and_then(|v| v.to_str().ok())
+                .and_then(|v| v.trim().parse::<f64>().ok())
+        };
+        let mut buckets = self.buckets.lock().unwrap();
+        if let Some(requests) = remaining("x-ratelimit-remaining-requests") {
+            buckets.requests.available = buckets.requests.available.min(requests);
+        }
+        if let Some(tokens) = remaining("x-ratelimit-remaining-tokens") {
+            buckets.tokens.available = buckets.tokens.available.min(tokens);
+        }
+    }
+}
+
+/// Estimated token cost of a request body: its tokens as counted for the
+/// model, plus the completion budget, which the API counts against the limit.
+pub(crate) fn estimate_tokens(body: &Value) -> u32 {
+    let model = body["model"].as_str().unwrap_or_default();
+    let prompt = u64::from(tokens::count_tokens(model, &body.to_string()));
+    let completion = ["max_completion_tokens", "max_tokens", "max_output_tokens"]
+        .iter()
+        .find_map(|field| body[*field].as_u64())
+        .unwrap_or(0);
+    (prompt + completion).min(u64::from(u32::MAX)) as u32
+}
@@ -0,0 +1,284 @@
+use crate::openai::{OpenAiClient, ParseError};
+use crate::transport::{self, InsecureEndpoint};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use futures::{SinkExt, StreamExt};
+use reqwest::Url;
+use schemars::JsonSchema;
+use serde::de::DeserializeOwned;
+use serde_json::{json, Value};
+use std::error::Error;
+use std::fmt;
+use tokio::net::TcpStream;
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::tungstenite::http::HeaderValue;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::{MaybeTlsStream, WebSocketStream};
+
+pub const DEFAULT_REALTIME_ENDPOINT: &str = "wss://api.openai.com/v1/realtime";
+
+/// Connects to the Realtime API, which holds one conversation per WebSocket
+/// session and streams the model's text and audio back as events.
+#[derive(Debug, Clone)]
+pub struct RealtimeClient {
+    endpoint: String,
+    model: String,
+    api_key: String,
+    insecure_loopback: bool,
+}
+
+/// An open Realtime session. Events are sent and received in order; the
+/// socket is closed when the session is dropped.
+pub struct RealtimeSession {
+    socket: WebSocketStream<MaybeTlsStream<TcpStream>>,
+}
+
+/// A server event, e.g. `response.output_text.delta` or `response.done`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RealtimeEvent {
+    /// The event's `type`.
+    pub kind: String,
+    pub payload: Value,
+}
+
+/// An `error` event sent by the server, or a response that did not complete.
+#[derive(Debug, Clone)]
+pub struct RealtimeError {
+    pub code: Option<String>,
+    pub message: String,
+}
+
+impl fmt::Display for RealtimeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.code {
+            Some(code) => write!(f, "Realtime API error ({}): {}", code, self.message),
+            None => write!(f, "Realtime API error: {}", self.message),
+        }
+    }
+}
+
+impl Error for RealtimeError {}
+
+impl RealtimeClient {
+    pub fn new(model: impl Into<String>, api_key: impl Into<String>) -> Self {
+        Self {
+            endpoint: DEFAULT_REALTIME_ENDPOINT.to_string(),
+            model: model.into(),
+            api_key: api_key.into(),
+            insecure_loopback: false,
+        }
+    }
+
+    /// The WebSocket URL to connect to; the model is added as a query parameter.
+    pub fn with_endpoint(mut self, endpoint: impl Into<String>) -> Self {
+        self.endpoint = endpoint.into();
+        self
+    }
+
+    /// Allows `ws://` endpoints on this machine, such as a local test server.
+    /// Any other unencrypted endpoint is refused with `InsecureEndpoint`.
+    pub fn with_insecure_loopback(mut self, allow: bool) -> Self {
+        self.insecure_loopback = allow;
+        self
+    }
+
+    pub async fn connect(&self) -> Result<RealtimeSession, Box<dyn Error>> {
+        let mut url = Url::parse(&self.endpoint)?;
+        if url.scheme() == "ws" && !(self.insecure_loopback && transport::is_loopback(url.as_str()))
+        {
+            return Err(Box::new(InsecureEndpoint {
+                url: self.endpoint.clone(),
+            }));
+        }
+        url.query_pairs_mut().append_pair("model", &self.model);
+
+        let mut request = url.as_str().into_client_request()?;
+        request.headers_mut().insert(
+            "authorization",
+            HeaderValue::from_str(&format!("Bearer {}", self.api_key))?,
+        );
+        let (socket, _) = tokio_tungstenite::connect_async(request).await?;
+        Ok(RealtimeSession { socket })
+    }
+}
+
+impl RealtimeSession {
+    /// Sends a client event as is, e.g. `session.update`.
+    pub async fn send(&mut self, event: Value) -> Result<(), Box<dyn Error>> {
+        self.socket
+            .send(Message::text(event.to_string()))
+            .await
+            .map_err(Into::into)
+    }
+
+    /// Adds a user message to the conversation; `create_response` asks the
+    /// model to answer it.
+    pub async fn send_text(&mut self, text: &str) -> Result<(), Box<dyn Error>> {
+        self.send(json!({
+            "type": "conversation.item.create",
+            "item": {
+                "type": "message",
+                "role": "user",
+                "content": [{ "type": "input_text", "text": text }],
+            },
+        }))
+        .await
+    }
+
+    /// Appends raw audio, in the session's input format (PCM16 by default), to
+    /// the input buffer.
+    pub async fn append_audio(&mut self, audio: &[u8]) -> Result<(), Box<dyn Error>> {
+        self.send(json!({
+            "type": "input_audio_buffer.append",
+            "audio": STANDARD.encode(audio),
+        }))
+        .await
+    }
+
+    /// Commits the input buffer as a user message. Not needed when the session
+    /// uses server-side voice activity detection.
+    pub async fn commit_audio(&mut self) -> Result<(), Box<dyn Error>> {
+        self.send(json!({ "type": "input_audio_buffer.commit" }))
+            .await
+    }
+
+    pub async fn create_response(&mut self) -> Result<(), Box<dyn Error>> {
+        self.send(json!({ "type": "response.create" })).await
+    }
+
+    /// The next server event; `None` once the server closes the session.
+    pub async fn next_event(&mut self) -> Option<Result<RealtimeEvent, Box<dyn Error>>> {
+        loop {
+            let text = match self.socket.next().await? {
+                Ok(Message::Text(text)) => text,
+                Ok(Message::Close(_)) => return None,
+                Ok(_) => continue,
+                Err(err) => return Some(Err(err.into())),
+            };
+            return Some(
+                serde_json::from_str::<Value>(&text)
+                    .map(|payload| RealtimeEvent {
+                        kind: payload["type"].as_str().unwrap_or_default().to_string(),
+                        payload,
+                    })
+                    .map_err(Into::into),
+            );
+        }
+    }
+
+    /// Asks the model to answer the conversation so far with T, by forcing a
+    /// call to a single function whose parameters are T's schema, and waits
+    /// for the response to finish. Other events received meanwhile are
+    /// dropped.
+    pub async fn respond_structured<T>(&mut self) -> Result<T, Box<dyn Error>>
+    where
+        T: DeserializeOwned + JsonSchema + 'static,
+    {
+        let name = OpenAiClient::schema_name_for::<T>();
+        let mut schema = OpenAiClient::generate_schema::<T>()?;
+        let wrapper = OpenAiClient::wrap_non_object_root(&mut schema);
+        self.send(json!({
+            "type": "response.create",
+            "response": {
+                "tools": [{ "type": "function", "name": name, "parameters": schema }],
+                "tool_choice": { "type": "function", "name": name },
+            },
+        }))
+        .await?;
+
+        while let Some(event) = self.next_event().await {
+            let event = event?;
+            match event.kind.as_str() {
+                "error" => return Err(Box::new(server_error(&event.payload["error"]))),
+                "response.done" => {
+                    return parse_response(&event.payload["response"], &name, wrapper)
+                }
+                _ => {}
+            }
+        }
+        Err(Box::new(RealtimeError {
+            code: None,
+            message: "session closed before the response finished".to_string(),
+        }))
+    }
+
+    pub async fn close(mut self) -> Result<(), Box<dyn Error>> {
+        self.socket.close(None).await.map_err(Into::into)
+    }
+}
+
+impl RealtimeEvent {
+    /// The text of a text or transcript delta event.
+    pub fn text_delta(&self) -> Option<&str> {
+        match self.kind.as_str() {
+            "response.output_text.delta"
+            | "response.text.delta"
+            | "response.output_audio_transcript.delta"
+            | "response.audio_transcript.delta" => self.payload["delta"].as_str(),
+            _ => None,
+        }
+    }
+
+    /// The decoded audio of an audio delta event.
+    pub fn audio_delta(&self) -> Option<Vec<u8>> {
+        match self.kind.as_str() {
+            "response.output_audio.delta" | "response.audio.delta" => self.payload["delta"]
+                .as_str()
+                .and_then(|delta| STANDARD.decode(delta).ok()),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Debug for RealtimeSession {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RealtimeSession").finish_non_exhaustive()
+    }
+}
+
+fn server_error(error: &Value) -> RealtimeError {
+    RealtimeError {
+        code: error["code"].as_str().map(str::to_string),
+        message: error["message"]
+            .as_str()
+            .unwrap_or("unknown error")
+            .to_string(),
+    }
+}
+
+fn parse_response<T: DeserializeOwned>(
+    response: &Value,
+    name: &str,
+    wrapper: Option<&str>,
+) -> Result<T, Box<dyn Error>> {
+    let status = response["status"].as_str().unwrap_or_default();
+    if status != "completed" {
+        let details = &response["status_details"];
+        return Err(Box::new(match details.get("error") {
+            Some(error) => server_error(error),
+            None => RealtimeError {
+                code: details["reason"].as_str().map(str::to_string),
+                message: format!("response {}", status),
+            },
+        }));
+    }
+    let arguments = response["output"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .find(|item| item["type"] == "function_call" && item["name"] == name)
+        .and_then(|item| item["arguments"].as_str())
+        .ok_or_else(|| RealtimeError {
+            code: None,
+            message: format!("response has no call to {}", name),
+        })?;
+    let parse_error = |err: serde_json::Error| ParseError {
+        raw: arguments.to_string(),
+        message: err.to_string(),
+    };
+    let mut value: Value = serde_json::from_str(arguments).map_err(parse_error)?;
+    if let Some(key) = wrapper {
+        value = value[key].take();
+    }
+    Ok(serde_json::from_value(value).map_err(parse_error)?)
+}
@@ -0,0 +1,73 @@
+//! Request shaping for reasoning models (o1, o3, o4-mini, gpt-5), which take
+//! `developer` instead of `system` messages, reject the sampling options of
+//! other chat models and accept a `reasoning_effort`.
+
+use serde::Serialize;
+use serde_json::{json, Value};
+
+/// Model families that reason before answering, matched by prefix.
+const REASONING_MODELS: &[&str] = &["o1", "o3", "o4", "gpt-5"];
+
+/// Non-reasoning chat models whose names match a reasoning family.
+const CHAT_ALIASES: &[&str] = &["gpt-5-chat"];
+
+/// Early reasoning models that accept neither system nor developer messages;
+/// instructions are sent as user messages instead.
+const NO_INSTRUCTION_ROLE: &[&str] = &["o1-mini", "o1-preview"];
+
+/// Sampling options reasoning models reject with a 400.
+const UNSUPPORTED_OPTIONS: &[&str] = &[
+    "temperature",
+    "top_p",
+    "presence_penalty",
+    "frequency_penalty",
+    "logprobs",
+];
+
+/// How much a reasoning model thinks before answering, trading latency and
+/// reasoning tokens for accuracy.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReasoningEffort {
+    /// Only supported by `gpt-5` models.
+    Minimal,
+    Low,
+    Medium,
+    High,
+}
+
+/// Whether `model` is a reasoning model, judged by its name.
+pub fn is_reasoning_model(model: &str) -> bool {
+    REASONING_MODELS
+        .iter()
+        .any(|prefix| model.starts_with(prefix))
+        && !CHAT_ALIASES.iter().any(|prefix| model.starts_with(prefix))
+}
+
+/// Adapts a chat-completions or Responses body for a reasoning model: system
+/// messages become developer messages and rejected sampling options are left
+/// out.
+pub(crate) fn adapt_body(model: &str, body: &mut Value) {
+    let Some(object) = body.as_object_mut() else {
+        return;
+    };
+    for option in UNSUPPORTED_OPTIONS {
+        object.remove(*option);
+    }
+    let role = if NO_INSTRUCTION_ROLE
+        .iter()
+        .any(|prefix| model.starts_with(prefix))
+    {
+        "user"
+    } else {
+        "developer"
+    };
+    for key in ["messages", "input"] {
+        let messages = object.get_mut(key).and_then(Value::as_array_mut);
+        for message in messages.into_iter().flatten() {
+            if message["role"] == "system" {
+                message["role"] = json!(role);
+            }
+        }
+    }
+}
@@ -0,0 +1,90 @@
+use std::error::Error;
+use std::fmt;
+use std::sync::Arc;
+
+/// A prompt that was refused, and the refusal the model gave for it.
+#[derive(Debug, Clone)]
+pub struct RefusalAttempt {
+    pub prompt: String,
+    pub refusal: String,
+}
+
+/// How to change the conversation before retrying a refused call.
+#[derive(Debug, Clone)]
+pub enum PromptAdjustment {
+    /// Retry with the same prompt.
+    Unchanged,
+    /// Retry with a rewritten prompt.
+    Rewrite(String),
+    /// Keep the refused exchange and append a clarifying user message.
+    Clarify(String),
+}
+
+type Adjuster = Arc<dyn Fn(&RefusalAttempt) -> PromptAdjustment + Send + Sync>;
+
+/// Retries calls the model refuses, optionally adjusting the prompt in between.
+#[derive(Clone)]
+pub struct RefusalPolicy {
+    pub(crate) max_retries: u32,
+    pub(crate) adjust: Option<Adjuster>,
+}
+
+impl RefusalPolicy {
+    pub fn new(max_retries: u32) -> Self {
+        Self {
+            max_retries,
+            adjust: None,
+        }
+    }
+
+    /// Decides, from the latest refused attempt, how to change the next attempt.
+    pub fn with_adjustment(
+        mut self,
+        adjust: impl Fn(&RefusalAttempt) -> PromptAdjustment + Send + Sync + 'static,
+    ) -> Self {
+        self.adjust = Some(Arc::new(adjust));
+        self
+    }
+
+    pub(crate) fn adjustment(&self, attempt: &RefusalAttempt) -> PromptAdjustment {
+        match &self.adjust {
+            Some(adjust) => adjust(attempt),
+            None => PromptAdjustment::Unchanged,
+        }
+    }
+}
+
+impl fmt::Debug for RefusalPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RefusalPolicy")
+            .field("max_retries", &self.max_retries)
+            .field("adjust", &self.adjust.is_some())
+            .finish()
+    }
+}
+
+/// Returned when the model still refuses after every retry of the refusal policy.
+#[derive(Debug, Clone)]
+pub struct RefusalsExhausted {
+    pub attempts: Vec<RefusalAttempt>,
+}
+
+impl RefusalsExhausted {
+    /// The refusal text of the final attempt.
+    pub fn refusal(&self) -> &str {
+        self.attempts.last().map_or("", |a| a.refusal.as_str())
+    }
+}
+
+impl fmt::Display for RefusalsExhausted {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "LLM refusal after {} attempts: {}",
+            self.attempts.len(),
+            self.refusal()
+        )
+    }
+}
+
+impl Error for RefusalsExhausted {}
@@ -0,0 +1,146 @@
+//! Translation between the chat-completions shapes used throughout the client
+//! and the Responses API (`/v1/responses`), which takes `input` items and
+//! `text.format` instead of `messages` and `response_format`.
+
+use serde_json::{json, Map, Value};
+
+/// Builds a Responses API body from chat-style messages and a strict schema.
+pub(crate) fn request_body(
+    model: &str,
+    messages: &[Value],
+    schema_name: &str,
+    schema: Value,
+) -> Value {
+    let mut body = text_request_body(model, messages, false);
+    body["text"] = json!({
+        "format": {
+            "type": "json_schema",
+            "name": schema_name,
+            "strict": true,
+            "schema": schema
+        }
+    });
+    body
+}
+
+/// Builds a body without a schema, optionally in JSON mode.
+pub(crate) fn text_request_body(model: &str, messages: &[Value], json_mode: bool) -> Value {
+    let mut body = json!({
+        "model": model,
+        "input": messages.iter().map(to_input_item).collect::<Vec<_>>(),
+    });
+    if json_mode {
+        body["text"] = json!({ "format": { "type": "json_object" } });
+    }
+    body
+}
+
+/// Chat-completions options the Responses API rejects as unknown parameters.
+const UNSUPPORTED_OPTIONS: &[&str] = &["stop", "seed", "frequency_penalty", "presence_penalty"];
+
+/// Renames chat-completions options that have a different name in the
+/// Responses API and leaves out the ones it does not take.
+pub(crate) fn adapt_options(body: &mut Value) {
+    if let Some(body) = body.as_object_mut() {
+        for option in UNSUPPORTED_OPTIONS {
+            body.remove(*option);
+        }
+        if let Some(max_tokens) = body.remove("max_completion_tokens") {
+            body.insert("max_output_tokens".into(), max_tokens);
+        }
+        if let Some(effort) = body.remove("reasoning_effort") {
+            body.insert("reasoning".into(), json!({ "effort": effort }));
+        }
+        if body.remove("logprobs") == Some(json!(true)) {
+            body.insert("include".into(), json!(["message.output_text.logprobs"]));
+        }
+    }
+}
+
+fn to_input_item(message: &Value) -> Value {
+    let role = message["role"].clone();
+    let content = match (&message["content"], &message["refusal"]) {
+        (Value::Array(parts), _) => Value::Array(parts.iter().map(to_input_part).collect()),
+        (Value::Null, Value::String(refusal)) => Value::String(refusal.clone()),
+        (content, _) => content.clone(),
+    };
+    json!({ "role": role, "content": content })
+}
+
+fn to_input_part(part: &Value) -> Value {
+    match part["type"].as_str() {
+        Some("text") => json!({ "type": "input_text", "text": part["text"] }),
+        Some("image_url") => {
+            json!({ "type": "input_image", "image_url": part["image_url"]["url"] })
+        }
+        _ => part.clone(),
+    }
+}
+
+/// Converts a Responses API response into the equivalent chat-completions
+/// response, so it can be parsed like any other. Error bodies pass through.
+pub(crate) fn to_chat_completion(response: Value) -> Value {
+    if response.get("output").is_none() {
+        return response;
+    }
+
+    let mut message = Map::new();
+    message.insert("role".into(), json!("assistant"));
+    let mut logprobs = Value::Null;
+    let parts = response["output"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter(|item| item["type"] == "message")
+        .flat_map(|item| item["content"].as_array().cloned().unwrap_or_default());
+    for part in parts {
+        match part["type"].as_str() {
+            Some("output_text") => {
+                message.insert("content".into(), part["text"].clone());
+                if part["logprobs"].is_array() {
+                    logprobs = json!({ "content": part["logprobs"] });
+                }
+            }
+            Some("refusal") => {
+                message.insert("refusal".into(), part["refusal"].clone());
+            }
+            _ => {}
+        }
+    }
+
+    let finish_reason = match response["status"].as_str() {
+        Some("incomplete") => match response["incomplete_details"]["reason"].as_str() {
+            Some("max_output_tokens") => "length",
+            Some("content_filter") => "content_filter",
+            _ => "incomplete",
+        },
+        _ => "stop",
+    };
+
+    let usage = &response["usage"];
+    json!({
+        "id": response["id"],
+        "model": response["model"],
+        "created": response["created_at"],
+        "choices": [{
+            "message": message,
+            "finish_reason": finish_reason,
+            "logprobs": logprobs
+        }],
+        "usage": if usage.is_object() {
+            json!({
+                "prompt_tokens": usage["input_tokens"],
+                "completion_tokens": usage["output_tokens"],
+                "total_tokens": usage["total_tokens"],
+                "prompt_tokens_details": {
+                    "cached_tokens": usage["input_tokens_details"]["cached_tokens"].as_u64().unwrap_or(0)
+                },
+                "completion_tokens_details": {
+                    "reasoning_tokens": usage["output_tokens_details"]["reasoning_tokens"].as_u64().unwrap_or(0)
+                }
+            })
+        } else {
+            Value::Null
+        }
+    })
+}
@@ -0,0 +1,94 @@
+use reqwest::header::{HeaderMap, RETRY_AFTER};
+use reqwest::StatusCode;
+use std::collections::hash_map::RandomState;
+use std::error::Error;
+use std::hash::{BuildHasher, Hasher};
+use std::io;
+use std::time::Duration;
+
+/// Controls how transient failures (429/500/502/503 and connection errors) are retried.
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    /// Total number of attempts, including the first request.
+    pub max_attempts: u32,
+    /// Delay before the first retry; doubled on every subsequent retry.
+    pub base_delay: Duration,
+    /// Randomize each delay between zero and the exponential backoff value.
+    pub jitter: bool,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            base_delay: Duration::from_millis(500),
+            jitter: true,
+        }
+    }
+}
+
+impl RetryPolicy {
+    pub(crate) fn is_retryable_status(status: StatusCode) -> bool {
+        matches!(
+            status,
+            StatusCode::TOO_MANY_REQUESTS
+                | StatusCode::INTERNAL_SERVER_ERROR
+                | StatusCode::BAD_GATEWAY
+                | StatusCode::SERVICE_UNAVAILABLE
+        )
+    }
+
+    /// Connection failures and timeouts, as reported by reqwest or as I/O
+    /// errors of a custom transport.
+    pub(crate) fn is_retryable_error(err: &(dyn Error + 'static)) -> bool {
+        if let Some(err) = err.downcast_ref::<reqwest::Error>() {
+            // The browser's fetch does not tell connection failures apart.
+            #[cfg(not(target_arch = "wasm32"))]
+            if err.is_connect() {
+                return true;
+            }
+            return err.is_timeout();
+        }
+        err.downcast_ref::<io::Error>().is_some_and(|err| {
+            matches!(
+                err.kind(),
+                io::ErrorKind::ConnectionRefused
+                    | io::ErrorKind::ConnectionReset
+                    | io::ErrorKind::ConnectionAborted
+                    | io::ErrorKind::TimedOut
+            )
+        })
+    }
+
+    /// Delay before retrying after the given (1-based) attempt failed.
+    /// A server-provided Retry-After always takes precedence over the backoff.
+    pub(crate) fn delay_for(&self, attempt: u32, retry_after: Option<Duration>) -> Duration {
+        if let Some(retry_after) = retry_after {
+            return retry_after;
+        }
+        let exponent = attempt.saturating_sub(1).min(16);
+        let backoff = self.base_delay.saturating_mul(1 << exponent);
+        if self.jitter {
+            backoff.mul_f64(random_fraction())
+        } else {
+            backoff
+        }
+    }
+}
+
+/// Parses the Retry-After header, given in (possibly fractional) seconds.
+pub(crate) fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    let value = headers.get(RETRY_AFTER)?.to_str().ok()?;
+    let seconds: f64 = value.trim().parse().ok()?;
+    if seconds.is_finite() && seconds >= 0.0 {
+        Some(Duration::from_secs_f64(seconds))
+    } else {
+        None
+    }
+}
+
+pub(crate) fn random_fraction() -> f64 {
+    // RandomState is seeded randomly per instance, which is enough for jitter.
+    let bits = RandomState::new().build_hasher().finish();
+    (bits >> 11) as f64 / (1u64 << 53) as f64
+}
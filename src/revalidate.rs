@@ -0,0 +1,167 @@
+use crate::openai::OpenAiClient;
+use schemars::JsonSchema;
+use serde::de::DeserializeOwned;
+use serde_json::{Map, Value};
+use std::error::Error;
+
+/// How a stored output relates to the current version of a type.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Verdict {
+    /// Deserializes and matches the strict schema exactly.
+    Conforms,
+    /// Deserializes, but fields are missing (defaulted) or unknown (dropped).
+    NeedsMigration,
+    /// Does not deserialize into the new type at all.
+    Invalid,
+}
+
+/// Per-record result of re-validating a stored output. Field paths are JSON pointers.
+#[derive(Debug, Clone)]
+pub struct RecordReport {
+    pub index: usize,
+    pub verdict: Verdict,
+    pub missing_fields: Vec<String>,
+    pub unknown_fields: Vec<String>,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct RevalidationReport {
+    pub records: Vec<RecordReport>,
+}
+
+impl RevalidationReport {
+    pub fn count(&self, verdict: Verdict) -> usize {
+        self.records.iter().filter(|r| r.verdict == verdict).count()
+    }
+
+    pub fn all_conform(&self) -> bool {
+        self.records.iter().all(|r| r.verdict == Verdict::Conforms)
+    }
+}
+
+/// Checks whether a stored raw JSON output conforms to the (possibly newer) type `T`.
+pub fn revalidate<T: DeserializeOwned + JsonSchema + 'static>(
+    stored_raw_json: &str,
+) -> Result<RecordReport, Box<dyn Error>> {
+    let schema = OpenAiClient::generate_schema::<T>()?;
+    Ok(revalidate_record::<T>(0, stored_raw_json, &schema))
+}
+
+/// Re-validates many stored outputs against `T`, producing one report per record.
+pub fn revalidate_all<T, I, S>(stored_raw_json: I) -> Result<RevalidationReport, Box<dyn Error>>
+where
+    T: DeserializeOwned + JsonSchema + 'static,
+    I: IntoIterator<Item = S>,
+    S: AsRef<str>,
+{
+    let schema = OpenAiClient::generate_schema::<T>()?;
+    let records = stored_raw_json
+        .into_iter()
+        .enumerate()
+        .map(|(index, raw)| revalidate_record::<T>(index, raw.as_ref(), &schema))
+        .collect();
+    Ok(RevalidationReport { records })
+}
+
+fn revalidate_record<T: DeserializeOwned>(index: usize, raw: &str, schema: &Value) -> RecordReport {
+    let mut report = RecordReport {
+        index,
+        verdict: Verdict::Conforms,
+        missing_fields: Vec::new(),
+        unknown_fields: Vec::new(),
+        error: None,
+    };
+
+    let value: Value = match serde_json::from_str(raw) {
+        Ok(value) => value,
+        Err(err) => {
+            report.verdict = Verdict::Invalid;
+            report.error = Some(err.to_string());
+            return report;
+        }
+    };
+
+    let definitions = schema.get("definitions").and_then(Value::as_object);
+    compare(&value, schema, definitions, "", &mut report);
+
+    if let Err(err) = serde_json::from_value::<T>(value) {
+        report.verdict = Verdict::Invalid;
+        report.error = Some(err.to_string());
+    } else if !report.missing_fields.is_empty() || !report.unknown_fields.is_empty() {
+        report.verdict = Verdict::NeedsMigration;
+    }
+    report
+}
+
+/// Walks the record alongside the schema, collecting missing and unknown object fields.
+fn compare(
+    value: &Value,
+    schema: &Value,
+    definitions: Option<&Map<String, Value>>,
+    path: &str,
+    report: &mut RecordReport,
+) {
+    let schema = resolve(schema, definitions);
+
+    for key in ["anyOf", "oneOf", "allOf"] {
+        if let Some(Value::Array(variants)) = schema.get(key) {
+            // Descend into the first variant that is not the null branch of an Option.
+            if let Some(variant) = variants
+                .iter()
+                .map(|v| resolve(v, definitions))
+                .find(|v| !value.is_null() && v.get("type") != Some(&Value::from("null")))
+            {
+                compare(value, variant, definitions, path, report);
+            }
+            return;
+        }
+    }
+
+    match value {
+        Value::Object(object) => {
+            let Some(properties) = schema.get("properties").and_then(Value::as_object) else {
+                return;
+            };
+            if let Some(Value::Array(required)) = schema.get("required") {
+                for name in required.iter().filter_map(Value::as_str) {
+                    if !object.contains_key(name) {
+                        report.missing_fields.push(format!("{}/{}", path, name));
+                    }
+                }
+            }
+            for (name, field) in object {
+                let field_path = format!("{}/{}", path, name);
+                match properties.get(name) {
+                    Some(field_schema) => {
+                        compare(field, field_schema, definitions, &field_path, report)
+                    }
+                    None => report.unknown_fields.push(field_path),
+                }
+            }
+        }
+        Value::Array(items) => {
+            if let Some(item_schema) = schema.get("items") {
+                for (i, item) in items.iter().enumerate() {
+                    compare(
+                        item,
+                        item_schema,
+                        definitions,
+                        &format!("{}/{}", path, i),
+                        report,
+                    );
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+fn resolve<'a>(schema: &'a Value, definitions: Option<&'a Map<String, Value>>) -> &'a Value {
+    schema
+        .get("$ref")
+        .and_then(Value::as_str)
+        .and_then(|reference| reference.strip_prefix("#/definitions/"))
+        .and_then(|name| definitions?.get(name))
+        .unwrap_or(schema)
+}
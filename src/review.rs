@@ -0,0 +1,297 @@
+use crate::fallback::ContentParser;
+use crate::openai::StructuredResponse;
+use serde::Serialize;
+use serde_json::Value;
+use std::error::Error;
+use std::fmt::Write;
+
+/// Input characters shown per item unless set with `with_excerpt_length`.
+const DEFAULT_EXCERPT_CHARS: usize = 300;
+
+/// A report of a batch of extractions for subject-matter experts to
+/// spot-check: per item, an excerpt of the input, the output as a table of
+/// its top-level fields, and its confidence, warnings and cost.
+#[derive(Debug, Clone)]
+pub struct ReviewReport {
+    title: String,
+    items: Vec<ReviewItem>,
+    excerpt_chars: usize,
+}
+
+/// One extraction in a `ReviewReport`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReviewItem {
+    pub input: String,
+    /// The output as JSON, or the error message of a failed call.
+    pub output: Result<Value, String>,
+    /// A score in `[0, 1]` from the caller, e.g. the agreement of a
+    /// consensus call.
+    pub confidence: Option<f64>,
+    pub warnings: Vec<String>,
+    /// Cost in USD.
+    pub cost: Option<f64>,
+}
+
+impl ReviewItem {
+    pub fn new(input: impl Into<String>, output: Value) -> Self {
+        Self {
+            input: input.into(),
+            output: Ok(output),
+            confidence: None,
+            warnings: Vec::new(),
+            cost: None,
+        }
+    }
+
+    pub fn failed(input: impl Into<String>, error: impl Into<String>) -> Self {
+        Self {
+            input: input.into(),
+            output: Err(error.into()),
+            confidence: None,
+            warnings: Vec::new(),
+            cost: None,
+        }
+    }
+
+    /// An item for a call made with `call_schema_with_meta`, taking the cost
+    /// from its metadata and warning about responses that were truncated,
+    /// needed repairs or were read by a fallback parser.
+    pub fn from_result<T: Serialize>(
+        input: impl Into<String>,
+        result: &Result<StructuredResponse<T>, Box<dyn Error>>,
+    ) -> Self {
+        let response = match result {
+            Ok(response) => response,
+            Err(err) => return Self::failed(input, err.to_string()),
+        };
+        let mut item = match serde_json::to_value(&response.value) {
+            Ok(output) => Self::new(input, output),
+            Err(err) => return Self::failed(input, err.to_string()),
+        };
+        let meta = &response.meta;
+        item.cost = meta.cost;
+        if meta.finish_reason.as_deref() == Some("length") {
+            item.warnings
+                .push("Output was cut off at the token limit".to_string());
+        }
+        if meta.repair_attempts > 0 {
+            item.warnings.push(format!(
+                "Needed {} repair round-trip(s) to parse",
+                meta.repair_attempts
+            ));
+        }
+        if meta.parser != ContentParser::Json {
+            item.warnings
+                .push(format!("Read with the {:?} fallback parser", meta.parser));
+        }
+        item
+    }
+
+    pub fn with_confidence(mut self, confidence: f64) -> Self {
+        self.confidence = Some(confidence);
+        self
+    }
+
+    pub fn with_warning(mut self, warning: impl Into<String>) -> Self {
+        self.warnings.push(warning.into());
+        self
+    }
+
+    fn status(&self) -> &'static str {
+        match (&self.output, self.warnings.is_empty()) {
+            (Err(_), _) => "failed",
+            (Ok(_), false) => "check",
+            (Ok(_), true) => "ok",
+        }
+    }
+}
+
+impl ReviewReport {
+    pub fn new(title: impl Into<String>) -> Self {
+        Self {
+            title: title.into(),
+            items: Vec::new(),
+            excerpt_chars: DEFAULT_EXCERPT_CHARS,
+        }
+    }
+
+    /// Characters of each input to show; longer inputs end in `…`.
+    pub fn with_excerpt_length(mut self, chars: usize) -> Self {
+        self.excerpt_chars = chars;
+        self
+    }
+
+    pub fn push(&mut self, item: ReviewItem) {
+        self.items.push(item);
+    }
+
+    pub fn items(&self) -> &[ReviewItem] {
+        &self.items
+    }
+
+    pub fn to_markdown(&self) -> String {
+        let mut out = format!("# {}\n\n{}\n", self.title, self.summary());
+        for (index, item) in self.items.iter().enumerate() {
+            let _ = write!(out, "\n## {}. {}\n\n", index + 1, item.status());
+            for line in self.excerpt(&item.input).lines() {
+                let _ = writeln!(out, "> {}", line);
+            }
+            out.push('\n');
+            match &item.output {
+                Ok(output) => {
+                    out.push_str("| Field | Value |\n| --- | --- |\n");
+                    for (field, value) in rows(output) {
+                        let _ = writeln!(
+                            out,
+                            "| {} | {} |",
+                            markdown_cell(&field),
+                            markdown_cell(&value)
+                        );
+                    }
+                }
+                Err(error) => {
+                    let _ = writeln!(out, "**Error:** {}", error);
+                }
+            }
+            let details = details(item);
+            if !details.is_empty() {
+                let _ = write!(out, "\n{}\n", details.join(" · "));
+            }
+            if !item.warnings.is_empty() {
+                out.push_str("\nWarnings:\n\n");
+                for warning in &item.warnings {
+                    let _ = writeln!(out, "- {}", warning);
+                }
+            }
+        }
+        out
+    }
+
+    /// A standalone HTML page.
+    pub fn to_html(&self) -> String {
+        let mut out = format!(
+            "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n\
+             <title>{title}</title>\n<style>\n\
+             body {{ font-family: sans-serif; max-width: 60em; margin: auto; }}\n\
+             blockquote {{ color: #555; white-space: pre-wrap; }}\n\
+             table {{ border-collapse: collapse; }}\n\
+             td, th {{ border: 1px solid #ccc; padding: 0.3em 0.6em; text-align: left; vertical-align: top; }}\n\
+             .failed h2, .error {{ color: #b00; }}\n.check h2, .warnings {{ color: #a60; }}\n\
+             </style>\n</head>\n<body>\n<h1>{title}</h1>\n<p>{summary}</p>\n",
+            title = html_escape(&self.title),
+            summary = html_escape(&self.summary()),
+        );
+        for (index, item) in self.items.iter().enumerate() {
+            let status = item.status();
+            let _ = write!(
+                out,
+                "<section class=\"{status}\">\n<h2>{}. {status}</h2>\n<blockquote>{}</blockquote>\n",
+                index + 1,
+                html_escape(&self.excerpt(&item.input)),
+            );
+            match &item.output {
+                Ok(output) => {
+                    out.push_str("<table>\n<tr><th>Field</th><th>Value</th></tr>\n");
+                    for (field, value) in rows(output) {
+                        let _ = writeln!(
+                            out,
+                            "<tr><td>{}</td><td>{}</td></tr>",
+                            html_escape(&field),
+                            html_escape(&value)
+                        );
+                    }
+                    out.push_str("</table>\n");
+                }
+                Err(error) => {
+                    let _ = writeln!(out, "<p class=\"error\">{}</p>", html_escape(error));
+                }
+            }
+            let details = details(item);
+            if !details.is_empty() {
+                let _ = writeln!(out, "<p>{}</p>", html_escape(&details.join(" · ")));
+            }
+            if !item.warnings.is_empty() {
+                out.push_str("<ul class=\"warnings\">\n");
+                for warning in &item.warnings {
+                    let _ = writeln!(out, "<li>{}</li>", html_escape(warning));
+                }
+                out.push_str("</ul>\n");
+            }
+            out.push_str("</section>\n");
+        }
+        out.push_str("</body>\n</html>\n");
+        out
+    }
+
+    fn summary(&self) -> String {
+        let failed = self
+            .items
+            .iter()
+            .filter(|item| item.output.is_err())
+            .count();
+        let to_check = self
+            .items
+            .iter()
+            .filter(|item| item.status() == "check")
+            .count();
+        let mut summary = format!(
+            "{} extractions: {} failed, {} with warnings.",
+            self.items.len(),
+            failed,
+            to_check
+        );
+        let costs: Vec<f64> = self.items.iter().filter_map(|item| item.cost).collect();
+        if !costs.is_empty() {
+            let _ = write!(summary, " Total cost ${:.4}.", costs.iter().sum::<f64>());
+        }
+        summary
+    }
+
+    fn excerpt(&self, input: &str) -> String {
+        let input = input.trim();
+        match input.char_indices().nth(self.excerpt_chars) {
+            Some((end, _)) => format!("{}…", input[..end].trim_end()),
+            None => input.to_string(),
+        }
+    }
+}
+
+/// One row per top-level field; other outputs are a single `value` row.
+/// Strings are shown as is and anything else as compact JSON.
+fn rows(output: &Value) -> Vec<(String, String)> {
+    let cell = |value: &Value| match value {
+        Value::String(text) => text.clone(),
+        other => other.to_string(),
+    };
+    match output {
+        Value::Object(fields) => fields
+            .iter()
+            .map(|(field, value)| (field.clone(), cell(value)))
+            .collect(),
+        other => vec![("value".to_string(), cell(other))],
+    }
+}
+
+fn details(item: &ReviewItem) -> Vec<String> {
+    let mut details = Vec::new();
+    if let Some(confidence) = item.confidence {
+        details.push(format!("Confidence: {:.2}", confidence));
+    }
+    if let Some(cost) = item.cost {
+        details.push(format!("Cost: ${:.4}", cost));
+    }
+    details
+}
+
+fn markdown_cell(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace('|', "\\|")
+        .replace('\n', "<br>")
+}
+
+fn html_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
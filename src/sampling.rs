@@ -0,0 +1,97 @@
+use crate::openai::OpenAiClient;
+use crate::options::RequestOptions;
+use futures::future;
+use schemars::JsonSchema;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value;
+use std::error::Error;
+
+pub use openai_structured_client_derive::FieldSampling;
+
+/// Which fields of an output type are extracted deterministically, usually
+/// derived with `#[derive(FieldSampling)]` and `#[sampling(deterministic)]`
+/// on those fields.
+pub trait FieldSampling {
+    /// Top-level fields (as serialized) taken from a temperature-0 pass.
+    const DETERMINISTIC: &'static [&'static str];
+    /// Top-level fields taken from a pass with their own temperature or
+    /// model, from `#[sampling(temperature = ..., model = "...")]`.
+    const HINTS: &'static [FieldHint] = &[];
+}
+
+/// The temperature and model a field is extracted with.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FieldHint {
+    /// The field as serialized.
+    pub field: &'static str,
+    pub temperature: Option<f32>,
+    pub model: Option<&'static str>,
+}
+
+impl OpenAiClient {
+    /// Extracts T in concurrent passes and merges them: one pass with
+    /// `options`, one at temperature 0 for the deterministic fields and one
+    /// for each other temperature and model among the field hints, which then
+    /// supply their fields. Types without deterministic fields or hints take
+    /// a single call.
+    ///
+    /// Every pass asks for the whole output, so a call costs about as much as
+    /// one `call_schema_with` per pass.
+    pub async fn call_schema_two_pass<T>(
+        &self,
+        user_prompt: &str,
+        options: &RequestOptions,
+    ) -> Result<T, Box<dyn Error>>
+    where
+        T: FieldSampling + Serialize + DeserializeOwned + JsonSchema + Clone + 'static,
+    {
+        let mut passes: Vec<(Option<f32>, Option<&str>, Vec<&str>)> = Vec::new();
+        let deterministic = T::DETERMINISTIC.iter().map(|field| FieldHint {
+            field,
+            temperature: Some(0.0),
+            model: None,
+        });
+        for hint in deterministic.chain(T::HINTS.iter().copied()) {
+            let pass = passes.iter_mut().find(|(temperature, model, _)| {
+                (*temperature, *model) == (hint.temperature, hint.model)
+            });
+            match pass {
+                Some((_, _, fields)) => fields.push(hint.field),
+                None => passes.push((hint.temperature, hint.model, vec![hint.field])),
+            }
+        }
+        if passes.is_empty() {
+            return self.call_schema_with::<T>(user_prompt, options).await;
+        }
+        let hinted = passes.iter().map(|(temperature, model, _)| {
+            let options = match temperature {
+                Some(temperature) => options.clone().temperature(*temperature),
+                None => options.clone(),
+            };
+            let client = match model {
+                Some(model) => self.clone().with_model(*model),
+                None => self.clone(),
+            };
+            async move { client.call_schema_with::<T>(user_prompt, &options).await }
+        });
+        let (base, hinted) = future::try_join(
+            self.call_schema_with::<T>(user_prompt, options),
+            future::try_join_all(hinted),
+        )
+        .await?;
+        let mut merged = serde_json::to_value(base)?;
+        for ((_, _, fields), output) in passes.iter().zip(hinted) {
+            if let (Value::Object(merged), Value::Object(mut output)) =
+                (&mut merged, serde_json::to_value(output)?)
+            {
+                for field in fields {
+                    if let Some(value) = output.remove(*field) {
+                        merged.insert(field.to_string(), value);
+                    }
+                }
+            }
+        }
+        Ok(serde_json::from_value(merged)?)
+    }
+}
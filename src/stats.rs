@@ -0,0 +1,183 @@
+use crate::openai::{Refusal, StageTimings};
+use crate::refusal::RefusalsExhausted;
+use crate::time::Instant;
+use std::collections::{BTreeMap, VecDeque};
+use std::error::Error;
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Outcome of a single call, as recorded in the client statistics.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CallOutcome {
+    Success,
+    Refusal,
+    Error,
+}
+
+impl CallOutcome {
+    pub(crate) fn of<T>(result: &Result<T, Box<dyn Error>>) -> Self {
+        match result {
+            Ok(_) => CallOutcome::Success,
+            Err(err) if err.is::<Refusal>() || err.is::<RefusalsExhausted>() => {
+                CallOutcome::Refusal
+            }
+            Err(_) => CallOutcome::Error,
+        }
+    }
+}
+
+/// Aggregates over the calls recorded within the stats window.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct StatsSnapshot {
+    pub calls: usize,
+    pub success_rate: f64,
+    pub refusal_rate: f64,
+    /// Fraction of calls that needed at least one repair round-trip.
+    pub repair_rate: f64,
+    pub p50_latency: Duration,
+    pub p95_latency: Duration,
+    pub total_tokens: u64,
+    /// Cost in USD of the calls priced by `OpenAiClient::with_pricing`.
+    pub total_cost: f64,
+    /// Mean per-stage timings of the successful calls.
+    pub mean_timings: StageTimings,
+}
+
+/// Rolling-window statistics, overall and per call tag.
+#[derive(Debug, Clone, Default)]
+pub struct ClientStats {
+    pub window: Duration,
+    pub overall: StatsSnapshot,
+    pub by_tag: BTreeMap<String, StatsSnapshot>,
+}
+
+#[derive(Debug, Clone)]
+struct Sample {
+    at: Instant,
+    tag: Option<String>,
+    latency: Duration,
+    outcome: CallOutcome,
+    tokens: u32,
+    cost: Option<f64>,
+    repaired: bool,
+    timings: StageTimings,
+}
+
+#[derive(Debug)]
+pub(crate) struct StatsRecorder {
+    window: Duration,
+    samples: Mutex<VecDeque<Sample>>,
+}
+
+impl StatsRecorder {
+    pub(crate) fn new(window: Duration) -> Self {
+        Self {
+            window,
+            samples: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn record(
+        &self,
+        tag: Option<&str>,
+        latency: Duration,
+        outcome: CallOutcome,
+        tokens: u32,
+        cost: Option<f64>,
+        repaired: bool,
+        timings: StageTimings,
+    ) {
+        let now = Instant::now();
+        let mut samples = self.samples.lock().unwrap();
+        Self::prune(&mut samples, now, self.window);
+        samples.push_back(Sample {
+            at: now,
+            tag: tag.map(str::to_string),
+            latency,
+            outcome,
+            tokens,
+            cost,
+            repaired,
+            timings,
+        });
+    }
+
+    pub(crate) fn snapshot(&self) -> ClientStats {
+        let mut samples = self.samples.lock().unwrap();
+        Self::prune(&mut samples, Instant::now(), self.window);
+
+        let mut tags: BTreeMap<String, Vec<&Sample>> = BTreeMap::new();
+        for sample in samples.iter() {
+            if let Some(tag) = &sample.tag {
+                tags.entry(tag.clone()).or_default().push(sample);
+            }
+        }
+
+        ClientStats {
+            window: self.window,
+            overall: aggregate(samples.iter().collect()),
+            by_tag: tags
+                .into_iter()
+                .map(|(tag, samples)| (tag, aggregate(samples)))
+                .collect(),
+        }
+    }
+
+    fn prune(samples: &mut VecDeque<Sample>, now: Instant, window: Duration) {
+        while samples
+            .front()
+            .is_some_and(|sample| now.duration_since(sample.at) > window)
+        {
+            samples.pop_front();
+        }
+    }
+}
+
+fn aggregate(samples: Vec<&Sample>) -> StatsSnapshot {
+    if samples.is_empty() {
+        return StatsSnapshot::default();
+    }
+    let calls = samples.len();
+    let rate =
+        |outcome| samples.iter().filter(|s| s.outcome == outcome).count() as f64 / calls as f64;
+    let mut latencies: Vec<Duration> = samples.iter().map(|s| s.latency).collect();
+    latencies.sort();
+    let percentile = |p: f64| latencies[((calls - 1) as f64 * p).round() as usize];
+
+    StatsSnapshot {
+        calls,
+        success_rate: rate(CallOutcome::Success),
+        refusal_rate: rate(CallOutcome::Refusal),
+        repair_rate: samples.iter().filter(|s| s.repaired).count() as f64 / calls as f64,
+        p50_latency: percentile(0.50),
+        p95_latency: percentile(0.95),
+        total_tokens: samples.iter().map(|s| u64::from(s.tokens)).sum(),
+        total_cost: samples.iter().filter_map(|s| s.cost).sum(),
+        mean_timings: mean_timings(&samples),
+    }
+}
+
+fn mean_timings(samples: &[&Sample]) -> StageTimings {
+    let successes: Vec<&StageTimings> = samples
+        .iter()
+        .filter(|s| s.outcome == CallOutcome::Success)
+        .map(|s| &s.timings)
+        .collect();
+    if successes.is_empty() {
+        return StageTimings::default();
+    }
+    let count = successes.len() as u32;
+    let mean = |stage: fn(&StageTimings) -> Duration| {
+        successes.iter().map(|t| stage(t)).sum::<Duration>() / count
+    };
+    StageTimings {
+        schema: mean(|t| t.schema),
+        validation: mean(|t| t.validation),
+        queue: mean(|t| t.queue),
+        first_byte: mean(|t| t.first_byte),
+        body: mean(|t| t.body),
+        parse: mean(|t| t.parse),
+        repair: mean(|t| t.repair),
+    }
+}
@@ -0,0 +1,126 @@
+use crate::openai::{OpenAiClient, Usage};
+use reqwest::Method;
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::error::Error;
+
+/// A chat completion stored with `RequestOptions::store(true)`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct StoredCompletion {
+    pub id: String,
+    #[serde(default)]
+    pub model: String,
+    #[serde(default)]
+    pub created: u64,
+    #[serde(default)]
+    pub metadata: BTreeMap<String, String>,
+    #[serde(default)]
+    pub choices: Vec<Value>,
+    pub usage: Option<Usage>,
+}
+
+impl StoredCompletion {
+    /// Parses the stored structured output of the first choice into `T`.
+    pub fn content<T: DeserializeOwned>(&self) -> Result<T, Box<dyn Error>> {
+        let content = self
+            .choices
+            .first()
+            .and_then(|choice| choice["message"]["content"].as_str())
+            .unwrap_or_default();
+        Ok(serde_json::from_str(content)?)
+    }
+}
+
+/// One page of stored completions.
+#[derive(Debug, Clone, Deserialize)]
+pub struct StoredCompletionList {
+    pub data: Vec<StoredCompletion>,
+    pub first_id: Option<String>,
+    pub last_id: Option<String>,
+    #[serde(default)]
+    pub has_more: bool,
+}
+
+/// Filters and pagination for `list_stored_completions`.
+#[derive(Debug, Clone, Default)]
+pub struct ListStoredOptions {
+    pub model: Option<String>,
+    pub metadata: BTreeMap<String, String>,
+    pub after: Option<String>,
+    pub limit: Option<u32>,
+}
+
+impl ListStoredOptions {
+    fn query(&self) -> Vec<(String, String)> {
+        let mut query = Vec::new();
+        if let Some(model) = &self.model {
+            query.push(("model".to_string(), model.clone()));
+        }
+        for (key, value) in &self.metadata {
+            query.push((format!("metadata[{}]", key), value.clone()));
+        }
+        if let Some(after) = &self.after {
+            query.push(("after".to_string(), after.clone()));
+        }
+        if let Some(limit) = self.limit {
+            query.push(("limit".to_string(), limit.to_string()));
+        }
+        query
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct Deleted {
+    deleted: bool,
+}
+
+impl OpenAiClient {
+    /// Retrieves a stored completion by id.
+    pub async fn get_stored_completion(
+        &self,
+        id: &str,
+    ) -> Result<StoredCompletion, Box<dyn Error>> {
+        let request = self.authorized(Method::GET, &self.stored_url(id))?;
+        let res = self.execute(request).await?;
+        self.read_json(res).await
+    }
+
+    /// Lists stored completions, optionally filtered by model and metadata.
+    pub async fn list_stored_completions(
+        &self,
+        options: &ListStoredOptions,
+    ) -> Result<StoredCompletionList, Box<dyn Error>> {
+        let request = self
+            .authorized(Method::GET, self.endpoint())?
+            .query(&options.query())?;
+        let res = self.execute(request).await?;
+        self.read_json(res).await
+    }
+
+    /// Replaces the metadata of a stored completion.
+    pub async fn update_stored_metadata(
+        &self,
+        id: &str,
+        metadata: BTreeMap<String, String>,
+    ) -> Result<StoredCompletion, Box<dyn Error>> {
+        let request = self
+            .authorized(Method::POST, &self.stored_url(id))?
+            .json(&json!({ "metadata": metadata }))?;
+        let res = self.execute(request).await?;
+        self.read_json(res).await
+    }
+
+    /// Deletes a stored completion, returning whether it was deleted.
+    pub async fn delete_stored_completion(&self, id: &str) -> Result<bool, Box<dyn Error>> {
+        let request = self.authorized(Method::DELETE, &self.stored_url(id))?;
+        let res = self.execute(request).await?;
+        let deleted: Deleted = self.read_json(res).await?;
+        Ok(deleted.deleted)
+    }
+
+    fn stored_url(&self, id: &str) -> String {
+        format!("{}/{}", self.endpoint().trim_end_matches('/'), id)
+    }
+}
@@ -0,0 +1,76 @@
+use crate::dynamic::DynSchema;
+use crate::openai::OpenAiClient;
+use crate::options::RequestOptions;
+use schemars::JsonSchema;
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use std::error::Error;
+
+pub use openai_structured_client_derive::StructuredOutput;
+
+/// Per-type customization of the schema and request sent for an output type,
+/// usually derived with `#[derive(StructuredOutput)]`, which also adds an
+/// inherent `T::extract(client, prompt)`.
+///
+/// ```ignore
+/// #[derive(Deserialize, JsonSchema, Clone, StructuredOutput)]
+/// #[structured(name = "invoice", system_prompt = "You read invoices.")]
+/// struct Invoice {
+///     #[structured(description = "Total including tax")]
+///     total: f64,
+/// }
+/// ```
+pub trait StructuredOutput: DeserializeOwned + JsonSchema + Clone + 'static {
+    /// Schema name sent instead of the one derived from the type name.
+    const SCHEMA_NAME: Option<&'static str> = None;
+    /// System role used instead of the client's.
+    const SYSTEM_PROMPT: Option<&'static str> = None;
+    /// Whether OpenAI enforces the schema while decoding.
+    const STRICT: bool = true;
+    /// Description of the output as a whole, replacing the doc comment.
+    const DESCRIPTION: Option<&'static str> = None;
+    /// Descriptions of top-level fields (as serialized), replacing their doc
+    /// comments.
+    const FIELD_DESCRIPTIONS: &'static [(&'static str, &'static str)] = &[];
+
+    /// The schema sent for the type, with the overrides applied.
+    fn output_schema() -> Result<DynSchema, Box<dyn Error>> {
+        let mut schema = DynSchema::of::<Self>()?.with_strict(Self::STRICT);
+        if let Some(name) = Self::SCHEMA_NAME {
+            schema.name = name.to_string();
+        }
+        if let Some(description) = Self::DESCRIPTION {
+            schema.schema["description"] = Value::from(description);
+        }
+        if let Some(properties) = schema
+            .schema
+            .get_mut("properties")
+            .and_then(Value::as_object_mut)
+        {
+            for (field, description) in Self::FIELD_DESCRIPTIONS {
+                if let Some(Value::Object(property)) = properties.get_mut(*field) {
+                    property.insert("description".into(), Value::from(*description));
+                }
+            }
+        }
+        Ok(schema)
+    }
+}
+
+impl OpenAiClient {
+    /// Like `call_schema_with`, with the schema and system role customized by
+    /// T's `StructuredOutput` implementation. A system role set in `options`
+    /// takes precedence over T's.
+    pub async fn call_structured<T: StructuredOutput>(
+        &self,
+        user_prompt: &str,
+        options: &RequestOptions,
+    ) -> Result<T, Box<dyn Error>> {
+        let mut options = options.clone();
+        options.output_schema = Some(T::output_schema()?);
+        if options.system_role.is_none() {
+            options.system_role = T::SYSTEM_PROMPT.map(str::to_string);
+        }
+        self.call_schema_with::<T>(user_prompt, &options).await
+    }
+}
@@ -0,0 +1,307 @@
+use serde_json::Value;
+use std::error::Error;
+use std::fmt;
+
+/// Texts shorter than this many words are not checked for language.
+const MIN_WORDS_FOR_LANGUAGE: usize = 8;
+/// Texts shorter than this many words are not checked for reading level.
+const MIN_WORDS_FOR_READING_LEVEL: usize = 30;
+
+/// Languages whose use can be checked in outputs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Language {
+    English,
+    French,
+    German,
+    Spanish,
+    Italian,
+    Portuguese,
+    Dutch,
+}
+
+const LANGUAGES: [Language; 7] = [
+    Language::English,
+    Language::French,
+    Language::German,
+    Language::Spanish,
+    Language::Italian,
+    Language::Portuguese,
+    Language::Dutch,
+];
+
+impl Language {
+    pub fn name(self) -> &'static str {
+        match self {
+            Language::English => "English",
+            Language::French => "French",
+            Language::German => "German",
+            Language::Spanish => "Spanish",
+            Language::Italian => "Italian",
+            Language::Portuguese => "Portuguese",
+            Language::Dutch => "Dutch",
+        }
+    }
+
+    /// Frequent function words, which make up much of any text in the language.
+    /// One-letter words such as "a" and "e" are left out, being common to
+    /// several languages.
+    fn stopwords(self) -> &'static [&'static str] {
+        match self {
+            Language::English => &[
+                "the", "and", "is", "are", "of", "to", "in", "that", "it", "with", "for", "this",
+                "was", "you", "not", "be", "have",
+            ],
+            Language::French => &[
+                "le", "la", "les", "et", "est", "des", "un", "une", "du", "que", "qui", "dans",
+                "pour", "pas", "sur", "avec", "ce", "il", "elle", "nous", "vous",
+            ],
+            Language::German => &[
+                "der", "die", "das", "und", "ist", "nicht", "ein", "eine", "mit", "auf", "den",
+                "dem", "zu", "ich", "sie", "es", "wir", "auch",
+            ],
+            Language::Spanish => &[
+                "el", "la", "los", "las", "es", "de", "que", "en", "un", "una", "por", "con",
+                "para", "no", "se", "del", "muy",
+            ],
+            Language::Italian => &[
+                "il", "lo", "la", "gli", "le", "è", "di", "che", "non", "un", "una", "per", "con",
+                "sono", "della", "del",
+            ],
+            Language::Portuguese => &[
+                "os", "as", "é", "de", "que", "não", "um", "uma", "para", "com", "do", "da", "em",
+                "são",
+            ],
+            Language::Dutch => &[
+                "de", "het", "een", "en", "is", "van", "niet", "dat", "op", "te", "met", "voor",
+                "zijn", "ik", "ook",
+            ],
+        }
+    }
+}
+
+/// Register of the output's text. Only instructed, not checked.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Tone {
+    Formal,
+    Neutral,
+    Friendly,
+    Casual,
+}
+
+/// How hard the output's text may be to read, checked with the
+/// Flesch-Kincaid grade level.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReadingLevel {
+    /// Up to grade 6: short sentences and everyday words.
+    Simple,
+    /// Up to grade 10.
+    Standard,
+    /// Any level; technical terms allowed.
+    Advanced,
+}
+
+impl ReadingLevel {
+    fn max_grade(self) -> Option<f64> {
+        match self {
+            ReadingLevel::Simple => Some(6.0),
+            ReadingLevel::Standard => Some(10.0),
+            ReadingLevel::Advanced => None,
+        }
+    }
+}
+
+/// Language, tone and reading level for the text in an output, added to the
+/// system prompt as instructions and checked on the output of schema calls.
+///
+/// Checks look at every string in the output and are rough: language is
+/// detected from function words and reading level scored from sentence and
+/// word lengths, so short texts are not checked. A violation fails the call
+/// with a `StyleViolation`, which the client's repair attempts (`with_repair`)
+/// send back to the model to fix.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ResponseStyle {
+    language: Option<Language>,
+    tone: Option<Tone>,
+    reading_level: Option<ReadingLevel>,
+}
+
+impl ResponseStyle {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_language(mut self, language: Language) -> Self {
+        self.language = Some(language);
+        self
+    }
+
+    pub fn with_tone(mut self, tone: Tone) -> Self {
+        self.tone = Some(tone);
+        self
+    }
+
+    pub fn with_reading_level(mut self, reading_level: ReadingLevel) -> Self {
+        self.reading_level = Some(reading_level);
+        self
+    }
+
+    /// The instruction block as added to the system prompt.
+    pub fn render(&self) -> String {
+        let mut lines = Vec::new();
+        if let Some(language) = self.language {
+            lines.push(format!(
+                "- Write all text in {}, whatever the language of the input.",
+                language.name()
+            ));
+        }
+        if let Some(tone) = self.tone {
+            lines.push(
+                match tone {
+                    Tone::Formal => {
+                        "- Use a formal, professional tone without contractions or slang."
+                    }
+                    Tone::Neutral => "- Use a neutral, matter-of-fact tone.",
+                    Tone::Friendly => "- Use a warm, friendly tone.",
+                    Tone::Casual => "- Use a casual, conversational tone.",
+                }
+                .to_string(),
+            );
+        }
+        if let Some(level) = self.reading_level {
+            lines.push(
+                match level {
+                    ReadingLevel::Simple => {
+                        "- Write for a young reader: short sentences and everyday words."
+                    }
+                    ReadingLevel::Standard => {
+                        "- Write for a general audience: clear sentences, technical terms explained."
+                    }
+                    ReadingLevel::Advanced => {
+                        "- Write for experts: technical terms may be used without explanation."
+                    }
+                }
+                .to_string(),
+            );
+        }
+        format!("Style of all text values:\n{}", lines.join("\n"))
+    }
+
+    /// Checks the strings of an output, returning what was violated.
+    pub fn check(&self, output: &Value) -> Vec<String> {
+        let mut strings = Vec::new();
+        collect_strings(output, &mut strings);
+        let text = strings.join(". ");
+        let words = words(&text);
+        let mut violations = Vec::new();
+
+        if let Some(language) = self.language {
+            if words.len() >= MIN_WORDS_FOR_LANGUAGE {
+                if let Some(detected) = detect_language(&words) {
+                    if detected != language {
+                        violations.push(format!(
+                            "the text is in {}, not {}",
+                            detected.name(),
+                            language.name()
+                        ));
+                    }
+                }
+            }
+        }
+        if let Some(max_grade) = self.reading_level.and_then(ReadingLevel::max_grade) {
+            if words.len() >= MIN_WORDS_FOR_READING_LEVEL {
+                let grade = reading_grade(&text, &words);
+                if grade > max_grade {
+                    violations.push(format!(
+                        "the text reads at grade {:.1}, above the requested grade {}",
+                        grade, max_grade
+                    ));
+                }
+            }
+        }
+        violations
+    }
+}
+
+/// The output did not follow the requested `ResponseStyle`.
+#[derive(Debug, Clone)]
+pub struct StyleViolation {
+    /// The raw message content returned by the model.
+    pub raw: String,
+    pub violations: Vec<String>,
+}
+
+impl fmt::Display for StyleViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Response style violated: {}", self.violations.join("; "))
+    }
+}
+
+impl Error for StyleViolation {}
+
+fn collect_strings<'a>(value: &'a Value, strings: &mut Vec<&'a str>) {
+    match value {
+        Value::String(text) => strings.push(text),
+        Value::Array(items) => items.iter().for_each(|item| collect_strings(item, strings)),
+        Value::Object(fields) => fields
+            .values()
+            .for_each(|field| collect_strings(field, strings)),
+        _ => {}
+    }
+}
+
+fn words(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphabetic() && c != '\'')
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect()
+}
+
+/// The language with the most function words in the text, if it has clearly
+/// more than any other; `None` when unsure.
+fn detect_language(words: &[String]) -> Option<Language> {
+    let mut scores: Vec<(Language, usize)> = LANGUAGES
+        .iter()
+        .map(|&language| {
+            let stopwords = language.stopwords();
+            let hits = words
+                .iter()
+                .filter(|word| stopwords.contains(&word.as_str()))
+                .count();
+            (language, hits)
+        })
+        .collect();
+    scores.sort_by_key(|&(_, hits)| std::cmp::Reverse(hits));
+    let (language, best) = scores[0];
+    let runner_up = scores.get(1).map_or(0, |&(_, hits)| hits);
+    (best > 0 && best * 2 >= runner_up * 3).then_some(language)
+}
+
+/// Flesch-Kincaid grade level, with syllables counted as vowel groups.
+fn reading_grade(text: &str, words: &[String]) -> f64 {
+    let sentences = text
+        .split(['.', '!', '?', ';'])
+        .filter(|sentence| sentence.chars().any(char::is_alphabetic))
+        .count()
+        .max(1);
+    let syllables: usize = words.iter().map(|word| syllables(word)).sum();
+    let words_per_sentence = words.len() as f64 / sentences as f64;
+    let syllables_per_word = syllables as f64 / words.len() as f64;
+    0.39 * words_per_sentence + 11.8 * syllables_per_word - 15.59
+}
+
+fn syllables(word: &str) -> usize {
+    let is_vowel = |c: char| "aeiouyàâäéèêëíìîïóòôöúùûü".contains(c);
+    let mut count = 0;
+    let mut previous_vowel = false;
+    for c in word.chars() {
+        let vowel = is_vowel(c);
+        if vowel && !previous_vowel {
+            count += 1;
+        }
+        previous_vowel = vowel;
+    }
+    if word.ends_with('e') && count > 1 {
+        count -= 1;
+    }
+    count.max(1)
+}
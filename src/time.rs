@@ -0,0 +1,61 @@
+//! Clocks and timers that also work on `wasm32-unknown-unknown`, where
+//! `std::time::Instant::now` panics and no tokio runtime drives timers.
+//! Elsewhere these are the std and tokio items.
+
+use std::future::Future;
+use std::time::Duration;
+
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) use std::time::{Instant, SystemTime, UNIX_EPOCH};
+#[cfg(target_arch = "wasm32")]
+pub(crate) use web_time::{Instant, SystemTime, UNIX_EPOCH};
+
+/// Returned by `timeout` when the duration elapses first.
+#[derive(Debug)]
+pub(crate) struct Elapsed;
+
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) async fn sleep(duration: Duration) {
+    tokio::time::sleep(duration).await;
+}
+
+/// Sleeps with the host's `setTimeout`.
+#[cfg(target_arch = "wasm32")]
+pub(crate) async fn sleep(duration: Duration) {
+    let millis = u32::try_from(duration.as_millis()).unwrap_or(u32::MAX);
+    // wasm32 is single-threaded, so the timer never actually crosses threads.
+    send_wrapper::SendWrapper::new(gloo_timers::future::TimeoutFuture::new(millis)).await;
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) async fn timeout<F: Future>(
+    duration: Duration,
+    future: F,
+) -> Result<F::Output, Elapsed> {
+    tokio::time::timeout(duration, future)
+        .await
+        .map_err(|_| Elapsed)
+}
+
+#[cfg(target_arch = "wasm32")]
+pub(crate) async fn timeout<F: Future>(
+    duration: Duration,
+    future: F,
+) -> Result<F::Output, Elapsed> {
+    use futures::future::{self, Either};
+
+    let future = std::pin::pin!(future);
+    let timer = std::pin::pin!(sleep(duration));
+    match future::select(future, timer).await {
+        Either::Left((output, _)) => Ok(output),
+        Either::Right(_) => Err(Elapsed),
+    }
+}
+
+/// Like `timeout`, until an instant rather than for a duration.
+pub(crate) async fn timeout_at<F: Future>(
+    deadline: Instant,
+    future: F,
+) -> Result<F::Output, Elapsed> {
+    timeout(deadline.saturating_duration_since(Instant::now()), future).await
+}
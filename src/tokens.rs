@@ -0,0 +1,144 @@
+use serde_json::Value;
+use std::fmt;
+
+/// Context window sizes of known model families in tokens, matched by prefix.
+const CONTEXT_WINDOWS: &[(&str, u32)] = &[
+    ("gpt-4o", 128_000),
+    ("gpt-4.1", 1_047_576),
+    ("gpt-4-turbo", 128_000),
+    ("gpt-4", 8_192),
+    ("gpt-3.5-turbo", 16_385),
+    ("gpt-5", 400_000),
+    ("o1-mini", 128_000),
+    ("o1", 200_000),
+    ("o3", 200_000),
+    ("o4-mini", 200_000),
+    ("claude", 200_000),
+    ("gemini-1.5-pro", 2_097_152),
+    ("gemini", 1_048_576),
+];
+
+/// Framing tokens the chat format adds per message, and to prime the reply.
+const MESSAGE_OVERHEAD: u32 = 3;
+const REPLY_PRIMING: u32 = 3;
+
+/// Counts the tokens of `text` for the model. With the `tiktoken` feature,
+/// OpenAI models are counted exactly with their tokenizer; otherwise, and for
+/// other providers, the count is estimated at four bytes per token.
+pub fn count_tokens(model: &str, text: &str) -> u32 {
+    #[cfg(feature = "tiktoken")]
+    if let Ok(bpe) = tiktoken_rs::bpe_for_model(model) {
+        let tokens = bpe.encode_with_special_tokens(text).len();
+        return tokens.min(u32::MAX as usize) as u32;
+    }
+    let _ = model;
+    text.len().div_ceil(4).min(u32::MAX as usize) as u32
+}
+
+/// Prompt tokens of chat messages. Only text is counted, not images.
+pub(crate) fn count_message_tokens(model: &str, messages: &[Value]) -> u32 {
+    messages
+        .iter()
+        .map(|message| MESSAGE_OVERHEAD + count_tokens(model, &message_text(message)))
+        .fold(REPLY_PRIMING, u32::saturating_add)
+}
+
+fn message_text(message: &Value) -> String {
+    match &message["content"] {
+        Value::String(text) => text.clone(),
+        Value::Array(parts) => parts
+            .iter()
+            .filter_map(|part| part["text"].as_str())
+            .collect::<Vec<_>>()
+            .join("\n"),
+        _ => String::new(),
+    }
+}
+
+/// Context window sizes by model name prefix, starting from a built-in table
+/// of known models. The longest matching prefix wins.
+#[derive(Debug, Clone)]
+pub struct ContextWindows {
+    models: Vec<(String, u32)>,
+}
+
+impl Default for ContextWindows {
+    fn default() -> Self {
+        Self {
+            models: CONTEXT_WINDOWS
+                .iter()
+                .map(|(prefix, tokens)| (prefix.to_string(), *tokens))
+                .collect(),
+        }
+    }
+}
+
+impl ContextWindows {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the context window of models whose name starts with `prefix`,
+    /// e.g. a fine-tune or a local model.
+    pub fn with_model(mut self, prefix: impl Into<String>, tokens: u32) -> Self {
+        let prefix = prefix.into();
+        self.models.retain(|(known, _)| *known != prefix);
+        self.models.push((prefix, tokens));
+        self
+    }
+
+    pub fn get(&self, model: &str) -> Option<u32> {
+        self.models
+            .iter()
+            .filter(|(prefix, _)| model.starts_with(prefix.as_str()))
+            .max_by_key(|(prefix, _)| prefix.len())
+            .map(|(_, tokens)| *tokens)
+    }
+}
+
+/// What to do when a request is estimated not to fit the model's context window.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContextCheck {
+    /// Sends the request anyway, logging a warning with the `tracing` feature.
+    Warn,
+    /// Fails with `ContextOverflow` without sending the request.
+    Reject,
+}
+
+/// A request's prompt, schema and completion budget are estimated to exceed
+/// the model's context window.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ContextOverflow {
+    pub model: String,
+    pub prompt_tokens: u32,
+    pub schema_tokens: u32,
+    /// The call's `max_completion_tokens`, or 0 if unset.
+    pub completion_tokens: u32,
+    pub context_window: u32,
+}
+
+impl ContextOverflow {
+    pub fn total_tokens(&self) -> u32 {
+        self.prompt_tokens
+            .saturating_add(self.schema_tokens)
+            .saturating_add(self.completion_tokens)
+    }
+}
+
+impl fmt::Display for ContextOverflow {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Request needs about {} tokens (prompt {}, schema {}, completion {}), more than \
+             the {} token context window of {}",
+            self.total_tokens(),
+            self.prompt_tokens,
+            self.schema_tokens,
+            self.completion_tokens,
+            self.context_window,
+            self.model
+        )
+    }
+}
+
+impl std::error::Error for ContextOverflow {}
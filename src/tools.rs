@@ -0,0 +1,217 @@
+use crate::openai::{OpenAIError, OpenAiClient, Refusal};
+use schemars::JsonSchema;
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::error::Error;
+use std::fmt;
+
+type SchemaFn = fn() -> Result<Value, Box<dyn Error>>;
+
+/// A tool the model may call, with arguments described by a `JsonSchema` type.
+#[derive(Clone)]
+pub struct ToolDefinition {
+    pub name: String,
+    pub description: Option<String>,
+    parameters: SchemaFn,
+}
+
+/// The set of tools registered for a call to `call_with_tools`.
+#[derive(Clone, Default)]
+pub struct ToolSet {
+    tools: Vec<ToolDefinition>,
+    choice: Option<ToolChoice>,
+}
+
+/// Whether and which tool the model must call, sent as `tool_choice`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ToolChoice {
+    /// The model decides between answering and calling tools; the API default.
+    Auto,
+    /// The model must answer without calling tools.
+    None,
+    /// The model must call at least one tool.
+    Required,
+    /// The model must call this registered tool.
+    Named(String),
+}
+
+impl ToolChoice {
+    fn to_request_value(&self) -> Value {
+        match self {
+            ToolChoice::Auto => json!("auto"),
+            ToolChoice::None => json!("none"),
+            ToolChoice::Required => json!("required"),
+            ToolChoice::Named(name) => json!({ "type": "function", "function": { "name": name } }),
+        }
+    }
+}
+
+/// A `ToolChoice::Named` tool is not in the tool set.
+#[derive(Debug, Clone)]
+pub struct UnknownTool {
+    pub name: String,
+}
+
+impl fmt::Display for UnknownTool {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "tool choice names unregistered tool `{}`", self.name)
+    }
+}
+
+impl Error for UnknownTool {}
+
+impl ToolSet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers a tool whose arguments deserialize into `A`.
+    pub fn with_tool<A: JsonSchema + 'static>(
+        mut self,
+        name: impl Into<String>,
+        description: impl Into<String>,
+    ) -> Self {
+        self.tools.push(ToolDefinition {
+            name: name.into(),
+            description: Some(description.into()),
+            parameters: OpenAiClient::generate_schema::<A>,
+        });
+        self
+    }
+
+    /// Sets `tool_choice`, e.g. `ToolChoice::Named` to force a typed tool call
+    /// instead of a free answer.
+    pub fn with_choice(mut self, choice: ToolChoice) -> Self {
+        self.choice = Some(choice);
+        self
+    }
+
+    pub fn tools(&self) -> &[ToolDefinition] {
+        &self.tools
+    }
+
+    /// The `tool_choice` of the request body, if set.
+    pub(crate) fn choice_value(&self) -> Result<Option<Value>, UnknownTool> {
+        if let Some(ToolChoice::Named(name)) = &self.choice {
+            if !self.tools.iter().any(|tool| &tool.name == name) {
+                return Err(UnknownTool { name: name.clone() });
+            }
+        }
+        Ok(self.choice.as_ref().map(ToolChoice::to_request_value))
+    }
+
+    /// Builds the 'tools' array of the request body, generating a strict schema per tool.
+    pub(crate) fn to_request_value(&self) -> Result<Value, Box<dyn Error>> {
+        let mut tools = Vec::with_capacity(self.tools.len());
+        for tool in &self.tools {
+            let mut function = json!({
+                "name": tool.name,
+                "parameters": (tool.parameters)()?,
+                "strict": true
+            });
+            if let Some(description) = &tool.description {
+                function["description"] = json!(description);
+            }
+            tools.push(json!({
+                "type": "function",
+                "function": function
+            }));
+        }
+        Ok(Value::Array(tools))
+    }
+}
+
+/// Either the final structured answer or the tool invocations requested by the model.
+#[derive(Debug, Clone)]
+pub enum ToolResponse<T> {
+    Answer(T),
+    ToolCalls(Vec<ToolCall>),
+}
+
+/// A single tool invocation requested by the model.
+#[derive(Debug, Clone)]
+pub struct ToolCall {
+    pub id: String,
+    pub name: String,
+    /// The raw JSON-encoded arguments as returned by the model.
+    pub arguments: String,
+}
+
+impl ToolCall {
+    /// Deserializes the arguments into the tool's argument type.
+    pub fn arguments<A: DeserializeOwned>(&self) -> Result<A, Box<dyn Error>> {
+        Ok(serde_json::from_str(&self.arguments)?)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub(crate) enum ToolCallingResponse {
+    Ok(ToolChatResponse),
+    Err(OpenAIError),
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct ToolChatResponse {
+    pub choices: Vec<ToolChatChoice>,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct ToolChatChoice {
+    pub message: ToolMessage,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub(crate) struct ToolMessage {
+    content: Option<String>,
+    refusal: Option<String>,
+    #[serde(default)]
+    tool_calls: Vec<RawToolCall>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+struct RawToolCall {
+    id: String,
+    function: RawFunction,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+struct RawFunction {
+    name: String,
+    arguments: String,
+}
+
+impl ToolMessage {
+    /// Reads the model's turn; a final answer sent inside the wrapper property
+    /// added by `wrap_non_object_root` is read from that property.
+    pub(crate) fn into_response<T: DeserializeOwned>(
+        self,
+        wrapper: Option<&str>,
+    ) -> Result<ToolResponse<T>, Box<dyn Error>> {
+        if let Some(refusal) = self.refusal {
+            return Err(Box::new(Refusal { refusal }));
+        }
+        if !self.tool_calls.is_empty() {
+            let calls = self
+                .tool_calls
+                .into_iter()
+                .map(|call| ToolCall {
+                    id: call.id,
+                    name: call.function.name,
+                    arguments: call.function.arguments,
+                })
+                .collect();
+            return Ok(ToolResponse::ToolCalls(calls));
+        }
+        let content = self.content.unwrap_or_default();
+        let answer = match wrapper {
+            Some(key) => {
+                let mut output: Value = serde_json::from_str(&content)?;
+                serde_json::from_value(output[key].take())?
+            }
+            None => serde_json::from_str(&content)?,
+        };
+        Ok(ToolResponse::Answer(answer))
+    }
+}
@@ -0,0 +1,166 @@
+//! `tracing` spans for schema calls. Without the `tracing` feature every helper
+//! compiles to a no-op.
+
+use crate::encryption::EncryptionError;
+use crate::guard::GuardReport;
+use crate::openai::Usage;
+use crate::tokens::ContextOverflow;
+use reqwest::header::HeaderMap;
+use std::future::Future;
+use std::time::Duration;
+
+#[cfg(feature = "tracing")]
+pub(crate) use tracing::Span;
+
+#[cfg(not(feature = "tracing"))]
+#[derive(Debug, Clone)]
+pub(crate) struct Span;
+
+/// Root span of a `call_schema` call. Usage, latency and the request id are
+/// recorded once known.
+pub(crate) fn call_span(model: &str, schema: &str) -> Span {
+    #[cfg(feature = "tracing")]
+    {
+        use tracing::field::Empty;
+        tracing::info_span!(
+            "call_schema",
+            model,
+            schema,
+            request_id = Empty,
+            prompt_tokens = Empty,
+            completion_tokens = Empty,
+            total_tokens = Empty,
+            latency_ms = Empty,
+        )
+    }
+    #[cfg(not(feature = "tracing"))]
+    {
+        let _ = (model, schema);
+        Span
+    }
+}
+
+pub(crate) fn schema_span() -> Span {
+    #[cfg(feature = "tracing")]
+    {
+        tracing::debug_span!("generate_schema")
+    }
+    #[cfg(not(feature = "tracing"))]
+    Span
+}
+
+/// The span the caller is in, such as the call span around an HTTP request.
+pub(crate) fn current_span() -> Span {
+    #[cfg(feature = "tracing")]
+    {
+        Span::current()
+    }
+    #[cfg(not(feature = "tracing"))]
+    Span
+}
+
+pub(crate) fn http_span() -> Span {
+    #[cfg(feature = "tracing")]
+    {
+        tracing::debug_span!("http", status = tracing::field::Empty)
+    }
+    #[cfg(not(feature = "tracing"))]
+    Span
+}
+
+pub(crate) fn parse_span() -> Span {
+    #[cfg(feature = "tracing")]
+    {
+        tracing::debug_span!("parse")
+    }
+    #[cfg(not(feature = "tracing"))]
+    Span
+}
+
+/// Runs a synchronous step inside `span`.
+pub(crate) fn in_span<R>(span: &Span, f: impl FnOnce() -> R) -> R {
+    #[cfg(feature = "tracing")]
+    {
+        span.in_scope(f)
+    }
+    #[cfg(not(feature = "tracing"))]
+    {
+        let _ = span;
+        f()
+    }
+}
+
+/// Runs a future inside `span`.
+pub(crate) async fn instrument<F: Future>(span: Span, future: F) -> F::Output {
+    #[cfg(feature = "tracing")]
+    {
+        tracing::Instrument::instrument(future, span).await
+    }
+    #[cfg(not(feature = "tracing"))]
+    {
+        let _ = span;
+        future.await
+    }
+}
+
+/// Records the HTTP status on `http` and the `x-request-id` header on the
+/// call span.
+pub(crate) fn record_response(call: &Span, http: &Span, status: u16, headers: &HeaderMap) {
+    #[cfg(feature = "tracing")]
+    {
+        http.record("status", status);
+        if let Some(id) = headers.get("x-request-id").and_then(|v| v.to_str().ok()) {
+            call.record("request_id", id);
+        }
+    }
+    #[cfg(not(feature = "tracing"))]
+    let _ = (call, http, status, headers);
+}
+
+pub(crate) fn record_call(span: &Span, usage: Option<&Usage>, latency: Duration) {
+    #[cfg(feature = "tracing")]
+    {
+        span.record("latency_ms", latency.as_millis() as u64);
+        if let Some(usage) = usage {
+            span.record("prompt_tokens", usage.prompt_tokens);
+            span.record("completion_tokens", usage.completion_tokens);
+            span.record("total_tokens", usage.total_tokens);
+        }
+    }
+    #[cfg(not(feature = "tracing"))]
+    let _ = (span, usage, latency);
+}
+
+/// Warns that a cache entry could not be encrypted or decrypted and was skipped.
+pub(crate) fn warn_cache_error(err: &EncryptionError) {
+    #[cfg(feature = "tracing")]
+    tracing::warn!(error = %err, "skipped a cache entry");
+    #[cfg(not(feature = "tracing"))]
+    let _ = err;
+}
+
+/// Warns that the input guard changed a prompt before it was sent.
+pub(crate) fn warn_guarded_input(report: &GuardReport) {
+    #[cfg(feature = "tracing")]
+    tracing::warn!(
+        normalized = report.normalized,
+        removed = report.removed.len(),
+        transliterated = report.transliterated.len(),
+        "input guard changed a prompt"
+    );
+    #[cfg(not(feature = "tracing"))]
+    let _ = report;
+}
+
+/// Warns that a request is estimated to exceed the model's context window.
+pub(crate) fn warn_context_overflow(overflow: &ContextOverflow) {
+    #[cfg(feature = "tracing")]
+    tracing::warn!(
+        model = %overflow.model,
+        estimated_tokens = overflow.total_tokens(),
+        context_window = overflow.context_window,
+        "request may exceed the context window"
+    );
+    #[cfg(not(feature = "tracing"))]
+    let _ = overflow;
+}
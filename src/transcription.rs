@@ -0,0 +1,180 @@
+use crate::openai::OpenAiClient;
+use crate::options::RequestOptions;
+use crate::transport::Multipart;
+use bytes::Bytes;
+use reqwest::Method;
+use schemars::JsonSchema;
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use std::error::Error;
+
+const DEFAULT_TRANSCRIPTION_MODEL: &str = "whisper-1";
+
+/// The `response_format` of a transcription.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TranscriptFormat {
+    #[default]
+    Json,
+    /// JSON with the detected language, duration and timed segments; only
+    /// supported by `whisper-1`.
+    VerboseJson,
+    Text,
+    Srt,
+    Vtt,
+}
+
+impl TranscriptFormat {
+    fn as_str(self) -> &'static str {
+        match self {
+            TranscriptFormat::Json => "json",
+            TranscriptFormat::VerboseJson => "verbose_json",
+            TranscriptFormat::Text => "text",
+            TranscriptFormat::Srt => "srt",
+            TranscriptFormat::Vtt => "vtt",
+        }
+    }
+
+    fn is_json(self) -> bool {
+        matches!(self, TranscriptFormat::Json | TranscriptFormat::VerboseJson)
+    }
+}
+
+/// Parameters of a transcription request. The API reads the audio format
+/// from the file name's extension.
+#[derive(Debug, Clone)]
+pub struct TranscriptionOptions {
+    model: String,
+    file_name: String,
+    language: Option<String>,
+    prompt: Option<String>,
+    temperature: Option<f32>,
+    format: TranscriptFormat,
+}
+
+impl Default for TranscriptionOptions {
+    fn default() -> Self {
+        Self {
+            model: DEFAULT_TRANSCRIPTION_MODEL.to_string(),
+            file_name: "audio.mp3".to_string(),
+            language: None,
+            prompt: None,
+            temperature: None,
+            format: TranscriptFormat::default(),
+        }
+    }
+}
+
+impl TranscriptionOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// `whisper-1` by default.
+    pub fn model(mut self, model: impl Into<String>) -> Self {
+        self.model = model.into();
+        self
+    }
+
+    /// e.g. `call.wav`; `audio.mp3` by default.
+    pub fn file_name(mut self, file_name: impl Into<String>) -> Self {
+        self.file_name = file_name.into();
+        self
+    }
+
+    /// The spoken language as ISO-639-1, e.g. `de`, which improves accuracy
+    /// and latency.
+    pub fn language(mut self, language: impl Into<String>) -> Self {
+        self.language = Some(language.into());
+        self
+    }
+
+    /// Text that guides the style and vocabulary of the transcript, such as
+    /// names and jargon.
+    pub fn prompt(mut self, prompt: impl Into<String>) -> Self {
+        self.prompt = Some(prompt.into());
+        self
+    }
+
+    pub fn temperature(mut self, temperature: f32) -> Self {
+        self.temperature = Some(temperature);
+        self
+    }
+
+    pub fn format(mut self, format: TranscriptFormat) -> Self {
+        self.format = format;
+        self
+    }
+}
+
+/// A transcript. Formats other than JSON only fill `text`, with the
+/// subtitles for `Srt` and `Vtt`.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+pub struct Transcription {
+    pub text: String,
+    #[serde(default)]
+    pub language: Option<String>,
+    /// Length of the audio in seconds.
+    #[serde(default)]
+    pub duration: Option<f64>,
+    #[serde(default)]
+    pub segments: Vec<TranscriptSegment>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+pub struct TranscriptSegment {
+    /// Start and end in seconds.
+    pub start: f64,
+    pub end: f64,
+    pub text: String,
+}
+
+impl OpenAiClient {
+    /// Transcribes audio with the transcriptions endpoint next to the
+    /// client's `/chat/completions` endpoint.
+    pub async fn transcribe(
+        &self,
+        audio: impl Into<Bytes>,
+        options: &TranscriptionOptions,
+    ) -> Result<Transcription, Box<dyn Error>> {
+        let audio: Bytes = audio.into();
+        let mut form = Multipart::new();
+        form.file("file", &options.file_name, &audio);
+        form.text("model", &options.model);
+        form.text("response_format", options.format.as_str());
+        if let Some(language) = &options.language {
+            form.text("language", language);
+        }
+        if let Some(prompt) = &options.prompt {
+            form.text("prompt", prompt);
+        }
+        if let Some(temperature) = options.temperature {
+            form.text("temperature", &temperature.to_string());
+        }
+        let (content_type, body) = form.finish();
+
+        let request = self
+            .authorized(Method::POST, &self.api_url("audio/transcriptions")?)?
+            .body(&content_type, body)?;
+        let res = self.execute(request).await?;
+        if options.format.is_json() || !res.status.is_success() {
+            return self.read_billed_json(res).await;
+        }
+        let text = res.bytes().await.map_err(|err| err as Box<dyn Error>)?;
+        Ok(Transcription {
+            text: String::from_utf8_lossy(&text).into_owned(),
+            ..Default::default()
+        })
+    }
+
+    /// Transcribes the audio and extracts T from the transcript with
+    /// `call_schema_with`.
+    pub async fn call_schema_from_audio<T: DeserializeOwned + JsonSchema + Clone + 'static>(
+        &self,
+        audio: impl Into<Bytes>,
+        transcription: &TranscriptionOptions,
+        options: &RequestOptions,
+    ) -> Result<T, Box<dyn Error>> {
+        let transcript = self.transcribe(audio, transcription).await?;
+        self.call_schema_with(&transcript.text, options).await
+    }
+}
@@ -0,0 +1,521 @@
+use crate::retry;
+use crate::time::{self, Instant, SystemTime, UNIX_EPOCH};
+use bytes::{BufMut, Bytes, BytesMut};
+use futures::future::BoxFuture;
+use futures::stream::{self, BoxStream};
+use futures::{Stream, StreamExt, TryStreamExt};
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, AUTHORIZATION, CONTENT_TYPE};
+use reqwest::{Client, Method, StatusCode, Url};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::net::IpAddr;
+#[cfg(unix)]
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::AsyncRead;
+use tokio_util::io::ReaderStream;
+
+/// Error returned by an `HttpTransport`; any HTTP stack's error type fits.
+pub type TransportError = Box<dyn Error + Send + Sync>;
+
+/// Sends the client's HTTP requests. Implemented for `reqwest::Client`, which
+/// is used by default; implement it to route requests through another HTTP
+/// stack, such as hyper over a unix socket or a corporate SDK, and pass it to
+/// `OpenAiClient::with_http_transport` or `with_transport`.
+///
+/// Transports only move bytes: authentication, retries and rate limiting are
+/// applied by the client around every call.
+pub trait HttpTransport: Send + Sync {
+    fn send(&self, request: HttpRequest) -> BoxFuture<'_, Result<HttpResponse, TransportError>>;
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl HttpTransport for Client {
+    fn send(&self, request: HttpRequest) -> BoxFuture<'_, Result<HttpResponse, TransportError>> {
+        let mut builder = self
+            .request(request.method, request.url)
+            .headers(request.headers);
+        if let Some(body) = request.body {
+            builder = builder.body(body);
+        }
+        Box::pin(async move {
+            if let Some(body) = request.body_stream {
+                builder = builder.body(reqwest::Body::wrap_stream(body.take()?));
+            }
+            let res = builder.send().await?;
+            Ok(HttpResponse {
+                status: res.status(),
+                headers: res.headers().clone(),
+                body: res.bytes_stream().map_err(TransportError::from).boxed(),
+            })
+        })
+    }
+}
+
+/// On wasm32, reqwest sends with the host's `fetch`, whose futures are not
+/// `Send`; they are wrapped, as wasm32 is single-threaded.
+#[cfg(target_arch = "wasm32")]
+impl HttpTransport for Client {
+    fn send(&self, request: HttpRequest) -> BoxFuture<'_, Result<HttpResponse, TransportError>> {
+        use send_wrapper::SendWrapper;
+
+        let mut builder = self
+            .request(request.method, request.url)
+            .headers(request.headers);
+        if let Some(body) = request.body {
+            builder = builder.body(body);
+        }
+        Box::pin(async move {
+            // `fetch` takes whole bodies only.
+            if let Some(body) = request.body_stream {
+                let chunks: Vec<Bytes> = body.take()?.try_collect().await?;
+                builder = builder.body(chunks.concat());
+            }
+            let res = SendWrapper::new(builder.send()).await?;
+            Ok(HttpResponse {
+                status: res.status(),
+                headers: res.headers().clone(),
+                body: SendWrapper::new(res.bytes_stream().map_err(TransportError::from)).boxed(),
+            })
+        })
+    }
+}
+
+/// Builds an HTTP client that sends every request over the unix domain socket
+/// at `path`, e.g. `/run/ollama/ollama.sock`. The endpoint URL still selects
+/// the path (`http://localhost/api/chat`); its host is only sent as `Host`.
+#[cfg(unix)]
+pub fn unix_socket_client(path: impl AsRef<Path>) -> Result<Client, reqwest::Error> {
+    Client::builder().unix_socket(path.as_ref()).build()
+}
+
+/// Whether `url` is plain HTTP, as opposed to HTTPS or a custom transport's scheme.
+pub(crate) fn is_plain_http(url: &str) -> bool {
+    Url::parse(url).is_ok_and(|url| url.scheme() == "http")
+}
+
+/// Whether the host of `url` is this machine: `localhost` or a loopback address.
+pub(crate) fn is_loopback(url: &str) -> bool {
+    let Ok(url) = Url::parse(url) else {
+        return false;
+    };
+    match url.host_str() {
+        Some("localhost") => true,
+        Some(host) => host
+            .trim_start_matches('[')
+            .trim_end_matches(']')
+            .parse::<IpAddr>()
+            .is_ok_and(|ip| ip.is_loopback()),
+        None => false,
+    }
+}
+
+/// Returned instead of sending a request over plain HTTP to an endpoint that
+/// is not allowed to receive one.
+#[derive(Debug, Clone)]
+pub struct InsecureEndpoint {
+    pub url: String,
+}
+
+impl fmt::Display for InsecureEndpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Refusing to send over plain HTTP to {}; use https, \
+             with_insecure_loopback(true) for a server on this machine or \
+             with_insecure_remote(true) for another host",
+            self.url
+        )
+    }
+}
+
+impl Error for InsecureEndpoint {}
+
+/// A header that cannot be sent, because its name is not a valid token or its
+/// value contains control characters.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvalidHeader {
+    pub name: String,
+}
+
+impl fmt::Display for InvalidHeader {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Invalid HTTP header {:?}", self.name)
+    }
+}
+
+impl Error for InvalidHeader {}
+
+/// Returned when a privacy profile with a proxy is set on a client that sends
+/// through a custom transport; configure the proxy on the transport instead.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProxiedCustomTransport;
+
+impl fmt::Display for ProxiedCustomTransport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "A privacy profile's proxy cannot be applied to a custom transport; \
+             configure the proxy on the transport instead"
+        )
+    }
+}
+
+impl Error for ProxiedCustomTransport {}
+
+/// A URL that cannot be parsed, such as one missing its scheme.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvalidUrl {
+    pub url: String,
+}
+
+impl fmt::Display for InvalidUrl {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Invalid URL {:?}", self.url)
+    }
+}
+
+impl Error for InvalidUrl {}
+
+/// Parses a header name and value, failing with `InvalidHeader`.
+pub(crate) fn parse_header(
+    name: &str,
+    value: &str,
+) -> Result<(HeaderName, HeaderValue), InvalidHeader> {
+    let invalid = || InvalidHeader {
+        name: name.to_string(),
+    };
+    let header_name = HeaderName::try_from(name).map_err(|_| invalid())?;
+    let header_value = HeaderValue::from_bytes(value.as_bytes()).map_err(|_| invalid())?;
+    Ok((header_name, header_value))
+}
+
+/// An outgoing request, with the body already serialized, or streamed while
+/// it is sent.
+#[derive(Debug, Clone)]
+pub struct HttpRequest {
+    pub method: Method,
+    pub url: String,
+    pub headers: HeaderMap,
+    pub body: Option<Bytes>,
+    /// A body read while the request is sent, instead of `body`.
+    pub body_stream: Option<BodyStream>,
+}
+
+/// A request body read from a stream while it is sent, such as a file upload
+/// too large to hold in memory. It can be sent once; middleware and fixtures
+/// see the request without a body.
+#[derive(Clone)]
+pub struct BodyStream(Arc<Mutex<Option<ByteStream>>>);
+
+type ByteStream = BoxStream<'static, Result<Bytes, TransportError>>;
+
+impl BodyStream {
+    pub fn new(stream: impl Stream<Item = Result<Bytes, TransportError>> + Send + 'static) -> Self {
+        Self(Arc::new(Mutex::new(Some(stream.boxed()))))
+    }
+
+    /// Takes the stream, failing if the body was already sent.
+    pub fn take(
+        &self,
+    ) -> Result<BoxStream<'static, Result<Bytes, TransportError>>, TransportError> {
+        self.0
+            .lock()
+            .unwrap()
+            .take()
+            .ok_or_else(|| "The streamed request body was already sent".into())
+    }
+}
+
+impl fmt::Debug for BodyStream {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("BodyStream")
+    }
+}
+
+impl HttpRequest {
+    pub fn new(method: Method, url: impl Into<String>) -> Self {
+        Self {
+            method,
+            url: url.into(),
+            headers: HeaderMap::new(),
+            body: None,
+            body_stream: None,
+        }
+    }
+
+    /// Sets a header, failing if the name is not a valid token or the value
+    /// contains control characters.
+    pub fn header(
+        mut self,
+        name: impl AsRef<str>,
+        value: impl AsRef<str>,
+    ) -> Result<Self, InvalidHeader> {
+        let (name, value) = parse_header(name.as_ref(), value.as_ref())?;
+        self.headers.insert(name, value);
+        Ok(self)
+    }
+
+    pub fn bearer_auth(self, token: &str) -> Result<Self, InvalidHeader> {
+        self.header(AUTHORIZATION, format!("Bearer {}", token))
+    }
+
+    /// Sets a JSON body and its content type.
+    pub fn json<B: Serialize + ?Sized>(mut self, body: &B) -> Result<Self, serde_json::Error> {
+        self.body = Some(Bytes::from(serde_json::to_vec(body)?));
+        self.headers
+            .insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+        Ok(self)
+    }
+
+    /// Sets a raw body, such as a multipart form, and its content type.
+    pub fn body(
+        mut self,
+        content_type: &str,
+        body: impl Into<Bytes>,
+    ) -> Result<Self, InvalidHeader> {
+        self.body = Some(body.into());
+        self.header(CONTENT_TYPE, content_type)
+    }
+
+    /// Sets a body streamed while the request is sent, and its content type.
+    pub fn streaming_body(
+        mut self,
+        content_type: &str,
+        body: impl Stream<Item = Result<Bytes, TransportError>> + Send + 'static,
+    ) -> Result<Self, InvalidHeader> {
+        self.body_stream = Some(BodyStream::new(body));
+        self.header(CONTENT_TYPE, content_type)
+    }
+
+    /// Appends URL-encoded query parameters, failing if the URL cannot be parsed.
+    pub fn query(mut self, params: &[(String, String)]) -> Result<Self, InvalidUrl> {
+        let url = Url::parse_with_params(&self.url, params).map_err(|_| InvalidUrl {
+            url: self.url.clone(),
+        })?;
+        self.url = url.into();
+        Ok(self)
+    }
+}
+
+/// A `multipart/form-data` body.
+pub(crate) struct Multipart {
+    boundary: String,
+    body: BytesMut,
+}
+
+impl Multipart {
+    pub(crate) fn new() -> Self {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_nanos());
+        Self {
+            boundary: format!("----osc-boundary-{:x}", nanos),
+            body: BytesMut::new(),
+        }
+    }
+
+    pub(crate) fn text(&mut self, name: &str, value: &str) {
+        self.part(name, None, value.as_bytes());
+    }
+
+    pub(crate) fn file(&mut self, name: &str, file_name: &str, content: &[u8]) {
+        self.part(name, Some(file_name), content);
+    }
+
+    fn part(&mut self, name: &str, file_name: Option<&str>, content: &[u8]) {
+        self.part_header(name, file_name);
+        self.body.put_slice(content);
+        self.body.put_slice(b"\r\n");
+    }
+
+    fn part_header(&mut self, name: &str, file_name: Option<&str>) {
+        let quote = |value: &str| value.replace('"', "%22").replace(['\r', '\n'], " ");
+        self.body
+            .put_slice(format!("--{}\r\n", self.boundary).as_bytes());
+        let disposition = match file_name {
+            Some(file_name) => format!(
+                "Content-Disposition: form-data; name=\"{}\"; filename=\"{}\"\r\n\
+                 Content-Type: application/octet-stream\r\n\r\n",
+                quote(name),
+                quote(file_name)
+            ),
+            None => format!(
+                "Content-Disposition: form-data; name=\"{}\"\r\n\r\n",
+                quote(name)
+            ),
+        };
+        self.body.put_slice(disposition.as_bytes());
+    }
+
+    /// The content type and body.
+    pub(crate) fn finish(mut self) -> (String, Bytes) {
+        self.body
+            .put_slice(format!("--{}--\r\n", self.boundary).as_bytes());
+        (
+            format!("multipart/form-data; boundary={}", self.boundary),
+            self.body.freeze(),
+        )
+    }
+
+    /// Ends the form with a file part read from `reader` while the body is
+    /// sent, returning the content type and the body stream.
+    pub(crate) fn finish_with_file(
+        mut self,
+        name: &str,
+        file_name: &str,
+        reader: impl AsyncRead + Send + 'static,
+    ) -> (
+        String,
+        impl Stream<Item = Result<Bytes, TransportError>> + Send + 'static,
+    ) {
+        self.part_header(name, Some(file_name));
+        let head = self.body.split().freeze();
+        let tail = Bytes::from(format!("\r\n--{}--\r\n", self.boundary));
+        let body = stream::once(async { Ok(head) })
+            .chain(ReaderStream::new(reader).map_err(TransportError::from))
+            .chain(stream::once(async { Ok(tail) }));
+        (
+            format!("multipart/form-data; boundary={}", self.boundary),
+            body,
+        )
+    }
+}
+
+/// A response whose body arrives as a stream of chunks.
+pub struct HttpResponse {
+    pub status: StatusCode,
+    pub headers: HeaderMap,
+    pub body: BoxStream<'static, Result<Bytes, TransportError>>,
+}
+
+impl HttpResponse {
+    /// A response with a body that is already fully read.
+    pub fn new(status: StatusCode, headers: HeaderMap, body: impl Into<Bytes>) -> Self {
+        let body: Bytes = body.into();
+        Self::streaming(status, headers, stream::once(async move { Ok(body) }))
+    }
+
+    pub fn streaming(
+        status: StatusCode,
+        headers: HeaderMap,
+        body: impl Stream<Item = Result<Bytes, TransportError>> + Send + 'static,
+    ) -> Self {
+        Self {
+            status,
+            headers,
+            body: body.boxed(),
+        }
+    }
+
+    /// Reads the whole body.
+    pub async fn bytes(self) -> Result<Bytes, TransportError> {
+        let chunks: Vec<Bytes> = self.body.try_collect().await?;
+        Ok(chunks.concat().into())
+    }
+
+    /// Reads the whole body as JSON.
+    pub async fn json<T: DeserializeOwned>(self) -> Result<T, TransportError> {
+        Ok(serde_json::from_slice(&self.bytes().await?)?)
+    }
+}
+
+impl fmt::Debug for HttpResponse {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HttpResponse")
+            .field("status", &self.status)
+            .field("headers", &self.headers)
+            .finish()
+    }
+}
+
+/// The error of a request that exceeded the client's request timeout.
+pub(crate) fn timed_out(timeout: Duration) -> TransportError {
+    Box::new(io::Error::new(
+        io::ErrorKind::TimedOut,
+        format!("Request timed out after {} ms", timeout.as_millis()),
+    ))
+}
+
+/// Ends a response body with a timeout error if it is still being received
+/// at `deadline`.
+pub(crate) fn body_until(
+    body: BoxStream<'static, Result<Bytes, TransportError>>,
+    deadline: Instant,
+    timeout: Duration,
+) -> BoxStream<'static, Result<Bytes, TransportError>> {
+    stream::unfold(Some(body), move |body| async move {
+        let mut body = body?;
+        match time::timeout_at(deadline, body.next()).await {
+            Ok(Some(chunk)) => Some((chunk, Some(body))),
+            Ok(None) => None,
+            Err(_) => Some((Err(timed_out(timeout)), None)),
+        }
+    })
+    .boxed()
+}
+
+/// Transport settings for routing traffic through controlled egress, such as a
+/// research network or Tor: an optional SOCKS5 proxy (with the `socks` cargo
+/// feature), a random delay before every request, and only the headers the API
+/// requires (no `User-Agent` or `X-Client-*`).
+#[derive(Debug, Clone)]
+pub struct PrivacyProfile {
+    pub(crate) proxy: Option<String>,
+    pub(crate) max_jitter: Duration,
+    pub(crate) minimal_headers: bool,
+}
+
+impl Default for PrivacyProfile {
+    fn default() -> Self {
+        Self {
+            proxy: None,
+            max_jitter: Duration::ZERO,
+            minimal_headers: true,
+        }
+    }
+}
+
+impl PrivacyProfile {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Routes all requests through a SOCKS5 proxy, e.g. `socks5h://127.0.0.1:9050`
+    /// for a local Tor daemon. `socks5h` also resolves hostnames through the proxy.
+    #[cfg(feature = "socks")]
+    pub fn with_socks5_proxy(mut self, url: impl Into<String>) -> Self {
+        self.proxy = Some(url.into());
+        self
+    }
+
+    /// Waits a random delay between zero and `max` before every request.
+    pub fn with_jitter(mut self, max: Duration) -> Self {
+        self.max_jitter = max;
+        self
+    }
+
+    /// Whether to leave out the `User-Agent` and `X-Client-*` headers (default true).
+    pub fn with_minimal_headers(mut self, minimal: bool) -> Self {
+        self.minimal_headers = minimal;
+        self
+    }
+
+    /// Builds an HTTP client using the profile's proxy, if any. Proxies are
+    /// up to the host on wasm32.
+    pub fn http_client(&self) -> Result<Client, reqwest::Error> {
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(proxy) = &self.proxy {
+            return Client::builder().proxy(reqwest::Proxy::all(proxy)?).build();
+        }
+        Client::builder().build()
+    }
+
+    pub(crate) fn jitter(&self) -> Duration {
+        self.max_jitter.mul_f64(retry::random_fraction())
+    }
+}
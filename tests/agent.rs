@@ -0,0 +1,227 @@
+mod common;
+
+use common::MockOpenAi;
+use openai_structured_client::agent::{Agent, IterationsExhausted};
+use openai_structured_client::openai::Cancelled;
+use schemars::JsonSchema;
+use serde::Deserialize;
+use serde_json::json;
+use std::cell::{Cell, RefCell};
+use std::error::Error;
+use std::rc::Rc;
+use std::time::Duration;
+use tokio_util::sync::CancellationToken;
+use wiremock::ResponseTemplate;
+
+#[derive(Debug, Deserialize, JsonSchema)]
+struct WeatherArgs {
+    city: String,
+}
+
+#[derive(Debug, Deserialize, JsonSchema, Clone, PartialEq)]
+struct Forecast {
+    summary: String,
+}
+
+fn tool_calls(calls: &[(&str, &str, serde_json::Value)]) -> ResponseTemplate {
+    let tool_calls: Vec<_> = calls
+        .iter()
+        .map(|(id, name, arguments)| {
+            json!({
+                "id": id,
+                "type": "function",
+                "function": { "name": name, "arguments": arguments.to_string() }
+            })
+        })
+        .collect();
+    let message = json!({ "role": "assistant", "content": null, "tool_calls": tool_calls });
+    ResponseTemplate::new(200).set_body_json(common::completion(message))
+}
+
+fn weather_agent(mock: &MockOpenAi) -> Agent {
+    Agent::new(mock.client()).with_tool(
+        "get_weather",
+        "Current weather",
+        |args: WeatherArgs| async move {
+            Ok::<_, Box<dyn Error>>(json!({ "city": args.city, "celsius": 21 }))
+        },
+    )
+}
+
+#[tokio::test]
+async fn runs_tools_until_the_final_answer() {
+    let mock = MockOpenAi::start().await;
+    mock.mount(
+        tool_calls(&[
+            ("call_1", "get_weather", json!({ "city": "Oslo" })),
+            ("call_2", "get_tides", json!({})),
+        ]),
+        Some(1),
+    )
+    .await;
+    mock.respond_with_content(json!({ "summary": "Sunny, 21°C" }))
+        .await;
+
+    let forecast: Forecast = weather_agent(&mock).run("Weather in Oslo?").await.unwrap();
+    assert_eq!(forecast.summary, "Sunny, 21°C");
+
+    let bodies = mock.request_bodies().await;
+    assert_eq!(bodies.len(), 2);
+    let messages = bodies[1]["messages"].as_array().unwrap();
+    let [.., assistant, weather, tides] = messages.as_slice() else {
+        panic!("expected tool results");
+    };
+    assert_eq!(assistant["tool_calls"][0]["id"], "call_1");
+    assert_eq!(weather["role"], "tool");
+    assert_eq!(weather["tool_call_id"], "call_1");
+    assert_eq!(
+        serde_json::from_str::<serde_json::Value>(weather["content"].as_str().unwrap()).unwrap(),
+        json!({ "city": "Oslo", "celsius": 21 })
+    );
+    assert!(tides["content"]
+        .as_str()
+        .unwrap()
+        .contains("unknown tool `get_tides`"));
+}
+
+#[tokio::test]
+async fn stops_after_the_maximum_iterations() {
+    let mock = MockOpenAi::start().await;
+    mock.respond_with_tool_calls(&[("call_1", "get_weather", json!({ "city": "Oslo" }))])
+        .await;
+
+    let err = weather_agent(&mock)
+        .with_max_iterations(3)
+        .run::<Forecast>("Weather in Oslo?")
+        .await
+        .unwrap_err();
+
+    let exhausted = err.downcast_ref::<IterationsExhausted>().unwrap();
+    assert_eq!(exhausted.max_iterations, 3);
+    assert_eq!(exhausted.pending[0].id, "call_1");
+    assert_eq!(mock.request_bodies().await.len(), 3);
+}
+
+#[tokio::test]
+async fn bounds_concurrent_tools_and_reports_each_outcome() {
+    let mock = MockOpenAi::start().await;
+    mock.mount(
+        tool_calls(&[
+            ("call_1", "get_weather", json!({ "city": "Oslo" })),
+            ("call_2", "get_weather", json!({ "city": "Nowhere" })),
+            ("call_3", "get_weather", json!({ "city": "Lima" })),
+        ]),
+        Some(1),
+    )
+    .await;
+    mock.respond_with_content(json!({ "summary": "Mixed" }))
+        .await;
+
+    let (running, peak) = (Rc::new(Cell::new(0)), Rc::new(Cell::new(0)));
+    let (running_in, peak_in) = (running.clone(), peak.clone());
+    let agent = Agent::new(mock.client())
+        .with_max_concurrent_tools(2)
+        .with_tool(
+            "get_weather",
+            "Current weather",
+            move |args: WeatherArgs| {
+                let (running, peak) = (running_in.clone(), peak_in.clone());
+                async move {
+                    running.set(running.get() + 1);
+                    peak.set(peak.get().max(running.get()));
+                    tokio::time::sleep(Duration::from_millis(20)).await;
+                    running.set(running.get() - 1);
+                    if args.city == "Nowhere" {
+                        return Err::<serde_json::Value, Box<dyn Error>>("no such city".into());
+                    }
+                    Ok(json!({ "city": args.city }))
+                }
+            },
+        );
+    let run = agent
+        .run_with_executions::<Forecast>("Weather?")
+        .await
+        .unwrap();
+
+    assert_eq!(run.answer.summary, "Mixed");
+    assert_eq!(peak.get(), 2);
+    let ids: Vec<_> = run.executions.iter().map(|e| e.call.id.as_str()).collect();
+    assert_eq!(ids, ["call_1", "call_2", "call_3"]);
+    assert_eq!(run.executions[1].output, Err("no such city".to_string()));
+    assert!(run.executions[2].output.is_ok());
+
+    let messages = mock.request_bodies().await[1]["messages"].clone();
+    let results: Vec<_> = messages
+        .as_array()
+        .unwrap()
+        .iter()
+        .rev()
+        .take(3)
+        .rev()
+        .collect();
+    assert_eq!(results[1]["tool_call_id"], "call_2");
+    assert!(results[1]["content"]
+        .as_str()
+        .unwrap()
+        .contains("no such city"));
+}
+
+#[tokio::test]
+async fn cancels_running_tools_and_calls_the_cleanup_hook() {
+    let mock = MockOpenAi::start().await;
+    mock.mount(
+        tool_calls(&[("call_1", "get_weather", json!({ "city": "Oslo" }))]),
+        None,
+    )
+    .await;
+
+    let token = CancellationToken::new();
+    let (finished, cancelled) = (Rc::new(Cell::new(false)), Rc::new(RefCell::new(Vec::new())));
+    let (finished_in, cancelled_in) = (finished.clone(), cancelled.clone());
+    let (token_in, started) = (token.clone(), Rc::new(Cell::new(false)));
+    let started_in = started.clone();
+    let agent = Agent::new(mock.client())
+        .with_cancellation(token)
+        .on_cancel(move |calls| {
+            cancelled_in.replace(calls.iter().map(|call| call.id.clone()).collect());
+        })
+        .with_tool("get_weather", "Current weather", move |_: WeatherArgs| {
+            let (finished, token, started) =
+                (finished_in.clone(), token_in.clone(), started_in.clone());
+            async move {
+                started.set(true);
+                token.cancel();
+                tokio::time::sleep(Duration::from_millis(50)).await;
+                finished.set(true);
+                Ok::<_, Box<dyn Error>>(json!({}))
+            }
+        });
+    let err = agent.run::<Forecast>("Weather?").await.unwrap_err();
+
+    assert!(err.downcast_ref::<Cancelled>().is_some());
+    assert!(started.get());
+    assert!(!finished.get());
+    assert_eq!(*cancelled.borrow(), ["call_1"]);
+}
+
+#[tokio::test]
+async fn calls_the_cleanup_hook_when_a_run_is_dropped() {
+    let mock = MockOpenAi::start().await;
+    mock.mount(
+        ResponseTemplate::new(200).set_delay(Duration::from_secs(5)),
+        None,
+    )
+    .await;
+
+    let dropped = Rc::new(Cell::new(false));
+    let dropped_in = dropped.clone();
+    let agent = weather_agent(&mock).on_cancel(move |calls| {
+        assert!(calls.is_empty());
+        dropped_in.set(true);
+    });
+    let run = agent.run::<Forecast>("Weather?");
+    assert!(tokio::time::timeout(Duration::from_millis(50), run)
+        .await
+        .is_err());
+    assert!(dropped.get());
+}
@@ -0,0 +1,148 @@
+mod common;
+
+use common::{MockOpenAi, MODEL};
+use openai_structured_client::audit::{AuditLog, AuditRecord};
+use openai_structured_client::embeddings::EmbeddingsClient;
+use openai_structured_client::tools::ToolSet;
+use regex::Regex;
+use schemars::JsonSchema;
+use serde::Deserialize;
+use serde_json::json;
+use std::io::{self, Write};
+use std::sync::{Arc, Mutex};
+use wiremock::ResponseTemplate;
+
+#[derive(Debug, Deserialize, JsonSchema, Clone, PartialEq)]
+struct Sentiment {
+    label: String,
+}
+
+#[derive(Clone, Default)]
+struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+impl Write for SharedBuffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl SharedBuffer {
+    fn records(&self) -> Vec<AuditRecord> {
+        let bytes = self.0.lock().unwrap().clone();
+        String::from_utf8(bytes)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect()
+    }
+}
+
+#[tokio::test]
+async fn records_calls_with_hashed_prompts() {
+    let mock = MockOpenAi::start().await;
+    mock.respond_with_content(json!({ "label": "positive" }))
+        .await;
+    let buffer = SharedBuffer::default();
+
+    let client = mock.client().with_audit_log(AuditLog::new(buffer.clone()));
+    let _: Sentiment = client.call_schema("Great!").await.unwrap();
+
+    let records = buffer.records();
+    assert_eq!(records.len(), 1);
+    let record = &records[0];
+    assert_eq!(record.model, MODEL);
+    assert!(record
+        .schema
+        .as_deref()
+        .unwrap()
+        .ends_with("sentiment_response"));
+    assert_eq!(record.outcome, "success");
+    assert_eq!(record.prompt, None);
+    assert_eq!(record.prompt_hash.len(), 16);
+    assert_eq!(record.input_tokens, Some(12));
+    assert_eq!(record.output_tokens, Some(8));
+}
+
+#[tokio::test]
+async fn records_redacted_prompts_and_failures() {
+    let mock = MockOpenAi::start().await;
+    mock.mount(ResponseTemplate::new(500), None).await;
+    let buffer = SharedBuffer::default();
+
+    let client = mock.client().with_audit_log(
+        AuditLog::new(buffer.clone())
+            .with_prompts(true)
+            .with_redaction(Regex::new(r"\S+@\S+").unwrap()),
+    );
+    client
+        .call_text("Reply to ada@example.com")
+        .await
+        .unwrap_err();
+
+    let record = &buffer.records()[0];
+    assert_eq!(record.schema, None);
+    assert_eq!(record.outcome, "error");
+    assert!(record.error.is_some());
+    assert_eq!(record.prompt.as_deref(), Some("Reply to [REDACTED]"));
+}
+
+#[tokio::test]
+async fn records_requests_outside_schema_calls() {
+    let mock = MockOpenAi::start().await;
+    mock.respond_with_content(json!({ "label": "positive" }))
+        .await;
+    mock.mount_at(
+        "/v1/embeddings",
+        common::error_response(400, "Cannot embed ada@example.com"),
+    )
+    .await;
+    let buffer = SharedBuffer::default();
+    let client = mock.client().with_audit_log(
+        AuditLog::new(buffer.clone())
+            .with_prompts(true)
+            .with_redaction(Regex::new(r"\S+@\S+").unwrap()),
+    );
+
+    client
+        .call_with_tools::<Sentiment>("Great!", &ToolSet::new())
+        .await
+        .unwrap();
+    EmbeddingsClient::new(client, "text-embedding-3-small")
+        .embed_one("Mail ada@example.com")
+        .await
+        .unwrap_err();
+
+    let records = buffer.records();
+    assert_eq!(records.len(), 2);
+    assert_eq!(records[0].model, MODEL);
+    assert_eq!(records[0].prompt.as_deref(), Some("Great!"));
+    assert_eq!(records[0].outcome, "success");
+    assert!(records[0].schema.is_some());
+    assert_eq!(records[1].model, "text-embedding-3-small");
+    assert_eq!(records[1].prompt.as_deref(), Some("Mail [REDACTED]"));
+    assert_eq!(records[1].outcome, "error");
+}
+
+#[tokio::test]
+async fn redacts_recorded_errors() {
+    let mock = MockOpenAi::start().await;
+    mock.mount(
+        common::error_response(400, "Cannot process ada@example.com"),
+        None,
+    )
+    .await;
+    let buffer = SharedBuffer::default();
+    let client = mock.client().with_audit_log(
+        AuditLog::new(buffer.clone()).with_redaction(Regex::new(r"\S+@\S+").unwrap()),
+    );
+
+    client.call_text("Reply to Ada").await.unwrap_err();
+
+    let error = buffer.records()[0].error.clone().unwrap();
+    assert!(error.contains("[REDACTED]"));
+    assert!(!error.contains("ada@example.com"));
+}
@@ -0,0 +1,284 @@
+mod common;
+
+use common::{error_response, MockOpenAi, MODEL};
+use openai_structured_client::balance::{Deployment, LoadBalancer, Routing};
+use openai_structured_client::openai::OpenAiClient;
+use openai_structured_client::retry::RetryPolicy;
+use openai_structured_client::transport::InsecureEndpoint;
+use schemars::JsonSchema;
+use serde::Deserialize;
+use serde_json::json;
+use std::time::Duration;
+use wiremock::{Request, ResponseTemplate};
+
+#[derive(Debug, Deserialize, JsonSchema, Clone, PartialEq)]
+struct Sentiment {
+    label: String,
+}
+
+fn balanced_client(primary: &MockOpenAi, balancer: LoadBalancer) -> OpenAiClient {
+    OpenAiClient::new(
+        reqwest::Client::new(),
+        primary.endpoint(),
+        MODEL,
+        "test-key",
+    )
+    .with_insecure_loopback(true)
+    .with_transport(balancer)
+}
+
+#[tokio::test]
+async fn spreads_requests_by_weight() {
+    let (east, west) = (MockOpenAi::start().await, MockOpenAi::start().await);
+    east.respond_with_content(json!({ "label": "positive" }))
+        .await;
+    west.respond_with_content(json!({ "label": "positive" }))
+        .await;
+
+    let balancer = LoadBalancer::new(
+        reqwest::Client::new(),
+        [
+            Deployment::new(east.endpoint()).with_weight(2),
+            Deployment::new(west.endpoint()).with_header("authorization", "Bearer west-key"),
+        ],
+    );
+    let client = balanced_client(&east, balancer.clone());
+    for _ in 0..6 {
+        let _: Sentiment = client.call_schema("Great!").await.unwrap();
+    }
+
+    assert_eq!(east.requests().await.len(), 4);
+    let west_requests = west.requests().await;
+    assert_eq!(west_requests.len(), 2);
+    assert_eq!(west_requests[0].headers["authorization"], "Bearer west-key");
+    let status = balancer.status();
+    assert_eq!(status[0].requests, 4);
+    assert!(status[1].latency.is_some());
+}
+
+#[tokio::test]
+async fn skips_failing_deployments_until_they_cool_down() {
+    let (east, west) = (MockOpenAi::start().await, MockOpenAi::start().await);
+    east.mount(ResponseTemplate::new(503), None).await;
+    west.respond_with_content(json!({ "label": "positive" }))
+        .await;
+
+    let balancer = LoadBalancer::new(
+        reqwest::Client::new(),
+        [
+            Deployment::new(east.endpoint()),
+            Deployment::new(west.endpoint()),
+        ],
+    )
+    .with_health_check(1, Duration::from_secs(60));
+    let client = balanced_client(&east, balancer.clone());
+    client.call_schema::<Sentiment>("Great!").await.unwrap_err();
+    for _ in 0..3 {
+        let _: Sentiment = client.call_schema("Great!").await.unwrap();
+    }
+
+    assert_eq!(east.requests().await.len(), 1);
+    assert_eq!(west.requests().await.len(), 3);
+    let status = balancer.status();
+    assert!(!status[0].healthy);
+    assert_eq!(status[0].failures, 1);
+}
+
+fn keys_of(requests: &[Request]) -> Vec<String> {
+    requests
+        .iter()
+        .map(|request| {
+            request.headers["authorization"]
+                .to_str()
+                .unwrap()
+                .to_string()
+        })
+        .collect()
+}
+
+fn retry_at_once() -> RetryPolicy {
+    RetryPolicy {
+        max_attempts: 2,
+        base_delay: Duration::ZERO,
+        jitter: false,
+    }
+}
+
+#[tokio::test]
+async fn benches_rate_limited_api_keys() {
+    let mock = MockOpenAi::start().await;
+    mock.mount_for_key("key-a", error_response(429, "Rate limit reached"), None)
+        .await;
+    mock.respond_with_content(json!({ "label": "positive" }))
+        .await;
+
+    let balancer = LoadBalancer::for_api_keys(
+        reqwest::Client::new(),
+        mock.endpoint(),
+        ["key-a", "key-b", "key-c"],
+    );
+    let client = balanced_client(&mock, balancer.clone()).with_retry(retry_at_once());
+    for _ in 0..5 {
+        let _: Sentiment = client.call_schema("Great!").await.unwrap();
+    }
+
+    assert_eq!(
+        keys_of(&mock.requests().await),
+        [
+            "Bearer key-a",
+            "Bearer key-b",
+            "Bearer key-c",
+            "Bearer key-b",
+            "Bearer key-c",
+            "Bearer key-b",
+        ]
+    );
+    let status = balancer.status();
+    assert!(!status[0].healthy);
+    assert_eq!(status[0].rate_limited, 1);
+}
+
+#[tokio::test]
+async fn prefers_keys_rate_limited_least_recently() {
+    let mock = MockOpenAi::start().await;
+    let limited = error_response(429, "Rate limit reached").insert_header("retry-after", "0");
+    mock.mount_for_key("key-a", limited, Some(1)).await;
+    mock.respond_with_content(json!({ "label": "positive" }))
+        .await;
+
+    let balancer =
+        LoadBalancer::for_api_keys(reqwest::Client::new(), mock.endpoint(), ["key-a", "key-b"])
+            .with_health_check(3, Duration::from_millis(100))
+            .with_routing(Routing::LeastRecentlyRateLimited);
+    let client = balanced_client(&mock, balancer.clone()).with_retry(retry_at_once());
+    for _ in 0..3 {
+        let _: Sentiment = client.call_schema("Great!").await.unwrap();
+    }
+    assert_eq!(
+        keys_of(&mock.requests().await),
+        [
+            "Bearer key-a",
+            "Bearer key-b",
+            "Bearer key-b",
+            "Bearer key-b"
+        ]
+    );
+
+    // Two cooldowns later key-a has its full share again.
+    tokio::time::sleep(Duration::from_millis(250)).await;
+    for _ in 0..4 {
+        let _: Sentiment = client.call_schema("Great!").await.unwrap();
+    }
+    let keys = keys_of(&mock.requests().await[4..]);
+    assert!(keys.contains(&"Bearer key-a".to_string()));
+    assert!(balancer.status()[0].healthy);
+}
+
+#[tokio::test]
+async fn retries_on_another_key_without_waiting_out_retry_after() {
+    let mock = MockOpenAi::start().await;
+    let limited = error_response(429, "Rate limit reached").insert_header("retry-after", "30");
+    mock.mount_for_key("key-a", limited, None).await;
+    mock.respond_with_content(json!({ "label": "positive" }))
+        .await;
+
+    let balancer =
+        LoadBalancer::for_api_keys(reqwest::Client::new(), mock.endpoint(), ["key-a", "key-b"]);
+    let client = balanced_client(&mock, balancer).with_retry(retry_at_once());
+    let started = std::time::Instant::now();
+    let _: Sentiment = client.call_schema("Great!").await.unwrap();
+
+    assert!(started.elapsed() < Duration::from_secs(5));
+    assert_eq!(
+        keys_of(&mock.requests().await),
+        ["Bearer key-a", "Bearer key-b"]
+    );
+}
+
+#[tokio::test]
+async fn refuses_deployments_less_secure_than_the_endpoint() {
+    let balancer = LoadBalancer::new(
+        reqwest::Client::new(),
+        [
+            Deployment::new("https://api.openai.com/v1/chat/completions").with_weight(0),
+            Deployment::new("http://gateway.internal/v1/chat/completions"),
+        ],
+    );
+    let client = OpenAiClient::new(
+        reqwest::Client::new(),
+        "https://api.openai.com/v1/chat/completions",
+        MODEL,
+        "test-key",
+    )
+    .with_transport(balancer.clone());
+
+    let err = client.call_schema::<Sentiment>("Great!").await.unwrap_err();
+
+    assert!(err.is::<InsecureEndpoint>());
+    assert_eq!(balancer.status()[1].requests, 0);
+}
+
+#[tokio::test]
+async fn merges_deployment_queries_with_the_request_query() {
+    let (east, west) = (MockOpenAi::start().await, MockOpenAi::start().await);
+    west.respond_with_content(json!({ "label": "positive" }))
+        .await;
+
+    let balancer = LoadBalancer::new(
+        reqwest::Client::new(),
+        [
+            Deployment::new(east.endpoint()).with_weight(0),
+            Deployment::new(format!("{}?api-version=2024-06-01", west.endpoint())),
+        ],
+    );
+    let client = balanced_client(&east, balancer)
+        .with_query_param("api-version", "2023-05-15")
+        .with_query_param("user", "alice");
+    let _: Sentiment = client.call_schema("Great!").await.unwrap();
+
+    let query: Vec<(String, String)> = west.requests().await[0]
+        .url
+        .query_pairs()
+        .into_owned()
+        .collect();
+    assert_eq!(
+        query,
+        [
+            ("user".into(), "alice".into()),
+            ("api-version".into(), "2024-06-01".into())
+        ]
+    );
+}
+
+#[tokio::test]
+async fn leaves_the_rotation_alone_for_other_urls() {
+    let (east, west, other) = (
+        MockOpenAi::start().await,
+        MockOpenAi::start().await,
+        MockOpenAi::start().await,
+    );
+    for mock in [&east, &west, &other] {
+        mock.respond_with_content(json!({ "label": "positive" }))
+            .await;
+    }
+
+    let balancer = LoadBalancer::new(
+        reqwest::Client::new(),
+        [
+            Deployment::new(east.endpoint()),
+            Deployment::new(west.endpoint()),
+        ],
+    );
+    let _: Sentiment = balanced_client(&other, balancer.clone())
+        .call_schema("Great!")
+        .await
+        .unwrap();
+    let _: Sentiment = balanced_client(&east, balancer)
+        .call_schema("Great!")
+        .await
+        .unwrap();
+
+    assert_eq!(other.requests().await.len(), 1);
+    assert_eq!(east.requests().await.len(), 1);
+    assert!(west.requests().await.is_empty());
+}
@@ -0,0 +1,44 @@
+use openai_structured_client::binary::{decode_base64, Base64Bytes, BinaryError};
+use schemars::{schema_for, JsonSchema};
+use serde::Deserialize;
+use serde_json::json;
+
+#[derive(Debug, Deserialize, JsonSchema)]
+struct Export {
+    name: String,
+    csv: Base64Bytes<16>,
+}
+
+#[test]
+fn decodes_base64_fields() {
+    let export: Export =
+        serde_json::from_value(json!({ "name": "scores", "csv": "YSxiCjEsMgo=" })).unwrap();
+
+    assert_eq!(export.name, "scores");
+    assert_eq!(&*export.csv, b"a,b\n1,2\n");
+    assert_eq!(serde_json::to_value(&export.csv).unwrap(), "YSxiCjEsMgo=");
+}
+
+#[test]
+fn rejects_oversized_and_malformed_payloads() {
+    let oversized = json!({ "name": "scores", "csv": "QUFBQUFBQUFBQUFBQUFBQUFB" });
+    let err = serde_json::from_value::<Export>(oversized).unwrap_err();
+    assert!(err.to_string().contains("exceeds 16 bytes"));
+
+    let malformed = json!({ "name": "scores", "csv": "not base64!" });
+    assert!(serde_json::from_value::<Export>(malformed).is_err());
+
+    assert_eq!(
+        decode_base64("QUFBQQ==".as_bytes(), 3),
+        Err(BinaryError::TooLarge { max_bytes: 3 })
+    );
+}
+
+#[test]
+fn bounds_the_encoded_length_in_the_schema() {
+    let schema = serde_json::to_value(schema_for!(Export)).unwrap();
+    let csv = &schema["properties"]["csv"];
+
+    assert_eq!(csv["type"], "string");
+    assert_eq!(csv["maxLength"], 24);
+}
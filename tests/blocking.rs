@@ -0,0 +1,40 @@
+#![cfg(feature = "blocking")]
+
+mod common;
+
+use common::MockOpenAi;
+use openai_structured_client::blocking::OpenAiClient;
+use schemars::JsonSchema;
+use serde::Deserialize;
+use serde_json::json;
+
+#[derive(Debug, Deserialize, JsonSchema, Clone, PartialEq)]
+struct Sentiment {
+    label: String,
+}
+
+#[test]
+fn calls_without_an_async_runtime() {
+    // Only the mock server needs a runtime; the client brings its own.
+    let server_runtime = tokio::runtime::Runtime::new().unwrap();
+    let mock = server_runtime.block_on(async {
+        let mock = MockOpenAi::start().await;
+        mock.respond_with_content(json!({ "label": "positive" }))
+            .await;
+        mock
+    });
+
+    let client = OpenAiClient::new(mock.client().with_system_role("Classify sentiment.")).unwrap();
+    let sentiment: Sentiment = client.call_schema("Great!").unwrap();
+    assert_eq!(sentiment.label, "positive");
+
+    let text = client
+        .clone()
+        .run(|client| client.call_text("Great!"))
+        .unwrap();
+    assert_eq!(text, json!({ "label": "positive" }).to_string());
+
+    let bodies = server_runtime.block_on(mock.request_bodies());
+    assert_eq!(bodies.len(), 2);
+    assert_eq!(bodies[0]["messages"][0]["content"], "Classify sentiment.");
+}
@@ -0,0 +1,139 @@
+mod common;
+
+use common::{MockOpenAi, MODEL};
+use futures::future::BoxFuture;
+use openai_structured_client::builder::ConfigError;
+use openai_structured_client::credentials::{CredentialError, CredentialProvider, SecretString};
+use openai_structured_client::openai::OpenAiClient;
+use schemars::JsonSchema;
+use serde::Deserialize;
+use serde_json::json;
+
+#[derive(Debug, Deserialize, JsonSchema, Clone, PartialEq)]
+struct Sentiment {
+    label: String,
+}
+
+struct Vault;
+
+impl CredentialProvider for Vault {
+    fn token(&self) -> BoxFuture<'_, Result<SecretString, CredentialError>> {
+        Box::pin(async { Ok(SecretString::from("vault-key".to_string())) })
+    }
+}
+
+#[tokio::test]
+async fn builds_clients_from_config_files_and_the_environment() {
+    let mock = MockOpenAi::start().await;
+    mock.respond_with_content(json!({ "label": "positive" }))
+        .await;
+
+    // The only test in this binary that touches these variables.
+    std::env::set_var("BUILDER_TEST_KEY", "file-key");
+    std::env::set_var("OPENAI_ORG_ID", "org-env");
+    std::env::remove_var("OPENAI_API_KEY");
+    let path = std::env::temp_dir().join(format!("llm-client-{}.toml", std::process::id()));
+    std::fs::write(
+        &path,
+        format!(
+            "model = \"{}\"\napi_key_env = \"BUILDER_TEST_KEY\"\nbase_url = \"{}/v1/\"\n\
+             organization = \"org-file\"\nsystem_role = \"Classify sentiment.\"\n\
+             insecure_loopback = true\n",
+            MODEL,
+            mock.uri()
+        ),
+    )
+    .unwrap();
+
+    let client = OpenAiClient::builder()
+        .from_config_file(&path)
+        .unwrap()
+        .from_env()
+        .build()
+        .unwrap();
+    std::fs::remove_file(&path).unwrap();
+    let _: Sentiment = client.call_schema("Great!").await.unwrap();
+
+    let request = &mock.requests().await[0];
+    assert_eq!(request.url.path(), "/v1/chat/completions");
+    assert_eq!(request.headers["authorization"], "Bearer file-key");
+    assert_eq!(request.headers["openai-organization"], "org-env");
+    let body: serde_json::Value = serde_json::from_slice(&request.body).unwrap();
+    assert_eq!(body["messages"][0]["content"], "Classify sentiment.");
+}
+
+#[test]
+fn explains_missing_and_malformed_settings() {
+    let missing_key = OpenAiClient::builder().model(MODEL).build().err().unwrap();
+    assert_eq!(
+        missing_key.downcast_ref::<ConfigError>(),
+        Some(&ConfigError::MissingApiKey)
+    );
+
+    let bad_url = OpenAiClient::builder()
+        .api_key("sk-test")
+        .model(MODEL)
+        .base_url("api.openai.com/v1")
+        .build()
+        .err()
+        .unwrap();
+    assert!(matches!(
+        bad_url.downcast_ref::<ConfigError>(),
+        Some(ConfigError::InvalidBaseUrl { .. })
+    ));
+    assert!(bad_url.to_string().contains("api.openai.com/v1"));
+}
+
+#[test]
+fn rejects_conflicting_and_insecure_settings() {
+    let path = std::env::temp_dir().join(format!("llm-client-bad-{}.toml", std::process::id()));
+    std::fs::write(&path, "api_key = \"sk-a\"\napi_key_env = \"OTHER_KEY\"\n").unwrap();
+    let both_keys = OpenAiClient::builder().from_config_file(&path).err();
+    std::fs::write(&path, "timeout_secs = -1\n").unwrap();
+    let bad_timeout = OpenAiClient::builder().from_config_file(&path).err();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(
+        both_keys.unwrap().downcast_ref::<ConfigError>(),
+        Some(&ConfigError::ConflictingApiKeys)
+    );
+    assert!(bad_timeout.unwrap().to_string().contains("timeout_secs"));
+
+    let local = || {
+        OpenAiClient::builder()
+            .api_key("sk-test")
+            .model(MODEL)
+            .base_url("http://localhost:11434/v1")
+    };
+    let err = local().build().err().unwrap();
+    assert!(matches!(
+        err.downcast_ref::<ConfigError>(),
+        Some(ConfigError::InsecureBaseUrl { .. })
+    ));
+    assert!(local().insecure_loopback(true).build().is_ok());
+    let remote = OpenAiClient::builder()
+        .api_key("sk-test")
+        .model(MODEL)
+        .base_url("http://gateway.example.com/v1")
+        .insecure_loopback(true)
+        .build();
+    assert!(remote.is_err());
+}
+
+#[tokio::test]
+async fn builds_clients_with_a_credential_provider_instead_of_a_key() {
+    let mock = MockOpenAi::start().await;
+    mock.respond_with_content(json!({ "label": "positive" }))
+        .await;
+
+    let client = OpenAiClient::builder()
+        .credentials(Vault)
+        .model(MODEL)
+        .base_url(format!("{}/v1", mock.uri()))
+        .insecure_loopback(true)
+        .build()
+        .unwrap();
+    let _: Sentiment = client.call_schema("Great!").await.unwrap();
+
+    let request = &mock.requests().await[0];
+    assert_eq!(request.headers["authorization"], "Bearer vault-key");
+}
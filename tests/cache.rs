@@ -0,0 +1,29 @@
+use openai_structured_client::cache::{CacheBackend, MemoryCache};
+use std::time::Duration;
+
+#[test]
+fn evicts_least_recently_used_entries() {
+    let cache = MemoryCache::new(2);
+    cache.put("a", "1".into());
+    cache.put("b", "2".into());
+    assert_eq!(cache.get("a").as_deref(), Some("1"));
+
+    cache.put("c", "3".into());
+
+    assert_eq!(cache.len(), 2);
+    assert_eq!(cache.get("b"), None);
+    assert_eq!(cache.get("a").as_deref(), Some("1"));
+    assert_eq!(cache.get("c").as_deref(), Some("3"));
+}
+
+#[test]
+fn expires_entries_after_ttl() {
+    let cache = MemoryCache::new(8).with_ttl(Duration::from_millis(20));
+    cache.put("a", "1".into());
+    assert_eq!(cache.get("a").as_deref(), Some("1"));
+
+    std::thread::sleep(Duration::from_millis(40));
+
+    assert_eq!(cache.get("a"), None);
+    assert!(cache.is_empty());
+}
@@ -0,0 +1,40 @@
+use openai_structured_client::canonical::{canonical_hash, canonicalize, to_canonical_string};
+use serde::Serialize;
+use serde_json::json;
+
+#[derive(Serialize)]
+struct Extraction {
+    name: String,
+    score: f64,
+    tags: Vec<&'static str>,
+}
+
+#[test]
+fn sorts_keys_and_normalizes_numbers() {
+    let a: serde_json::Value =
+        serde_json::from_str(r#"{"b": 1.0, "a": {"y": -0.0, "x": "é\n"}, "c": [2.5, 3]}"#).unwrap();
+    let b = json!({ "c": [2.5, 3], "a": { "x": "é\n", "y": 0 }, "b": 1 });
+
+    assert_eq!(
+        canonicalize(&a),
+        r#"{"a":{"x":"é\n","y":0},"b":1,"c":[2.5,3]}"#
+    );
+    assert_eq!(canonicalize(&a), canonicalize(&b));
+    assert_eq!(canonical_hash(&a).unwrap(), canonical_hash(&b).unwrap());
+}
+
+#[test]
+fn typed_values_match_reordered_raw_json() {
+    let typed = Extraction {
+        name: "invoice".into(),
+        score: 2.0,
+        tags: vec!["a", "b"],
+    };
+    let raw = json!({ "tags": ["a", "b"], "score": 2, "name": "invoice" });
+
+    assert_eq!(to_canonical_string(&typed).unwrap(), canonicalize(&raw));
+    assert_ne!(
+        canonical_hash(&typed).unwrap(),
+        canonical_hash(&json!({ "tags": ["b", "a"], "score": 2, "name": "invoice" })).unwrap()
+    );
+}
@@ -0,0 +1,258 @@
+//! In-process mock of the chat-completions endpoint, emulating structured-output
+//! responses, refusals, API errors, rate limiting and streamed (SSE) completions.
+#![allow(dead_code)]
+
+use openai_structured_client::openai::OpenAiClient;
+use serde_json::{json, Value};
+use wiremock::matchers::{body_partial_json, header, method, path, query_param};
+use wiremock::{Mock, MockServer, Request, ResponseTemplate};
+
+pub const COMPLETIONS_PATH: &str = "/v1/chat/completions";
+pub const MODEL: &str = "gpt-4o-2024-08-06";
+
+pub struct MockOpenAi {
+    server: MockServer,
+}
+
+impl MockOpenAi {
+    pub async fn start() -> Self {
+        Self {
+            server: MockServer::start().await,
+        }
+    }
+
+    pub fn endpoint(&self) -> String {
+        format!("{}{}", self.server.uri(), COMPLETIONS_PATH)
+    }
+
+    pub fn uri(&self) -> String {
+        self.server.uri()
+    }
+
+    /// Mounts a POST response on an arbitrary path, e.g. `/v1/responses`.
+    pub async fn mount_at(&self, at: &str, response: ResponseTemplate) {
+        Mock::given(method("POST"))
+            .and(path(at))
+            .respond_with(response)
+            .mount(&self.server)
+            .await;
+    }
+
+    /// Like `mount_at`, for only the next `times` requests; earlier mounts take
+    /// precedence until exhausted.
+    pub async fn mount_at_times(&self, at: &str, response: ResponseTemplate, times: u64) {
+        Mock::given(method("POST"))
+            .and(path(at))
+            .respond_with(response)
+            .up_to_n_times(times)
+            .mount(&self.server)
+            .await;
+    }
+
+    /// Mounts a GET response on an exact path.
+    pub async fn mount_get(&self, at: &str, response: ResponseTemplate) {
+        Mock::given(method("GET"))
+            .and(path(at))
+            .respond_with(response)
+            .mount(&self.server)
+            .await;
+    }
+
+    /// Mounts a GET response on an exact path for requests with the query
+    /// parameter `name=value`.
+    pub async fn mount_get_with_query(
+        &self,
+        at: &str,
+        (name, value): (&str, &str),
+        response: ResponseTemplate,
+    ) {
+        Mock::given(method("GET"))
+            .and(path(at))
+            .and(query_param(name, value))
+            .respond_with(response)
+            .mount(&self.server)
+            .await;
+    }
+
+    /// Mounts a DELETE response on an exact path.
+    pub async fn mount_delete(&self, at: &str, response: ResponseTemplate) {
+        Mock::given(method("DELETE"))
+            .and(path(at))
+            .respond_with(response)
+            .mount(&self.server)
+            .await;
+    }
+
+    /// A client pointed at the mock server.
+    pub fn client(&self) -> OpenAiClient {
+        OpenAiClient::new(reqwest::Client::new(), self.endpoint(), MODEL, "test-key")
+            .with_insecure_loopback(true)
+    }
+
+    /// Mounts a response for every subsequent request, or only the next `times`
+    /// requests when given. Earlier mounts take precedence until exhausted.
+    pub async fn mount(&self, response: ResponseTemplate, times: Option<u64>) {
+        let mock = Mock::given(method("POST"))
+            .and(path(COMPLETIONS_PATH))
+            .respond_with(response);
+        let mock = match times {
+            Some(times) => mock.up_to_n_times(times),
+            None => mock,
+        };
+        mock.mount(&self.server).await;
+    }
+
+    /// Like `mount`, for requests authorized with the bearer token `key` only.
+    pub async fn mount_for_key(&self, key: &str, response: ResponseTemplate, times: Option<u64>) {
+        let mock = Mock::given(method("POST"))
+            .and(path(COMPLETIONS_PATH))
+            .and(header("authorization", format!("Bearer {}", key).as_str()))
+            .respond_with(response);
+        let mock = match times {
+            Some(times) => mock.up_to_n_times(times),
+            None => mock,
+        };
+        mock.mount(&self.server).await;
+    }
+
+    /// Responds with a structured output whose content is `value` serialized as a string.
+    pub async fn respond_with_content(&self, value: Value) {
+        let message = json!({ "role": "assistant", "content": value.to_string(), "refusal": null });
+        self.mount(
+            ResponseTemplate::new(200).set_body_json(completion(message)),
+            None,
+        )
+        .await;
+    }
+
+    /// Responds with a structured output to requests whose body contains `body`,
+    /// e.g. `{"temperature": 0.0}`. Takes precedence over later mounts.
+    pub async fn respond_with_content_matching(&self, body: Value, value: Value) {
+        let message = json!({ "role": "assistant", "content": value.to_string(), "refusal": null });
+        Mock::given(method("POST"))
+            .and(path(COMPLETIONS_PATH))
+            .and(body_partial_json(body))
+            .respond_with(ResponseTemplate::new(200).set_body_json(completion(message)))
+            .mount(&self.server)
+            .await;
+    }
+
+    /// Responds with `response` to requests whose body contains `body`. Takes
+    /// precedence over later mounts.
+    pub async fn mount_matching(&self, body: Value, response: ResponseTemplate) {
+        Mock::given(method("POST"))
+            .and(path(COMPLETIONS_PATH))
+            .and(body_partial_json(body))
+            .respond_with(response)
+            .mount(&self.server)
+            .await;
+    }
+
+    /// Responds with arbitrary (possibly invalid) message content for the next `times` requests.
+    pub async fn respond_with_raw_content(&self, content: &str, times: Option<u64>) {
+        let message = json!({ "role": "assistant", "content": content, "refusal": null });
+        self.mount(
+            ResponseTemplate::new(200).set_body_json(completion(message)),
+            times,
+        )
+        .await;
+    }
+
+    pub async fn respond_with_refusal(&self, refusal: &str) {
+        let message = json!({ "role": "assistant", "content": null, "refusal": refusal });
+        self.mount(
+            ResponseTemplate::new(200).set_body_json(completion(message)),
+            None,
+        )
+        .await;
+    }
+
+    /// Responds with tool calls given as (id, name, arguments) triples.
+    pub async fn respond_with_tool_calls(&self, calls: &[(&str, &str, Value)]) {
+        let tool_calls: Vec<Value> = calls
+            .iter()
+            .map(|(id, name, arguments)| {
+                json!({
+                    "id": id,
+                    "type": "function",
+                    "function": { "name": name, "arguments": arguments.to_string() }
+                })
+            })
+            .collect();
+        let message = json!({ "role": "assistant", "content": null, "tool_calls": tool_calls });
+        self.mount(
+            ResponseTemplate::new(200).set_body_json(completion(message)),
+            None,
+        )
+        .await;
+    }
+
+    pub async fn respond_with_error(&self, status: u16, message: &str) {
+        self.mount(error_response(status, message), None).await;
+    }
+
+    /// Responds with 429 and the given Retry-After header for the next `times` requests.
+    pub async fn respond_rate_limited(&self, retry_after: &str, times: u64) {
+        let response =
+            error_response(429, "Rate limit reached").insert_header("retry-after", retry_after);
+        self.mount(response, Some(times)).await;
+    }
+
+    /// Every request received so far.
+    pub async fn requests(&self) -> Vec<Request> {
+        self.server.received_requests().await.unwrap_or_default()
+    }
+
+    /// The JSON bodies of every request received so far.
+    pub async fn request_bodies(&self) -> Vec<Value> {
+        self.server
+            .received_requests()
+            .await
+            .unwrap_or_default()
+            .iter()
+            .filter(|request| request.method == wiremock::http::Method::POST)
+            .map(|request| serde_json::from_slice(&request.body).expect("request body is JSON"))
+            .collect()
+    }
+}
+
+pub fn completion(message: Value) -> Value {
+    json!({
+        "id": "chatcmpl-mock",
+        "object": "chat.completion",
+        "created": 1_700_000_000,
+        "model": MODEL,
+        "choices": [{ "index": 0, "message": message, "finish_reason": "stop" }],
+        "usage": { "prompt_tokens": 12, "completion_tokens": 8, "total_tokens": 20 }
+    })
+}
+
+pub fn error_response(status: u16, message: &str) -> ResponseTemplate {
+    ResponseTemplate::new(status).set_body_json(json!({
+        "error": { "message": message, "type": "mock_error", "param": null, "code": null }
+    }))
+}
+
+pub fn sse_response(content: &str, chunk_size: usize) -> ResponseTemplate {
+    let chars: Vec<char> = content.chars().collect();
+    let mut body = String::new();
+    for piece in chars.chunks(chunk_size.max(1)) {
+        let chunk = json!({
+            "id": "chatcmpl-mock",
+            "object": "chat.completion.chunk",
+            "created": 1_700_000_000,
+            "model": MODEL,
+            "choices": [{ "index": 0, "delta": { "content": piece.iter().collect::<String>() }, "finish_reason": null }]
+        });
+        body.push_str(&format!("data: {}\n\n", chunk));
+    }
+    let last = json!({
+        "id": "chatcmpl-mock",
+        "object": "chat.completion.chunk",
+        "created": 1_700_000_000,
+        "model": MODEL,
+        "choices": [{ "index": 0, "delta": {}, "finish_reason": "stop" }]
+    });
+    body.push_str(&format!("data: {}\n\ndata: [DONE]\n\n", last));
+    ResponseTemplate::new(200).set_body_raw(body, "text/event-stream")
+}
@@ -0,0 +1,52 @@
+mod common;
+
+use common::MockOpenAi;
+use openai_structured_client::compare::Comparison;
+use openai_structured_client::cost::{ModelPrice, PricingTable};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone)]
+struct Ticket {
+    category: String,
+    urgent: bool,
+}
+
+#[tokio::test]
+async fn reports_field_agreement_and_picks_the_cheapest_match() {
+    let large = MockOpenAi::start().await;
+    large
+        .respond_with_raw_content(r#"{"category":"billing","urgent":true}"#, None)
+        .await;
+    let small = MockOpenAi::start().await;
+    small
+        .respond_with_raw_content(r#"{"category":"billing","urgent":false}"#, None)
+        .await;
+    let broken = MockOpenAi::start().await;
+    broken.respond_with_raw_content("not json", None).await;
+
+    let pricing =
+        |input| PricingTable::new().with_model("gpt-4o", ModelPrice::new(input, 0.5, 0.5));
+    let report = Comparison::new()
+        .with_candidate("large", large.client().with_pricing(pricing(4.0)))
+        .with_candidate("small", small.client().with_pricing(pricing(0.5)))
+        .with_candidate("broken", broken.client().with_pricing(pricing(0.5)))
+        .run::<Ticket>(&["I was charged twice", "Refund please"])
+        .await;
+
+    assert_eq!(report.candidates[0].successes, 2);
+    assert_eq!(report.candidates[2].failures, 2);
+    assert_eq!(report.candidates[2].cost, None);
+    assert_eq!(report.agreement["category"][0][1], Some(1.0));
+    assert_eq!(report.agreement["urgent"][0][1], Some(0.0));
+    assert_eq!(report.agreement["urgent"][0][2], None);
+    assert_eq!(report.agreement_with("large", "small"), Some(0.5));
+
+    let cheapest = report.cheapest_agreeing_with("large", 0.5).unwrap();
+    assert_eq!(cheapest.name, "small");
+    assert_eq!(
+        report.cheapest_agreeing_with("large", 0.9).unwrap().name,
+        "large"
+    );
+    assert!(report.to_string().contains("small: 2 ok, 0 failed"));
+}
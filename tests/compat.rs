@@ -0,0 +1,123 @@
+use openai_structured_client::compat::{self, ChangeKind, SchemaChange};
+use openai_structured_client::openai::OpenAiClient;
+use serde_json::json;
+
+#[allow(dead_code)]
+mod v1 {
+    use schemars::JsonSchema;
+
+    #[derive(JsonSchema)]
+    pub enum Status {
+        Paid,
+        Open,
+        Void,
+    }
+
+    #[derive(JsonSchema)]
+    pub struct Invoice {
+        pub number: String,
+        pub status: Status,
+        pub total: f64,
+        pub note: Option<String>,
+        pub lines: Vec<Line>,
+    }
+
+    #[derive(JsonSchema)]
+    pub struct Line {
+        pub amount: f64,
+    }
+}
+
+#[allow(dead_code)]
+mod v2 {
+    use schemars::JsonSchema;
+
+    #[derive(JsonSchema)]
+    pub enum Status {
+        Paid,
+        Open,
+        Overdue,
+    }
+
+    #[derive(JsonSchema)]
+    pub struct Invoice {
+        /// Invoice number as printed.
+        pub number: String,
+        pub status: Status,
+        pub total: String,
+        pub customer: String,
+        pub discount: Option<f64>,
+        pub lines: Vec<Line>,
+    }
+
+    #[derive(JsonSchema)]
+    pub struct Line {
+        pub amount: Option<f64>,
+    }
+}
+
+#[test]
+fn classifies_schema_changes() {
+    let report = compat::compare(
+        &OpenAiClient::schema_for::<v1::Invoice>().unwrap(),
+        &OpenAiClient::schema_for::<v2::Invoice>().unwrap(),
+    );
+    let change = |path: &str, kind: ChangeKind| SchemaChange {
+        path: path.into(),
+        kind,
+    };
+
+    for expected in [
+        change("/number", ChangeKind::DescriptionChanged),
+        change(
+            "/status",
+            ChangeKind::EnumValueRemoved {
+                value: json!("Void"),
+            },
+        ),
+        change(
+            "/status",
+            ChangeKind::EnumValueAdded {
+                value: json!("Overdue"),
+            },
+        ),
+        change(
+            "/total",
+            ChangeKind::TypeChanged {
+                old: "number".into(),
+                new: "string".into(),
+            },
+        ),
+        change("/note", ChangeKind::FieldRemoved),
+        change("/customer", ChangeKind::FieldAdded { nullable: false }),
+        change("/discount", ChangeKind::FieldAdded { nullable: true }),
+        change("/lines/*/amount", ChangeKind::NullAdded),
+    ] {
+        assert!(report.changes.contains(&expected), "missing {}", expected);
+    }
+    assert_eq!(report.changes.len(), 8, "{}", report);
+    assert!(report.breaks_stored_data());
+    assert!(report.breaks_prompts());
+
+    let added = change("/discount", ChangeKind::FieldAdded { nullable: true });
+    assert!(!added.breaks_stored_data() && !added.breaks_prompts());
+    assert_eq!(
+        change("/note", ChangeKind::FieldRemoved).to_string(),
+        "/note: field removed"
+    );
+}
+
+#[test]
+fn checks_against_a_baseline_file() {
+    let path = std::env::temp_dir().join(format!("invoice-schema-{}.json", std::process::id()));
+    compat::write_baseline::<v1::Invoice>(&path).unwrap();
+
+    assert!(compat::check_baseline::<v1::Invoice>(&path)
+        .unwrap()
+        .is_unchanged());
+    assert!(compat::check_baseline::<v2::Invoice>(&path)
+        .unwrap()
+        .breaks_stored_data());
+
+    std::fs::remove_file(&path).unwrap();
+}
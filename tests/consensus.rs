@@ -0,0 +1,69 @@
+mod common;
+
+use common::MockOpenAi;
+use openai_structured_client::consensus::{majority, NoConsensus};
+use openai_structured_client::options::RequestOptions;
+use schemars::JsonSchema;
+use serde::Deserialize;
+
+#[derive(Debug, Deserialize, JsonSchema, Clone, PartialEq)]
+struct Sentiment {
+    label: String,
+}
+
+#[tokio::test]
+async fn returns_the_majority_of_sampled_outputs() {
+    let mock = MockOpenAi::start().await;
+    mock.respond_with_raw_content(r#"{"label":"neutral"}"#, Some(1))
+        .await;
+    mock.respond_with_raw_content(r#"{"label":"positive"}"#, None)
+        .await;
+
+    let options = RequestOptions::new().temperature(0.5);
+    let winner: Sentiment = mock
+        .client()
+        .call_schema_consensus("Loved it, mostly", 3, &options, majority)
+        .await
+        .unwrap();
+
+    assert_eq!(winner.label, "positive");
+    assert_eq!(mock.requests().await.len(), 3);
+}
+
+#[tokio::test]
+async fn keeps_the_samples_that_parsed() {
+    let mock = MockOpenAi::start().await;
+    mock.respond_with_raw_content("not json", Some(1)).await;
+    mock.respond_with_raw_content(r#"{"label":"negative"}"#, None)
+        .await;
+
+    let samples = mock
+        .client()
+        .call_schema_samples::<Sentiment>("Awful", 2, &RequestOptions::new())
+        .await
+        .unwrap();
+
+    assert_eq!(samples.len(), 1);
+    assert_eq!(samples[0].label, "negative");
+}
+
+#[tokio::test]
+async fn fails_when_the_consensus_function_picks_nothing() {
+    let mock = MockOpenAi::start().await;
+    mock.respond_with_raw_content(r#"{"label":"neutral"}"#, None)
+        .await;
+
+    let err = mock
+        .client()
+        .call_schema_consensus::<Sentiment, _>("Fine", 2, &RequestOptions::new(), |_| None)
+        .await
+        .unwrap_err();
+
+    assert_eq!(err.downcast_ref::<NoConsensus>().unwrap().candidates, 2);
+}
+
+#[test]
+fn majority_breaks_ties_by_first_seen() {
+    assert_eq!(majority(vec!["b", "a", "a", "b"]), Some("b"));
+    assert_eq!(majority(Vec::<&str>::new()), None);
+}
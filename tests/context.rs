@@ -0,0 +1,63 @@
+mod common;
+
+use common::MockOpenAi;
+use openai_structured_client::context::{PromptContext, UnitSystem};
+use openai_structured_client::options::RequestOptions;
+use serde_json::json;
+use std::time::{Duration, SystemTime};
+
+fn thursday_morning() -> SystemTime {
+    // 2025-10-09T08:53:20Z
+    SystemTime::UNIX_EPOCH + Duration::from_secs(1_760_000_000)
+}
+
+#[test]
+fn renders_time_in_the_configured_zone() {
+    let context = PromptContext::new()
+        .with_time_zone("Europe/Berlin")
+        .unwrap()
+        .with_now(thursday_morning())
+        .unwrap()
+        .with_locale("de-DE")
+        .with_units(UnitSystem::Metric);
+
+    assert_eq!(
+        context.render(),
+        "Context:\n\
+         - Current date and time: Thursday, 2025-10-09 10:53 (Europe/Berlin, UTC+02:00)\n\
+         - Locale: de-DE\n\
+         - Units: metric\n\
+         Interpret relative dates, times and quantities against this context."
+    );
+    assert!(PromptContext::new().with_time_zone("Mars/Olympus").is_err());
+}
+
+#[tokio::test]
+async fn appends_the_context_to_the_system_prompt() {
+    let mock = MockOpenAi::start().await;
+    mock.respond_with_raw_content("Tuesday", None).await;
+    let utc = PromptContext::new().with_now(thursday_morning()).unwrap();
+    let client = mock
+        .client()
+        .with_system_role("You schedule meetings.")
+        .with_context(utc.clone());
+
+    client.call_text("When is next Tuesday?").await.unwrap();
+    let tokyo = utc.with_time_zone("Asia/Tokyo").unwrap();
+    let options = RequestOptions::new().context(tokyo);
+    client
+        .call_text_with("When is next Tuesday?", &options)
+        .await
+        .unwrap();
+
+    let bodies = mock.request_bodies().await;
+    let system = bodies[0]["messages"][0]["content"].as_str().unwrap();
+    assert!(system.starts_with("You schedule meetings.\n\nContext:\n"));
+    assert!(system.contains("Thursday, 2025-10-09 08:53 (UTC, UTC+00:00)"));
+    let system = bodies[1]["messages"][0]["content"].as_str().unwrap();
+    assert!(system.contains("17:53 (Asia/Tokyo, UTC+09:00)"));
+    assert_eq!(
+        bodies[1]["messages"][1],
+        json!({ "role": "user", "content": "When is next Tuesday?" })
+    );
+}
@@ -0,0 +1,256 @@
+mod common;
+
+use common::MockOpenAi;
+use openai_structured_client::cache::MemoryCache;
+use openai_structured_client::cost::{
+    Budget, BudgetExceeded, ModelPrice, PricingTable, UnpricedBudget,
+};
+use openai_structured_client::embeddings::EmbeddingsClient;
+use openai_structured_client::openai::{PromptTokensDetails, Usage};
+use openai_structured_client::options::RequestOptions;
+use openai_structured_client::tools::ToolSet;
+use schemars::JsonSchema;
+use serde::Deserialize;
+use serde_json::json;
+use std::time::Duration;
+use wiremock::ResponseTemplate;
+
+#[derive(Debug, Deserialize, JsonSchema, Clone)]
+struct Label {
+    label: String,
+}
+
+fn close(a: f64, b: f64) -> bool {
+    (a - b).abs() < 1e-12
+}
+
+#[test]
+fn prices_cached_prompt_tokens_separately() {
+    let usage = Usage {
+        prompt_tokens: 1_000_000,
+        completion_tokens: 500_000,
+        total_tokens: 1_500_000,
+        prompt_tokens_details: Some(PromptTokensDetails {
+            cached_tokens: 500_000,
+        }),
+        ..Default::default()
+    };
+
+    let cost = ModelPrice::new(2.0, 8.0, 0.5).cost(&usage);
+
+    assert!(close(cost, 1.0 + 0.25 + 4.0));
+}
+
+#[test]
+fn looks_up_prices_by_longest_prefix() {
+    let pricing = PricingTable::new().with_model("gpt-4o", ModelPrice::new(1.0, 2.0, 0.5));
+
+    assert_eq!(pricing.get("gpt-4o-2024-08-06").unwrap().input, 1.0);
+    assert_eq!(pricing.get("gpt-4o-mini").unwrap().input, 0.15);
+    assert!(pricing.get("llama3.2").is_none());
+}
+
+#[tokio::test]
+async fn accumulates_cost_per_call_and_per_tag() {
+    let mock = MockOpenAi::start().await;
+    mock.respond_with_content(json!({ "label": "spam" })).await;
+    let pricing = PricingTable::new().with_model("gpt-4o", ModelPrice::new(2.0, 10.0, 1.0));
+    let client = mock
+        .client()
+        .with_pricing(pricing)
+        .with_cache(MemoryCache::new(16));
+
+    let acme = RequestOptions::new().tag("acme");
+    let response = client
+        .call_schema_with_meta::<Label>("Buy now", &acme)
+        .await
+        .unwrap();
+    let cached = client
+        .call_schema_with_meta::<Label>("Buy now", &acme)
+        .await
+        .unwrap();
+    client
+        .call_schema_with::<Label>("Hello", &RequestOptions::new().tag("globex"))
+        .await
+        .unwrap();
+
+    assert_eq!(cached.value.label, "spam");
+    let per_call = (12.0 * 2.0 + 8.0 * 10.0) / 1_000_000.0;
+    assert!(close(response.meta.cost.unwrap(), per_call));
+    assert_eq!(cached.meta.cost, Some(0.0));
+    let summary = client.take_cost_summary();
+    assert_eq!(summary.responses, 2);
+    assert_eq!(summary.input_tokens, 24);
+    assert_eq!(summary.output_tokens, 16);
+    assert!(close(summary.total_cost, 2.0 * per_call));
+    assert!(close(summary.by_tag["acme"], per_call));
+    assert_eq!(client.cost_summary().responses, 0);
+    let stats = client.stats();
+    assert!(close(stats.overall.total_cost, 2.0 * per_call));
+    assert!(close(stats.by_tag["acme"].total_cost, per_call));
+}
+
+#[tokio::test]
+async fn counts_unpriced_responses_without_a_pricing_table() {
+    let mock = MockOpenAi::start().await;
+    mock.respond_with_raw_content("Hi", None).await;
+    let client = mock.client();
+
+    client.call_text("Greet me").await.unwrap();
+
+    let summary = client.cost_summary();
+    assert_eq!(summary.unpriced_responses, 1);
+    assert_eq!(summary.total_cost, 0.0);
+    assert_eq!(summary.output_tokens, 8);
+    assert_eq!(client.stats().overall.total_tokens, 20);
+}
+
+#[test]
+fn prices_mini_reasoning_models_apart_from_their_family() {
+    let pricing = PricingTable::new();
+    assert_eq!(pricing.get("o1-mini-2024-09-12").unwrap().input, 1.10);
+    assert_eq!(pricing.get("o1-2024-12-17").unwrap().input, 15.00);
+    assert_eq!(pricing.get("o3-mini").unwrap().output, 4.40);
+    assert_eq!(pricing.get("o3").unwrap().output, 8.00);
+    assert_eq!(pricing.get("o4-mini").unwrap().output, 4.40);
+}
+
+#[tokio::test]
+async fn records_the_spend_of_failed_and_repaired_calls() {
+    let mock = MockOpenAi::start().await;
+    mock.respond_with_raw_content("not json", Some(2)).await;
+    mock.respond_with_content(json!({ "label": "spam" })).await;
+    let pricing = PricingTable::new().with_model("gpt-4o", ModelPrice::new(1.0, 1.0, 1.0));
+    let client = mock.client().with_pricing(pricing);
+
+    assert!(client.call_schema::<Label>("One").await.is_err());
+    let stats = client.stats();
+    assert_eq!(stats.overall.total_tokens, 20);
+    assert!(stats.overall.total_cost > 0.0);
+
+    let _: Label = client
+        .clone()
+        .with_repair(1)
+        .call_schema("Two")
+        .await
+        .unwrap();
+    let stats = client.stats();
+    assert_eq!(stats.overall.total_tokens, 60);
+    assert!(close(
+        stats.overall.total_cost,
+        client.cost_summary().total_cost
+    ));
+}
+
+#[tokio::test]
+async fn attributes_tool_calls_to_their_tag() {
+    let mock = MockOpenAi::start().await;
+    mock.respond_with_content(json!({ "label": "spam" })).await;
+    let pricing = PricingTable::new().with_model("gpt-4o", ModelPrice::new(1.0, 1.0, 1.0));
+    let client = mock.client().with_pricing(pricing);
+
+    client
+        .call_with_tools_with::<Label>("One", &ToolSet::new(), &RequestOptions::new().tag("agent"))
+        .await
+        .unwrap();
+
+    let summary = client.cost_summary();
+    assert!(summary.by_tag["agent"] > 0.0);
+    assert!(close(summary.by_tag["agent"], summary.total_cost));
+}
+
+#[tokio::test]
+async fn refuses_calls_once_the_budget_is_spent() {
+    let mock = MockOpenAi::start().await;
+    mock.respond_with_content(json!({ "label": "spam" })).await;
+    let client = mock
+        .client()
+        .with_budget(Budget::new().max_tokens(30))
+        .unwrap();
+
+    client.call_schema::<Label>("One").await.unwrap();
+    client.call_schema::<Label>("Two").await.unwrap();
+    let err = client.call_schema::<Label>("Three").await.unwrap_err();
+
+    let exceeded = err.downcast_ref::<BudgetExceeded>().unwrap();
+    assert_eq!(exceeded.spent_tokens, 40);
+    assert_eq!(mock.requests().await.len(), 2);
+}
+
+#[tokio::test]
+async fn frees_budget_as_spending_leaves_the_window() {
+    let mock = MockOpenAi::start().await;
+    mock.respond_with_raw_content("Hi", None).await;
+    let pricing = PricingTable::new().with_model("gpt-4o", ModelPrice::new(0.5, 0.5, 0.5));
+    let client = mock
+        .client()
+        .with_pricing(pricing)
+        .with_budget(
+            Budget::new()
+                .max_usd(0.000_01)
+                .per(Duration::from_millis(100)),
+        )
+        .unwrap();
+
+    client.call_text("Greet me").await.unwrap();
+    assert!(client.call_text("Greet me").await.is_err());
+    tokio::time::sleep(Duration::from_millis(150)).await;
+
+    client.call_text("Greet me").await.unwrap();
+    let stats = client.stats();
+    assert_eq!(stats.overall.total_tokens, 40);
+    assert!(close(
+        stats.overall.total_cost,
+        client.cost_summary().total_cost
+    ));
+}
+
+#[tokio::test]
+async fn rejects_usd_budgets_without_prices() {
+    let mock = MockOpenAi::start().await;
+
+    let err = mock
+        .client()
+        .with_budget(Budget::new().max_usd(1.0))
+        .err()
+        .unwrap();
+    assert_eq!(
+        err,
+        UnpricedBudget {
+            model: common::MODEL.into()
+        }
+    );
+
+    let fine_tuned = PricingTable::new().with_model("ft:", ModelPrice::new(1.0, 1.0, 1.0));
+    let client = mock
+        .client()
+        .with_model("ft:custom")
+        .with_pricing(fine_tuned);
+    assert!(client.with_budget(Budget::new().max_usd(1.0)).is_ok());
+}
+
+#[tokio::test]
+async fn counts_embeddings_against_the_budget() {
+    let mock = MockOpenAi::start().await;
+    mock.mount_at(
+        "/v1/embeddings",
+        ResponseTemplate::new(200).set_body_json(json!({
+            "data": [{ "index": 0, "embedding": [1.0, 0.0] }],
+            "model": "text-embedding-3-small",
+            "usage": { "prompt_tokens": 4, "total_tokens": 4 },
+        })),
+    )
+    .await;
+    let client = mock
+        .client()
+        .with_budget(Budget::new().max_tokens(4))
+        .unwrap();
+    let embeddings = EmbeddingsClient::new(client.clone(), "text-embedding-3-small");
+
+    embeddings.embed_one("a").await.unwrap();
+    let err = embeddings.embed_one("b").await.unwrap_err();
+
+    assert!(err.downcast_ref::<BudgetExceeded>().is_some());
+    assert_eq!(mock.requests().await.len(), 1);
+    assert_eq!(client.cost_summary().input_tokens, 4);
+}
@@ -0,0 +1,76 @@
+mod common;
+
+use common::MockOpenAi;
+use futures::future::BoxFuture;
+use openai_structured_client::credentials::{
+    CredentialError, CredentialProvider, CredentialUnavailable, SecretString,
+};
+use schemars::JsonSchema;
+use serde::Deserialize;
+use serde_json::json;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+#[derive(Debug, Deserialize, JsonSchema, Clone, PartialEq)]
+struct Sentiment {
+    label: String,
+}
+
+/// Hands out a new key on every call, as a secrets manager rotating keys would.
+#[derive(Default)]
+struct RotatingKeys {
+    calls: AtomicUsize,
+}
+
+impl CredentialProvider for RotatingKeys {
+    fn token(&self) -> BoxFuture<'_, Result<SecretString, CredentialError>> {
+        let n = self.calls.fetch_add(1, Ordering::SeqCst);
+        Box::pin(async move { Ok(SecretString::from(format!("key-{}", n))) })
+    }
+}
+
+struct Unavailable;
+
+impl CredentialProvider for Unavailable {
+    fn token(&self) -> BoxFuture<'_, Result<SecretString, CredentialError>> {
+        Box::pin(async { Err("vault is sealed".into()) })
+    }
+}
+
+#[tokio::test]
+async fn fetches_the_key_for_every_request() {
+    let mock = MockOpenAi::start().await;
+    mock.respond_with_content(json!({ "label": "positive" }))
+        .await;
+    let client = mock.client().with_credentials(RotatingKeys::default());
+
+    for _ in 0..2 {
+        let _: Sentiment = client.call_schema("Great!").await.unwrap();
+    }
+
+    let keys: Vec<_> = mock
+        .requests()
+        .await
+        .iter()
+        .map(|request| {
+            request.headers["authorization"]
+                .to_str()
+                .unwrap()
+                .to_string()
+        })
+        .collect();
+    assert_eq!(keys, ["Bearer key-0", "Bearer key-1"]);
+}
+
+#[tokio::test]
+async fn fails_without_sending_when_no_key_is_available() {
+    let mock = MockOpenAi::start().await;
+    mock.respond_with_content(json!({ "label": "positive" }))
+        .await;
+    let client = mock.client().with_credentials(Unavailable);
+
+    let err = client.call_schema::<Sentiment>("Great!").await.unwrap_err();
+
+    let unavailable = err.downcast_ref::<CredentialUnavailable>().unwrap();
+    assert_eq!(unavailable.source.to_string(), "vault is sealed");
+    assert!(mock.requests().await.is_empty());
+}
@@ -0,0 +1,60 @@
+mod common;
+
+use common::MockOpenAi;
+use openai_structured_client::completion::StructuredCompletion;
+use openai_structured_client::dynamic::{DynSchema, DynStructuredClient};
+use openai_structured_client::options::RequestOptions;
+use schemars::JsonSchema;
+use serde::Deserialize;
+use serde_json::json;
+
+#[derive(Debug, Deserialize, JsonSchema, Clone, PartialEq)]
+struct Sentiment {
+    label: String,
+}
+
+#[tokio::test]
+async fn calls_with_runtime_schemas() {
+    let mock = MockOpenAi::start().await;
+    mock.respond_with_content(json!({ "city": "Oslo" })).await;
+    let schema = json!({
+        "type": "object",
+        "properties": { "city": { "type": "string" } },
+        "required": ["city"],
+        "additionalProperties": false
+    });
+
+    let client: Box<dyn DynStructuredClient> = Box::new(mock.client());
+    let value = client
+        .call_schema_value(
+            &DynSchema::new("plugin_city", schema.clone()),
+            "Where?",
+            &RequestOptions::default(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(value, json!({ "city": "Oslo" }));
+
+    let body = &mock.request_bodies().await[0];
+    let format = &body["response_format"]["json_schema"];
+    assert_eq!(format["name"], "plugin_city");
+    assert_eq!(format["schema"], schema);
+}
+
+#[tokio::test]
+async fn adapts_dynamic_clients_to_typed_outputs() {
+    let mock = MockOpenAi::start().await;
+    mock.respond_with_content(json!({ "label": "positive" }))
+        .await;
+
+    let client: Box<dyn DynStructuredClient> = Box::new(mock.client());
+    let sentiment: Sentiment = client.call_schema("Great!").await.unwrap();
+    assert_eq!(sentiment.label, "positive");
+
+    let body = &mock.request_bodies().await[0];
+    let expected = DynSchema::of::<Sentiment>().unwrap();
+    assert_eq!(
+        body["response_format"]["json_schema"]["name"],
+        expected.name
+    );
+}
@@ -0,0 +1,111 @@
+mod common;
+
+use common::{MockOpenAi, MODEL};
+use openai_structured_client::embeddings::{cosine_similarity, EmbeddingsClient};
+use openai_structured_client::openai::{OpenAiClient, UnknownApiBase};
+use openai_structured_client::retry::RetryPolicy;
+use serde_json::{json, Value};
+use std::time::Duration;
+use wiremock::ResponseTemplate;
+
+#[tokio::test]
+async fn embeds_in_batches_next_to_the_completions_endpoint() {
+    let mock = MockOpenAi::start().await;
+    mock.mount_at(
+        "/v1/embeddings",
+        ResponseTemplate::new(200).set_body_json(json!({
+            "data": [
+                { "index": 1, "embedding": [0.0, 1.0] },
+                { "index": 0, "embedding": [1.0, 0.0] },
+            ],
+            "usage": { "prompt_tokens": 4, "total_tokens": 4 },
+        })),
+    )
+    .await;
+
+    let embeddings = EmbeddingsClient::new(mock.client(), "text-embedding-3-small")
+        .with_dimensions(2)
+        .with_batch_size(2);
+    let (vectors, usage) = embeddings
+        .embed_with_usage(&["a", "b", "c", "d"])
+        .await
+        .unwrap();
+
+    assert_eq!(vectors.len(), 4);
+    assert_eq!(vectors[0], vec![1.0, 0.0]);
+    assert_eq!(vectors[1], vec![0.0, 1.0]);
+    assert_eq!(usage.prompt_tokens, 8);
+
+    let bodies = mock.request_bodies().await;
+    assert_eq!(bodies.len(), 2);
+    assert_eq!(bodies[1]["input"], json!(["c", "d"]));
+    assert_eq!(bodies[1]["model"], "text-embedding-3-small");
+    assert_eq!(bodies[1]["dimensions"], 2);
+    let requests = mock.requests().await;
+    assert_eq!(requests[0].headers["authorization"], "Bearer test-key");
+}
+
+fn embedding_response() -> Value {
+    json!({
+        "data": [{ "index": 0, "embedding": [1.0, 0.0] }],
+        "usage": { "prompt_tokens": 1, "total_tokens": 1 },
+    })
+}
+
+#[tokio::test]
+async fn needs_an_api_base_next_to_other_endpoints() {
+    let mock = MockOpenAi::start().await;
+    mock.mount_at(
+        "/v1/embeddings",
+        ResponseTemplate::new(200).set_body_json(embedding_response()),
+    )
+    .await;
+    let azure = format!(
+        "{}/openai/deployments/gpt-4o/chat/completions?api-version=2024-06-01",
+        mock.uri()
+    );
+    let client = OpenAiClient::new(reqwest::Client::new(), &azure, MODEL, "test-key")
+        .with_insecure_loopback(true);
+
+    let err = EmbeddingsClient::new(client.clone(), "text-embedding-3-small")
+        .embed_one("a")
+        .await
+        .unwrap_err();
+    assert_eq!(
+        err.downcast_ref::<UnknownApiBase>().unwrap().resource,
+        "embeddings"
+    );
+    assert!(mock.requests().await.is_empty());
+
+    let client = client.with_api_base(format!("{}/v1", mock.uri()));
+    let embeddings = EmbeddingsClient::new(client, "text-embedding-3-small");
+    assert_eq!(embeddings.embed_one("a").await.unwrap(), vec![1.0, 0.0]);
+}
+
+#[tokio::test]
+async fn retries_rate_limited_batches() {
+    let mock = MockOpenAi::start().await;
+    mock.mount_at_times("/v1/embeddings", ResponseTemplate::new(429), 1)
+        .await;
+    mock.mount_at(
+        "/v1/embeddings",
+        ResponseTemplate::new(200).set_body_json(embedding_response()),
+    )
+    .await;
+    let client = mock.client().with_retry(RetryPolicy {
+        max_attempts: 2,
+        base_delay: Duration::ZERO,
+        jitter: false,
+    });
+
+    let embeddings = EmbeddingsClient::new(client, "text-embedding-3-small");
+    assert_eq!(embeddings.embed_one("a").await.unwrap(), vec![1.0, 0.0]);
+    assert_eq!(mock.requests().await.len(), 2);
+}
+
+#[test]
+fn compares_embeddings_by_angle() {
+    assert_eq!(cosine_similarity(&[1.0, 0.0], &[2.0, 0.0]), 1.0);
+    assert_eq!(cosine_similarity(&[1.0, 0.0], &[0.0, 3.0]), 0.0);
+    assert_eq!(cosine_similarity(&[0.0, 0.0], &[1.0, 0.0]), 0.0);
+}
@@ -0,0 +1,48 @@
+use openai_structured_client::encryption::{FieldEncryptor, StaticKey};
+use serde_json::json;
+
+fn encryptor() -> FieldEncryptor {
+    FieldEncryptor::new(StaticKey::new("k1", [7; 32]))
+        .with_field("/email")
+        .with_field("/contacts/*/phone")
+}
+
+#[test]
+fn encrypts_designated_fields_only() {
+    let original = json!({
+        "name": "Ada",
+        "email": "ada@example.com",
+        "contacts": [{ "kind": "home", "phone": "555-0100" }, { "kind": "work", "phone": null }]
+    });
+    let mut value = original.clone();
+
+    encryptor().encrypt(&mut value).unwrap();
+
+    assert_eq!(value["name"], "Ada");
+    assert_eq!(value["contacts"][1]["kind"], "work");
+    for field in [
+        &value["email"],
+        &value["contacts"][0]["phone"],
+        &value["contacts"][1]["phone"],
+    ] {
+        assert!(field.as_str().unwrap().starts_with("enc:v1:k1:"));
+    }
+    assert!(!value.to_string().contains("555-0100"));
+
+    encryptor().decrypt(&mut value).unwrap();
+    assert_eq!(value, original);
+}
+
+#[test]
+fn rejects_ciphertext_moved_to_another_field() {
+    let mut value = json!({ "email": "ada@example.com", "contacts": [] });
+    encryptor().encrypt(&mut value).unwrap();
+
+    let mut moved = json!({ "contacts": [{ "phone": value["email"].clone() }] });
+    let err = encryptor().decrypt(&mut moved).unwrap_err();
+
+    assert_eq!(
+        err.to_string(),
+        "Field encryption error: cannot decrypt /contacts/0/phone"
+    );
+}
@@ -0,0 +1,70 @@
+mod common;
+
+use common::MockOpenAi;
+use openai_structured_client::fallback::ContentParser;
+use openai_structured_client::options::RequestOptions;
+use schemars::JsonSchema;
+use serde::Deserialize;
+use serde_json::json;
+
+#[derive(Debug, Deserialize, JsonSchema, Clone, PartialEq)]
+struct Person {
+    name: String,
+    age: u32,
+}
+
+#[test]
+fn repairs_fenced_trailing_comma_and_truncated_json() {
+    let content = "Sure! ```json\n{\"name\": \"Ada\", \"tags\": [\"math\",], \"note\": \"cut";
+    assert_eq!(
+        ContentParser::RepairedJson.parse(content),
+        Some(json!({ "name": "Ada", "tags": ["math"], "note": "cut" }))
+    );
+    assert_eq!(ContentParser::Json.parse(content), None);
+}
+
+#[test]
+fn reads_yaml_and_key_value_lines() {
+    assert_eq!(
+        ContentParser::Yaml.parse("```yaml\nname: Ada\nage: 36\n```"),
+        Some(json!({ "name": "Ada", "age": 36 }))
+    );
+    assert_eq!(ContentParser::Yaml.parse("Ada is 36."), None);
+    assert_eq!(
+        ContentParser::KeyValue.parse("Here you go\n- Name = \"Ada\",\n- Age: 36"),
+        Some(json!({ "name": "Ada", "age": 36 }))
+    );
+}
+
+#[tokio::test]
+async fn falls_back_through_the_chain_and_reports_the_parser() {
+    let mock = MockOpenAi::start().await;
+    mock.respond_with_raw_content("Name: Ada Lovelace, born 1815\nAge: 36", None)
+        .await;
+
+    let options = RequestOptions::new().parsers(ContentParser::chain());
+    let response = mock
+        .client()
+        .call_schema_with_meta::<Person>("Who was Ada?", &options)
+        .await
+        .unwrap();
+
+    assert_eq!(response.value.age, 36);
+    assert_eq!(response.value.name, "Ada Lovelace, born 1815");
+    assert_eq!(response.meta.parser, ContentParser::KeyValue);
+}
+
+#[tokio::test]
+async fn only_parses_strict_json_by_default() {
+    let mock = MockOpenAi::start().await;
+    mock.respond_with_raw_content("name: Ada\nage: 36", None)
+        .await;
+
+    let err = mock
+        .client()
+        .call_schema::<Person>("Who was Ada?")
+        .await
+        .unwrap_err();
+
+    assert!(err.to_string().starts_with("Failed to parse LLM output"));
+}
@@ -0,0 +1,73 @@
+#![cfg(feature = "ffi")]
+
+mod common;
+
+use common::MockOpenAi;
+use openai_structured_client::ffi::{
+    osc_call, osc_client_free, osc_client_new, osc_string_free, OscClient, OSC_INVALID_ARGUMENT,
+    OSC_OK,
+};
+use serde_json::{json, Value};
+use std::ffi::{CStr, CString};
+use std::ptr;
+
+fn take_string(string: *mut std::ffi::c_char) -> String {
+    let owned = unsafe { CStr::from_ptr(string) }
+        .to_str()
+        .unwrap()
+        .to_string();
+    unsafe { osc_string_free(string) };
+    owned
+}
+
+#[test]
+fn calls_through_the_c_abi() {
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    let mock = runtime.block_on(async {
+        let mock = MockOpenAi::start().await;
+        mock.respond_with_content(json!({ "city": "Oslo" })).await;
+        mock
+    });
+
+    let config = CString::new(
+        json!({
+            "endpoint": mock.endpoint(),
+            "model": common::MODEL,
+            "api_key": "test-key",
+            "insecure_loopback": true
+        })
+        .to_string(),
+    )
+    .unwrap();
+    let mut client: *mut OscClient = ptr::null_mut();
+    let mut error = ptr::null_mut();
+    let code = unsafe { osc_client_new(config.as_ptr(), &mut client, &mut error) };
+    assert_eq!(code, OSC_OK);
+
+    let request = CString::new(
+        json!({
+            "schema_name": "city",
+            "schema": {
+                "type": "object",
+                "properties": { "city": { "type": "string" } },
+                "required": ["city"],
+                "additionalProperties": false
+            },
+            "prompt": "Where?"
+        })
+        .to_string(),
+    )
+    .unwrap();
+    let mut out = ptr::null_mut();
+    let code = unsafe { osc_call(client, request.as_ptr(), &mut out) };
+    assert_eq!(code, OSC_OK);
+    let output: Value = serde_json::from_str(&take_string(out)).unwrap();
+    assert_eq!(output, json!({ "city": "Oslo" }));
+
+    let invalid = CString::new("{}").unwrap();
+    let code = unsafe { osc_call(client, invalid.as_ptr(), &mut out) };
+    assert_eq!(code, OSC_INVALID_ARGUMENT);
+    assert!(take_string(out).contains("missing field"));
+
+    unsafe { osc_client_free(client) };
+}
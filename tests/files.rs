@@ -0,0 +1,184 @@
+mod common;
+
+use common::MockOpenAi;
+use futures::future::BoxFuture;
+use futures::TryStreamExt;
+use openai_structured_client::files::FilePurpose;
+use openai_structured_client::openai::OpenAiClient;
+use openai_structured_client::transport::{
+    HttpRequest, HttpResponse, HttpTransport, TransportError,
+};
+use serde_json::json;
+use std::sync::{Arc, Mutex};
+use wiremock::ResponseTemplate;
+
+fn file_object(id: &str, purpose: &str) -> serde_json::Value {
+    json!({
+        "id": id,
+        "object": "file",
+        "bytes": 120,
+        "created_at": 1_700_000_000,
+        "filename": "requests.jsonl",
+        "purpose": purpose,
+    })
+}
+
+#[tokio::test]
+async fn uploads_from_a_reader_and_manages_files() {
+    let mock = MockOpenAi::start().await;
+    mock.mount_at(
+        "/v1/files",
+        ResponseTemplate::new(200).set_body_json(file_object("file-1", "batch")),
+    )
+    .await;
+    mock.mount_get(
+        "/v1/files/file-1",
+        ResponseTemplate::new(200).set_body_json(file_object("file-1", "batch")),
+    )
+    .await;
+    mock.mount_get(
+        "/v1/files/file-2/content",
+        ResponseTemplate::new(200).set_body_string("{\"custom_id\":\"a\"}\n"),
+    )
+    .await;
+    mock.mount_delete(
+        "/v1/files/file-1",
+        ResponseTemplate::new(200)
+            .set_body_json(json!({ "id": "file-1", "object": "file", "deleted": true })),
+    )
+    .await;
+    let client = mock.client();
+
+    let jsonl: &[u8] = b"{\"custom_id\":\"a\"}\n";
+    let file = client
+        .upload_file(jsonl, "requests.jsonl", FilePurpose::Batch)
+        .await
+        .unwrap();
+    assert_eq!(file.id, "file-1");
+    assert_eq!(file.purpose, FilePurpose::Batch);
+    assert_eq!(client.retrieve_file("file-1").await.unwrap(), file);
+    assert_eq!(
+        client.file_content("file-2").await.unwrap(),
+        "{\"custom_id\":\"a\"}\n"
+    );
+    assert!(client.delete_file("file-1").await.unwrap());
+
+    let requests = mock.requests().await;
+    let content_type = requests[0].headers["content-type"].to_str().unwrap();
+    assert!(content_type.starts_with("multipart/form-data; boundary="));
+    let form = String::from_utf8_lossy(&requests[0].body);
+    assert!(form.contains("name=\"purpose\"\r\n\r\nbatch\r\n"));
+    assert!(form.contains("filename=\"requests.jsonl\"\r\nContent-Type: application/octet-stream\r\n\r\n{\"custom_id\":\"a\"}\n\r\n"));
+}
+
+#[tokio::test]
+async fn lists_every_page_of_files() {
+    let mock = MockOpenAi::start().await;
+    mock.mount_get_with_query(
+        "/v1/files",
+        ("after", "file-1"),
+        ResponseTemplate::new(200).set_body_json(json!({
+            "object": "list",
+            "data": [file_object("file-2", "fine-tune-results")],
+            "has_more": false,
+        })),
+    )
+    .await;
+    mock.mount_get(
+        "/v1/files",
+        ResponseTemplate::new(200).set_body_json(json!({
+            "object": "list",
+            "data": [file_object("file-1", "fine-tune")],
+            "has_more": true,
+        })),
+    )
+    .await;
+
+    let files = mock.client().list_files(None).await.unwrap();
+    let purposes: Vec<_> = files.into_iter().map(|file| file.purpose).collect();
+    assert_eq!(
+        purposes,
+        [FilePurpose::FineTune, FilePurpose::FineTuneResults]
+    );
+
+    mock.client()
+        .list_files(Some(FilePurpose::FineTune))
+        .await
+        .unwrap();
+    let requests = mock.requests().await;
+    assert_eq!(requests.len(), 4);
+    assert!(requests[2]
+        .url
+        .query()
+        .unwrap()
+        .contains("purpose=fine-tune"));
+}
+
+#[tokio::test]
+async fn sends_and_reads_purposes_it_does_not_know() {
+    let mock = MockOpenAi::start().await;
+    mock.mount_at(
+        "/v1/files",
+        ResponseTemplate::new(200).set_body_json(file_object("file-1", "responses_input")),
+    )
+    .await;
+
+    let purpose = FilePurpose::Other("responses_input".into());
+    let file = mock
+        .client()
+        .upload_file(&b"{}"[..], "input.json", purpose.clone())
+        .await
+        .unwrap();
+
+    assert_eq!(file.purpose, purpose);
+    let form = String::from_utf8_lossy(&mock.requests().await[0].body).into_owned();
+    assert!(form.contains("name=\"purpose\"\r\n\r\nresponses_input\r\n"));
+}
+
+/// Reads streamed request bodies, answering with a file object.
+#[derive(Clone, Default)]
+struct StreamedBodies(Arc<Mutex<Vec<Vec<u8>>>>);
+
+impl HttpTransport for StreamedBodies {
+    fn send(&self, request: HttpRequest) -> BoxFuture<'_, Result<HttpResponse, TransportError>> {
+        Box::pin(async move {
+            assert!(request.body.is_none());
+            let chunks: Vec<bytes::Bytes> =
+                request.body_stream.unwrap().take()?.try_collect().await?;
+            self.0.lock().unwrap().push(chunks.concat());
+            let body = file_object("file-1", "batch").to_string();
+            Ok(HttpResponse::new(
+                reqwest::StatusCode::OK,
+                reqwest::header::HeaderMap::new(),
+                body,
+            ))
+        })
+    }
+}
+
+#[tokio::test]
+async fn streams_uploads_through_the_transport() {
+    let transport = StreamedBodies::default();
+    let client = OpenAiClient::new(
+        reqwest::Client::new(),
+        "https://api.example.com/v1/chat/completions",
+        common::MODEL,
+        "test-key",
+    )
+    .with_transport(transport.clone());
+
+    let file = client
+        .upload_file(
+            &b"{\"custom_id\":\"a\"}\n"[..],
+            "requests.jsonl",
+            FilePurpose::Batch,
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(file.id, "file-1");
+    let bodies = transport.0.lock().unwrap();
+    let form = String::from_utf8_lossy(&bodies[0]);
+    assert!(form.contains("\r\n\r\n{\"custom_id\":\"a\"}\n\r\n--"));
+    assert!(form.ends_with("--\r\n"));
+}
@@ -0,0 +1,68 @@
+mod common;
+
+use common::{MockOpenAi, MODEL};
+use openai_structured_client::fixtures::{FixtureTransport, MissingFixture};
+use openai_structured_client::openai::OpenAiClient;
+use schemars::JsonSchema;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::path::PathBuf;
+
+#[derive(Debug, Deserialize, JsonSchema, Clone, PartialEq)]
+struct Sentiment {
+    label: String,
+}
+
+/// Removes the fixture directory when the test ends, even if it fails.
+struct TempDir(PathBuf);
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+#[tokio::test]
+async fn replays_recorded_responses_without_the_network() {
+    let temp = TempDir(std::env::temp_dir().join(format!("llm-fixtures-{}", std::process::id())));
+    let dir = &temp.0;
+    let mock = MockOpenAi::start().await;
+    mock.respond_with_content(json!({ "label": "positive" }))
+        .await;
+
+    let recorder = mock
+        .client()
+        .with_secret_header("Helicone-Auth", "Bearer sk-helicone")
+        .unwrap()
+        .with_header("Cookie", "session=s3cr3t")
+        .unwrap()
+        .with_organization("org-acme")
+        .with_project("proj-acme")
+        .with_transport(FixtureTransport::record(dir, reqwest::Client::new()));
+    let recorded: Sentiment = recorder.call_schema("Great!").await.unwrap();
+
+    let files: Vec<_> = std::fs::read_dir(dir).unwrap().collect();
+    assert_eq!(files.len(), 1);
+    let fixture: Value =
+        serde_json::from_slice(&std::fs::read(files[0].as_ref().unwrap().path()).unwrap()).unwrap();
+    assert_eq!(fixture["request"]["headers"]["authorization"], "REDACTED");
+    assert_eq!(fixture["request"]["headers"]["helicone-auth"], "REDACTED");
+    assert!(!fixture.to_string().contains("test-key"));
+    for secret in ["sk-helicone", "s3cr3t", "org-acme", "proj-acme"] {
+        assert!(!fixture.to_string().contains(secret));
+    }
+
+    let endpoint = mock.endpoint();
+    drop(mock);
+    let replayer = OpenAiClient::new(reqwest::Client::new(), &endpoint, MODEL, "other-key")
+        .with_insecure_loopback(true)
+        .with_transport(FixtureTransport::replay(dir));
+    let replayed: Sentiment = replayer.call_schema("Great!").await.unwrap();
+    assert_eq!(replayed, recorded);
+
+    let err = replayer
+        .call_schema::<Sentiment>("Terrible!")
+        .await
+        .unwrap_err();
+    assert!(err.downcast_ref::<MissingFixture>().is_some());
+}
@@ -0,0 +1,89 @@
+mod common;
+
+use common::MockOpenAi;
+use openai_structured_client::options::{Instruction, RequestOptions};
+use schemars::JsonSchema;
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+#[derive(Debug, Deserialize, JsonSchema, Clone, PartialEq)]
+struct Sentiment {
+    label: String,
+}
+
+fn roles(body: &Value) -> Vec<(String, String)> {
+    body["messages"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|message| {
+            (
+                message["role"].as_str().unwrap().to_string(),
+                message["content"].as_str().unwrap_or_default().to_string(),
+            )
+        })
+        .collect()
+}
+
+#[tokio::test]
+async fn sends_instruction_messages_with_their_roles() {
+    let mock = MockOpenAi::start().await;
+    mock.respond_with_content(json!({ "label": "positive" }))
+        .await;
+    let client = mock
+        .client()
+        .with_system_role("You classify sentiment.")
+        .with_instructions([
+            Instruction::developer("Answer in lowercase."),
+            Instruction::system("Never mention competitors."),
+        ]);
+
+    let _: Sentiment = client.call_schema("Great!").await.unwrap();
+    let _: Sentiment = client
+        .call_schema_with(
+            "Great!",
+            &RequestOptions::new()
+                .system_role("You rate reviews.")
+                .instructions([Instruction::developer("Be terse.")]),
+        )
+        .await
+        .unwrap();
+    let _: Sentiment = client
+        .with_model("o3-mini")
+        .call_schema("Great!")
+        .await
+        .unwrap();
+
+    let bodies = mock.request_bodies().await;
+    let expected = |pairs: &[(&str, &str)]| -> Vec<(String, String)> {
+        pairs
+            .iter()
+            .map(|(role, content)| (role.to_string(), content.to_string()))
+            .collect()
+    };
+    assert_eq!(
+        roles(&bodies[0]),
+        expected(&[
+            ("system", "You classify sentiment."),
+            ("developer", "Answer in lowercase."),
+            ("system", "Never mention competitors."),
+            ("user", "Great!"),
+        ])
+    );
+    assert_eq!(
+        roles(&bodies[1]),
+        expected(&[
+            ("system", "You rate reviews."),
+            ("developer", "Be terse."),
+            ("user", "Great!"),
+        ])
+    );
+    let reasoning_roles: Vec<String> = roles(&bodies[2])
+        .into_iter()
+        .map(|(role, _)| role)
+        .collect();
+    assert_eq!(
+        reasoning_roles,
+        ["developer", "developer", "developer", "user"]
+    );
+}
@@ -0,0 +1,66 @@
+use openai_structured_client::limits::{Limit, SchemaLimits};
+use serde_json::json;
+
+#[test]
+fn reports_path_of_violated_limit() {
+    let schema = json!({
+        "type": "object",
+        "properties": {
+            "items": {
+                "type": "array",
+                "items": { "$ref": "#/definitions/Item" }
+            }
+        },
+        "definitions": {
+            "Item": {
+                "type": "object",
+                "properties": { "sku": { "type": "string", "pattern": "^[A-Z]+$" } }
+            }
+        }
+    });
+
+    let err = SchemaLimits::openai().validate(&schema).unwrap_err();
+
+    assert_eq!(err.path, "#/definitions/Item/properties/sku");
+    assert_eq!(err.limit, Limit::UnsupportedKeyword("pattern".into()));
+    assert_eq!(
+        err.to_string(),
+        "Schema limit exceeded at #/definitions/Item/properties/sku: unsupported keyword `pattern`"
+    );
+}
+
+#[test]
+fn measures_depth_through_references_and_strips_keywords() {
+    let mut nested =
+        json!({ "type": "object", "properties": { "leaf": { "type": "integer", "minimum": 0 } } });
+    for _ in 0..5 {
+        nested = json!({ "type": "object", "properties": { "child": nested } });
+    }
+    let limits = SchemaLimits::openai();
+
+    let err = limits.apply(&mut nested.clone()).unwrap_err();
+    assert_eq!(err.limit, Limit::Depth { max: 5 });
+
+    let mut shallow = nested["properties"]["child"].clone();
+    limits.apply(&mut shallow).unwrap();
+    let leaf = &shallow["properties"]["child"]["properties"]["child"]["properties"]["child"]
+        ["properties"]["child"]["properties"]["leaf"];
+    assert_eq!(leaf, &json!({ "type": "integer" }));
+}
+
+#[test]
+fn counts_properties_across_definitions() {
+    let properties: serde_json::Map<_, _> = (0..60)
+        .map(|i| (format!("f{}", i), json!({ "type": "string" })))
+        .collect();
+    let schema = json!({
+        "type": "object",
+        "properties": properties,
+        "definitions": { "Other": { "type": "object", "properties": properties } }
+    });
+
+    let err = SchemaLimits::openai().validate(&schema).unwrap_err();
+
+    assert_eq!(err.path, "#/definitions/Other");
+    assert_eq!(err.limit, Limit::TotalProperties { max: 100 });
+}
@@ -0,0 +1,47 @@
+use openai_structured_client::completion::StructuredCompletion;
+use openai_structured_client::mock::MockClient;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, PartialEq)]
+struct Sentiment {
+    label: String,
+    score: f32,
+}
+
+async fn classify(client: &impl StructuredCompletion, text: &str) -> Result<String, String> {
+    let sentiment: Sentiment = client
+        .call_schema(&format!("Classify: {}", text))
+        .await
+        .map_err(|err| err.to_string())?;
+    Ok(sentiment.label)
+}
+
+#[tokio::test]
+async fn replays_scripted_responses_in_order() {
+    let client = MockClient::new()
+        .with_response(Sentiment {
+            label: "positive".into(),
+            score: 0.9,
+        })
+        .with_response(json!({ "label": "negative", "score": 0.2 }))
+        .with_refusal("Not allowed")
+        .with_error("boom");
+
+    assert_eq!(classify(&client, "great").await.unwrap(), "positive");
+    assert_eq!(classify(&client, "awful").await.unwrap(), "negative");
+    assert_eq!(
+        classify(&client, "x").await.unwrap_err(),
+        "LLM refusal: Not allowed"
+    );
+    assert_eq!(
+        classify(&client, "y").await.unwrap_err(),
+        "Mock error: boom"
+    );
+    assert_eq!(
+        classify(&client, "z").await.unwrap_err(),
+        "Mock error: no scripted response left"
+    );
+    assert_eq!(client.prompts()[0], "Classify: great");
+}
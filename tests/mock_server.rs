@@ -0,0 +1,1896 @@
+mod common;
+
+use common::MockOpenAi;
+use openai_structured_client::cache::{CacheBackend, MemoryCache};
+use openai_structured_client::content::ImagePart;
+use openai_structured_client::cost::{ModelPrice, PricingTable};
+use openai_structured_client::encryption::{FieldEncryptor, KeyProvider, StaticKey};
+use openai_structured_client::guard::InputGuard;
+use openai_structured_client::limits::{SchemaLimitError, SchemaLimits};
+use openai_structured_client::middleware::{Middleware, RequestParts, ResponseParts};
+use openai_structured_client::openai::{
+    ApiFlavor, Cancelled, DeadlineExceeded, DryRun, LocalServer, OpenAiClient, ParseError, Provider,
+};
+use openai_structured_client::options::RequestOptions;
+use openai_structured_client::partial::Degraded;
+use openai_structured_client::refusal::{PromptAdjustment, RefusalPolicy, RefusalsExhausted};
+use openai_structured_client::retry::RetryPolicy;
+use openai_structured_client::stored::ListStoredOptions;
+use openai_structured_client::tools::{ToolChoice, ToolResponse, ToolSet, UnknownTool};
+use openai_structured_client::transport::{
+    self, HttpRequest, HttpResponse, HttpTransport, InsecureEndpoint, InvalidHeader, InvalidUrl,
+    PrivacyProfile, TransportError,
+};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio_util::sync::CancellationToken;
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, PartialEq)]
+struct Review {
+    explanation: String,
+    incorrect_words: Option<Vec<String>>,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+struct WeatherArgs {
+    city: String,
+}
+
+/// Enum with a hand-written inline schema, so `Option<Priority>` is generated
+/// as a nullable type rather than an `anyOf` with a reference.
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "lowercase")]
+enum Priority {
+    Low,
+    High,
+}
+
+impl JsonSchema for Priority {
+    fn schema_name() -> String {
+        "Priority".into()
+    }
+
+    fn is_referenceable() -> bool {
+        false
+    }
+
+    fn json_schema(_: &mut schemars::gen::SchemaGenerator) -> schemars::schema::Schema {
+        schemars::schema::SchemaObject {
+            instance_type: Some(schemars::schema::InstanceType::String.into()),
+            enum_values: Some(vec![json!("low"), json!("high")]),
+            ..Default::default()
+        }
+        .into()
+    }
+}
+
+#[derive(Debug, Deserialize, JsonSchema, Clone, PartialEq)]
+struct Task {
+    title: String,
+    priority: Option<Priority>,
+    labels: Option<BTreeMap<String, String>>,
+}
+
+#[derive(Debug, Deserialize, JsonSchema, Clone, PartialEq)]
+struct Backlog {
+    tasks: Vec<Task>,
+    next: Option<Task>,
+    history: Vec<Option<Priority>>,
+}
+
+#[tokio::test]
+async fn parses_structured_output() {
+    let mock = MockOpenAi::start().await;
+    mock.respond_with_content(json!({ "explanation": "typo", "incorrect_words": ["penn"] }))
+        .await;
+
+    let review: Review = mock.client().call_schema("Check: penn").await.unwrap();
+
+    assert_eq!(
+        review,
+        Review {
+            explanation: "typo".into(),
+            incorrect_words: Some(vec!["penn".into()]),
+        }
+    );
+}
+
+#[tokio::test]
+async fn sends_strict_schema_and_messages() {
+    let mock = MockOpenAi::start().await;
+    mock.respond_with_content(json!({ "explanation": "ok", "incorrect_words": null }))
+        .await;
+
+    let client = mock.client().with_system_role("You are a tutor.");
+    let _: Review = client.call_schema("Check this").await.unwrap();
+
+    let body = &mock.request_bodies().await[0];
+    assert_eq!(body["model"], common::MODEL);
+    assert_eq!(
+        body["messages"][0],
+        json!({ "role": "system", "content": "You are a tutor." })
+    );
+    assert_eq!(
+        body["messages"][1],
+        json!({ "role": "user", "content": "Check this" })
+    );
+    let format = &body["response_format"]["json_schema"];
+    assert_eq!(format["strict"], true);
+    assert_eq!(format["schema"]["additionalProperties"], false);
+    assert_eq!(
+        format["schema"]["required"],
+        json!(["explanation", "incorrect_words"])
+    );
+}
+
+#[tokio::test]
+async fn merges_request_options_into_body() {
+    let mock = MockOpenAi::start().await;
+    mock.respond_with_content(json!({ "explanation": "ok", "incorrect_words": null }))
+        .await;
+
+    let options = RequestOptions::new()
+        .temperature(0.0)
+        .seed(42)
+        .max_completion_tokens(256)
+        .stop(["END"])
+        .user("user-1");
+    let _: Review = mock
+        .client()
+        .call_schema_with("...", &options)
+        .await
+        .unwrap();
+
+    let body = &mock.request_bodies().await[0];
+    assert_eq!(body["temperature"], 0.0);
+    assert_eq!(body["seed"], 42);
+    assert_eq!(body["max_completion_tokens"], 256);
+    assert_eq!(body["stop"], json!(["END"]));
+    assert_eq!(body["user"], "user-1");
+    assert!(body.get("top_p").is_none());
+}
+
+#[tokio::test]
+async fn returns_usage_and_metadata() {
+    let mock = MockOpenAi::start().await;
+    mock.respond_with_content(json!({ "explanation": "ok", "incorrect_words": null }))
+        .await;
+
+    let response = mock
+        .client()
+        .call_schema_with_meta::<Review>("...", &RequestOptions::default())
+        .await
+        .unwrap();
+
+    assert_eq!(response.value.explanation, "ok");
+    assert_eq!(response.meta.id, "chatcmpl-mock");
+    assert_eq!(response.meta.model, common::MODEL);
+    assert_eq!(response.meta.finish_reason.as_deref(), Some("stop"));
+    assert!(!response.meta.is_truncated());
+    let usage = response.meta.usage.unwrap();
+    assert_eq!(usage.total_tokens, 20);
+    assert_eq!(usage.cached_tokens(), 0);
+}
+
+#[tokio::test]
+async fn aggregates_stats_per_tag() {
+    let mock = MockOpenAi::start().await;
+    mock.respond_with_content(json!({ "explanation": "ok", "incorrect_words": null }))
+        .await;
+
+    let client = mock.client();
+    let options = RequestOptions::new().tag("review");
+    for _ in 0..3 {
+        let _: Review = client.call_schema_with("...", &options).await.unwrap();
+    }
+    let _: Review = client.call_schema("...").await.unwrap();
+
+    let stats = client.stats();
+    assert_eq!(stats.overall.calls, 4);
+    assert_eq!(stats.overall.success_rate, 1.0);
+    assert_eq!(stats.overall.total_tokens, 80);
+    assert_eq!(stats.by_tag["review"].calls, 3);
+    assert!(stats.overall.p95_latency >= stats.overall.p50_latency);
+}
+
+#[tokio::test]
+async fn batches_preserve_order_and_isolate_failures() {
+    let mock = MockOpenAi::start().await;
+    for i in 0..5u64 {
+        let prompt = format!("prompt {}", i);
+        let content = match i {
+            3 => "not json".to_string(),
+            _ => json!({ "explanation": prompt, "incorrect_words": null }).to_string(),
+        };
+        let message = json!({ "role": "assistant", "content": content, "refusal": null });
+        // Later prompts answer sooner, so completion order differs from input order.
+        let response = wiremock::ResponseTemplate::new(200)
+            .set_body_json(common::completion(message))
+            .set_delay(Duration::from_millis(20 * (5 - i)));
+        mock.mount_matching(
+            json!({ "messages": [{ "role": "user", "content": prompt }] }),
+            response,
+        )
+        .await;
+    }
+
+    let prompts: Vec<String> = (0..5).map(|i| format!("prompt {}", i)).collect();
+    let results = mock.client().call_schema_batch::<Review>(prompts, 5).await;
+
+    assert_eq!(results.len(), 5);
+    for (i, result) in results.iter().enumerate() {
+        match result {
+            Ok(review) => assert_eq!(review.explanation, format!("prompt {}", i)),
+            Err(_) => assert_eq!(i, 3),
+        }
+    }
+    assert!(results[3].is_err());
+}
+
+#[tokio::test]
+async fn identifies_client_in_headers() {
+    let mock = MockOpenAi::start().await;
+    mock.respond_with_content(json!({ "explanation": "ok", "incorrect_words": null }))
+        .await;
+
+    let client = mock
+        .client()
+        .with_app_name("grader/1.0")
+        .with_client_header("Operation", "review");
+    let _: Review = client.call_schema("...").await.unwrap();
+
+    let request = &mock.requests().await[0];
+    let user_agent = request.headers["user-agent"].to_str().unwrap();
+    assert!(user_agent.starts_with("openai-structured-client/"));
+    assert!(user_agent.ends_with(" grader/1.0"));
+    assert_eq!(request.headers["x-client-operation"], "review");
+}
+
+#[tokio::test]
+async fn sends_organization_and_project_headers() {
+    let mock = MockOpenAi::start().await;
+    mock.respond_with_content(json!({ "explanation": "ok", "incorrect_words": null }))
+        .await;
+
+    let client = mock
+        .client()
+        .with_organization("org-acme")
+        .with_project("proj_billing")
+        .with_privacy_profile(PrivacyProfile::new())
+        .unwrap();
+    let _: Review = client.call_schema("...").await.unwrap();
+
+    let request = &mock.requests().await[0];
+    assert_eq!(request.headers["openai-organization"], "org-acme");
+    assert_eq!(request.headers["openai-project"], "proj_billing");
+}
+
+#[tokio::test]
+async fn sends_default_headers_and_query_params() {
+    let mock = MockOpenAi::start().await;
+    mock.respond_with_content(json!({ "explanation": "ok", "incorrect_words": null }))
+        .await;
+
+    let client = mock
+        .client()
+        .with_header("Helicone-Auth", "Bearer old")
+        .unwrap()
+        .with_header("helicone-auth", "Bearer sk-helicone")
+        .unwrap()
+        .with_query_param("api-version", "2024-06-01")
+        .with_privacy_profile(PrivacyProfile::new())
+        .unwrap();
+    let _: Review = client.call_schema("...").await.unwrap();
+
+    let request = &mock.requests().await[0];
+    assert_eq!(request.headers["helicone-auth"], "Bearer sk-helicone");
+    let query: Vec<(String, String)> = request.url.query_pairs().into_owned().collect();
+    assert_eq!(query, [("api-version".into(), "2024-06-01".into())]);
+
+    let err = mock
+        .client()
+        .with_header("Helicone Auth", "x")
+        .err()
+        .unwrap();
+    assert_eq!(err.name, "Helicone Auth");
+    assert!(mock.client().with_header("X-Id", "a\nb").is_err());
+}
+
+#[tokio::test]
+async fn fails_requests_that_cannot_be_built() {
+    let mock = MockOpenAi::start().await;
+    mock.respond_with_content(json!({ "explanation": "ok", "incorrect_words": null }))
+        .await;
+
+    let err = mock
+        .client()
+        .with_app_name("grader\n1.0")
+        .call_schema::<Review>("...")
+        .await
+        .unwrap_err();
+    assert_eq!(
+        err.downcast_ref::<InvalidHeader>().unwrap().name,
+        "user-agent"
+    );
+    assert!(mock.requests().await.is_empty());
+
+    let request = HttpRequest::new(reqwest::Method::GET, "not a url");
+    assert!(request.clone().header("X Id", "a").is_err());
+    let unkeyed = BTreeMap::from([(vec![1u8], 1)]);
+    assert!(request.clone().json(&unkeyed).is_err());
+    let params = [("limit".to_string(), "1".to_string())];
+    let err = request.query(&params).unwrap_err();
+    assert_eq!(
+        err,
+        InvalidUrl {
+            url: "not a url".into()
+        }
+    );
+}
+
+#[tokio::test]
+async fn sends_images_as_content_parts() {
+    let mock = MockOpenAi::start().await;
+    mock.respond_with_content(json!({ "explanation": "ok", "incorrect_words": null }))
+        .await;
+
+    let options = RequestOptions::new()
+        .image(ImagePart::Url("https://example.com/receipt.png".into()))
+        .image(ImagePart::from_bytes("image/png", b"png"));
+    let _: Review = mock
+        .client()
+        .call_schema_with("Extract the receipt", &options)
+        .await
+        .unwrap();
+
+    let content = &mock.request_bodies().await[0]["messages"][0]["content"];
+    assert_eq!(
+        content[0],
+        json!({ "type": "text", "text": "Extract the receipt" })
+    );
+    assert_eq!(
+        content[1]["image_url"]["url"],
+        "https://example.com/receipt.png"
+    );
+    assert_eq!(content[2]["image_url"]["url"], "data:image/png;base64,cG5n");
+}
+
+#[tokio::test]
+async fn speaks_the_responses_api() {
+    let mock = MockOpenAi::start().await;
+    let output = json!({ "explanation": "ok", "incorrect_words": ["penn"] });
+    mock.mount_at(
+        "/v1/responses",
+        wiremock::ResponseTemplate::new(200).set_body_json(json!({
+            "id": "resp_1",
+            "object": "response",
+            "created_at": 1_700_000_000,
+            "model": common::MODEL,
+            "status": "completed",
+            "output": [
+                { "type": "reasoning", "summary": [] },
+                {
+                    "type": "message",
+                    "role": "assistant",
+                    "content": [{ "type": "output_text", "text": output.to_string() }]
+                }
+            ],
+            "usage": { "input_tokens": 30, "output_tokens": 10, "total_tokens": 40 }
+        })),
+    )
+    .await;
+
+    let client = OpenAiClient::new(
+        reqwest::Client::new(),
+        format!("{}/v1/responses", mock.uri()),
+        common::MODEL,
+        "test-key",
+    )
+    .with_insecure_loopback(true)
+    .with_api_flavor(ApiFlavor::Responses)
+    .with_system_role("You are a tutor.");
+    let response = client
+        .call_schema_with_meta::<Review>(
+            "Check",
+            &RequestOptions::new()
+                .max_completion_tokens(100)
+                .stop(["END"])
+                .seed(7)
+                .frequency_penalty(0.5)
+                .presence_penalty(0.5),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.value.incorrect_words.unwrap(), ["penn"]);
+    assert_eq!(response.meta.id, "resp_1");
+    assert_eq!(response.meta.usage.unwrap().total_tokens, 40);
+    let body = &mock.request_bodies().await[0];
+    assert_eq!(
+        body["input"][1],
+        json!({ "role": "user", "content": "Check" })
+    );
+    assert_eq!(body["text"]["format"]["type"], "json_schema");
+    assert_eq!(body["text"]["format"]["strict"], true);
+    assert_eq!(body["max_output_tokens"], 100);
+    assert!(body.get("response_format").is_none());
+    for option in ["stop", "seed", "frequency_penalty", "presence_penalty"] {
+        assert!(body.get(option).is_none(), "{} was sent", option);
+    }
+}
+
+#[tokio::test]
+async fn stores_and_retrieves_completions() {
+    let mock = MockOpenAi::start().await;
+    mock.respond_with_content(json!({ "explanation": "ok", "incorrect_words": null }))
+        .await;
+    let stored = json!({
+        "id": "chatcmpl-mock",
+        "model": common::MODEL,
+        "created": 1_700_000_000,
+        "metadata": { "dataset": "grammar" },
+        "choices": [{ "message": { "role": "assistant", "content": r#"{"explanation":"ok","incorrect_words":null}"# } }]
+    });
+    mock.mount_get(
+        &format!("{}/chatcmpl-mock", common::COMPLETIONS_PATH),
+        wiremock::ResponseTemplate::new(200).set_body_json(stored.clone()),
+    )
+    .await;
+    mock.mount_get(
+        common::COMPLETIONS_PATH,
+        wiremock::ResponseTemplate::new(200).set_body_json(json!({
+            "object": "list", "data": [stored], "first_id": "chatcmpl-mock",
+            "last_id": "chatcmpl-mock", "has_more": false
+        })),
+    )
+    .await;
+
+    let client = mock.client();
+    let options = RequestOptions::new()
+        .store(true)
+        .metadata("dataset", "grammar");
+    let _: Review = client.call_schema_with("...", &options).await.unwrap();
+    let body = &mock.request_bodies().await[0];
+    assert_eq!(body["store"], true);
+    assert_eq!(body["metadata"], json!({ "dataset": "grammar" }));
+
+    let completion = client.get_stored_completion("chatcmpl-mock").await.unwrap();
+    assert_eq!(completion.metadata["dataset"], "grammar");
+    assert_eq!(completion.content::<Review>().unwrap().explanation, "ok");
+
+    let list = client
+        .list_stored_completions(&ListStoredOptions {
+            metadata: [("dataset".to_string(), "grammar".to_string())].into(),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+    assert_eq!(list.data.len(), 1);
+    let listed = mock.requests().await;
+    assert_eq!(
+        listed.last().unwrap().url.query(),
+        Some("metadata%5Bdataset%5D=grammar")
+    );
+}
+
+#[tokio::test]
+async fn extracts_through_anthropic_forced_tool_use() {
+    let mock = MockOpenAi::start().await;
+    mock.mount_at(
+        "/v1/messages",
+        wiremock::ResponseTemplate::new(200).set_body_json(json!({
+            "id": "msg_1",
+            "type": "message",
+            "role": "assistant",
+            "model": "claude-sonnet-4-5",
+            "content": [{
+                "type": "tool_use",
+                "id": "toolu_1",
+                "name": "review",
+                "input": { "explanation": "ok", "incorrect_words": ["penn"] }
+            }],
+            "stop_reason": "tool_use",
+            "usage": { "input_tokens": 50, "output_tokens": 20 }
+        })),
+    )
+    .await;
+
+    let client = OpenAiClient::new(
+        reqwest::Client::new(),
+        format!("{}/v1/messages", mock.uri()),
+        "claude-sonnet-4-5",
+        "anthropic-key",
+    )
+    .with_insecure_loopback(true)
+    .with_provider(Provider::Anthropic)
+    .with_system_role("You are a tutor.");
+    let response = client
+        .call_schema_with_meta::<Review>("Check", &RequestOptions::new().seed(1).stop(["END"]))
+        .await
+        .unwrap();
+
+    assert_eq!(response.value.incorrect_words.unwrap(), ["penn"]);
+    assert_eq!(response.meta.usage.unwrap().total_tokens, 70);
+    let request = &mock.requests().await[0];
+    assert_eq!(request.headers["x-api-key"], "anthropic-key");
+    assert!(request.headers.get("authorization").is_none());
+    let body: Value = serde_json::from_slice(&request.body).unwrap();
+    assert_eq!(body["system"], "You are a tutor.");
+    assert_eq!(
+        body["messages"],
+        json!([{ "role": "user", "content": "Check" }])
+    );
+    let tool_name = body["tools"][0]["name"].clone();
+    assert_eq!(
+        body["tool_choice"],
+        json!({ "type": "tool", "name": tool_name })
+    );
+    assert_eq!(body["tools"][0]["input_schema"]["type"], "object");
+    assert_eq!(body["stop_sequences"], json!(["END"]));
+    assert!(body.get("seed").is_none());
+}
+
+#[tokio::test]
+async fn extracts_through_gemini_response_schema() {
+    let mock = MockOpenAi::start().await;
+    let path = "/v1beta/models/gemini-2.5-flash:generateContent";
+    mock.mount_at(
+        path,
+        wiremock::ResponseTemplate::new(200).set_body_json(json!({
+            "candidates": [{
+                "content": {
+                    "role": "model",
+                    "parts": [{ "text": "{\"explanation\":\"ok\",\"incorrect_words\":null}" }]
+                },
+                "finishReason": "STOP"
+            }],
+            "usageMetadata": {
+                "promptTokenCount": 40,
+                "candidatesTokenCount": 10,
+                "totalTokenCount": 50
+            },
+            "modelVersion": "gemini-2.5-flash",
+            "responseId": "resp_1"
+        })),
+    )
+    .await;
+
+    let client = OpenAiClient::new(
+        reqwest::Client::new(),
+        format!("{}{}", mock.uri(), path),
+        "gemini-2.5-flash",
+        "gemini-key",
+    )
+    .with_insecure_loopback(true)
+    .with_provider(Provider::Gemini)
+    .with_system_role("You are a tutor.");
+    let response = client
+        .call_schema_with_meta::<Review>("Check", &RequestOptions::new().temperature(0.5))
+        .await
+        .unwrap();
+
+    assert_eq!(response.value.explanation, "ok");
+    assert_eq!(response.meta.id, "resp_1");
+    assert_eq!(response.meta.usage.unwrap().total_tokens, 50);
+    let request = &mock.requests().await[0];
+    assert_eq!(request.headers["x-goog-api-key"], "gemini-key");
+    let body: Value = serde_json::from_slice(&request.body).unwrap();
+    assert_eq!(
+        body["systemInstruction"],
+        json!({ "parts": [{ "text": "You are a tutor." }] })
+    );
+    assert_eq!(
+        body["contents"],
+        json!([{ "role": "user", "parts": [{ "text": "Check" }] }])
+    );
+    let config = &body["generationConfig"];
+    assert_eq!(config["responseMimeType"], "application/json");
+    assert_eq!(config["temperature"], 0.5);
+    let schema = &config["responseSchema"];
+    assert!(schema.get("additionalProperties").is_none());
+    assert!(schema.get("$schema").is_none());
+    assert_eq!(schema["properties"]["incorrect_words"]["type"], "array");
+    assert_eq!(schema["properties"]["incorrect_words"]["nullable"], true);
+    assert_eq!(
+        schema["propertyOrdering"],
+        json!(["explanation", "incorrect_words"])
+    );
+    assert!(body.get("model").is_none());
+}
+
+#[tokio::test]
+async fn surfaces_gemini_safety_blocks_as_refusals() {
+    let mock = MockOpenAi::start().await;
+    let path = "/v1beta/models/gemini-2.5-flash:generateContent";
+    mock.mount_at(
+        path,
+        wiremock::ResponseTemplate::new(200).set_body_json(json!({
+            "promptFeedback": { "blockReason": "SAFETY" }
+        })),
+    )
+    .await;
+
+    let client = OpenAiClient::new(
+        reqwest::Client::new(),
+        format!("{}{}", mock.uri(), path),
+        "gemini-2.5-flash",
+        "gemini-key",
+    )
+    .with_insecure_loopback(true)
+    .with_provider(Provider::Gemini);
+    let err = client.call_schema::<Review>("Check").await.unwrap_err();
+
+    assert_eq!(err.to_string(), "LLM refusal: Blocked by Gemini: SAFETY");
+}
+
+#[tokio::test]
+async fn keeps_gemini_options_on_plain_text_calls() {
+    let client = OpenAiClient::new(
+        reqwest::Client::new(),
+        "https://example.com/v1beta/models/gemini-2.5-flash:generateContent",
+        "gemini-2.5-flash",
+        "gemini-key",
+    )
+    .with_provider(Provider::Gemini)
+    .with_dry_run(true);
+
+    let err = client
+        .call_text_with("Summarize", &RequestOptions::new().temperature(0.5))
+        .await
+        .unwrap_err();
+
+    let body = &err.downcast_ref::<DryRun>().unwrap().body;
+    assert_eq!(body["generationConfig"], json!({ "temperature": 0.5 }));
+}
+
+#[tokio::test]
+async fn extracts_through_ollama_format_field() {
+    let mock = MockOpenAi::start().await;
+    mock.mount_at(
+        "/api/chat",
+        wiremock::ResponseTemplate::new(200).set_body_json(json!({
+            "model": "llama3.2",
+            "created_at": "2025-01-01T00:00:00Z",
+            "message": {
+                "role": "assistant",
+                "content": "{\"explanation\":\"ok\",\"incorrect_words\":[\"penn\"]}"
+            },
+            "done": true,
+            "done_reason": "stop",
+            "prompt_eval_count": 30,
+            "eval_count": 12
+        })),
+    )
+    .await;
+
+    let client = OpenAiClient::new(
+        reqwest::Client::new(),
+        format!("{}/api/chat", mock.uri()),
+        "llama3.2",
+        "",
+    )
+    .with_insecure_loopback(true)
+    .with_provider(Provider::Local(LocalServer::Ollama));
+    let response = client
+        .call_schema_with_meta::<Review>(
+            "Check",
+            &RequestOptions::new().seed(7).max_completion_tokens(200),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.value.incorrect_words.unwrap(), ["penn"]);
+    assert_eq!(response.meta.usage.unwrap().total_tokens, 42);
+    let request = &mock.requests().await[0];
+    assert!(request.headers.get("authorization").is_none());
+    let body: Value = serde_json::from_slice(&request.body).unwrap();
+    assert_eq!(body["format"]["type"], "object");
+    assert_eq!(body["stream"], false);
+    assert_eq!(body["options"], json!({ "seed": 7, "num_predict": 200 }));
+    assert!(body.get("response_format").is_none());
+}
+
+#[tokio::test]
+async fn surfaces_llama_cpp_string_errors() {
+    let mock = MockOpenAi::start().await;
+    mock.mount(
+        wiremock::ResponseTemplate::new(500)
+            .set_body_json(json!({ "error": "failed to load model" })),
+        Some(1),
+    )
+    .await;
+
+    let client = mock
+        .client()
+        .with_provider(Provider::Local(LocalServer::LlamaCpp));
+    let err = client.call_schema::<Review>("Check").await.unwrap_err();
+
+    assert_eq!(err.to_string(), "OpenAI Error: failed to load model");
+    let body = &mock.request_bodies().await[0];
+    assert_eq!(body["json_schema"]["type"], "object");
+    assert!(body.get("response_format").is_none());
+}
+
+#[tokio::test]
+async fn attributes_completion_tokens_to_fields() {
+    let mock = MockOpenAi::start().await;
+    let tokens = [
+        "{\"",
+        "explanation",
+        "\":\"",
+        "Two",
+        " typos",
+        "\",\"",
+        "incorrect",
+        "_words",
+        "\":[\"",
+        "penn",
+        "\"]}",
+    ];
+    let content: String = tokens.concat();
+    let mut body = common::completion(json!({ "role": "assistant", "content": content }));
+    body["choices"][0]["logprobs"] = json!({
+        "content": tokens
+            .iter()
+            .map(|t| json!({ "token": t, "logprob": -0.1, "bytes": t.as_bytes() }))
+            .collect::<Vec<_>>()
+    });
+    mock.mount(
+        wiremock::ResponseTemplate::new(200).set_body_json(body),
+        None,
+    )
+    .await;
+
+    // A dollar per output token.
+    let pricing = PricingTable::new().with_model(common::MODEL, ModelPrice::new(0.0, 1e6, 0.0));
+    let response = mock
+        .client()
+        .with_pricing(pricing)
+        .call_schema_with_meta::<Review>("Check", &RequestOptions::new().logprobs(true))
+        .await
+        .unwrap();
+
+    let attribution = response.meta.token_attribution.unwrap();
+    assert_eq!(attribution.by_field["explanation"], 5);
+    assert_eq!(attribution.by_field["incorrect_words"], 5);
+    assert_eq!(attribution.structural, 1);
+    assert_eq!(attribution.cost_by_field["explanation"], 5.0);
+    assert_eq!(attribution.structural_cost, Some(1.0));
+    assert_eq!(mock.request_bodies().await[0]["logprobs"], true);
+}
+
+#[tokio::test]
+async fn serves_identical_requests_from_cache() {
+    let mock = MockOpenAi::start().await;
+    mock.respond_with_content(json!({ "explanation": "ok", "incorrect_words": null }))
+        .await;
+    let client = mock.client().with_cache(MemoryCache::new(16));
+
+    let first = client
+        .call_schema_with_meta::<Review>("Check", &RequestOptions::new())
+        .await
+        .unwrap();
+    let second = client
+        .call_schema_with_meta::<Review>("Check", &RequestOptions::new())
+        .await
+        .unwrap();
+    client
+        .call_schema_with::<Review>("Check", &RequestOptions::new().seed(1))
+        .await
+        .unwrap();
+
+    assert!(!first.meta.cached);
+    assert!(second.meta.cached);
+    assert_eq!(second.value.explanation, "ok");
+    assert_eq!(second.meta.id, first.meta.id);
+    assert_eq!(mock.requests().await.len(), 2);
+}
+
+#[tokio::test]
+async fn rate_limit_follows_remaining_request_headers() {
+    let mock = MockOpenAi::start().await;
+    let message = json!({
+        "role": "assistant",
+        "content": json!({ "explanation": "ok", "incorrect_words": null }).to_string()
+    });
+    mock.mount(
+        wiremock::ResponseTemplate::new(200)
+            .set_body_json(common::completion(message))
+            .insert_header("x-ratelimit-remaining-requests", "0"),
+        None,
+    )
+    .await;
+    // 600 requests per minute refill one request every 100ms.
+    let client = mock.client().with_rate_limit(600, 1_000_000);
+
+    client.call_schema::<Review>("Check").await.unwrap();
+    let started = Instant::now();
+    client.call_schema::<Review>("Check").await.unwrap();
+
+    assert!(started.elapsed() >= Duration::from_millis(90));
+    assert_eq!(mock.requests().await.len(), 2);
+}
+
+#[tokio::test]
+async fn privacy_profile_sends_minimal_headers() {
+    let mock = MockOpenAi::start().await;
+    mock.respond_with_content(json!({ "explanation": "ok", "incorrect_words": null }))
+        .await;
+    let client = mock
+        .client()
+        .with_client_header("Operation", "review")
+        .with_privacy_profile(PrivacyProfile::new().with_jitter(Duration::from_millis(5)))
+        .unwrap();
+
+    client.call_schema::<Review>("Check").await.unwrap();
+
+    let request = &mock.requests().await[0];
+    assert!(request.headers.get("user-agent").is_none());
+    assert!(request.headers.get("x-client-operation").is_none());
+    assert_eq!(request.headers["authorization"], "Bearer test-key");
+}
+
+#[tokio::test]
+async fn strict_schema_makes_options_nullable_and_required() {
+    let mock = MockOpenAi::start().await;
+    mock.respond_with_content(json!({
+        "tasks": [{ "title": "a", "priority": null, "labels": null }],
+        "next": null,
+        "history": ["low", null]
+    }))
+    .await;
+
+    let backlog: Backlog = mock.client().call_schema("Plan").await.unwrap();
+
+    assert_eq!(
+        backlog,
+        Backlog {
+            tasks: vec![Task {
+                title: "a".into(),
+                priority: None,
+                labels: None,
+            }],
+            next: None,
+            history: vec![Some(Priority::Low), None],
+        }
+    );
+    let body = &mock.request_bodies().await[0];
+    let schema = &body["response_format"]["json_schema"]["schema"];
+    let task = &schema["definitions"]["Task"];
+    assert_eq!(task["required"], json!(["labels", "priority", "title"]));
+    assert_eq!(
+        task["properties"]["priority"],
+        json!({ "type": ["string", "null"], "enum": ["low", "high", null] })
+    );
+    assert_eq!(task["properties"]["labels"]["additionalProperties"], false);
+    assert_eq!(
+        schema["properties"]["history"]["items"]["enum"],
+        json!(["low", "high", null])
+    );
+    assert_eq!(schema["required"], json!(["history", "next", "tasks"]));
+}
+
+#[tokio::test]
+async fn encrypts_designated_fields_before_caching() {
+    #[derive(Clone, Default)]
+    struct RecordingCache(Arc<Mutex<HashMap<String, String>>>);
+
+    impl CacheBackend for RecordingCache {
+        fn get(&self, key: &str) -> Option<String> {
+            self.0.lock().unwrap().get(key).cloned()
+        }
+
+        fn put(&self, key: &str, value: String) {
+            self.0.lock().unwrap().insert(key.to_string(), value);
+        }
+    }
+
+    let mock = MockOpenAi::start().await;
+    mock.respond_with_content(json!({ "explanation": "secret", "incorrect_words": ["penn"] }))
+        .await;
+    let cache = RecordingCache::default();
+    let client = mock
+        .client()
+        .with_cache(cache.clone())
+        .with_field_encryption(
+            FieldEncryptor::new(StaticKey::new("k1", [1; 32])).with_field("/explanation"),
+        );
+
+    client.call_schema::<Review>("Check").await.unwrap();
+    let cached: Review = client.call_schema("Check").await.unwrap();
+
+    assert_eq!(cached.explanation, "secret");
+    let stored = cache.0.lock().unwrap().values().next().cloned().unwrap();
+    assert!(!stored.contains("secret"));
+    assert!(stored.contains("penn"));
+    assert_eq!(mock.requests().await.len(), 1);
+}
+
+#[tokio::test]
+async fn skips_the_cache_when_fields_cannot_be_encrypted_or_decrypted() {
+    struct RetiredKeys;
+
+    impl KeyProvider for RetiredKeys {
+        fn current_key_id(&self) -> String {
+            "retired".to_string()
+        }
+
+        fn key(&self, _: &str) -> Option<[u8; 32]> {
+            None
+        }
+    }
+
+    let mock = MockOpenAi::start().await;
+    mock.respond_with_content(json!({ "explanation": "secret", "incorrect_words": null }))
+        .await;
+    let encrypted =
+        |key: [u8; 32]| FieldEncryptor::new(StaticKey::new("k1", key)).with_field("/explanation");
+    let client = mock.client().with_cache(MemoryCache::new(16));
+    let options = RequestOptions::new();
+
+    client
+        .clone()
+        .with_field_encryption(encrypted([1; 32]))
+        .call_schema_with_meta::<Review>("Check", &options)
+        .await
+        .unwrap();
+    let rotated = client
+        .clone()
+        .with_field_encryption(encrypted([2; 32]))
+        .call_schema_with_meta::<Review>("Check", &options)
+        .await
+        .unwrap();
+    assert!(!rotated.meta.cached);
+    assert!(rotated.meta.cache_error.is_some());
+
+    let unencryptable = client
+        .with_field_encryption(FieldEncryptor::new(RetiredKeys).with_field("/explanation"))
+        .call_schema_with_meta::<Review>("Check again", &options)
+        .await
+        .unwrap();
+    assert_eq!(unencryptable.value.explanation, "secret");
+    assert!(unencryptable.meta.cache_error.is_some());
+    assert_eq!(mock.requests().await.len(), 3);
+}
+
+#[tokio::test]
+async fn rejects_schemas_over_limits_before_sending() {
+    #[derive(Debug, Deserialize, JsonSchema, Clone)]
+    struct Counter {
+        count: u32,
+    }
+
+    let mock = MockOpenAi::start().await;
+    mock.respond_with_content(json!({ "count": 1 })).await;
+
+    let err = mock
+        .client()
+        .with_schema_limits(SchemaLimits::openai().strip_unsupported(false))
+        .call_schema::<Counter>("Count")
+        .await
+        .unwrap_err();
+    let limit_error = err.downcast_ref::<SchemaLimitError>().unwrap();
+    assert_eq!(limit_error.path, "#/properties/count");
+    assert!(mock.requests().await.is_empty());
+
+    let counter = mock
+        .client()
+        .with_schema_limits(SchemaLimits::openai())
+        .call_schema::<Counter>("Count")
+        .await
+        .unwrap();
+    assert_eq!(counter.count, 1);
+    let body = &mock.request_bodies().await[0];
+    assert_eq!(
+        body["response_format"]["json_schema"]["schema"]["properties"]["count"],
+        json!({ "type": "integer" })
+    );
+}
+
+#[tokio::test]
+async fn breaks_down_call_time_by_stage() {
+    let mock = MockOpenAi::start().await;
+    let content = json!({ "explanation": "ok", "incorrect_words": null }).to_string();
+    let message = json!({ "role": "assistant", "content": content, "refusal": null });
+    mock.mount(
+        wiremock::ResponseTemplate::new(200)
+            .set_body_json(common::completion(message))
+            .set_delay(Duration::from_millis(200)),
+        None,
+    )
+    .await;
+
+    let client = mock.client();
+    let response = client
+        .call_schema_with_meta::<Review>("Check", &RequestOptions::new())
+        .await
+        .unwrap();
+
+    let timings = response.meta.timings;
+    assert!(timings.first_byte >= Duration::from_millis(200));
+    assert!(timings.queue < Duration::from_millis(200));
+    assert_eq!(timings.repair, Duration::ZERO);
+    assert_eq!(client.stats().overall.mean_timings, timings);
+}
+
+#[derive(Debug, Deserialize, JsonSchema, Clone, PartialEq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+enum Shape {
+    Circle { radius: f64 },
+    Rectangle { width: f64, height: f64 },
+    Point,
+}
+
+#[derive(Debug, Deserialize, JsonSchema, Clone, PartialEq)]
+#[serde(tag = "op", content = "args", rename_all = "snake_case")]
+enum Command {
+    Draw(Shape),
+    Erase { id: u32 },
+    Clear,
+}
+
+#[derive(Debug, Deserialize, JsonSchema, Clone, PartialEq)]
+struct Canvas {
+    shapes: Vec<Shape>,
+    background: Option<Shape>,
+}
+
+#[tokio::test]
+async fn round_trips_internally_tagged_enums() {
+    let mock = MockOpenAi::start().await;
+    let output = json!({
+        "shapes": [
+            { "kind": "circle", "radius": 0.5 },
+            { "kind": "rectangle", "width": 2.0, "height": 1.0 },
+            { "kind": "point" },
+        ],
+        "background": null,
+    });
+    mock.respond_with_content(output).await;
+
+    let canvas: Canvas = mock.client().call_schema("Draw").await.unwrap();
+
+    assert_eq!(
+        canvas.shapes,
+        vec![
+            Shape::Circle { radius: 0.5 },
+            Shape::Rectangle {
+                width: 2.0,
+                height: 1.0
+            },
+            Shape::Point,
+        ]
+    );
+    assert_eq!(canvas.background, None);
+    let schema = &mock.request_bodies().await[0]["response_format"]["json_schema"]["schema"];
+    let shape = &schema["definitions"]["Shape"];
+    assert!(shape.get("oneOf").is_none());
+    let variants = shape["anyOf"].as_array().unwrap();
+    assert_eq!(variants.len(), 3);
+    for variant in variants {
+        assert_eq!(variant["additionalProperties"], json!(false));
+        assert!(variant["required"]
+            .as_array()
+            .unwrap()
+            .contains(&json!("kind")));
+    }
+}
+
+#[tokio::test]
+async fn wraps_adjacently_tagged_enum_at_the_root() {
+    let mock = MockOpenAi::start().await;
+    mock.respond_with_content(json!({
+        "value": { "op": "draw", "args": { "kind": "circle", "radius": 0.5 } }
+    }))
+    .await;
+
+    let command: Command = mock.client().call_schema("Draw a circle").await.unwrap();
+
+    assert_eq!(command, Command::Draw(Shape::Circle { radius: 0.5 }));
+    let schema = &mock.request_bodies().await[0]["response_format"]["json_schema"]["schema"];
+    assert_eq!(schema["type"], "object");
+    assert_eq!(schema["required"], json!(["value"]));
+    assert_eq!(schema["additionalProperties"], json!(false));
+    assert_eq!(
+        schema["properties"]["value"]["anyOf"]
+            .as_array()
+            .unwrap()
+            .len(),
+        3
+    );
+    assert!(schema["definitions"]["Shape"]["anyOf"].is_array());
+}
+
+#[tokio::test]
+async fn wraps_lists_in_an_items_property() {
+    let mock = MockOpenAi::start().await;
+    mock.respond_with_content(json!({
+        "items": [{ "explanation": "fine", "incorrect_words": null }]
+    }))
+    .await;
+
+    let reviews: Vec<Review> = mock.client().call_schema_list("Check").await.unwrap();
+
+    assert_eq!(reviews.len(), 1);
+    assert_eq!(reviews[0].explanation, "fine");
+    let schema = &mock.request_bodies().await[0]["response_format"]["json_schema"]["schema"];
+    assert_eq!(schema["required"], json!(["items"]));
+    assert_eq!(schema["properties"]["items"]["type"], "array");
+    assert_eq!(schema, &OpenAiClient::schema_for::<Vec<Review>>().unwrap());
+}
+
+#[tokio::test]
+async fn builds_request_bodies_without_sending() {
+    let mock = MockOpenAi::start().await;
+    let client = mock.client().with_system_role("Review spelling");
+    let options = RequestOptions::new().temperature(0.5);
+
+    let body = client.build_request::<Review>("Check", &options).unwrap();
+
+    assert_eq!(body["model"], common::MODEL);
+    assert_eq!(body["messages"][0]["content"], "Review spelling");
+    assert_eq!(body["temperature"], 0.5);
+    let schema = &body["response_format"]["json_schema"]["schema"];
+    assert_eq!(
+        schema["required"],
+        json!(["explanation", "incorrect_words"])
+    );
+
+    let err = client
+        .with_dry_run(true)
+        .call_schema_with::<Review>("Check", &options)
+        .await
+        .unwrap_err();
+    assert_eq!(err.downcast_ref::<DryRun>().unwrap().body, body);
+    assert!(mock.requests().await.is_empty());
+}
+
+/// Answers every request from memory, delivering the body in small chunks.
+#[derive(Clone, Default)]
+struct CannedTransport {
+    requests: Arc<Mutex<Vec<HttpRequest>>>,
+}
+
+impl HttpTransport for CannedTransport {
+    fn send(
+        &self,
+        request: HttpRequest,
+    ) -> futures::future::BoxFuture<'_, Result<HttpResponse, TransportError>> {
+        self.requests.lock().unwrap().push(request);
+        let content = json!({ "explanation": "ok", "incorrect_words": null }).to_string();
+        let message = json!({ "role": "assistant", "content": content, "refusal": null });
+        let body = common::completion(message).to_string().into_bytes();
+        let chunks: Vec<Result<bytes::Bytes, TransportError>> = body
+            .chunks(16)
+            .map(|chunk| Ok(bytes::Bytes::copy_from_slice(chunk)))
+            .collect();
+        Box::pin(async move {
+            Ok(HttpResponse::streaming(
+                reqwest::StatusCode::OK,
+                reqwest::header::HeaderMap::new(),
+                futures::stream::iter(chunks),
+            ))
+        })
+    }
+}
+
+#[tokio::test]
+async fn sends_through_custom_transports() {
+    let transport = CannedTransport::default();
+    let client = OpenAiClient::new(
+        reqwest::Client::new(),
+        "unix:///run/llm.sock",
+        common::MODEL,
+        "test-key",
+    )
+    .with_transport(transport.clone())
+    .with_client_header("Operation", "review");
+
+    let review: Review = client.call_schema("Check").await.unwrap();
+
+    assert_eq!(review.explanation, "ok");
+    let requests = transport.requests.lock().unwrap();
+    let request = &requests[0];
+    assert_eq!(request.method, reqwest::Method::POST);
+    assert_eq!(request.url, "unix:///run/llm.sock");
+    assert_eq!(request.headers["authorization"], "Bearer test-key");
+    assert_eq!(request.headers["x-client-operation"], "review");
+    let body: Value = serde_json::from_slice(request.body.as_ref().unwrap()).unwrap();
+    assert_eq!(body["model"], common::MODEL);
+}
+
+#[tokio::test]
+async fn keeps_custom_transports_under_a_privacy_profile() {
+    let transport = CannedTransport::default();
+    let client = OpenAiClient::new(
+        reqwest::Client::new(),
+        "unix:///run/llm.sock",
+        common::MODEL,
+        "test-key",
+    )
+    .with_transport(transport.clone())
+    .with_client_header("Operation", "review")
+    .with_privacy_profile(PrivacyProfile::new())
+    .unwrap();
+
+    let _: Review = client.call_schema("Check").await.unwrap();
+
+    let requests = transport.requests.lock().unwrap();
+    assert_eq!(requests.len(), 1);
+    assert!(requests[0].headers.get("x-client-operation").is_none());
+
+    #[cfg(feature = "socks")]
+    {
+        let proxied = PrivacyProfile::new().with_socks5_proxy("socks5h://127.0.0.1:9050");
+        let err = client.with_privacy_profile(proxied).err().unwrap();
+        assert!(err.is::<transport::ProxiedCustomTransport>());
+    }
+}
+
+#[tokio::test]
+async fn builds_clients_without_reqwest() {
+    let transport = CannedTransport::default();
+    let client = OpenAiClient::with_http_transport(
+        transport.clone(),
+        "https://llm.internal/v1/chat/completions",
+        common::MODEL,
+        "test-key",
+    )
+    .with_retry(RetryPolicy::default());
+
+    let review: Review = client.call_schema("Check").await.unwrap();
+
+    assert_eq!(review.explanation, "ok");
+    assert_eq!(transport.requests.lock().unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn exposes_the_schema_that_is_sent() {
+    let mock = MockOpenAi::start().await;
+    mock.respond_with_content(json!({ "explanation": "ok", "incorrect_words": null }))
+        .await;
+
+    let _: Review = mock.client().call_schema("Check").await.unwrap();
+
+    let json_schema = &mock.request_bodies().await[0]["response_format"]["json_schema"];
+    assert_eq!(
+        json_schema["schema"],
+        OpenAiClient::schema_for::<Review>().unwrap()
+    );
+    assert_eq!(
+        json_schema["name"],
+        OpenAiClient::schema_name_for::<Review>()
+    );
+    assert_eq!(
+        OpenAiClient::schema_for::<Command>().unwrap()["required"],
+        json!(["value"])
+    );
+}
+
+/// Routes through a gateway: adds its header, masks e-mail addresses in the
+/// prompt and records response statuses.
+#[derive(Clone, Default)]
+struct Gateway {
+    statuses: Arc<Mutex<Vec<u16>>>,
+}
+
+impl Middleware for Gateway {
+    fn on_request(&self, request: &mut RequestParts) {
+        request
+            .headers
+            .insert("helicone-auth", "Bearer gateway-key".parse().unwrap());
+        if let Some(messages) = request
+            .body
+            .as_mut()
+            .and_then(|b| b["messages"].as_array_mut())
+        {
+            for message in messages {
+                if let Some(text) = message["content"].as_str() {
+                    message["content"] = json!(text.replace("ada@example.com", "[email]"));
+                }
+            }
+        }
+    }
+
+    fn on_response(&self, response: &ResponseParts) {
+        self.statuses.lock().unwrap().push(response.status.as_u16());
+        assert!(response.json().is_some());
+    }
+}
+
+#[tokio::test]
+async fn runs_middleware_around_requests() {
+    let mock = MockOpenAi::start().await;
+    mock.respond_with_content(json!({ "explanation": "ok", "incorrect_words": null }))
+        .await;
+    let gateway = Gateway::default();
+
+    let review: Review = mock
+        .client()
+        .with_middleware(gateway.clone())
+        .call_schema("Check ada@example.com")
+        .await
+        .unwrap();
+
+    assert_eq!(review.explanation, "ok");
+    assert_eq!(*gateway.statuses.lock().unwrap(), vec![200]);
+    let request = &mock.requests().await[0];
+    assert_eq!(request.headers["helicone-auth"], "Bearer gateway-key");
+    let body: Value = serde_json::from_slice(&request.body).unwrap();
+    assert_eq!(body["messages"][0]["content"], "Check [email]");
+}
+
+#[tokio::test]
+async fn refuses_plain_http_unless_loopback_is_allowed() {
+    let mock = MockOpenAi::start().await;
+    mock.respond_with_content(json!({ "explanation": "ok", "incorrect_words": null }))
+        .await;
+
+    let loopback = OpenAiClient::new(reqwest::Client::new(), mock.endpoint(), "m", "k");
+    let err = loopback.call_schema::<Review>("Check").await.unwrap_err();
+    assert!(err.is::<InsecureEndpoint>());
+
+    let remote = OpenAiClient::new(
+        reqwest::Client::new(),
+        "http://api.example.com/v1/chat/completions",
+        "m",
+        "k",
+    )
+    .with_insecure_loopback(true);
+    let err = remote.call_schema::<Review>("Check").await.unwrap_err();
+    assert!(err.is::<InsecureEndpoint>());
+    assert!(mock.requests().await.is_empty());
+}
+
+#[tokio::test]
+async fn allows_plain_http_to_remote_hosts_when_opted_in() {
+    let transport = CannedTransport::default();
+    let client = OpenAiClient::new(
+        reqwest::Client::new(),
+        "http://gateway.internal/v1/chat/completions",
+        common::MODEL,
+        "test-key",
+    )
+    .with_transport(transport.clone());
+    let err = client.call_schema::<Review>("Check").await.unwrap_err();
+    assert!(err.is::<InsecureEndpoint>());
+    assert!(transport.requests.lock().unwrap().is_empty());
+
+    let review: Review = client
+        .with_insecure_remote(true)
+        .call_schema("Check")
+        .await
+        .unwrap();
+    assert_eq!(review.explanation, "ok");
+    assert_eq!(transport.requests.lock().unwrap().len(), 1);
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn connects_over_unix_domain_sockets() {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    let path = std::env::temp_dir().join(format!("llm-{}.sock", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let listener = tokio::net::UnixListener::bind(&path).unwrap();
+    let server = tokio::spawn(async move {
+        let (mut stream, _) = listener.accept().await.unwrap();
+        let mut request = Vec::new();
+        let mut buf = [0; 4096];
+        // The request body is JSON ending in `}` after the blank line.
+        while !request.ends_with(b"}") {
+            let n = stream.read(&mut buf).await.unwrap();
+            request.extend_from_slice(&buf[..n]);
+        }
+        let content = json!({ "explanation": "ok", "incorrect_words": null }).to_string();
+        let message = json!({ "role": "assistant", "content": content, "refusal": null });
+        let body = common::completion(message).to_string();
+        let response = format!(
+            "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{}",
+            body.len(),
+            body
+        );
+        stream.write_all(response.as_bytes()).await.unwrap();
+        String::from_utf8(request).unwrap()
+    });
+
+    let client = OpenAiClient::new(
+        transport::unix_socket_client(&path).unwrap(),
+        "http://localhost/v1/chat/completions",
+        "llama3.2",
+        "",
+    )
+    .with_provider(Provider::Local(LocalServer::LlamaCpp))
+    .with_insecure_loopback(true);
+    let review: Review = client.call_schema("Check").await.unwrap();
+
+    assert_eq!(review.explanation, "ok");
+    let request = server.await.unwrap();
+    assert!(request.starts_with("POST /v1/chat/completions HTTP/1.1"));
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[tokio::test]
+async fn returns_plain_text_completions() {
+    let mock = MockOpenAi::start().await;
+    mock.respond_with_raw_content("Ada wrote the first program.", None)
+        .await;
+
+    let text = mock.client().call_text("Who was Ada?").await.unwrap();
+
+    assert_eq!(text, "Ada wrote the first program.");
+    let body = &mock.request_bodies().await[0];
+    assert!(body.get("response_format").is_none());
+    assert_eq!(body["messages"][0]["content"], "Who was Ada?");
+}
+
+#[tokio::test]
+async fn requests_json_mode_without_a_schema() {
+    let mock = MockOpenAi::start().await;
+    mock.respond_with_raw_content(r#"{"tags": ["math"]}"#, None)
+        .await;
+
+    let value = mock
+        .client()
+        .call_json("List tags for Ada as JSON")
+        .await
+        .unwrap();
+
+    assert_eq!(value, json!({ "tags": ["math"] }));
+    let body = &mock.request_bodies().await[0];
+    assert_eq!(body["response_format"], json!({ "type": "json_object" }));
+}
+
+#[tokio::test]
+async fn surfaces_refusals() {
+    let mock = MockOpenAi::start().await;
+    mock.respond_with_refusal("I can't help with that.").await;
+
+    let err = mock
+        .client()
+        .call_schema::<Review>("...")
+        .await
+        .unwrap_err();
+
+    assert_eq!(err.to_string(), "LLM refusal: I can't help with that.");
+}
+
+#[tokio::test]
+async fn retries_refusals_with_adjusted_prompt() {
+    let mock = MockOpenAi::start().await;
+    mock.respond_with_refusal("Too vague.").await;
+
+    let policy = RefusalPolicy::new(2).with_adjustment(|attempt| {
+        PromptAdjustment::Rewrite(format!("{} (grammar exercise)", attempt.prompt))
+    });
+    let err = mock
+        .client()
+        .with_refusal_policy(policy)
+        .call_schema::<Review>("Check")
+        .await
+        .unwrap_err();
+
+    let exhausted = err.downcast_ref::<RefusalsExhausted>().unwrap();
+    assert_eq!(exhausted.attempts.len(), 3);
+    assert_eq!(exhausted.refusal(), "Too vague.");
+    let bodies = mock.request_bodies().await;
+    assert_eq!(bodies[0]["messages"][0]["content"], "Check");
+    assert_eq!(
+        bodies[2]["messages"][0]["content"],
+        "Check (grammar exercise) (grammar exercise)"
+    );
+}
+
+#[tokio::test]
+async fn clarifies_after_refusal() {
+    let mock = MockOpenAi::start().await;
+    mock.mount(
+        wiremock::ResponseTemplate::new(200).set_body_json(common::completion(
+            json!({ "role": "assistant", "content": null, "refusal": "Unclear." }),
+        )),
+        Some(1),
+    )
+    .await;
+    mock.respond_with_content(json!({ "explanation": "ok", "incorrect_words": null }))
+        .await;
+
+    let policy = RefusalPolicy::new(1)
+        .with_adjustment(|_| PromptAdjustment::Clarify("It is a grammar exercise.".into()));
+    let review: Review = mock
+        .client()
+        .with_refusal_policy(policy)
+        .call_schema("Check")
+        .await
+        .unwrap();
+
+    assert_eq!(review.explanation, "ok");
+    let messages = &mock.request_bodies().await[1]["messages"];
+    assert_eq!(
+        messages[1],
+        json!({ "role": "assistant", "refusal": "Unclear." })
+    );
+    assert_eq!(messages[2]["content"], "It is a grammar exercise.");
+}
+
+#[tokio::test]
+async fn repairs_unparseable_output() {
+    let mock = MockOpenAi::start().await;
+    mock.respond_with_raw_content(r#"{"explanation": 42}"#, Some(1))
+        .await;
+    mock.respond_with_content(json!({ "explanation": "fixed", "incorrect_words": null }))
+        .await;
+
+    let client = mock.client().with_repair(2);
+    let response = client
+        .call_schema_with_meta::<Review>("Check", &RequestOptions::default())
+        .await
+        .unwrap();
+
+    assert_eq!(response.value.explanation, "fixed");
+    assert_eq!(response.meta.repair_attempts, 1);
+    assert_eq!(client.stats().overall.repair_rate, 1.0);
+    let messages = &mock.request_bodies().await[1]["messages"];
+    assert_eq!(messages[1]["role"], "assistant");
+    assert_eq!(messages[1]["content"], r#"{"explanation": 42}"#);
+    assert!(messages[2]["content"]
+        .as_str()
+        .unwrap()
+        .contains("invalid type: integer `42`"));
+}
+
+#[tokio::test]
+async fn reports_parse_errors_without_repair() {
+    let mock = MockOpenAi::start().await;
+    mock.respond_with_raw_content("not json", None).await;
+
+    let err = mock
+        .client()
+        .call_schema::<Review>("Check")
+        .await
+        .unwrap_err();
+
+    let parse_error = err.downcast_ref::<ParseError>().unwrap();
+    assert_eq!(parse_error.raw, "not json");
+    assert!(err.to_string().starts_with("Failed to parse LLM output: "));
+}
+
+#[tokio::test]
+async fn degrades_to_partial_output() {
+    let mock = MockOpenAi::start().await;
+    mock.respond_with_raw_content(r#"{"incorrect_words": ["penn"], "explanation": 3}"#, None)
+        .await;
+
+    let degraded = mock
+        .client()
+        .with_repair(1)
+        .call_schema_degraded::<Review>("Check", &RequestOptions::default())
+        .await
+        .unwrap();
+
+    let Degraded::Partial(partial) = degraded else {
+        panic!("expected a partial result");
+    };
+    assert_eq!(partial.invalid, vec!["explanation"]);
+    assert!(partial.missing.is_empty());
+    assert_eq!(
+        partial.get::<Vec<String>>("incorrect_words").unwrap(),
+        ["penn"]
+    );
+    assert_eq!(partial.get::<String>("explanation"), None);
+    assert_eq!(mock.request_bodies().await.len(), 2);
+}
+
+#[tokio::test]
+async fn surfaces_api_errors() {
+    let mock = MockOpenAi::start().await;
+    mock.respond_with_error(400, "Invalid schema").await;
+
+    let err = mock
+        .client()
+        .call_schema::<Review>("...")
+        .await
+        .unwrap_err();
+
+    assert_eq!(err.to_string(), "OpenAI Error: Invalid schema");
+}
+
+#[tokio::test]
+async fn retries_rate_limits_honoring_retry_after() {
+    let mock = MockOpenAi::start().await;
+    mock.respond_rate_limited("0.2", 1).await;
+    mock.respond_with_content(json!({ "explanation": "ok", "incorrect_words": null }))
+        .await;
+
+    let client = mock.client().with_retry(RetryPolicy {
+        max_attempts: 3,
+        base_delay: Duration::from_millis(1),
+        jitter: false,
+    });
+    let started = Instant::now();
+    let review: Review = client.call_schema("...").await.unwrap();
+
+    assert_eq!(review.explanation, "ok");
+    assert!(started.elapsed() >= Duration::from_millis(200));
+    assert_eq!(mock.request_bodies().await.len(), 2);
+}
+
+#[tokio::test]
+async fn gives_up_after_max_attempts() {
+    let mock = MockOpenAi::start().await;
+    mock.respond_rate_limited("0", 5).await;
+
+    let client = mock.client().with_retry(RetryPolicy {
+        max_attempts: 2,
+        base_delay: Duration::from_millis(1),
+        jitter: true,
+    });
+    let err = client.call_schema::<Review>("...").await.unwrap_err();
+
+    assert_eq!(err.to_string(), "OpenAI Error: Rate limit reached");
+    assert_eq!(mock.request_bodies().await.len(), 2);
+}
+
+#[tokio::test]
+async fn returns_typed_tool_calls() {
+    let mock = MockOpenAi::start().await;
+    mock.respond_with_tool_calls(&[("call_1", "get_weather", json!({ "city": "Oslo" }))])
+        .await;
+
+    let tools = ToolSet::new().with_tool::<WeatherArgs>("get_weather", "Current weather");
+    let response = mock
+        .client()
+        .call_with_tools::<Review>("Weather in Oslo?", &tools)
+        .await
+        .unwrap();
+
+    let ToolResponse::ToolCalls(calls) = response else {
+        panic!("expected tool calls");
+    };
+    assert_eq!(calls[0].id, "call_1");
+    assert_eq!(calls[0].arguments::<WeatherArgs>().unwrap().city, "Oslo");
+
+    let body = &mock.request_bodies().await[0];
+    let function = &body["tools"][0]["function"];
+    assert_eq!(function["name"], "get_weather");
+    assert_eq!(function["strict"], true);
+    assert_eq!(function["parameters"]["additionalProperties"], false);
+}
+
+#[tokio::test]
+async fn wraps_non_object_answers_of_tool_calls() {
+    let mock = MockOpenAi::start().await;
+    mock.respond_with_content(
+        json!({ "items": [{ "explanation": "ok", "incorrect_words": null }] }),
+    )
+    .await;
+
+    let tools = ToolSet::new().with_tool::<WeatherArgs>("get_weather", "Current weather");
+    let response = mock
+        .client()
+        .call_with_tools::<Vec<Review>>("Review these", &tools)
+        .await
+        .unwrap();
+
+    let ToolResponse::Answer(reviews) = response else {
+        panic!("expected an answer");
+    };
+    assert_eq!(reviews[0].explanation, "ok");
+    let schema = &mock.request_bodies().await[0]["response_format"]["json_schema"]["schema"];
+    assert_eq!(schema["type"], "object");
+    assert_eq!(schema["properties"]["items"]["type"], "array");
+}
+
+#[tokio::test]
+async fn forces_a_named_tool_choice() {
+    let mock = MockOpenAi::start().await;
+    mock.respond_with_tool_calls(&[("call_1", "get_weather", json!({ "city": "Oslo" }))])
+        .await;
+    let client = mock.client();
+
+    let tools = ToolSet::new()
+        .with_tool::<WeatherArgs>("get_weather", "Current weather")
+        .with_choice(ToolChoice::Named("get_weather".into()));
+    client
+        .call_with_tools::<Review>("Weather in Oslo?", &tools)
+        .await
+        .unwrap();
+    let body = &mock.request_bodies().await[0];
+    assert_eq!(
+        body["tool_choice"],
+        json!({ "type": "function", "function": { "name": "get_weather" } })
+    );
+
+    let tools = tools.with_choice(ToolChoice::Named("get_tides".into()));
+    let err = client
+        .call_with_tools::<Review>("Tides?", &tools)
+        .await
+        .unwrap_err();
+    assert_eq!(err.downcast_ref::<UnknownTool>().unwrap().name, "get_tides");
+    assert_eq!(mock.request_bodies().await.len(), 1);
+}
+
+#[tokio::test]
+async fn applies_input_guard_before_sending() {
+    let mock = MockOpenAi::start().await;
+    mock.respond_with_content(json!({ "explanation": "ok", "incorrect_words": null }))
+        .await;
+
+    let client = mock.client().with_input_guard(InputGuard::default());
+    let _: Review = client
+        .call_schema("Hel\u{200B}lo\u{202E} there")
+        .await
+        .unwrap();
+
+    let body = &mock.request_bodies().await[0];
+    assert_eq!(body["messages"][0]["content"], "Hello there");
+}
+
+#[tokio::test]
+async fn sends_typed_examples_before_the_prompt() {
+    let mock = MockOpenAi::start().await;
+    mock.respond_with_content(json!({ "explanation": "ok", "incorrect_words": null }))
+        .await;
+
+    let client = mock
+        .client()
+        .with_examples(vec![(
+            "Teh cat".to_string(),
+            Review {
+                explanation: "Misspelled article".to_string(),
+                incorrect_words: Some(vec!["Teh".to_string()]),
+            },
+        )])
+        .unwrap()
+        .with_examples(vec![("one, two".to_string(), vec!["one".to_string()])])
+        .unwrap();
+    let _: Review = client.call_schema("Hello there").await.unwrap();
+
+    let messages = mock.request_bodies().await[0]["messages"].clone();
+    assert_eq!(messages.as_array().unwrap().len(), 3);
+    assert_eq!(messages[0], json!({ "role": "user", "content": "Teh cat" }));
+    assert_eq!(messages[1]["role"], "assistant");
+    let example: Value = serde_json::from_str(messages[1]["content"].as_str().unwrap()).unwrap();
+    assert_eq!(
+        example,
+        json!({ "explanation": "Misspelled article", "incorrect_words": ["Teh"] })
+    );
+    assert_eq!(messages[2]["content"], "Hello there");
+}
+
+#[tokio::test]
+async fn orders_schema_fields() {
+    let mock = MockOpenAi::start().await;
+    mock.respond_with_content(json!({ "explanation": "ok", "incorrect_words": null }))
+        .await;
+    mock.respond_with_content(json!({ "explanation": "ok", "incorrect_words": null }))
+        .await;
+
+    let _: Review = mock.client().call_schema("Check").await.unwrap();
+    let _: Review = mock
+        .client()
+        .with_field_order::<Review>(&["incorrect_words"])
+        .call_schema("Check")
+        .await
+        .unwrap();
+
+    let bodies = mock.request_bodies().await;
+    let names = |body: &Value| -> Vec<String> {
+        let schema = &body["response_format"]["json_schema"]["schema"];
+        schema["properties"]
+            .as_object()
+            .unwrap()
+            .keys()
+            .cloned()
+            .collect()
+    };
+    assert_eq!(names(&bodies[0]), ["explanation", "incorrect_words"]);
+    assert_eq!(names(&bodies[1]), ["incorrect_words", "explanation"]);
+    assert_eq!(
+        bodies[1]["response_format"]["json_schema"]["schema"]["required"],
+        json!(["incorrect_words", "explanation"])
+    );
+}
+
+#[tokio::test]
+async fn strips_the_scratchpad_from_outputs() {
+    let mock = MockOpenAi::start().await;
+    mock.respond_with_content(json!({
+        "reasoning": "The sentence has no errors.",
+        "explanation": "ok",
+        "incorrect_words": null
+    }))
+    .await;
+
+    let response = mock
+        .client()
+        .with_scratchpad("reasoning")
+        .call_schema_with_meta::<Review>("Check", &RequestOptions::default())
+        .await
+        .unwrap();
+
+    assert_eq!(response.value.explanation, "ok");
+    assert_eq!(
+        response.meta.scratchpad.as_deref(),
+        Some("The sentence has no errors.")
+    );
+    let body = &mock.request_bodies().await[0];
+    let schema = &body["response_format"]["json_schema"]["schema"];
+    let names: Vec<&String> = schema["properties"].as_object().unwrap().keys().collect();
+    assert_eq!(names, ["reasoning", "explanation", "incorrect_words"]);
+    assert_eq!(schema["required"][0], "reasoning");
+}
+
+#[tokio::test]
+async fn times_out_hung_requests_and_calls() {
+    let mock = MockOpenAi::start().await;
+    let message = json!({ "role": "assistant", "content": "{}", "refusal": null });
+    mock.mount(
+        wiremock::ResponseTemplate::new(200)
+            .set_body_json(common::completion(message))
+            .set_delay(Duration::from_secs(5)),
+        None,
+    )
+    .await;
+
+    let client = mock.client().with_timeout(Duration::from_millis(50));
+    let err = client.call_schema::<Review>("Check").await.unwrap_err();
+    let io = err.downcast_ref::<std::io::Error>().unwrap();
+    assert_eq!(io.kind(), std::io::ErrorKind::TimedOut);
+
+    let started = Instant::now();
+    let err = client
+        .with_retry(RetryPolicy {
+            max_attempts: 100,
+            base_delay: Duration::from_millis(1),
+            jitter: false,
+        })
+        .with_deadline(Duration::from_millis(300))
+        .call_schema::<Review>("Check")
+        .await
+        .unwrap_err();
+    assert!(err.is::<DeadlineExceeded>());
+    assert!(started.elapsed() < Duration::from_secs(2));
+    assert!(mock.requests().await.len() > 2);
+}
+
+#[tokio::test]
+async fn cancels_calls_through_their_token() {
+    let mock = MockOpenAi::start().await;
+    let message = json!({ "role": "assistant", "content": "{}", "refusal": null });
+    mock.mount(
+        wiremock::ResponseTemplate::new(200)
+            .set_body_json(common::completion(message))
+            .set_delay(Duration::from_secs(5)),
+        None,
+    )
+    .await;
+    let token = CancellationToken::new();
+    let canceller = token.clone();
+    tokio::spawn(async move {
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        canceller.cancel();
+    });
+
+    let started = Instant::now();
+    let options = RequestOptions::new().cancellation(token.clone());
+    let err = mock
+        .client()
+        .call_schema_with::<Review>("Check", &options)
+        .await
+        .unwrap_err();
+
+    assert!(err.is::<Cancelled>());
+    assert!(started.elapsed() < Duration::from_secs(2));
+
+    let err = mock
+        .client()
+        .call_with_tools_with::<Review>("Check", &ToolSet::new(), &options)
+        .await
+        .unwrap_err();
+    assert!(err.is::<Cancelled>());
+    assert!(started.elapsed() < Duration::from_secs(2));
+}
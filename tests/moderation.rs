@@ -0,0 +1,145 @@
+mod common;
+
+use common::MockOpenAi;
+use openai_structured_client::moderation::{ModerationFlagged, ModerationPolicy, ModerationStage};
+use openai_structured_client::openai::{OpenAiClient, UnknownApiBase};
+use schemars::JsonSchema;
+use serde::Deserialize;
+use serde_json::json;
+use wiremock::ResponseTemplate;
+
+#[derive(Debug, Deserialize, JsonSchema, Clone, PartialEq)]
+struct Sentiment {
+    label: String,
+}
+
+fn moderation(flagged: bool) -> ResponseTemplate {
+    ResponseTemplate::new(200).set_body_json(json!({
+        "id": "modr-1",
+        "model": "omni-moderation-latest",
+        "results": [{
+            "flagged": flagged,
+            "categories": { "harassment": flagged, "violence": false },
+            "category_scores": { "harassment": 0.91, "violence": 0.02 },
+        }],
+    }))
+}
+
+#[tokio::test]
+async fn screens_prompts_before_calling_the_model() {
+    let mock = MockOpenAi::start().await;
+    mock.mount_at("/v1/moderations", moderation(true)).await;
+    mock.respond_with_content(json!({ "label": "negative" }))
+        .await;
+
+    let client = mock.client().with_moderation(ModerationPolicy {
+        model: Some("omni-moderation-latest".to_string()),
+        ..Default::default()
+    });
+    let moderation = client.moderate("You idiot").await.unwrap();
+    assert_eq!(moderation.flagged_categories(), ["harassment"]);
+    assert_eq!(moderation.category_scores["harassment"], 0.91);
+
+    let err = client
+        .call_schema::<Sentiment>("You idiot")
+        .await
+        .unwrap_err();
+    let flagged = err.downcast_ref::<ModerationFlagged>().unwrap();
+    assert_eq!(flagged.stage, ModerationStage::Input);
+
+    let bodies = mock.request_bodies().await;
+    assert_eq!(bodies.len(), 2);
+    assert_eq!(bodies[1]["input"], "You idiot");
+    assert_eq!(bodies[1]["model"], "omni-moderation-latest");
+}
+
+#[tokio::test]
+async fn screens_outputs_when_enabled() {
+    let mock = MockOpenAi::start().await;
+    mock.mount_at("/v1/moderations", moderation(true)).await;
+    mock.respond_with_content(json!({ "label": "hateful" }))
+        .await;
+
+    let client = mock.client().with_moderation(ModerationPolicy {
+        screen_input: false,
+        screen_output: true,
+        model: None,
+    });
+    let err = client.call_schema::<Sentiment>("Review").await.unwrap_err();
+    let flagged = err.downcast_ref::<ModerationFlagged>().unwrap();
+    assert_eq!(flagged.stage, ModerationStage::Output);
+
+    let bodies = mock.request_bodies().await;
+    assert_eq!(bodies.len(), 2);
+    assert_eq!(
+        bodies[1]["input"],
+        json!({ "label": "hateful" }).to_string()
+    );
+}
+
+#[tokio::test]
+async fn screens_once_per_call_despite_repairs() {
+    let mock = MockOpenAi::start().await;
+    mock.mount_at("/v1/moderations", moderation(false)).await;
+    mock.respond_with_raw_content("not json", Some(1)).await;
+    mock.respond_with_content(json!({ "label": "positive" }))
+        .await;
+
+    let client = mock
+        .client()
+        .with_repair(1)
+        .with_moderation(ModerationPolicy {
+            screen_input: true,
+            screen_output: true,
+            model: None,
+        });
+    let _: Sentiment = client.call_schema("Great!").await.unwrap();
+
+    let screened: Vec<_> = mock
+        .requests()
+        .await
+        .iter()
+        .filter(|request| request.url.path() == "/v1/moderations")
+        .map(|request| request.body_json::<serde_json::Value>().unwrap()["input"].clone())
+        .collect();
+    assert_eq!(
+        screened,
+        [
+            json!("Great!"),
+            json!(json!({ "label": "positive" }).to_string())
+        ]
+    );
+}
+
+#[tokio::test]
+async fn screens_text_calls() {
+    let mock = MockOpenAi::start().await;
+    mock.mount_at("/v1/moderations", moderation(true)).await;
+    mock.respond_with_raw_content("Hello", None).await;
+
+    let client = mock.client().with_moderation(ModerationPolicy::default());
+    let err = client.call_text("You idiot").await.unwrap_err();
+
+    let flagged = err.downcast_ref::<ModerationFlagged>().unwrap();
+    assert_eq!(flagged.stage, ModerationStage::Input);
+    assert_eq!(mock.requests().await.len(), 1);
+}
+
+#[tokio::test]
+async fn fails_without_a_moderation_url() {
+    let mock = MockOpenAi::start().await;
+    mock.respond_with_content(json!({ "label": "positive" }))
+        .await;
+    let azure = format!(
+        "{}/openai/deployments/gpt-4o/chat/completions?api-version=2024-06-01",
+        mock.uri()
+    );
+    let client = OpenAiClient::new(reqwest::Client::new(), &azure, common::MODEL, "test-key")
+        .with_insecure_loopback(true)
+        .with_moderation(ModerationPolicy::default());
+
+    let err = client.call_schema::<Sentiment>("Great!").await.unwrap_err();
+
+    assert!(err.downcast_ref::<UnknownApiBase>().is_some());
+    assert!(mock.requests().await.is_empty());
+}
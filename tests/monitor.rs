@@ -0,0 +1,93 @@
+mod common;
+
+use common::MockOpenAi;
+use openai_structured_client::monitor::{DriftAlert, QualityMonitor, JUDGE};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::sync::{Arc, Mutex};
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone)]
+struct Invoice {
+    number: String,
+    total: f64,
+}
+
+fn invoice(number: &str, total: f64) -> Invoice {
+    Invoice {
+        number: number.into(),
+        total,
+    }
+}
+
+#[tokio::test]
+async fn alerts_once_when_recent_quality_drifts_below_baseline() {
+    let alerts: Arc<Mutex<Vec<DriftAlert>>> = Arc::default();
+    let sink = alerts.clone();
+    let monitor = QualityMonitor::new(1.0)
+        .with_check("has_number", |i: &Invoice| {
+            f64::from(u8::from(!i.number.is_empty()))
+        })
+        .with_check("positive_total", |i: &Invoice| {
+            f64::from(u8::from(i.total > 0.0))
+        })
+        .with_baseline(4)
+        .with_window(2)
+        .with_tolerance(0.2)
+        .on_drift(move |alert| sink.lock().unwrap().push(alert.clone()));
+
+    for _ in 0..4 {
+        monitor.observe("Extract", &invoice("INV-1", 10.0)).await;
+    }
+    assert_eq!(monitor.report().baseline, Some(1.0));
+    assert!(alerts.lock().unwrap().is_empty());
+
+    for _ in 0..3 {
+        monitor.observe("Extract", &invoice("", 10.0)).await;
+    }
+
+    let report = monitor.report();
+    assert_eq!(report.samples, 7);
+    assert_eq!(report.recent, Some(0.5));
+    assert_eq!(report.by_check["has_number"], 0.0);
+    assert!(report.drifting);
+    let alerts = alerts.lock().unwrap();
+    assert_eq!(alerts.len(), 1);
+    assert_eq!(alerts[0].baseline, 1.0);
+    assert_eq!(alerts[0].by_check["positive_total"], 1.0);
+}
+
+#[tokio::test]
+async fn samples_none_at_zero_rate() {
+    let monitor = QualityMonitor::new(0.0).with_check("any", |_: &Invoice| 1.0);
+
+    monitor.observe("Extract", &invoice("INV-1", 10.0)).await;
+
+    assert_eq!(monitor.report().samples, 0);
+}
+
+#[tokio::test]
+async fn scores_sampled_calls_with_the_judge() {
+    let mock = MockOpenAi::start().await;
+    mock.respond_with_content(json!({ "number": "INV-7", "total": 0.5 }))
+        .await;
+    let judge = MockOpenAi::start().await;
+    judge
+        .respond_with_content(json!({ "reason": "Total looks wrong", "score": 0.5 }))
+        .await;
+
+    let monitor = QualityMonitor::<Invoice>::new(1.0)
+        .with_judge(judge.client(), "The total matches the invoice text")
+        .with_baseline(1);
+    let output = monitor
+        .call(&mock.client(), "Invoice INV-7, total 50.00")
+        .await
+        .unwrap();
+
+    assert_eq!(output.number, "INV-7");
+    assert_eq!(monitor.report().by_check[JUDGE], 0.5);
+    let request = &judge.request_bodies().await[0];
+    let prompt = request["messages"][0]["content"].as_str().unwrap();
+    assert!(prompt.contains("The total matches the invoice text"));
+    assert!(prompt.contains(r#""number":"INV-7""#));
+}
@@ -0,0 +1,134 @@
+mod common;
+
+use common::MockOpenAi;
+use openai_structured_client::operations::{OperationError, OperationRegistry};
+use schemars::JsonSchema;
+use serde::Deserialize;
+use serde_json::json;
+use std::collections::BTreeMap;
+
+#[derive(Debug, Deserialize, JsonSchema, Clone, PartialEq)]
+struct Ticket {
+    category: String,
+}
+
+const OPERATIONS_YAML: &str = r#"
+operations:
+  classify_ticket:
+    prompt: "Classify this ticket: {ticket}"
+    schema: Ticket
+    model: gpt-4o-mini
+    temperature: 0
+    guard: { strip_invisible: true }
+"#;
+
+const OPERATIONS_TOML: &str = r#"
+[operations.extract_invoice]
+prompt = "Extract the invoice: {text}"
+schema_file = "schemas/invoice.json"
+system_role = "You read invoices."
+max_completion_tokens = 200
+"#;
+
+fn variables(pairs: &[(&str, &str)]) -> BTreeMap<String, String> {
+    pairs
+        .iter()
+        .map(|(name, value)| (name.to_string(), value.to_string()))
+        .collect()
+}
+
+#[tokio::test]
+async fn runs_operations_declared_in_config_files() {
+    let dir = std::env::temp_dir().join(format!("llm-operations-{}", std::process::id()));
+    std::fs::create_dir_all(dir.join("schemas")).unwrap();
+    std::fs::write(dir.join("operations.yaml"), OPERATIONS_YAML).unwrap();
+    std::fs::write(dir.join("invoices.toml"), OPERATIONS_TOML).unwrap();
+    std::fs::write(
+        dir.join("schemas/invoice.json"),
+        json!({
+            "type": "object",
+            "properties": { "total": { "type": "number" } },
+            "required": ["total"],
+            "additionalProperties": false,
+        })
+        .to_string(),
+    )
+    .unwrap();
+
+    let mut registry = OperationRegistry::new();
+    registry.register_schema::<Ticket>("Ticket").unwrap();
+    registry.load(dir.join("operations.yaml")).unwrap();
+    registry.load(dir.join("invoices.toml")).unwrap();
+    std::fs::remove_dir_all(&dir).unwrap();
+    assert_eq!(
+        registry.names().collect::<Vec<_>>(),
+        ["classify_ticket", "extract_invoice"]
+    );
+
+    let mock = MockOpenAi::start().await;
+    mock.respond_with_content_matching(
+        json!({ "model": "gpt-4o-mini" }),
+        json!({ "category": "billing" }),
+    )
+    .await;
+    mock.respond_with_content(json!({ "total": 42.5 })).await;
+    let client = mock.client();
+
+    let ticket: Ticket = client
+        .call_operation_as(
+            &registry,
+            "classify_ticket",
+            &variables(&[("ticket", "Charged twice\u{200B}")]),
+        )
+        .await
+        .unwrap();
+    assert_eq!(ticket.category, "billing");
+    let invoice = client
+        .call_operation(
+            &registry,
+            "extract_invoice",
+            &variables(&[("text", "Total: 42.50")]),
+        )
+        .await
+        .unwrap();
+    assert_eq!(invoice, json!({ "total": 42.5 }));
+
+    let bodies = mock.request_bodies().await;
+    assert_eq!(bodies[0]["temperature"], 0.0);
+    assert_eq!(
+        bodies[0]["messages"].as_array().unwrap().last().unwrap()["content"],
+        "Classify this ticket: Charged twice"
+    );
+    assert_eq!(
+        bodies[1]["response_format"]["json_schema"]["name"],
+        "invoice"
+    );
+    assert_eq!(bodies[1]["max_completion_tokens"], 200);
+    assert_eq!(bodies[1]["messages"][0]["content"], "You read invoices.");
+}
+
+#[tokio::test]
+async fn rejects_operations_with_unknown_references() {
+    let mut registry = OperationRegistry::new();
+    let err = registry
+        .load_yaml(OPERATIONS_YAML, std::path::Path::new("."))
+        .unwrap_err();
+    assert_eq!(
+        err.downcast_ref::<OperationError>(),
+        Some(&OperationError::UnknownSchema {
+            operation: "classify_ticket".to_string(),
+            schema: "Ticket".to_string(),
+        })
+    );
+
+    let mock = MockOpenAi::start().await;
+    let err = mock
+        .client()
+        .call_operation(&registry, "classify_ticket", &BTreeMap::new())
+        .await
+        .unwrap_err();
+    assert!(matches!(
+        err.downcast_ref::<OperationError>(),
+        Some(OperationError::UnknownOperation { .. })
+    ));
+}
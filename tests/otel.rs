@@ -0,0 +1,176 @@
+#![cfg(feature = "otel")]
+
+mod common;
+
+use common::MockOpenAi;
+use openai_structured_client::options::RequestOptions;
+use openai_structured_client::otel::CallLogger;
+use opentelemetry::logs::{AnyValue, LogRecord, Logger, Severity};
+use opentelemetry::Key;
+use regex::Regex;
+use schemars::JsonSchema;
+use serde::Deserialize;
+use serde_json::json;
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+#[derive(Debug, Deserialize, JsonSchema, Clone)]
+struct Contact {
+    name: String,
+}
+
+#[derive(Debug, Clone, Default)]
+struct Record {
+    severity: Option<Severity>,
+    body: Option<AnyValue>,
+    attributes: HashMap<String, AnyValue>,
+}
+
+impl Record {
+    fn attribute(&self, key: &str) -> Option<AnyValue> {
+        self.attributes.get(key).cloned()
+    }
+}
+
+impl LogRecord for Record {
+    fn set_event_name(&mut self, _: &'static str) {}
+
+    fn set_target<T: Into<Cow<'static, str>>>(&mut self, _: T) {}
+
+    fn set_timestamp(&mut self, _: SystemTime) {}
+
+    fn set_observed_timestamp(&mut self, _: SystemTime) {}
+
+    fn set_severity_text(&mut self, _: &'static str) {}
+
+    fn set_severity_number(&mut self, number: Severity) {
+        self.severity = Some(number);
+    }
+
+    fn set_body(&mut self, body: AnyValue) {
+        self.body = Some(body);
+    }
+
+    fn add_attributes<I, K, V>(&mut self, attributes: I)
+    where
+        I: IntoIterator<Item = (K, V)>,
+        K: Into<Key>,
+        V: Into<AnyValue>,
+    {
+        for (key, value) in attributes {
+            self.add_attribute(key, value);
+        }
+    }
+
+    fn add_attribute<K: Into<Key>, V: Into<AnyValue>>(&mut self, key: K, value: V) {
+        self.attributes
+            .insert(key.into().as_str().to_string(), value.into());
+    }
+}
+
+#[derive(Clone, Default)]
+struct CapturingLogger(Arc<Mutex<Vec<Record>>>);
+
+impl Logger for CapturingLogger {
+    type LogRecord = Record;
+
+    fn create_log_record(&self) -> Record {
+        Record::default()
+    }
+
+    fn emit(&self, record: Record) {
+        self.0.lock().unwrap().push(record);
+    }
+
+    fn event_enabled(&self, _: Severity, _: &str, _: Option<&str>) -> bool {
+        true
+    }
+}
+
+fn string(value: &str) -> Option<AnyValue> {
+    Some(AnyValue::from(value.to_string()))
+}
+
+#[tokio::test]
+async fn logs_each_call_with_schema_attributes() {
+    let mock = MockOpenAi::start().await;
+    mock.respond_with_content(json!({ "name": "Ada" })).await;
+    let logger = CapturingLogger::default();
+    let client = mock
+        .client()
+        .with_call_logger(CallLogger::new(logger.clone()));
+
+    let options = RequestOptions::new().tag("contact-extraction");
+    let contact: Contact = client
+        .call_schema_with("Ada, ada@example.com", &options)
+        .await
+        .unwrap();
+    assert_eq!(contact.name, "Ada");
+
+    let records = logger.0.lock().unwrap();
+    let record = &records[0];
+    assert_eq!(record.severity, Some(Severity::Info));
+    assert!(record.body.is_none());
+    assert_eq!(
+        record.attribute("llm.operation"),
+        string("contact-extraction")
+    );
+    assert_eq!(record.attribute("llm.outcome"), string("success"));
+    assert_eq!(
+        record.attribute("gen_ai.usage.output_tokens"),
+        Some(8.into())
+    );
+    assert_eq!(
+        record.attribute("llm.schema"),
+        string("otel_contact_response")
+    );
+}
+
+#[tokio::test]
+async fn logs_error_category_and_redacted_prompt() {
+    let mock = MockOpenAi::start().await;
+    mock.respond_with_raw_content("not json", None).await;
+    let logger = CapturingLogger::default();
+    let call_logger = CallLogger::new(logger.clone())
+        .with_prompts(true)
+        .with_redaction(Regex::new(r"\S+@\S+").unwrap());
+    let client = mock.client().with_call_logger(call_logger);
+
+    client
+        .call_schema::<Contact>("Ada, ada@example.com")
+        .await
+        .unwrap_err();
+
+    let records = logger.0.lock().unwrap();
+    let record = &records[0];
+    assert_eq!(record.severity, Some(Severity::Error));
+    assert_eq!(record.body, string("Ada, [REDACTED]"));
+    assert_eq!(record.attribute("llm.outcome"), string("error"));
+    assert_eq!(record.attribute("error.type"), string("parse"));
+}
+
+#[tokio::test]
+async fn redacts_error_messages() {
+    let mock = MockOpenAi::start().await;
+    mock.mount(
+        common::error_response(400, "Cannot process 'Ada, ada@example.com'"),
+        None,
+    )
+    .await;
+    let logger = CapturingLogger::default();
+    let call_logger =
+        CallLogger::new(logger.clone()).with_redaction(Regex::new(r"[^\s']+@[^\s']+").unwrap());
+    let client = mock.client().with_call_logger(call_logger);
+
+    client
+        .call_schema::<Contact>("Ada, ada@example.com")
+        .await
+        .unwrap_err();
+
+    let records = logger.0.lock().unwrap();
+    let message = records[0].attribute("error.message").unwrap();
+    assert!(!format!("{:?}", message).contains("ada@example.com"));
+    assert!(format!("{:?}", message).contains("[REDACTED]"));
+}
@@ -0,0 +1,58 @@
+use openai_structured_client::options::RequestOptions;
+use openai_structured_client::planner::{completion_limit, TokenPlanner};
+use schemars::JsonSchema;
+use serde_json::json;
+
+#[derive(JsonSchema)]
+#[allow(dead_code)]
+struct Invoice {
+    number: String,
+    total: f64,
+    lines: Vec<Line>,
+}
+
+#[derive(JsonSchema)]
+#[allow(dead_code)]
+struct Line {
+    description: String,
+    amount: f64,
+}
+
+#[test]
+fn plans_budget_with_safety_margin() {
+    let plan = TokenPlanner::for_model("gpt-4o-2024-08-06")
+        .plan::<Invoice>()
+        .unwrap();
+
+    assert!(plan.estimated_tokens > 0);
+    assert_eq!(
+        plan.max_completion_tokens,
+        (plan.estimated_tokens as f32 * 1.5).ceil() as u32
+    );
+    assert!(plan.fits);
+    assert!(plan.warnings.is_empty());
+    let options = plan.apply(RequestOptions::new());
+    assert!(format!("{:?}", options).contains(&plan.max_completion_tokens.to_string()));
+}
+
+#[test]
+fn uses_length_hints_and_warns_when_over_limit() {
+    let schema = json!({
+        "type": "object",
+        "properties": {
+            "chapters": {
+                "type": "array",
+                "minItems": 200,
+                "items": { "type": "string", "maxLength": 4000 }
+            }
+        }
+    });
+    let plan = TokenPlanner::for_model("gpt-4-turbo").plan_schema(&schema);
+
+    assert!(plan.estimated_tokens > 200 * 1000);
+    assert_eq!(plan.max_completion_tokens, 4_096);
+    assert!(!plan.fits);
+    assert_eq!(plan.warnings.len(), 1);
+    assert_eq!(completion_limit("gpt-4o-mini"), Some(16_384));
+    assert_eq!(completion_limit("unknown-model"), None);
+}
@@ -0,0 +1,65 @@
+mod common;
+
+use common::{error_response, sse_response, MockOpenAi};
+use openai_structured_client::openai::OutputMode;
+use openai_structured_client::probe::{Capabilities, ProbeError};
+use schemars::JsonSchema;
+use serde::Deserialize;
+use serde_json::json;
+
+#[derive(Debug, Deserialize, JsonSchema, Clone, PartialEq)]
+struct Status {
+    ok: bool,
+}
+
+#[tokio::test]
+async fn configures_json_mode_for_servers_without_structured_outputs() {
+    let mock = MockOpenAi::start().await;
+    mock.mount_matching(
+        json!({ "response_format": { "type": "json_schema" } }),
+        error_response(400, "response_format json_schema is not supported"),
+    )
+    .await;
+    mock.mount_matching(
+        json!({ "tool_choice": "required" }),
+        error_response(422, "tools are not supported"),
+    )
+    .await;
+    mock.mount_matching(json!({ "stream": true }), sse_response("ok", 2))
+        .await;
+    mock.respond_with_content(json!({ "ok": true })).await;
+
+    let capabilities = mock.client().probe().await.unwrap();
+    assert_eq!(
+        capabilities,
+        Capabilities {
+            json_schema: false,
+            json_object: true,
+            tools: false,
+            streaming: true,
+        }
+    );
+    assert_eq!(capabilities.output_mode(), OutputMode::JsonObject);
+
+    let client = mock.client().with_probe().await.unwrap();
+    let status: Status = client.call_schema("Are you up?").await.unwrap();
+    assert!(status.ok);
+
+    let body = mock.request_bodies().await.pop().unwrap();
+    assert_eq!(body["response_format"], json!({ "type": "json_object" }));
+    let instruction = body["messages"][0]["content"].as_str().unwrap();
+    assert!(instruction.starts_with("Respond with only a JSON value"));
+    assert!(instruction.contains("\"ok\""));
+}
+
+#[tokio::test]
+async fn fails_on_errors_that_say_nothing_about_support() {
+    let mock = MockOpenAi::start().await;
+    mock.respond_with_error(401, "Incorrect API key provided")
+        .await;
+
+    let err = mock.client().probe().await.unwrap_err();
+    let probe_error = err.downcast_ref::<ProbeError>().unwrap();
+    assert_eq!(probe_error.capability, "json_schema");
+    assert_eq!(probe_error.status, 401);
+}
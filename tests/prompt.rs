@@ -0,0 +1,134 @@
+mod common;
+
+use common::MockOpenAi;
+use openai_structured_client::prompt::{PromptError, PromptTemplate, PromptVariables};
+use schemars::JsonSchema;
+use serde::Deserialize;
+use serde_json::json;
+
+#[derive(Debug, PromptVariables)]
+struct Summary {
+    #[prompt(max_len = 20)]
+    text: String,
+    #[prompt(one_of("en", "de"))]
+    language: String,
+    words: Option<u32>,
+}
+
+#[test]
+fn renders_validated_variables() {
+    let summary = Summary::builder()
+        .text("Ada wrote a program")
+        .language("de")
+        .build()
+        .unwrap();
+    let template =
+        PromptTemplate::new("Summarize {text} in {language} ({words} words): {\"a\": 1}");
+
+    assert_eq!(
+        template.render(&summary).unwrap(),
+        "Summarize Ada wrote a program in de ( words): {\"a\": 1}"
+    );
+    assert_eq!(Summary::NAMES, ["text", "language", "words"]);
+}
+
+#[test]
+fn renders_double_brace_placeholders() {
+    let summary = Summary::builder()
+        .text("Ada")
+        .language("en")
+        .words(50u32)
+        .build()
+        .unwrap();
+    let template = PromptTemplate::new("Summarize {{text}} in {{ language }}, {words} words");
+
+    assert_eq!(
+        template.render(&summary).unwrap(),
+        "Summarize Ada in en, 50 words"
+    );
+}
+
+#[test]
+fn rejects_missing_and_invalid_variables() {
+    let missing = Summary::builder().text("Ada").build().unwrap_err();
+    assert_eq!(
+        missing,
+        PromptError::Missing {
+            variable: "language"
+        }
+    );
+
+    let too_long = Summary::builder()
+        .text("Ada Lovelace wrote the first program")
+        .language("en")
+        .build()
+        .unwrap_err();
+    assert!(matches!(too_long, PromptError::TooLong { len: 36, .. }));
+
+    let not_allowed = Summary::builder()
+        .text("Ada")
+        .language("fr")
+        .words(50u32)
+        .build()
+        .unwrap_err();
+    assert_eq!(
+        not_allowed.to_string(),
+        r#"Prompt variable `language` is "fr", not one of ["en", "de"]"#
+    );
+}
+
+#[test]
+fn rejects_placeholders_that_are_not_variables() {
+    let summary = Summary::builder()
+        .text("Ada")
+        .language("en")
+        .words(50u32)
+        .build()
+        .unwrap();
+
+    let err = PromptTemplate::new("Summarize {txt}")
+        .render(&summary)
+        .unwrap_err();
+
+    assert_eq!(
+        err,
+        PromptError::Unknown {
+            variable: "txt".into()
+        }
+    );
+}
+
+#[derive(Debug, Deserialize, JsonSchema, Clone, PartialEq)]
+struct Sentiment {
+    label: String,
+}
+
+#[tokio::test]
+async fn calls_with_a_rendered_template() {
+    let mock = MockOpenAi::start().await;
+    mock.respond_with_content(json!({ "label": "positive" }))
+        .await;
+    let summary = Summary::builder()
+        .text("Ada")
+        .language("en")
+        .build()
+        .unwrap();
+
+    let sentiment: Sentiment = mock
+        .client()
+        .call_schema_templated(&PromptTemplate::new("Rate {{text}}"), &summary)
+        .await
+        .unwrap();
+
+    assert_eq!(sentiment.label, "positive");
+    let body = &mock.request_bodies().await[0];
+    assert_eq!(body["messages"][0]["content"], "Rate Ada");
+
+    let err = mock
+        .client()
+        .call_schema_templated::<Sentiment, _>(&PromptTemplate::new("Rate {{tone}}"), &summary)
+        .await
+        .unwrap_err();
+    assert!(err.downcast_ref::<PromptError>().is_some());
+    assert_eq!(mock.request_bodies().await.len(), 1);
+}
@@ -0,0 +1,149 @@
+#![cfg(feature = "realtime")]
+
+use futures::{SinkExt, StreamExt};
+use openai_structured_client::realtime::{RealtimeClient, RealtimeError};
+use openai_structured_client::transport::InsecureEndpoint;
+use schemars::JsonSchema;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use tokio::net::TcpListener;
+use tokio::sync::oneshot;
+use tokio_tungstenite::tungstenite::handshake::server::{Request, Response};
+use tokio_tungstenite::tungstenite::Message;
+
+#[derive(Debug, Deserialize, JsonSchema, Clone, PartialEq)]
+struct Sentiment {
+    label: String,
+}
+
+/// Accepts one session, answers each `response.create` with `reply(request)`
+/// and reports the request URI, authorization header and client events.
+#[allow(clippy::result_large_err)]
+async fn serve(
+    reply: fn(&Value) -> Vec<Value>,
+) -> (String, oneshot::Receiver<(String, String, Vec<Value>)>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let endpoint = format!("ws://{}/v1/realtime", listener.local_addr().unwrap());
+    let (sender, receiver) = oneshot::channel();
+    tokio::spawn(async move {
+        let (stream, _) = listener.accept().await.unwrap();
+        let mut handshake = (String::new(), String::new());
+        let mut socket =
+            tokio_tungstenite::accept_hdr_async(stream, |request: &Request, response: Response| {
+                handshake = (
+                    request.uri().to_string(),
+                    request.headers()["authorization"]
+                        .to_str()
+                        .unwrap()
+                        .to_string(),
+                );
+                Ok(response)
+            })
+            .await
+            .unwrap();
+        let mut events = Vec::new();
+        while let Some(Ok(Message::Text(text))) = socket.next().await {
+            let event: Value = serde_json::from_str(&text).unwrap();
+            if event["type"] == "response.create" {
+                for reply in reply(&event) {
+                    socket.send(Message::text(reply.to_string())).await.unwrap();
+                }
+            }
+            events.push(event);
+        }
+        let _ = sender.send((handshake.0, handshake.1, events));
+    });
+    (endpoint, receiver)
+}
+
+fn client(endpoint: &str) -> RealtimeClient {
+    RealtimeClient::new("gpt-realtime", "test-key")
+        .with_endpoint(endpoint)
+        .with_insecure_loopback(true)
+}
+
+#[tokio::test]
+async fn parses_a_structured_response() {
+    let (endpoint, received) = serve(|request| {
+        let name = request["response"]["tool_choice"]["name"].clone();
+        vec![
+            json!({ "type": "response.created" }),
+            json!({
+                "type": "response.done",
+                "response": {
+                    "status": "completed",
+                    "output": [{
+                        "type": "function_call",
+                        "name": name,
+                        "arguments": "{\"label\":\"positive\"}",
+                    }],
+                },
+            }),
+        ]
+    })
+    .await;
+
+    let mut session = client(&endpoint).connect().await.unwrap();
+    session.send_text("Great!").await.unwrap();
+    let sentiment: Sentiment = session.respond_structured().await.unwrap();
+    assert_eq!(sentiment.label, "positive");
+    session.close().await.unwrap();
+
+    let (uri, authorization, events) = received.await.unwrap();
+    assert_eq!(uri, "/v1/realtime?model=gpt-realtime");
+    assert_eq!(authorization, "Bearer test-key");
+    assert_eq!(events[0]["item"]["content"][0]["text"], "Great!");
+    let response = &events[1]["response"];
+    assert_eq!(
+        response["tools"][0]["parameters"]["properties"]["label"]["type"],
+        "string"
+    );
+}
+
+#[tokio::test]
+async fn streams_events_and_reports_failed_responses() {
+    let (endpoint, _received) = serve(|request| {
+        if request.get("response").is_some() {
+            return vec![json!({
+                "type": "response.done",
+                "response": {
+                    "status": "failed",
+                    "status_details": {
+                        "error": { "code": "rate_limit_exceeded", "message": "slow down" },
+                    },
+                },
+            })];
+        }
+        vec![
+            json!({ "type": "response.output_text.delta", "delta": "Hel" }),
+            json!({ "type": "response.output_audio.delta", "delta": "AAE=" }),
+            json!({ "type": "response.done", "response": { "status": "completed" } }),
+        ]
+    })
+    .await;
+
+    let mut session = client(&endpoint).connect().await.unwrap();
+    session.append_audio(&[0, 1]).await.unwrap();
+    session.commit_audio().await.unwrap();
+    session.create_response().await.unwrap();
+    let text = session.next_event().await.unwrap().unwrap();
+    assert_eq!(text.text_delta(), Some("Hel"));
+    let audio = session.next_event().await.unwrap().unwrap();
+    assert_eq!(audio.audio_delta(), Some(vec![0, 1]));
+    let done = session.next_event().await.unwrap().unwrap();
+    assert_eq!(done.kind, "response.done");
+
+    let err = session.respond_structured::<Sentiment>().await.unwrap_err();
+    let err = err.downcast_ref::<RealtimeError>().unwrap();
+    assert_eq!(err.code.as_deref(), Some("rate_limit_exceeded"));
+}
+
+#[tokio::test]
+async fn refuses_unencrypted_remote_endpoints() {
+    let err = RealtimeClient::new("gpt-realtime", "test-key")
+        .with_endpoint("ws://example.com/v1/realtime")
+        .connect()
+        .await
+        .unwrap_err();
+    assert!(err.downcast_ref::<InsecureEndpoint>().is_some());
+}
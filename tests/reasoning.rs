@@ -0,0 +1,122 @@
+mod common;
+
+use common::{completion, MockOpenAi, MODEL};
+use openai_structured_client::openai::OpenAiClient;
+use openai_structured_client::options::RequestOptions;
+use openai_structured_client::reasoning::{is_reasoning_model, ReasoningEffort};
+use openai_structured_client::tools::{ToolResponse, ToolSet};
+use schemars::JsonSchema;
+use serde::Deserialize;
+use serde_json::json;
+use wiremock::ResponseTemplate;
+
+#[derive(Debug, Deserialize, JsonSchema, Clone, PartialEq)]
+struct Answer {
+    value: u32,
+}
+
+fn client(mock: &MockOpenAi, model: &str) -> OpenAiClient {
+    OpenAiClient::new(reqwest::Client::new(), mock.endpoint(), model, "test-key")
+        .with_insecure_loopback(true)
+        .with_system_role("You are precise.")
+}
+
+fn options() -> RequestOptions {
+    RequestOptions::new()
+        .temperature(0.2)
+        .max_completion_tokens(2_000)
+        .reasoning_effort(ReasoningEffort::High)
+}
+
+#[test]
+fn detects_reasoning_models_by_name() {
+    assert!(is_reasoning_model("o3-mini"));
+    assert!(is_reasoning_model("o1-2024-12-17"));
+    assert!(is_reasoning_model("gpt-5-mini"));
+    assert!(!is_reasoning_model("gpt-5-chat-latest"));
+    assert!(!is_reasoning_model(MODEL));
+}
+
+#[tokio::test]
+async fn shapes_requests_for_reasoning_models() {
+    let mock = MockOpenAi::start().await;
+    let mut response = completion(json!({
+        "role": "assistant",
+        "content": json!({ "value": 42 }).to_string(),
+    }));
+    response["usage"]["completion_tokens_details"] = json!({ "reasoning_tokens": 6 });
+    mock.mount(ResponseTemplate::new(200).set_body_json(response), None)
+        .await;
+
+    let client = client(&mock, "o3-mini");
+    let answer = client
+        .call_schema_with_meta::<Answer>("What is six times seven?", &options())
+        .await
+        .unwrap();
+    let usage = answer.meta.usage.unwrap();
+    assert_eq!(usage.reasoning_tokens(), 6);
+    assert_eq!(client.cost_summary().reasoning_tokens, 6);
+
+    client
+        .clone()
+        .with_model("o1-mini")
+        .call_schema_with::<Answer>("What is six times seven?", &options())
+        .await
+        .unwrap();
+    client
+        .with_model("my-deployment")
+        .with_reasoning_model(true)
+        .call_schema_with::<Answer>("What is six times seven?", &options())
+        .await
+        .unwrap();
+
+    let bodies = mock.request_bodies().await;
+    assert_eq!(bodies[0]["messages"][0]["role"], "developer");
+    assert_eq!(bodies[0]["reasoning_effort"], "high");
+    assert_eq!(bodies[0]["max_completion_tokens"], 2_000);
+    assert!(bodies[0].get("temperature").is_none());
+    assert_eq!(bodies[1]["messages"][0]["role"], "user");
+    assert_eq!(bodies[2]["messages"][0]["role"], "developer");
+}
+
+#[tokio::test]
+async fn leaves_other_models_unchanged() {
+    let mock = MockOpenAi::start().await;
+    mock.respond_with_content(json!({ "value": 42 })).await;
+
+    client(&mock, MODEL)
+        .call_schema_with::<Answer>("What is six times seven?", &options())
+        .await
+        .unwrap();
+
+    let body = &mock.request_bodies().await[0];
+    assert_eq!(body["messages"][0]["role"], "system");
+    assert_eq!(body["temperature"], json!(0.2f32));
+    assert!(body.get("reasoning_effort").is_none());
+}
+
+#[tokio::test]
+async fn shapes_tool_calls_for_reasoning_models() {
+    let mock = MockOpenAi::start().await;
+    mock.respond_with_content(json!({ "value": 42 })).await;
+
+    let response = client(&mock, "o3-mini")
+        .call_with_tools_with::<Answer>("What is six times seven?", &ToolSet::new(), &options())
+        .await
+        .unwrap();
+    assert!(matches!(
+        response,
+        ToolResponse::Answer(Answer { value: 42 })
+    ));
+    client(&mock, MODEL)
+        .call_with_tools_with::<Answer>("What is six times seven?", &ToolSet::new(), &options())
+        .await
+        .unwrap();
+
+    let bodies = mock.request_bodies().await;
+    assert_eq!(bodies[0]["messages"][0]["role"], "developer");
+    assert_eq!(bodies[0]["reasoning_effort"], "high");
+    assert!(bodies[0].get("temperature").is_none());
+    assert!(bodies[1].get("reasoning_effort").is_none());
+    assert_eq!(bodies[1]["messages"][0]["role"], "system");
+}
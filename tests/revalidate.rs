@@ -0,0 +1,46 @@
+use openai_structured_client::revalidate::{revalidate, revalidate_all, Verdict};
+use schemars::JsonSchema;
+use serde::Deserialize;
+
+#[derive(Debug, Deserialize, JsonSchema)]
+#[allow(dead_code)]
+struct ReviewV2 {
+    explanation: String,
+    severity: Option<u8>,
+    items: Vec<Item>,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+#[allow(dead_code)]
+struct Item {
+    word: String,
+}
+
+#[test]
+fn classifies_stored_records_against_new_type() {
+    let report = revalidate_all::<ReviewV2, _, _>([
+        r#"{"explanation":"a","severity":2,"items":[{"word":"penn"}]}"#,
+        r#"{"explanation":"b","items":[{"word":"x","note":"legacy"}]}"#,
+        r#"{"severity":1,"items":[]}"#,
+        "not json",
+    ])
+    .unwrap();
+
+    assert_eq!(report.records[0].verdict, Verdict::Conforms);
+    assert_eq!(report.records[1].verdict, Verdict::NeedsMigration);
+    assert_eq!(report.records[1].missing_fields, vec!["/severity"]);
+    assert_eq!(report.records[1].unknown_fields, vec!["/items/0/note"]);
+    assert_eq!(report.records[2].verdict, Verdict::Invalid);
+    assert_eq!(report.records[2].missing_fields, vec!["/explanation"]);
+    assert_eq!(report.records[3].verdict, Verdict::Invalid);
+    assert_eq!(report.count(Verdict::Invalid), 2);
+    assert!(!report.all_conform());
+}
+
+#[test]
+fn single_record_revalidation() {
+    let report =
+        revalidate::<ReviewV2>(r#"{"explanation":"a","severity":null,"items":[]}"#).unwrap();
+    assert_eq!(report.verdict, Verdict::Conforms);
+    assert!(report.error.is_none());
+}
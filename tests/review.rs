@@ -0,0 +1,51 @@
+mod common;
+
+use common::MockOpenAi;
+use openai_structured_client::review::{ReviewItem, ReviewReport};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, PartialEq)]
+struct Invoice {
+    vendor: String,
+    total: f64,
+}
+
+#[tokio::test]
+async fn reports_extractions_as_markdown_and_html() {
+    let mock = MockOpenAi::start().await;
+    mock.respond_with_content(json!({ "vendor": "A|B <Ltd>", "total": 12.5 }))
+        .await;
+    let client = mock.client();
+    let result = client
+        .call_schema_with_meta::<Invoice>("Invoice from A|B <Ltd>", &Default::default())
+        .await;
+
+    let mut report = ReviewReport::new("Invoices").with_excerpt_length(12);
+    report.push(ReviewItem::from_result("Invoice from A|B <Ltd>", &result).with_confidence(0.9));
+    report.push(ReviewItem::failed(
+        "Unreadable scan",
+        "Failed to parse LLM output",
+    ));
+    report.push(
+        ReviewItem::new("Credit note", json!({ "vendor": "C", "total": -3 }))
+            .with_warning("Negative total"),
+    );
+
+    let markdown = report.to_markdown();
+    assert!(markdown.starts_with("# Invoices\n\n3 extractions: 1 failed, 1 with warnings."));
+    assert!(markdown.contains("> Invoice from…"));
+    assert!(markdown.contains("| vendor | A\\|B <Ltd> |"));
+    assert!(markdown.contains("| total | 12.5 |"));
+    assert!(markdown.contains("Confidence: 0.90"));
+    assert!(markdown.contains("## 2. failed"));
+    assert!(markdown.contains("**Error:** Failed to parse LLM output"));
+    assert!(markdown.contains("## 3. check"));
+    assert!(markdown.contains("- Negative total"));
+
+    let html = report.to_html();
+    assert!(html.contains("<td>A|B &lt;Ltd&gt;</td>"));
+    assert!(html.contains("<section class=\"failed\">"));
+    assert!(html.contains("<li>Negative total</li>"));
+}
@@ -0,0 +1,120 @@
+mod common;
+
+use common::MockOpenAi;
+use openai_structured_client::options::RequestOptions;
+use openai_structured_client::sampling::{FieldHint, FieldSampling};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, PartialEq, FieldSampling)]
+struct Listing {
+    #[sampling(deterministic)]
+    price: u32,
+    #[sampling(deterministic)]
+    #[serde(rename = "city")]
+    location: String,
+    tagline: String,
+}
+
+#[test]
+fn derives_deterministic_fields_by_serialized_name() {
+    assert_eq!(Listing::DETERMINISTIC, ["price", "city"]);
+}
+
+#[tokio::test]
+async fn merges_deterministic_and_creative_passes() {
+    let mock = MockOpenAi::start().await;
+    mock.respond_with_content_matching(
+        json!({ "temperature": 0.0 }),
+        json!({ "price": 1200, "city": "Lyon", "tagline": "A flat" }),
+    )
+    .await;
+    mock.respond_with_content(json!({
+        "price": 1150,
+        "city": "Lyons",
+        "tagline": "Sunlit loft above the old town"
+    }))
+    .await;
+
+    let listing: Listing = mock
+        .client()
+        .call_schema_two_pass(
+            "Describe the listing",
+            &RequestOptions::new().temperature(1.0),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(
+        listing,
+        Listing {
+            price: 1200,
+            location: "Lyon".into(),
+            tagline: "Sunlit loft above the old town".into(),
+        }
+    );
+    assert_eq!(mock.request_bodies().await.len(), 2);
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, PartialEq, FieldSampling)]
+struct Ad {
+    #[sampling(deterministic)]
+    price: u32,
+    #[sampling(temperature = 1.5)]
+    slogan: String,
+    #[sampling(model = "gpt-4.1")]
+    summary: String,
+    body: String,
+}
+
+#[tokio::test]
+async fn takes_hinted_fields_from_passes_with_their_temperature_and_model() {
+    assert_eq!(
+        Ad::HINTS,
+        [
+            FieldHint {
+                field: "slogan",
+                temperature: Some(1.5),
+                model: None
+            },
+            FieldHint {
+                field: "summary",
+                temperature: None,
+                model: Some("gpt-4.1")
+            },
+        ]
+    );
+    let mock = MockOpenAi::start().await;
+    let output =
+        |name: &str| json!({ "price": 100, "slogan": name, "summary": name, "body": name });
+    mock.respond_with_content_matching(
+        json!({ "temperature": 0.0 }),
+        json!({
+            "price": 90, "slogan": "exact", "summary": "exact", "body": "exact"
+        }),
+    )
+    .await;
+    mock.respond_with_content_matching(json!({ "temperature": 1.5 }), output("wild"))
+        .await;
+    mock.respond_with_content_matching(json!({ "model": "gpt-4.1" }), output("careful"))
+        .await;
+    mock.respond_with_content(output("base")).await;
+
+    let ad: Ad = mock
+        .client()
+        .call_schema_two_pass("Write the ad", &RequestOptions::new().temperature(1.0))
+        .await
+        .unwrap();
+
+    assert_eq!(
+        ad,
+        Ad {
+            price: 90,
+            slogan: "wild".into(),
+            summary: "careful".into(),
+            body: "base".into(),
+        }
+    );
+    assert_eq!(mock.request_bodies().await.len(), 4);
+}
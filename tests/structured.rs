@@ -0,0 +1,73 @@
+mod common;
+
+use common::MockOpenAi;
+use openai_structured_client::options::RequestOptions;
+use openai_structured_client::structured::StructuredOutput;
+use schemars::JsonSchema;
+use serde::Deserialize;
+use serde_json::json;
+
+/// An invoice.
+#[derive(Debug, Deserialize, JsonSchema, Clone, PartialEq, StructuredOutput)]
+#[structured(
+    name = "invoice",
+    system_prompt = "You read invoices.",
+    description = "Totals of one invoice"
+)]
+struct Invoice {
+    /// The amount.
+    #[structured(description = "Total including tax")]
+    #[serde(rename = "total_due")]
+    total: f64,
+    currency: String,
+}
+
+#[derive(Debug, Deserialize, JsonSchema, Clone, PartialEq, StructuredOutput)]
+#[structured(strict = false)]
+struct Note {
+    text: String,
+}
+
+#[tokio::test]
+async fn extracts_with_the_declared_schema_and_system_prompt() {
+    let mock = MockOpenAi::start().await;
+    mock.respond_with_content(json!({ "total_due": 12.5, "currency": "EUR" }))
+        .await;
+
+    let invoice = Invoice::extract(&mock.client(), "Invoice #1: 12.50 EUR")
+        .await
+        .unwrap();
+    assert_eq!(invoice.total, 12.5);
+
+    let body = &mock.request_bodies().await[0];
+    assert_eq!(body["messages"][0]["role"], "system");
+    assert_eq!(body["messages"][0]["content"], "You read invoices.");
+    let format = &body["response_format"]["json_schema"];
+    assert_eq!(format["name"], "invoice");
+    assert_eq!(format["strict"], true);
+    assert_eq!(format["schema"]["description"], "Totals of one invoice");
+    assert_eq!(
+        format["schema"]["properties"]["total_due"]["description"],
+        "Total including tax"
+    );
+}
+
+#[tokio::test]
+async fn call_options_override_the_declared_system_prompt() {
+    let mock = MockOpenAi::start().await;
+    mock.respond_with_content(json!({ "text": "hi" })).await;
+
+    let options = RequestOptions::default().system_role("Be brief.");
+    let note: Note = mock
+        .client()
+        .with_system_role("Client role.")
+        .call_structured("Say hi", &options)
+        .await
+        .unwrap();
+    assert_eq!(note.text, "hi");
+    assert_eq!(Note::SCHEMA_NAME, None);
+
+    let body = &mock.request_bodies().await[0];
+    assert_eq!(body["messages"][0]["content"], "Be brief.");
+    assert_eq!(body["response_format"]["json_schema"]["strict"], false);
+}
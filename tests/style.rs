@@ -0,0 +1,102 @@
+mod common;
+
+use common::MockOpenAi;
+use openai_structured_client::options::RequestOptions;
+use openai_structured_client::style::{
+    Language, ReadingLevel, ResponseStyle, StyleViolation, Tone,
+};
+use schemars::JsonSchema;
+use serde::Deserialize;
+use serde_json::json;
+use wiremock::ResponseTemplate;
+
+#[derive(Debug, Deserialize, JsonSchema, Clone, PartialEq)]
+struct Summary {
+    text: String,
+}
+
+const ENGLISH: &str = "The meeting was moved to Friday and the team is not happy with this.";
+const FRENCH: &str =
+    "La réunion est déplacée à vendredi et l'équipe n'est pas contente de ce changement.";
+
+fn content(text: &str) -> ResponseTemplate {
+    let message = json!({
+        "role": "assistant",
+        "content": json!({ "text": text }).to_string(),
+        "refusal": null
+    });
+    ResponseTemplate::new(200).set_body_json(common::completion(message))
+}
+
+#[test]
+fn checks_language_and_reading_level() {
+    let french = ResponseStyle::new().with_language(Language::French);
+    assert!(french.check(&json!({ "text": FRENCH })).is_empty());
+    assert_eq!(
+        french.check(&json!({ "text": ENGLISH })),
+        ["the text is in English, not French"]
+    );
+    assert!(french.check(&json!({ "text": "Oui." })).is_empty());
+
+    let english = ResponseStyle::new().with_language(Language::English);
+    let articles = "A cat and a dog sat on a mat in a van.";
+    assert!(english.check(&json!({ "text": articles })).is_empty());
+
+    let simple = ResponseStyle::new().with_reading_level(ReadingLevel::Simple);
+    let dense = "Notwithstanding considerable organizational complexity, the \
+        interdepartmental reorganization necessitated comprehensive reevaluation \
+        of administrative responsibilities, institutional accountability mechanisms \
+        and operational interdependencies across geographically distributed \
+        international subsidiaries and affiliated organizations, particularly \
+        regarding procurement authorization hierarchies.";
+    assert_eq!(simple.check(&json!({ "text": dense })).len(), 1);
+    let plain = "The cat sat on the mat. It was a warm day. The sun was out. \
+        We had tea in the shade. Then we went home. The dog was glad to see us. \
+        We all had a nap.";
+    assert!(simple.check(&json!({ "text": plain })).is_empty());
+}
+
+#[tokio::test]
+async fn repairs_outputs_that_break_the_style() {
+    let mock = MockOpenAi::start().await;
+    mock.mount(content(ENGLISH), Some(1)).await;
+    mock.mount(content(FRENCH), None).await;
+
+    let style = ResponseStyle::new()
+        .with_language(Language::French)
+        .with_tone(Tone::Formal);
+    let options = RequestOptions::default().style(style);
+    let summary: Summary = mock
+        .client()
+        .with_repair(1)
+        .call_schema_with("Summarize the memo", &options)
+        .await
+        .unwrap();
+    assert_eq!(summary.text, FRENCH);
+
+    let bodies = mock.request_bodies().await;
+    let system = bodies[0]["messages"][0]["content"].as_str().unwrap();
+    assert!(system.contains("Write all text in French"));
+    assert!(system.contains("formal"));
+    let feedback = bodies[1]["messages"].as_array().unwrap().last().unwrap()["content"]
+        .as_str()
+        .unwrap()
+        .to_string();
+    assert!(feedback.contains("the text is in English, not French"));
+}
+
+#[tokio::test]
+async fn fails_with_the_violation_without_repairs() {
+    let mock = MockOpenAi::start().await;
+    mock.mount(content(ENGLISH), None).await;
+
+    let options =
+        RequestOptions::default().style(ResponseStyle::new().with_language(Language::German));
+    let err = mock
+        .client()
+        .call_schema_with::<Summary>("Summarize the memo", &options)
+        .await
+        .unwrap_err();
+    let violation = err.downcast_ref::<StyleViolation>().unwrap();
+    assert_eq!(violation.violations, ["the text is in English, not German"]);
+}
@@ -0,0 +1,91 @@
+mod common;
+
+use common::MockOpenAi;
+use openai_structured_client::openai::OpenAiClient;
+use openai_structured_client::options::RequestOptions;
+use openai_structured_client::tokens::{
+    count_tokens, ContextCheck, ContextOverflow, ContextWindows,
+};
+use schemars::JsonSchema;
+use serde::Deserialize;
+use serde_json::json;
+
+#[derive(Debug, Deserialize, JsonSchema, Clone)]
+struct Summary {
+    text: String,
+}
+
+#[test]
+fn looks_up_context_windows_by_longest_prefix() {
+    let windows = ContextWindows::new().with_model("gpt-4o-mini-ft", 4_096);
+
+    assert_eq!(windows.get("gpt-4o-2024-08-06"), Some(128_000));
+    assert_eq!(windows.get("gpt-4-0613"), Some(8_192));
+    assert_eq!(windows.get("gpt-4o-mini-ft:acme"), Some(4_096));
+    assert_eq!(windows.get("llama3.2"), None);
+}
+
+#[test]
+fn estimates_prompt_tokens_with_the_system_role() {
+    let client = OpenAiClient::new(
+        reqwest::Client::new(),
+        "https://example.com/v1/chat/completions",
+        "gpt-4o",
+        "key",
+    );
+    let prompt = "Summarize the minutes of the meeting";
+    let bare = client.estimate_tokens(prompt);
+    let with_role = client
+        .with_system_role("You are a careful secretary.")
+        .estimate_tokens(prompt);
+
+    assert!(bare >= count_tokens("gpt-4o", prompt));
+    assert!(with_role > bare);
+}
+
+#[cfg(feature = "tiktoken")]
+#[test]
+fn counts_openai_tokens_exactly_with_tiktoken() {
+    assert_eq!(count_tokens("gpt-4o", "hello world"), 2);
+}
+
+#[tokio::test]
+async fn rejects_requests_that_exceed_the_context_window() {
+    let mock = MockOpenAi::start().await;
+    mock.respond_with_content(json!({ "text": "Short" })).await;
+    let client = mock
+        .client()
+        .with_context_windows(ContextWindows::new().with_model("gpt-4o", 1_000))
+        .with_context_check(ContextCheck::Reject);
+
+    let options = RequestOptions::new().max_completion_tokens(990);
+    let err = client
+        .call_schema_with::<Summary>("Summarize", &options)
+        .await
+        .unwrap_err();
+
+    let overflow = err.downcast_ref::<ContextOverflow>().unwrap();
+    assert_eq!(overflow.context_window, 1_000);
+    assert_eq!(overflow.completion_tokens, 990);
+    assert!(overflow.schema_tokens > 0);
+    assert!(mock.requests().await.is_empty());
+
+    let options = RequestOptions::new().max_completion_tokens(500);
+    let summary: Summary = client
+        .call_schema_with("Summarize", &options)
+        .await
+        .unwrap();
+    assert_eq!(summary.text, "Short");
+}
+
+#[tokio::test]
+async fn warns_but_sends_when_configured_to() {
+    let mock = MockOpenAi::start().await;
+    mock.respond_with_raw_content("Done", None).await;
+    let client = mock
+        .client()
+        .with_context_windows(ContextWindows::new().with_model("gpt-4o", 10))
+        .with_context_check(ContextCheck::Warn);
+
+    assert_eq!(client.call_text("Summarize").await.unwrap(), "Done");
+}
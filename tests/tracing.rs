@@ -0,0 +1,169 @@
+#![cfg(feature = "tracing")]
+
+mod common;
+
+use common::MockOpenAi;
+use openai_structured_client::guard::InputGuard;
+use schemars::JsonSchema;
+use serde::Deserialize;
+use serde_json::json;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+
+use tracing::{Event, Metadata, Subscriber};
+use tracing_core::span::Current;
+use wiremock::ResponseTemplate;
+
+#[derive(Debug, Deserialize, JsonSchema, Clone, PartialEq)]
+struct Sentiment {
+    label: String,
+}
+
+/// Span fields recorded so far, as `(span name, field name, value)`.
+type Fields = Arc<Mutex<Vec<(&'static str, String, String)>>>;
+
+/// Collects the fields of every span, whether given at creation or recorded
+/// later, and tracks the entered spans as real subscribers do.
+#[derive(Default)]
+struct Capture {
+    next_id: AtomicU64,
+    spans: Mutex<HashMap<u64, &'static Metadata<'static>>>,
+    entered: Mutex<Vec<u64>>,
+    fields: Fields,
+}
+
+struct Collect<'a> {
+    span: &'static str,
+    fields: &'a Fields,
+}
+
+impl Visit for Collect<'_> {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        let entry = (self.span, field.name().to_string(), value.to_string());
+        self.fields.lock().unwrap().push(entry);
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        let entry = (self.span, field.name().to_string(), format!("{:?}", value));
+        self.fields.lock().unwrap().push(entry);
+    }
+}
+
+impl Subscriber for Capture {
+    fn enabled(&self, _: &Metadata<'_>) -> bool {
+        true
+    }
+
+    fn new_span(&self, attributes: &Attributes<'_>) -> Id {
+        let id = self.next_id.fetch_add(1, Ordering::SeqCst) + 1;
+        let metadata = attributes.metadata();
+        self.spans.lock().unwrap().insert(id, metadata);
+        let span = metadata.name();
+        attributes.record(&mut Collect {
+            span,
+            fields: &self.fields,
+        });
+        Id::from_u64(id)
+    }
+
+    fn record(&self, id: &Id, values: &Record<'_>) {
+        let span = self.spans.lock().unwrap()[&id.into_u64()].name();
+        values.record(&mut Collect {
+            span,
+            fields: &self.fields,
+        });
+    }
+
+    fn record_follows_from(&self, _: &Id, _: &Id) {}
+
+    fn event(&self, event: &Event<'_>) {
+        event.record(&mut Collect {
+            span: "event",
+            fields: &self.fields,
+        });
+    }
+
+    fn enter(&self, id: &Id) {
+        self.entered.lock().unwrap().push(id.into_u64());
+    }
+
+    fn exit(&self, id: &Id) {
+        let mut entered = self.entered.lock().unwrap();
+        if let Some(position) = entered.iter().rposition(|&span| span == id.into_u64()) {
+            entered.remove(position);
+        }
+    }
+
+    fn current_span(&self) -> Current {
+        match self.entered.lock().unwrap().last() {
+            Some(&id) => Current::new(Id::from_u64(id), self.spans.lock().unwrap()[&id]),
+            None => Current::none(),
+        }
+    }
+}
+
+#[tokio::test]
+async fn records_request_id_on_the_call_span() {
+    let mock = MockOpenAi::start().await;
+    let message = json!({
+        "role": "assistant",
+        "content": json!({ "label": "positive" }).to_string(),
+        "refusal": null
+    });
+    mock.mount(
+        ResponseTemplate::new(200)
+            .set_body_json(common::completion(message))
+            .insert_header("x-request-id", "req_123"),
+        None,
+    )
+    .await;
+    let capture = Capture::default();
+    let fields = capture.fields.clone();
+    let _guard = tracing::subscriber::set_default(capture);
+
+    let _: Sentiment = mock.client().call_schema("Great!").await.unwrap();
+
+    let fields = fields.lock().unwrap();
+    let find = |span: &str, field: &str| {
+        fields
+            .iter()
+            .find(|(s, f, _)| *s == span && f == field)
+            .map(|(_, _, value)| value.clone())
+    };
+    assert_eq!(
+        find("call_schema", "request_id").as_deref(),
+        Some("req_123")
+    );
+    assert_eq!(find("http", "status").as_deref(), Some("200"));
+    assert_eq!(find("call_schema", "total_tokens").as_deref(), Some("20"));
+}
+
+#[tokio::test]
+async fn warns_when_the_input_guard_changes_a_prompt() {
+    let mock = MockOpenAi::start().await;
+    mock.respond_with_content(json!({ "label": "positive" }))
+        .await;
+    let capture = Capture::default();
+    let fields = capture.fields.clone();
+    let _guard = tracing::subscriber::set_default(capture);
+
+    let client = mock.client().with_input_guard(InputGuard::default());
+    let _: Sentiment = client.call_schema("Gr\u{200B}eat\u{202E}!").await.unwrap();
+
+    let fields = fields.lock().unwrap();
+    let event = |field: &str| {
+        fields
+            .iter()
+            .find(|(s, f, _)| *s == "event" && f == field)
+            .map(|(_, _, value)| value.clone())
+    };
+    assert_eq!(
+        event("message").as_deref(),
+        Some("input guard changed a prompt")
+    );
+    assert_eq!(event("removed").as_deref(), Some("2"));
+}
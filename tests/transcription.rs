@@ -0,0 +1,77 @@
+mod common;
+
+use common::MockOpenAi;
+use openai_structured_client::options::RequestOptions;
+use openai_structured_client::transcription::{TranscriptFormat, TranscriptionOptions};
+use schemars::JsonSchema;
+use serde::Deserialize;
+use serde_json::json;
+use wiremock::ResponseTemplate;
+
+#[derive(Debug, Deserialize, JsonSchema, Clone, PartialEq)]
+struct Order {
+    item: String,
+    quantity: u32,
+}
+
+#[tokio::test]
+async fn extracts_structured_data_from_audio() {
+    let mock = MockOpenAi::start().await;
+    mock.mount_at(
+        "/v1/audio/transcriptions",
+        ResponseTemplate::new(200).set_body_json(json!({ "text": "Two coffees, please." })),
+    )
+    .await;
+    mock.respond_with_content(json!({ "item": "coffee", "quantity": 2 }))
+        .await;
+
+    let order: Order = mock
+        .client()
+        .call_schema_from_audio(
+            vec![0u8, 1, 2, 3],
+            &TranscriptionOptions::new()
+                .file_name("order.wav")
+                .language("en"),
+            &RequestOptions::new(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(order.quantity, 2);
+
+    let requests = mock.requests().await;
+    let upload = &requests[0];
+    let content_type = upload.headers["content-type"].to_str().unwrap();
+    assert!(content_type.starts_with("multipart/form-data; boundary="));
+    let form = String::from_utf8_lossy(&upload.body);
+    assert!(form.contains("name=\"file\"; filename=\"order.wav\""));
+    assert!(form.contains("name=\"model\"\r\n\r\nwhisper-1\r\n"));
+    assert!(form.contains("name=\"language\"\r\n\r\nen\r\n"));
+    assert!(upload.body.windows(4).any(|window| window == [0, 1, 2, 3]));
+
+    let completion: serde_json::Value = serde_json::from_slice(&requests[1].body).unwrap();
+    assert!(completion["messages"]
+        .to_string()
+        .contains("Two coffees, please."));
+}
+
+#[tokio::test]
+async fn returns_subtitle_formats_as_text() {
+    let mock = MockOpenAi::start().await;
+    let srt = "1\n00:00:00,000 --> 00:00:01,500\nHello\n";
+    mock.mount_at(
+        "/v1/audio/transcriptions",
+        ResponseTemplate::new(200).set_body_string(srt),
+    )
+    .await;
+
+    let transcript = mock
+        .client()
+        .transcribe(
+            vec![0u8; 8],
+            &TranscriptionOptions::new().format(TranscriptFormat::Srt),
+        )
+        .await
+        .unwrap();
+    assert_eq!(transcript.text, srt);
+    assert!(transcript.segments.is_empty());
+}